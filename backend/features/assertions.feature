@@ -0,0 +1,88 @@
+Feature: Checking bragi results
+
+  We are evaluating the assertions the runner makes on the results of a search, and the
+  messages it gives when they fail
+
+  Background:
+    Given the bragi results in './tests/data/bragi-paris.json'
+
+  Scenario: Counting results
+    When I check the step "I find at least 3 results"
+    Then I find that the step passes
+    When I check the step "I find between 1 and 3 results"
+    Then I find that the step fails with a message containing 'Expected between 1 and 3 results, found 4.'
+    When I check the step "I find no results"
+    Then I find that the step fails with a message containing 'Results were:\n  1. Paris (75000-75116), Île-de-France, France (zone)'
+
+  Scenario: Finding a result near a location
+    When I check the step "I find 'Paris (75000-75116), Île-de-France, France' within 500 meters of 48.8566, 2.3522"
+    Then I find that the step passes
+    When I check the step "I find 'Paris (75000-75116), Île-de-France, France' within 100 meters of 48.8566, 2.3522"
+    Then I find that the step fails with a message containing 'is at 48.8534951, 2.3483915, 444 meters from 48.8566, 2.3522'
+
+  Scenario: Finding a result in an administrative region
+    When I check the step "I find 'Rue de Paris (Montreuil)' in 'Montreuil'"
+    Then I find that the step passes
+    When I check the step "I find 'Rue de Paris (Montreuil)' in 'Paris'"
+    Then I find that the step fails with a message containing 'is not in 'Paris', but in [Montreuil'
+
+  Scenario: Checking zone types, levels, postcodes and house numbers
+    When I check the step "I find 'Paris 13e Arrondissement (75013), Paris, Île-de-France, France' with zone type 'city_district'"
+    Then I find that the step passes
+    When I check the step "I find 'Paris (75000-75116), Île-de-France, France' with level 9"
+    Then I find that the step fails with a message containing 'at position 1 has level 8, expected 9.'
+    When I check the step "I find 'Paris (75000-75116), Île-de-France, France' with postcode '75013'"
+    Then I find that the step passes
+    When I check the step "I find '20 Rue Hector Malot (Paris)' with house number '20'"
+    Then I find that the step passes
+    When I check the step "I find 'Rue de Paris (Montreuil)' with a house number"
+    Then I find that the step fails with a message containing ''Rue de Paris (Montreuil)' at position 3 has no house number.'
+
+  Scenario: Not finding a result
+    When I check the step "I do not find 'Paris, Texas'"
+    Then I find that the step passes
+    When I check the step "I do not find 'Rue de Paris (Montreuil)' within the first 3 results"
+    Then I find that the step fails with a message containing 'Found 'Rue de Paris (Montreuil)' at position 3.'
+
+  Scenario: Comparing the ranks of two results
+    When I check the step "'Paris (75000-75116), Île-de-France, France' ranks above 'Rue de Paris (Montreuil)'"
+    Then I find that the step passes
+    When I check the step "'20 Rue Hector Malot (Paris)' ranks above 'Rue de Paris (Montreuil)'"
+    Then I find that the step fails with a message containing ''20 Rue Hector Malot (Paris)' at position 4 does not rank above 'Rue de Paris (Montreuil)' at position 3.'
+    When I check the step "'Paris, Texas' ranks above 'Rue de Paris (Montreuil)'"
+    Then I find that the step fails with a message containing 'Could not find 'Paris, Texas'.'
+
+  Scenario: Matching labels loosely
+    When I check the step "I find '20 rue Hector-Malot, PARIS' of type 'house' within the first 5 results"
+    Then I find that the step fails with a message containing 'Could not find '20 rue Hector-Malot, PARIS' of type 'house' within the first 5 results.'
+    When I check the step "I find '20 rue Hector-Malot, PARIS' of type 'house' within the first 5 results using normalized matching"
+    Then I find that the step passes with a score of 1.00
+    When I check the step "I find 'rue Hector-Malot, Paris' of type 'house' within the first 5 results using tokens matching"
+    Then I find that the step passes with a score of 0.80
+    When I check the step "I find '20 rue Hector Mallot Paris' of type 'house' within the first 5 results using jaro-winkler 0.95 matching"
+    Then I find that the step passes with a score of 0.99
+    When I check the step "I find '^20 Rue Hector.Malot' of type 'house' within the first 5 results using regex matching"
+    Then I find that the step passes with a score of 1.00
+    When I check the step "I find 'Rue Hector Malot' of type 'house' within the first 5 results using levenshtein 0.9 matching"
+    Then I find that the step fails with a message containing 'Closest was '20 Rue Hector Malot (Paris)' at position 4, with a score of 0.64.'
+
+  Scenario: Choosing the match mode with tags
+    When I check the step "I find 'Rue de Paris (Montreuil)' in 'ile de france'" in a scenario tagged '@match:exact @match:normalized'
+    Then I find that the step passes with a score of 1.00
+    When I check the step "I find 'rue de paris, montreuil' with postcode '93100'" in a scenario tagged '@match:normalized'
+    Then I find that the step passes
+    When I check the step "I find 'rue de paris, montreuil' with postcode '93100' using exact matching" in a scenario tagged '@match:normalized'
+    Then I find that the step fails with a message containing 'Could not find 'rue de paris, montreuil'.'
+
+  Scenario: Checking the address found by reverse geocoding
+    Given the bragi results in './tests/data/bragi-reverse.json'
+    When I check the step "I find the address '20 Rue Hector Malot (Paris)'"
+    Then I find that the step passes
+    When I check the step "I find the street 'Rue Hector Malot'"
+    Then I find that the step passes
+    When I check the step "I find the admin 'Paris 13e Arrondissement'"
+    Then I find that the step passes
+    When I check the step "I find the street 'Rue de Tolbiac'"
+    Then I find that the step fails with a message containing 'The nearest result, '20 Rue Hector Malot (Paris)' of type 'house', does not have the street 'Rue de Tolbiac', but [Rue Hector Malot].'
+    When I check the step "I find the admin 'Montreuil'"
+    Then I find that the step fails with a message containing 'does not have the admin 'Montreuil', but [Paris 13e Arrondissement, Paris 13e Arrondissement (75013), Paris, Île-de-France, France, Paris, Paris (75000-75116), Île-de-France, France].'
@@ -0,0 +1,19 @@
+Feature: Auditing changes
+
+  We are evaluating the audit log, which records who changed what
+
+  Scenario: Adding a feature
+    When I add the feature 'Audited feature' through the API
+    Then I find an 'add_feature' audit entry for the feature by 'cucumber'
+    And I find that the audit entry records the feature after the change
+
+  Scenario: Deleting a feature
+    Given I have added the feature 'Deleted audited feature' through the API
+    When I delete the feature through the API
+    Then I find a 'delete_feature' audit entry for the feature by 'cucumber'
+    And I find that the audit entry records the feature before the change
+
+  Scenario: Pruning the audit log
+    Given I have added the feature 'Pruned audited feature' through the API
+    When I prune the audit log with a retention of 1 day
+    Then I find an 'add_feature' audit entry for the feature by 'cucumber'
@@ -0,0 +1,19 @@
+Feature: Authenticating with API tokens
+
+  We are evaluating the authentication with API tokens, and the roles they carry
+
+  Scenario: Authenticating with a token
+    Given I have an API token with the role 'editor'
+    When I authenticate with the token
+    Then I find that I am authenticated with the role 'editor'
+    And I find that I am allowed to act as 'viewer' but not as 'operator'
+
+  Scenario: Authenticating with an unknown token
+    When I authenticate with an unknown token
+    Then I find that the authentication is rejected
+
+  Scenario: Authenticating with a revoked token
+    Given I have an API token with the role 'admin'
+    When I revoke the token
+    And I authenticate with the token
+    Then I find that the authentication is rejected
@@ -0,0 +1,41 @@
+Feature: Benchmarking searches
+
+  We are measuring how fast bragi answers the searches of the scenarios, by replaying each of
+  them, and comparing the latency with that of a baseline run
+
+  Scenario: Computing the latency of a scenario
+    Given the replays of a search with the latencies '10, 20, 30, 40, 50, 60, 70, 80, 90, 100' and 0 errors, in 1000 ms
+    When I compute the latency metrics of the scenario
+    Then I find that the latency is 50.0 ms at p50, 100.0 ms at p95 and 100.0 ms at p99
+    And I find that there were 10 requests, at 10.0 requests per second, with an error rate of 0.00
+
+  Scenario: Computing the latency of a scenario with several searches, and errors
+    Given the replays of a search with the latencies '10, 20, 30, 40' and 0 errors, in 200 ms
+    And the replays of a search with the latencies '50, 60, 70, 80' and 2 errors, in 300 ms
+    When I compute the latency metrics of the scenario
+    Then I find that the latency is 40.0 ms at p50, 80.0 ms at p95 and 80.0 ms at p99
+    And I find that there were 10 requests, at 20.0 requests per second, with an error rate of 0.20
+
+  Scenario: Computing the latency of a scenario without searches
+    When I compute the latency metrics of the scenario
+    Then I find that there are no latency metrics
+
+  Scenario: Checking the latency of a search
+    When I check the step "the search responds within 60 ms at p50" against the latencies '10, 20, 30, 40, 50, 60, 70, 80, 90, 100'
+    Then I find that the step passes
+    When I check the step "the search responds within 60 ms at p95" against the latencies '10, 20, 30, 40, 50, 60, 70, 80, 90, 100'
+    Then I find that the step fails with a message containing 'The search responded in 100.0 ms at p95 (over 10 requests), more than 60 ms'
+
+  Scenario: Comparing the latency of two benchmark runs
+    Given I am using the in-memory store
+    And the baseline run where 'Paris stable' passed, with the expected result at 1 among 'Paris, Lyon'
+    And the baseline run where 'Paris later' passed, with the expected result at 1 among 'Paris, Lyon'
+    And the candidate run where 'Paris stable' passed, with the expected result at 1 among 'Paris, Lyon'
+    And the candidate run where 'Paris later' passed, with the expected result at 1 among 'Paris, Lyon'
+    And the latency of 'Paris stable' in the baseline run is 40 ms
+    And the latency of 'Paris stable' in the candidate run is 60 ms
+    And the latency of 'Paris later' in the candidate run is 60 ms
+    When I compare the candidate run with the baseline run
+    Then I find that the latency of 'Paris stable' changed by +50% at p95
+    And I find that the latency of 'Paris later' is not compared
+    And I find that the comparison report contains 'Paris / Paris stable: p50 40.0 -> 60.0 ms, p95 40.0 -> 60.0 ms (+50%)'
@@ -0,0 +1,40 @@
+Feature: Command line client
+
+  The command line client loads features, runs their scenarios and prints their reports. Its
+  exit code tells a run which passed (0) from one which failed (1), and from a command which
+  could not be carried out (2)
+
+  Scenario: Running scenarios which pass
+    Given a bragi at version 'v1.2.3' which finds Paris
+    When I load './tests/data/cli.feature' with the command line client
+    Then I find that the command line client exits with 0, printing 'loaded 'Searching from the command line''
+    When I run the scenarios tagged 'cli-paris' against that bragi with the command line client
+    Then I find that the command line client exits with 0, printing 'PASSED (1 passed, 0 failed, 0 skipped)'
+    When I print the 'junit' report of the run with the command line client
+    Then I find that the command line client exits with 0, printing 'failures="0" errors="0"'
+    When I print the 'cucumber-json' report of the run with the command line client
+    Then I find that the command line client exits with 0, printing '"status": "passed"'
+
+  Scenario: Running scenarios which fail
+    Given a bragi at version 'v1.2.3' which finds Paris
+    When I load './tests/data/cli.feature' with the command line client
+    And I run the scenarios tagged 'cli-nowhere' against that bragi with the command line client
+    Then I find that the command line client exits with 1, printing 'FAILED (0 passed, 1 failed, 0 skipped)'
+    When I print the 'junit' report of the run with the command line client
+    Then I find that the command line client exits with 0, printing '<failure message='
+    When I print the 'cucumber-json' report of the run with the command line client
+    Then I find that the command line client exits with 0, printing '"status": "failed"'
+
+  Scenario: Running scenarios against a bragi which answers with errors
+    Given a bragi at version 'v1.2.3' which answers every search with an error
+    When I load './tests/data/cli.feature' with the command line client
+    And I run the scenarios tagged 'cli-paris' against that bragi with the command line client
+    Then I find that the command line client exits with 1, printing 'FAILED (0 passed, 1 failed, 0 skipped)'
+    When I print the 'junit' report of the run with the command line client
+    Then I find that the command line client exits with 0, printing '<error message='
+    When I print the 'cucumber-json' report of the run with the command line client
+    Then I find that the command line client exits with 0, printing '"error_message"'
+
+  Scenario: Running scenarios through a server which cannot be reached
+    When I run the scenarios tagged 'cli-paris' with the command line client, through the server 'http://127.0.0.1:9'
+    Then I find that the command line client exits with 2, printing 'Could not reach'
@@ -0,0 +1,22 @@
+Feature: Comparing runs
+
+  We are evaluating how the results of a run differ from those of another run, typically of the
+  same scenarios against two builds of bragi
+
+  Scenario: Comparing two runs in memory
+    Given I am using the in-memory store
+    And the baseline run where 'Paris first' passed, with the expected result at 1 among 'Paris, Paris 13e'
+    And the baseline run where 'Paris later' failed, with the expected result at 3 among 'Lyon, Nice, Paris'
+    And the baseline run where 'Paris stable' passed, with the expected result at 1 among 'Paris, Lyon'
+    And the candidate run where 'Paris first' failed, with the expected result at 0 among 'Paris 13e, Lyon'
+    And the candidate run where 'Paris later' passed, with the expected result at 1 among 'Paris, Lyon, Nice'
+    And the candidate run where 'Paris stable' passed, with the expected result at 1 among 'Paris, Lyon'
+    When I compare the candidate run with the baseline run
+    Then I find that 'Paris first' is newly failing
+    And I find that 'Paris later' is newly passing
+    And I find that the expected result of 'Paris first' moved from 1 to 0
+    And I find that the expected result of 'Paris later' moved from 3 to 1
+    And I find that the results of 'Paris first' changed
+    And I find that the results of 'Paris stable' did not change
+    And I find that the comparison report contains 'Paris / Paris first: Passed -> Failed: Could not find 'Paris''
+    And I find that the comparison report contains 'Then I find 'Paris' of type 'zone' within the first 1 result: 1 -> not found'
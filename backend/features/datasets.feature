@@ -0,0 +1,56 @@
+Feature: Storing datasets
+
+  We are evaluating the creation of datasets and of their items, the download of items, the
+  removal of items which no longer belong to any dataset, and that dataset ids are only ever passed to the database as
+  values, so that ids with quotes, semicolons or comments cannot alter the queries.
+
+  Scenario: Creating and removing datasets with hostile ids
+    Given I have a dataset with an item
+    When I create 50 datasets with hostile ids, each with an item of the same id
+    Then I find each dataset with its item, with their ids unchanged
+    When I remove the hostile datasets and their items
+    Then I find that only the first dataset and its item are left
+
+  Scenario: Creating a dataset
+    Given I have created the bano dataset 'ile-de-france'
+    Then I find that creating the bano dataset 'ile-de-france' fails
+    And I find that creating the unknown dataset 'somewhere' fails
+    And I find that adding the item '77' to the dataset 'unknown' fails
+    And I find the dataset 'ile-de-france' with the items ''
+
+  Scenario: Adding items to a dataset
+    Given I have created the bano dataset 'paris'
+    And I have added the item '75' to the dataset 'paris'
+    And I have added the item '92' to the dataset 'paris'
+    Then I find that adding the item '75' to the dataset 'paris' fails
+    And I find the dataset 'paris' with the items '75, 92'
+
+  Scenario: Removing an item shared by two datasets
+    Given I have created the bano dataset 'petite-couronne'
+    And I have created the bano dataset 'val-de-marne'
+    And I have added the item '94' to the dataset 'petite-couronne'
+    And I have added the item '94' to the dataset 'val-de-marne'
+    When I remove the item '94' from the dataset 'petite-couronne'
+    Then I find the dataset 'petite-couronne' with the items ''
+    And I find the dataset 'val-de-marne' with the items '94'
+    And I find that the bano item '94' still exists
+    When I remove the item '94' from the dataset 'val-de-marne'
+    Then I find the dataset 'val-de-marne' with the items ''
+    And I find that the bano item '94' no longer exists
+
+  Scenario: Keeping the items of different data sources apart
+    Given I have created the bano dataset 'bano-seine-saint-denis'
+    And I have created the osm dataset 'osm-seine-saint-denis'
+    And I have added the item '93' to the dataset 'bano-seine-saint-denis'
+    And I have added the item '93' to the dataset 'osm-seine-saint-denis'
+    When I remove the item '93' from the dataset 'bano-seine-saint-denis'
+    Then I find that the bano item '93' no longer exists
+    And I find that the osm item '93' still exists
+    And I find the dataset 'osm-seine-saint-denis' with the items '93'
+
+  Scenario: Downloading an item
+    Given I have created the bano dataset 'essonne'
+    And I have added the item '91' to the dataset 'essonne'
+    And a server which serves the bano items, as 512 kilobytes files
+    When I download the item '91' of the dataset 'essonne'
+    Then I find that the item '91' of the dataset 'essonne' is available, with the file served
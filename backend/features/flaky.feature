@@ -0,0 +1,55 @@
+Feature: Flaky scenarios
+
+  We are attempting failed scenarios again, finding those whose outcome changes while the
+  environment and bragi do not, and keeping quarantined scenarios from failing runs
+
+  Scenario: Finding flaky scenarios in the history of runs
+    Given I am using the in-memory store
+    And 'Paris' passed at attempt 1 against the environment 'e1' and bragi 'v1'
+    And 'Paris' failed at attempt 1 against the environment 'e1' and bragi 'v1'
+    And 'Lyon' passed at attempt 1 against the environment 'e1' and bragi 'v1'
+    And 'Lyon' failed at attempt 1 against the environment 'e1' and bragi 'v2'
+    And 'Nice' failed at attempt 1 against the environment 'e1' and bragi 'v1'
+    And 'Nice' failed at attempt 1 against the environment 'e2' and bragi 'v1'
+    And 'Lille' passed at attempt 2 against the environment 'e1' and bragi 'v1'
+    When I look for flaky scenarios
+    Then I find that 'Paris' is flaky, with 1 passed, 1 failed and 0 retried
+    And I find that 'Lille' is flaky, with 1 passed, 0 failed and 1 retried
+    And I find that 'Lyon' is not flaky
+    And I find that 'Nice' is not flaky
+
+  Scenario: Attempting failed scenarios again
+    Given I am using the in-memory store
+    And a bragi at version 'v1.2.3' which finds Paris every other time
+    And the features of './tests/data/flaky.feature'
+    And scenarios are attempted 2 times
+    When I run the scenarios against that bragi
+    Then I find that the run passed
+    And I find that 'Searching Paris' passed at attempt 2
+    And I find that 'Searching Paris' ran against bragi 'v1.2.3', with no environment
+    And I find that 'Searching nowhere' failed at attempt 2, quarantined
+    And I find that the junit report of the run contains '<system-out>Passed at attempt 2</system-out>'
+    And I find that the junit report of the run contains '<skipped message="quarantined">'
+    And I find that the junit report of the run contains 'failures="0"'
+    And I find that the cucumber report of the run contains '"status": "skipped"'
+    And I find that the cucumber report of the run contains '"error_message"'
+    And I find that the cucumber report of the run does not contain '"status": "failed"'
+
+  Scenario: Attempting scenarios once
+    Given I am using the in-memory store
+    And a bragi at version 'v1.2.3' which finds Paris every other time
+    And the features of './tests/data/flaky.feature'
+    When I run the scenarios against that bragi
+    Then I find that the run failed
+    And I find that 'Searching Paris' failed at attempt 1
+    And I find that 'Searching nowhere' failed at attempt 1, quarantined
+
+  Scenario: Flagging the scenarios which passed at a later attempt
+    Given I am using the in-memory store
+    And a bragi at version 'v1.2.3' which finds Paris every other time
+    And the features of './tests/data/flaky.feature'
+    And scenarios are attempted 3 times
+    When I run the scenarios against that bragi
+    And I look for flaky scenarios
+    Then I find that 'Searching Paris' is flaky, with 1 passed, 0 failed and 1 retried
+    And I find that 'Searching nowhere' is not flaky
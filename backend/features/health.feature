@@ -0,0 +1,23 @@
+Feature: Probing mjolnir
+
+  We are evaluating the readiness checks and the metrics used by probes and monitoring
+
+  Scenario: Checking readiness without listening to notifications
+    When I check the readiness
+    Then I find that the 'database' check passes
+    And I find that the 'notifications' check fails
+    And I find that mjolnir is not ready
+
+  Scenario: Exposing metrics
+    When I render the metrics
+    Then I find the metric 'mjolnir_db_pool_max_connections'
+
+  Scenario: Labelling metrics with known operations only
+    When I time the GraphQL operations 'features' and 'cucumberOperation'
+    And I render the metrics
+    Then I find the metric 'mjolnir_graphql_requests_total{operation="features"'
+    And I find the metric 'mjolnir_graphql_requests_total{operation="other"'
+    And I find no metric about the operation 'cucumberOperation'
+
+  Scenario: Labelling metrics with every root field
+    Then I find that every root field of the schema is a known operation
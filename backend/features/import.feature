@@ -0,0 +1,65 @@
+Feature: Importing features
+
+  Features are uploaded one by one, or in .tar.gz and .zip archives
+
+  Scenario: Importing a .tar.gz archive
+    Given an archive 'features.tar.gz' with the features 'Imported tar A, Imported tar B'
+    And the archive also holds a symlink 'link.feature' to 'imported-tar-a.feature'
+    When I import the archive
+    Then I find that the import loaded 2 features and failed 0
+    And I find the features 'Imported tar A, Imported tar B'
+
+  Scenario: Importing a .zip archive
+    Given an archive 'features.zip' with the features 'Imported zip A, Imported zip B'
+    When I import the archive
+    Then I find that the import loaded 2 features and failed 0
+    And I find the features 'Imported zip A, Imported zip B'
+
+  Scenario: Importing an archive with a feature which does not parse
+    Given an archive 'features.tar.gz' with the features 'Unparsed A, Unparsed B'
+    And the archive also holds the unparsable feature 'Unparsed C'
+    When I import the archive atomically
+    Then I find that the import loaded 0 features and failed 3
+    And I find none of the features 'Unparsed A, Unparsed B, Unparsed C'
+
+  Scenario: Importing an archive with a feature which cannot be loaded
+    Given an archive 'features.zip' with the features 'Rolled back A, Rolled back B'
+    And the archive also holds the feature 'Rolled back C' with twice the same scenario
+    When I import the archive atomically
+    Then I find that the import loaded 0 features and failed 3
+    And I find none of the features 'Rolled back A, Rolled back B, Rolled back C'
+
+  Scenario: Importing an unsupported file
+    Given an archive 'features.rar' with the features 'Unsupported A'
+    When I import the archive
+    Then I find that the import is rejected with 'Unsupported file'
+    And I find none of the features 'Unsupported A'
+
+  Scenario: Importing a directory within the import paths
+    Given a directory on the server with the features 'Imported dir A, Imported dir B'
+    And the import paths are that directory
+    When I import the directory
+    Then I find that the import loaded 2 features and failed 0
+    And I find the features 'Imported dir A, Imported dir B'
+
+  Scenario: Importing a directory skips the symlinks
+    Given a directory on the server with the features 'Symlinked dir A'
+    And the directory also holds symlinks to the feature 'Symlinked Outside' outside of it, and to itself
+    And the import paths are that directory
+    When I import the directory
+    Then I find that the import loaded 1 feature and failed 0
+    And I find none of the features 'Symlinked Outside'
+
+  Scenario: Importing a directory outside the import paths
+    Given a directory on the server with the features 'Outside A'
+    And the import paths are another directory
+    When I import the directory
+    Then I find that the import is rejected with 'not within the configured import paths'
+    And I find none of the features 'Outside A'
+
+  Scenario: Importing a directory when no import path is configured
+    Given a directory on the server with the features 'Unconfigured A'
+    And the import paths are none
+    When I import the directory
+    Then I find that the import is rejected with 'no import path is configured'
+    And I find none of the features 'Unconfigured A'
@@ -0,0 +1,53 @@
+Feature: Load testing
+
+  We are replaying the searches found in bragi's access logs at a given rate, to see how bragi
+  copes with real queries
+
+  Scenario: Importing searches from an access log
+    When I import the query log 'tests/data/bragi-access.log'
+    Then I find 3 searches in the corpus, and 4 lines skipped
+    And I find that search 1 requests 'http://localhost:4000/autocomplete?q=paris'
+    And I find that search 2 requests 'http://localhost:4000/autocomplete?q=rue+de+rivoli&lat=48.85&lon=2.35&type%5B%5D=street&limit=5'
+    And I find that search 3 requests 'http://localhost:4000/autocomplete?q=gare+de+lyon&pt_dataset%5B%5D=stif&lang=fr'
+    And I find that line 3 is skipped, as 'not a search (/reverse)'
+    And I find that line 4 is skipped, as 'no query'
+    And I find that line 7 is skipped, as 'not a search (POST)'
+    And I find that line 8 is skipped, as 'not in the common log format'
+    And I find that the corpus reads back the same
+
+  Scenario: Importing searches from JSON lines
+    When I import the query log 'tests/data/bragi-queries.jsonl'
+    Then I find 3 searches in the corpus, and 3 lines skipped
+    And I find that search 2 requests 'http://localhost:4000/autocomplete?q=stade&lat=48.8566&lon=2.3522&type%5B%5D=poi&limit=5'
+    And I find that search 3 requests 'http://localhost:4000/autocomplete?q=gare&lat=48.84&lon=2.37&pt_dataset%5B%5D=stif&lang=fr'
+    And I find that line 4 is skipped, as 'no query'
+    And I find that line 5 is skipped, as 'invalid limit 'many''
+    And I find that line 6 is skipped, as 'invalid JSON'
+    And I find that the corpus reads back the same
+
+  Scenario: Replaying a corpus against a stub
+    Given a bragi stub answering in 20 ms
+    And a corpus of the searches 'paris, lyon, error, nice'
+    When I send 8 requests from the corpus at 40 requests per second
+    Then I find that 8 requests were sent, with 2 errors
+    And I find that the stub received 8 requests
+    And I find that the latency at p50 is at least 20 ms
+    And I find that the histogram counts 6 requests
+    And I find that the load test lasted at least 0.15 s
+    And I find that the load report contains '8 requests in'
+
+  Scenario: Replaying a corpus against nothing
+    Given a corpus of the searches 'paris, lyon'
+    When I send 2 requests from the corpus to 'http://127.0.0.1:1' at 10 requests per second
+    Then I find that 2 requests were sent, with 2 errors
+    And I find that the histogram counts 0 requests
+
+  Scenario: Replaying a corpus at no rate
+    Given a corpus of the searches 'paris'
+    When I send 2 requests from the corpus to 'http://127.0.0.1:1' at 0 requests per second
+    Then I find that the load test fails with 'The rate must be positive'
+
+  Scenario: Replaying a corpus at a rate too high to pace
+    Given a corpus of the searches 'paris'
+    When I send 2 requests from the corpus to 'http://127.0.0.1:1' at 10000000000 requests per second
+    Then I find that the load test fails with 'at most one request per nanosecond'
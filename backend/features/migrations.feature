@@ -0,0 +1,24 @@
+Feature: Migrating the database
+
+  We are evaluating the migrations applied by the server at startup
+
+  Scenario: Applying the migrations
+    When I apply the pending migrations
+    Then I find that applying them again does nothing
+    And I find that the database is at the latest version
+
+  Scenario: Keeping the features when migrating
+    Given I am loading a feature from file './tests/data/example.feature'
+    When I apply the pending migrations
+    And I search for the scenarios by id
+    Then I find that I have the correct number of scenarios
+
+  Scenario: Migrating a database built by provision.sh
+    Given a database built by provision.sh, holding the feature 'Provisioned'
+    When I apply the pending migrations
+    Then I find that the migrations after the baseline were applied
+    And I find that the database is at the latest version
+    And I find that the feature 'Provisioned' is kept
+    Given I am loading a feature from file './tests/data/example.feature'
+    When I search for the scenarios by id
+    Then I find that I have the correct number of scenarios
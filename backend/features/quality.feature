@@ -0,0 +1,26 @@
+Feature: Measuring the quality of the search
+
+  We are evaluating how far down the results bragi puts the results the scenarios expect,
+  over a run, and by feature, tag and index type
+
+  Scenario: Ranking the result expected by a step
+    Given the bragi results in './tests/data/bragi-paris.json'
+    Then I find that the step "I find 'Rue de Paris (Montreuil)' of type 'street' within the first 1 result" ranks the expected result 3
+    And I find that the step "I find 'Rue de Paris (Montreuil)' of type 'zone' within the first 5 results" ranks the expected result 0
+
+  Scenario: Computing the quality metrics of a run
+    Given the ranks '1, 3, -' in the feature 'Paris' tagged '@smoke' indexing 'admins streets'
+    And the ranks '7' in the feature 'Lyon' tagged '@smoke @lyon' indexing 'admins'
+    When I compute the quality metrics
+    Then I find that the run '' has 4 queries, an MRR of 0.369, a recall of 0.250, 0.500 and 0.750 at 1, 5 and 10, and an nDCG of 0.458
+    And I find that the feature 'Paris' has 3 queries, an MRR of 0.444, a recall of 0.333, 0.667 and 0.667 at 1, 5 and 10, and an nDCG of 0.500
+    And I find that the tag 'lyon' has 1 query, an MRR of 0.143, a recall of 0.000, 0.000 and 1.000 at 1, 5 and 10, and an nDCG of 0.333
+    And I find that the index type 'streets' has 3 queries, an MRR of 0.444, a recall of 0.333, 0.667 and 0.667 at 1, 5 and 10, and an nDCG of 0.500
+
+  Scenario: Following the quality of a tag over runs in memory
+    Given I am using the in-memory store
+    And the ranks '1, 2' in the feature 'Paris' tagged '@smoke' indexing 'admins'
+    When I record the quality metrics of 3 runs
+    And I fetch the quality trend of the tag 'smoke' over 2 runs
+    Then I find 2 quality metrics
+    And I find that the tag 'smoke' has 2 queries, an MRR of 0.750, a recall of 0.500, 1.000 and 1.000 at 1, 5 and 10, and an nDCG of 0.815
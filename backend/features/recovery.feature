@@ -0,0 +1,19 @@
+Feature: Recovering from an interrupted server
+
+  We are evaluating the recovery of the work left in progress by a server which did not stop
+  cleanly, and how the work in progress stops when the server shuts down
+
+  Scenario: Recovering a run left running
+    Given I have a run left running against 'http://localhost:4000'
+    When I recover the interrupted work
+    Then I find that the run is 'interrupted'
+
+  Scenario: Recovering a download left in progress
+    Given I have the download of the item 'recovered' of 'bano' left in progress
+    When I recover the interrupted work
+    Then I find that the item 'recovered' of 'bano' is 'not_available'
+
+  Scenario: Stopping a download when the server shuts down
+    Given I have started the download of the item 'stopped' of 'bano' from a server which does not answer
+    When the server shuts down
+    Then I find that the item 'stopped' of 'bano' is 'not_available'
@@ -0,0 +1,15 @@
+Feature: Synchronising features with a git repository
+
+  We are evaluating the synchronisation of features with a local repository
+
+  Scenario: Synchronising a repository, then removing a feature from it
+    Given I have a git repository containing the feature './tests/data/example.feature'
+    When I synchronise the features with the repository
+    Then I find that the feature from the repository is loaded at the latest commit
+    When I remove the feature from the repository and synchronise again
+    Then I find that the feature from the repository is deleted
+
+  Scenario: Synchronising a repository with a feature which cannot be stored
+    Given I have a git repository containing the feature 'Too long to store' with a scenario named with 300 characters
+    When I synchronise the features with the repository, which fails
+    Then I find that nothing of the feature from the repository is loaded
@@ -0,0 +1,29 @@
+Feature: Searching with parameters
+
+  We are evaluating how search steps are turned into bragi requests
+
+  Scenario: Searching for a query
+    When I prepare the search step "I search for 'rue hector malot'"
+    Then I find that it requests 'http://localhost:4000/autocomplete?q=rue+hector+malot'
+    And I find that it does not send a shape
+
+  Scenario: Searching near a location, with types, datasets, a limit and a language
+    When I prepare the search step "I search for 'stade' near 48.8566, 2.3522 with types 'poi, public_transport:stop_area', dataset 'stif', limit 5, lang 'fr' and debug"
+    Then I find that it requests 'http://localhost:4000/autocomplete?q=stade&lat=48.8566&lon=2.3522&type%5B%5D=poi&type%5B%5D=public_transport%3Astop_area&pt_dataset%5B%5D=stif&limit=5&lang=fr&_debug=true'
+
+  Scenario: Searching within a shape
+    When I prepare the search step "I search for 'stade' within the shape with dataset 'stif'"
+    Then I find that it requests 'http://localhost:4000/autocomplete?q=stade&pt_dataset%5B%5D=stif'
+    And I find that it sends a shape
+
+  Scenario: Searching for a query with a quote
+    When I prepare the search step "I search for 'l'église' with limit 3"
+    Then I find that it requests 'http://localhost:4000/autocomplete?q=l%27%C3%A9glise&limit=3'
+
+  Scenario: Searching with unknown parameters
+    When I prepare the search step "I search for 'stade' with radius 500"
+    Then I find that it is not a search step
+
+  Scenario: Reverse geocoding
+    When I prepare the search step "I reverse geocode 48.8262,2.3608"
+    Then I find that it requests 'http://localhost:4000/reverse?lat=48.8262&lon=2.3608'
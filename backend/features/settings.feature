@@ -0,0 +1,20 @@
+Feature: Configuring mjolnir
+
+  We are evaluating the configuration files, which are layered over the defaults
+
+  Scenario: Reading a configuration file
+    Given I read the configuration file './tests/data/settings.toml'
+    Then I find that the server listens on '0.0.0.0:8080' with a pool of 10 connections
+    And I find that the logs are written as 'json'
+    And I find that the configuration is valid
+    And I find that the printed configuration hides the passwords
+
+  Scenario: Reading an invalid configuration file
+    Given I read the configuration file './tests/data/invalid-settings.yaml'
+    Then I find that the configuration is invalid because of 'log level', 'listen address' and 'pool size'
+
+
+  Scenario: Reading a setting out of range from the environment
+    Given the environment variable 'MJOLNIR_POOL_SIZE' is '4294967296'
+    When I read the configuration
+    Then I find that the configuration is rejected because of 'MJOLNIR_POOL_SIZE'
@@ -0,0 +1,36 @@
+Feature: Snapshots
+
+  We are checking exploratory queries against the results recorded the first time they ran, and
+  accepting the new results when they changed for the better
+
+  Scenario: Results matching the snapshot
+    Given I am using the in-memory store
+    And the snapshot of 'Paris' / 'Search' at 1 recorded from the bragi results in 'tests/data/bragi-paris.json'
+    And the bragi results in 'tests/data/bragi-paris.json'
+    When I check the step "the results match the snapshot" against the snapshot
+    Then I find that the step passes
+
+  Scenario: Results differing from the snapshot
+    Given I am using the in-memory store
+    And the snapshot of 'Paris' / 'Search' at 1 recorded from the bragi results in 'tests/data/bragi-paris.json'
+    And the bragi results in 'tests/data/bragi-paris-changed.json'
+    When I check the step "the results match the snapshot" against the snapshot
+    Then I find that the step fails with a message containing '1. 'Paris (75000-75116), Île-de-France, France' moved 1112 meters'
+    And I find that the step fails with a message containing '3. expected 'Rue de Paris (Montreuil)' (street), found '20 Rue Hector Malot (Paris)' (house)'
+
+  Scenario: Results within the tolerance of the snapshot
+    Given I am using the in-memory store
+    And the snapshot of 'Paris' / 'Search' at 1 recorded from the bragi results in 'tests/data/bragi-paris.json'
+    And the bragi results in 'tests/data/bragi-paris-changed.json'
+    When I check the step "the first 2 results match the snapshot within 2000 meters" against the snapshot
+    Then I find that the step passes
+
+  Scenario: Accepting the new results
+    Given I am using the in-memory store
+    And the snapshot of 'Paris' / 'Search' at 1 recorded from the bragi results in 'tests/data/bragi-paris.json'
+    When I propose the bragi results in 'tests/data/bragi-paris-changed.json' for the snapshot
+    Then I find that the snapshot has 'Rue de Paris (Montreuil)' at 3
+    And I find that a snapshot cannot be accepted from another step result
+    When I accept the proposed snapshot
+    Then I find that the snapshot has '20 Rue Hector Malot (Paris)' at 3
+    And I find that nothing is pending for the snapshot
@@ -0,0 +1,64 @@
+Feature: Using the in-memory store
+
+  We are evaluating the GraphQL API with the features, environments, datasets, runs, API tokens
+  and audit log kept in memory, so that it does not depend on a database
+
+  Scenario: Loading a feature and finding scenarios in memory
+    Given I am using the in-memory store
+    And I am loading a feature from file './tests/data/example.feature'
+    When I search for the scenarios by id
+    Then I find that I have the correct number of scenarios
+
+  Scenario: Loading a feature and finding steps in memory
+    Given I am using the in-memory store
+    And I am loading a feature from file './tests/data/example.feature'
+    When I search for the steps belonging to the first scenario
+    Then I find that I have the correct number of steps
+
+  Scenario: Loading a feature with multiple scenarios with the same name in memory
+    Given I am using the in-memory store
+    And I am loading an invalid feature from file './tests/data/invalid.feature'
+
+  Scenario: Asking for a run which does not exist in memory
+    Given I am using the in-memory store
+    When I query the run '00000000-0000-0000-0000-000000000000'
+    Then I find an error with the code 'NOT_FOUND'
+
+  Scenario: Removing an item shared by two datasets in memory
+    Given I am using the in-memory store
+    And I have created the osm dataset 'grand-ouest'
+    And I have created the osm dataset 'nord-ouest'
+    And I have added the item 'europe/france/bretagne' to the dataset 'grand-ouest'
+    And I have added the item 'europe/france/bretagne' to the dataset 'nord-ouest'
+    When I remove the item 'europe/france/bretagne' from the dataset 'grand-ouest'
+    Then I find the dataset 'grand-ouest' with the items ''
+    And I find the dataset 'nord-ouest' with the items 'europe/france/bretagne'
+
+  Scenario: Authenticating with a token kept in memory
+    Given I am using the in-memory store
+    And I have an API token with the role 'editor'
+    When I authenticate with the token
+    Then I find that I am authenticated with the role 'editor'
+
+  Scenario: Authenticating with a token revoked in memory
+    Given I am using the in-memory store
+    And I have an API token with the role 'admin'
+    When I revoke the token
+    And I authenticate with the token
+    Then I find that the authentication is rejected
+
+  Scenario: Revoking an API token which does not exist in memory
+    Given I am using the in-memory store
+    When I revoke the API token '00000000-0000-0000-0000-000000000000'
+    Then I find an error with the code 'NOT_FOUND'
+
+  Scenario: Auditing the addition of a feature in memory
+    Given I am using the in-memory store
+    When I add the feature 'Audited feature' through the API
+    Then I find an 'add_feature' audit entry for the feature by 'cucumber'
+    And I find that the audit entry records the feature after the change
+
+  Scenario: Asking for the status of the features repository in memory
+    Given I am using the in-memory store
+    When I ask for the status of the features repository
+    Then I find that the features repository was never synchronised
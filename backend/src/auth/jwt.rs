@@ -0,0 +1,94 @@
+use super::{Principal, Role};
+use crate::{error, settings::AuthSettings};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use std::collections::HashMap;
+
+// The key used to check signatures.
+#[derive(Debug, Clone)]
+enum Key {
+    Secret(Vec<u8>),    // HS256
+    PublicKey(Vec<u8>), // RS256, PEM encoded
+}
+
+/// Validates JWTs (eg issued by an OIDC provider) with a locally configured key.
+/// The principal is the 'sub' claim, and its role is taken from the configured role claim
+/// (viewer if there is none).
+#[derive(Debug, Clone)]
+pub struct JwtValidator {
+    key: Key,
+    issuer: Option<String>,
+    audience: Option<String>,
+    role_claim: String,
+}
+
+/// Returns true if the token looks like a JWT (header.payload.signature).
+pub fn is_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+impl JwtValidator {
+    /// Returns None if no key is configured.
+    pub fn new(settings: &AuthSettings) -> Result<Option<Self>, error::Error> {
+        let key = match (&settings.jwt_secret, &settings.jwt_public_key) {
+            (Some(_), Some(_)) => {
+                return Err(error::Error::ConfigError {
+                    details: String::from("Only one of jwt_secret and jwt_public_key can be set"),
+                })
+            }
+            (Some(secret), None) => Key::Secret(secret.as_bytes().to_vec()),
+            (None, Some(path)) => {
+                let pem = std::fs::read(path).map_err(|err| error::Error::ConfigError {
+                    details: format!("Could not read JWT key '{}': {}", path.display(), err),
+                })?;
+                // Check the key now, rather than on every request.
+                DecodingKey::from_rsa_pem(&pem).map_err(|err| error::Error::ConfigError {
+                    details: format!("Invalid JWT key '{}': {}", path.display(), err),
+                })?;
+                Key::PublicKey(pem)
+            }
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(JwtValidator {
+            key,
+            issuer: settings.jwt_issuer.clone(),
+            audience: settings.jwt_audience.clone(),
+            role_claim: settings.jwt_role_claim.clone(),
+        }))
+    }
+
+    pub fn validate(&self, token: &str) -> Result<Principal, error::Error> {
+        let invalid = |err: jsonwebtoken::errors::Error| error::Error::AuthError {
+            details: format!("Invalid token: {}", err),
+        };
+
+        let (key, algorithm) = match &self.key {
+            Key::Secret(secret) => (DecodingKey::from_secret(secret), Algorithm::HS256),
+            Key::PublicKey(pem) => (
+                DecodingKey::from_rsa_pem(pem).map_err(invalid)?,
+                Algorithm::RS256,
+            ),
+        };
+        let mut validation = Validation::new(algorithm);
+        validation.iss = self.issuer.clone();
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+        }
+
+        let claims =
+            jsonwebtoken::decode::<HashMap<String, serde_json::Value>>(token, &key, &validation)
+                .map_err(invalid)?
+                .claims;
+
+        let name = claims
+            .get("sub")
+            .and_then(|sub| sub.as_str())
+            .ok_or_else(|| error::Error::AuthError {
+                details: String::from("Invalid token: no subject"),
+            })?;
+        let role = match claims.get(&self.role_claim).and_then(|role| role.as_str()) {
+            Some(role) => role.parse()?,
+            None => Role::Viewer,
+        };
+        Ok(Principal::new(name, role))
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{error, gql, settings::AuthSettings};
+use juniper::{GraphQLEnum, GraphQLObject};
+use serde::{Deserialize, Serialize};
+use slog::debug;
+use std::{fmt, str::FromStr};
+
+pub mod jwt;
+pub mod token;
+
+/// What a principal is allowed to do. Roles are ordered, and each role can do everything the
+/// previous ones can:
+/// - viewer: read anything, and subscribe to notifications,
+/// - editor: add, load, import and delete features,
+/// - operator: run scenarios, prepare environments, synchronise the features repository,
+/// - admin: manage API tokens.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    GraphQLEnum,
+)]
+#[sqlx(rename = "role")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[sqlx(rename = "viewer")]
+    Viewer,
+    #[sqlx(rename = "editor")]
+    Editor,
+    #[sqlx(rename = "operator")]
+    Operator,
+    #[sqlx(rename = "admin")]
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let role = match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        };
+        write!(f, "{}", role)
+    }
+}
+
+impl FromStr for Role {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(error::Error::UserError {
+                details: format!("Unknown role '{}'", s),
+            }),
+        }
+    }
+}
+
+/// Who is making a request, and what they are allowed to do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+impl Principal {
+    pub fn new(name: &str, role: Role) -> Self {
+        Principal {
+            name: String::from(name),
+            role,
+        }
+    }
+
+    /// The principal of requests without credentials, when they are allowed.
+    pub fn anonymous() -> Self {
+        Principal::new("anonymous", Role::Viewer)
+    }
+
+    /// Succeeds if the principal has at least the given role.
+    pub fn authorize(&self, role: Role) -> Result<(), error::Error> {
+        if self.role >= role {
+            Ok(())
+        } else {
+            Err(error::Error::AuthError {
+                details: format!(
+                    "'{}' has the role {}, but this requires the role {}",
+                    self.name, self.role, role
+                ),
+            })
+        }
+    }
+}
+
+/// Turns the credentials of a request into a principal.
+/// Credentials are either an API token, or a JWT if a key is configured.
+#[derive(Debug, Clone)]
+pub struct Authenticator {
+    anonymous: bool,
+    jwt: Option<jwt::JwtValidator>,
+}
+
+impl Authenticator {
+    pub fn new(settings: &AuthSettings) -> Result<Self, error::Error> {
+        Ok(Authenticator {
+            anonymous: settings.anonymous,
+            jwt: jwt::JwtValidator::new(settings)?,
+        })
+    }
+
+    /// Authenticate the credentials, which can be given as 'Bearer <token>', or just '<token>'.
+    pub async fn authenticate(
+        &self,
+        credentials: Option<&str>,
+        context: &gql::Context,
+    ) -> Result<Principal, error::Error> {
+        let token = credentials.map(|c| c.trim_start_matches("Bearer ").trim());
+        match token {
+            None | Some("") => {
+                if self.anonymous {
+                    Ok(Principal::anonymous())
+                } else {
+                    Err(error::Error::AuthError {
+                        details: String::from("Authentication required"),
+                    })
+                }
+            }
+            Some(token) => {
+                let principal = match &self.jwt {
+                    Some(jwt) if jwt::is_jwt(token) => jwt.validate(token)?,
+                    _ => context
+                        .store
+                        .tokens
+                        .authenticate_api_token(token, context)
+                        .await?
+                        .map(|token| Principal::new(&token.name, token.role))
+                        .ok_or_else(|| error::Error::AuthError {
+                            details: String::from("Invalid token"),
+                        })?,
+                };
+                debug!(
+                    context.logger,
+                    "Authenticated '{}' ({})", principal.name, principal.role
+                );
+                Ok(principal)
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use super::Role;
+use crate::{error, gql, utils::timing::Timed};
+use chrono::prelude::*;
+use juniper::GraphQLObject;
+use serde::{Deserialize, Serialize};
+use slog::info;
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+use uuid::Uuid;
+
+/// An API token, as stored in the database (only its hash is kept).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// This should match the main.return_api_token_type
+impl<'c> FromRow<'c, PgRow<'c>> for ApiToken {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(ApiToken {
+            id: row.get(0),
+            name: row.get(1),
+            role: row.get(2),
+            created_at: row.get(3),
+            last_used_at: row.get(4),
+            revoked_at: row.get(5),
+        })
+    }
+}
+
+/// A newly created API token. This is the only time the token itself is available.
+#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct NewApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub role: Role,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// This should match the main.return_new_api_token_type
+impl<'c> FromRow<'c, PgRow<'c>> for NewApiToken {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(NewApiToken {
+            id: row.get(0),
+            name: row.get(1),
+            role: row.get(2),
+            token: row.get(3),
+            created_at: row.get(4),
+        })
+    }
+}
+
+pub async fn create_api_token(
+    name: &str,
+    role: Role,
+    context: &gql::Context,
+) -> Result<NewApiToken, error::Error> {
+    info!(
+        context.logger,
+        "Creating API token '{}' with role {}", name, role
+    );
+    sqlx::query_as("SELECT * FROM main.create_api_token($1, $2)")
+        .bind(name)
+        .bind(role)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "token::create_api_token")
+        .await
+        .context(error::DBError {
+            details: format!("Could not create API token '{}'", name),
+        })
+}
+
+pub async fn revoke_api_token(id: &Uuid, context: &gql::Context) -> Result<ApiToken, error::Error> {
+    info!(context.logger, "Revoking API token '{}'", id);
+    let res: Option<ApiToken> = sqlx::query_as("SELECT * FROM main.revoke_api_token($1)")
+        .bind(id)
+        .fetch_optional(context.pool()?)
+        .timed(&context.logger, "token::revoke_api_token")
+        .await
+        .context(error::DBError {
+            details: format!("Could not revoke API token '{}'", id),
+        })?;
+
+    // No row is returned for an unknown token, which is reported like any missing row.
+    res.ok_or(sqlx::Error::RowNotFound).context(error::DBError {
+        details: format!("Could not find API token '{}'", id),
+    })
+}
+
+pub async fn fetch_api_tokens(context: &gql::Context) -> Result<Vec<ApiToken>, error::Error> {
+    sqlx::query_as(
+        "SELECT id, name, role, created_at, last_used_at, revoked_at
+        FROM main.api_tokens ORDER BY name",
+    )
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "token::fetch_api_tokens")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve API tokens",
+    })
+}
+
+/// Return the token matching the given (clear) token, if it has not been revoked, and record that
+/// it was used.
+pub async fn authenticate_api_token(
+    token: &str,
+    context: &gql::Context,
+) -> Result<Option<ApiToken>, error::Error> {
+    sqlx::query_as("SELECT * FROM main.authenticate_api_token($1)")
+        .bind(token)
+        .fetch_optional(context.pool()?)
+        .timed(&context.logger, "token::authenticate_api_token")
+        .await
+        .context(error::DBError {
+            details: "Could not check API token",
+        })
+}
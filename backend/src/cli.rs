@@ -0,0 +1,753 @@
+use serde_json::json;
+use slog::{o, Discard, Logger};
+use snafu::ResultExt;
+use std::{
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+};
+use structopt::StructOpt;
+
+use mjolnir::{
+    self,
+    auth::{Principal, Role},
+    error, gql,
+    load::{self, corpus},
+    model::features::import,
+    runner::steps,
+    settings::Settings,
+    shutdown::Shutdown,
+    store::Store,
+    utils::archive::FeatureSource,
+};
+
+// Exit codes, so that CI can tell a failed run from a broken one.
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FAILURE: i32 = 1; // scenarios failed, invalid features, environments not available
+const EXIT_ERROR: i32 = 2; // could not do what was asked
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "mjolnir-cli",
+    about = "Load, run and report on mjolnir features"
+)]
+struct Opt {
+    /// URL of a mjolnir server (eg http://localhost:3030). If not given, the database is used
+    /// directly, through DATABASE_URL.
+    #[structopt(short, long, env = "MJOLNIR_SERVER")]
+    server: Option<String>,
+
+    /// API token (or JWT) used to authenticate with the server.
+    #[structopt(short, long, env = "MJOLNIR_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Configuration file (TOML or YAML), used when there is no server.
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Load (or reload) features from '.feature' files, or directories of '.feature' files
+    Load {
+        #[structopt(parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Check that features parse, and that all their steps are understood by the runner
+    Validate {
+        #[structopt(parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// List the features, with their scenarios
+    List,
+    /// Run the scenarios matching any of the tags (all of them if no tag is given)
+    Run {
+        #[structopt(short, long)]
+        tags: Vec<String>,
+        /// bragi to run the scenarios against, instead of the configured one
+        #[structopt(long)]
+        bragi_url: Option<String>,
+    },
+    /// Run the scenarios matching any of the tags as a benchmark, and print the latency of the
+    /// searches of each scenario
+    Benchmark {
+        #[structopt(short, long)]
+        tags: Vec<String>,
+        /// bragi to run the scenarios against, instead of the configured one
+        #[structopt(long)]
+        bragi_url: Option<String>,
+        /// Times each search is replayed
+        #[structopt(short, long, default_value = "10")]
+        iterations: i32,
+        /// Requests sent at a time
+        #[structopt(long, default_value = "1")]
+        concurrency: i32,
+        /// Run to compare with
+        #[structopt(short, long)]
+        baseline: Option<String>,
+    },
+    /// Run the scenarios matching any of the tags against two bragi, and print the changes
+    Compare {
+        baseline_url: String,
+        candidate_url: String,
+        #[structopt(short, long)]
+        tags: Vec<String>,
+    },
+    /// List the scenarios which both passed and failed against the same environment and version
+    /// of bragi, or which only passed once attempted again
+    Flaky,
+    /// Turn bragi access logs (common log format, or JSON lines) into a corpus of searches
+    ImportLog {
+        #[structopt(parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
+        /// File the corpus is written to, instead of the standard output
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Replay a corpus of searches against bragi at the given rate, and print the latencies
+    LoadTest {
+        #[structopt(parse(from_os_str))]
+        corpus: PathBuf,
+        #[structopt(long, env = "BRAGI_URL")]
+        bragi_url: String,
+        /// Requests per second
+        #[structopt(short, long, default_value = "10")]
+        rate: f64,
+        /// Requests to send, going through the corpus again if need be (the size of the corpus
+        /// by default)
+        #[structopt(short = "n", long)]
+        requests: Option<usize>,
+    },
+    /// Print the report of a run (the last one by default)
+    Report {
+        #[structopt(short, long, default_value = "junit", possible_values = &["junit", "cucumber-json", "comparison"])]
+        format: String,
+        #[structopt(short, long)]
+        run: Option<String>,
+        /// Run to compare with, for the comparison format
+        #[structopt(short, long)]
+        baseline: Option<String>,
+    },
+    /// Environments
+    Env {
+        #[structopt(subcommand)]
+        cmd: EnvCommand,
+    },
+    /// API tokens (admin only)
+    Token {
+        #[structopt(subcommand)]
+        cmd: TokenCommand,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum EnvCommand {
+    /// Print the status of all the environments
+    Status,
+}
+
+#[derive(Debug, StructOpt)]
+enum TokenCommand {
+    /// Create a token, and print it. It cannot be retrieved later.
+    Create {
+        name: String,
+        #[structopt(short, long, default_value = "viewer", possible_values = &["viewer", "editor", "operator", "admin"])]
+        role: String,
+    },
+    /// List the tokens
+    List,
+    /// Revoke a token
+    Revoke { id: String },
+}
+
+// How we talk to mjolnir: through a server, or directly with the database.
+enum Backend {
+    Remote { url: String, token: Option<String> },
+    Local(gql::Context),
+}
+
+impl Backend {
+    async fn new(
+        server: Option<String>,
+        token: Option<String>,
+        config: Option<PathBuf>,
+        logger: Logger,
+    ) -> Result<Self, error::Error> {
+        match server {
+            Some(url) => Ok(Backend::Remote {
+                url: format!("{}/graphql", url.trim_end_matches('/')),
+                token,
+            }),
+            None => {
+                // In local mode, we use the same configuration as the server. Whoever can
+                // reach the database directly can do anything, so we act as an admin.
+                mjolnir::read_dotenv(logger.clone()).await?;
+                let settings = Settings::new(config.as_deref())?;
+                settings.validate()?;
+                let pool = mjolnir::connect_db(&settings.database, logger.clone()).await?;
+                Ok(Backend::Local(gql::Context {
+                    pool: Some(pool),
+                    logger,
+                    settings: Arc::new(settings),
+                    principal: Principal::new("mjolnir-cli", Role::Admin),
+                    shutdown: Shutdown::default(),
+                    store: Store::postgres(),
+                }))
+            }
+        }
+    }
+
+    // Execute the GraphQL query, and return its data. GraphQL errors are turned into UserError.
+    async fn query(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<serde_json::Value, error::Error> {
+        let request = json!({ "query": query, "variables": variables });
+        let response: serde_json::Value = match self {
+            Backend::Remote { url, token } => {
+                let mut req = reqwest::Client::new()
+                    .post(url)
+                    .header("Content-Type", "application/json");
+                if let Some(token) = token {
+                    req = req.header("Authorization", format!("Bearer {}", token));
+                }
+                let body = req
+                    .body(request.to_string())
+                    .send()
+                    .await
+                    .context(error::ReqwestError {
+                        details: format!("Could not reach {}", url),
+                    })?
+                    .text()
+                    .await
+                    .context(error::ReqwestError {
+                        details: format!("Could not read response from {}", url),
+                    })?;
+                serde_json::from_str(&body).context(error::SerdeJsonError {
+                    details: "Could not parse GraphQL response",
+                })?
+            }
+            Backend::Local(context) => {
+                let request: juniper::http::GraphQLRequest = serde_json::from_value(request)
+                    .context(error::SerdeJsonError {
+                        details: "Could not build GraphQL request",
+                    })?;
+                let schema = gql::schema();
+                let response = request.execute(&schema, context).await;
+                serde_json::to_value(&response).context(error::SerdeJsonError {
+                    details: "Could not serialize GraphQL response",
+                })?
+            }
+        };
+
+        // Requests rejected by the server (eg unauthorized) don't get a GraphQL response.
+        if let Some(error) = response["error"].as_str() {
+            return Err(error::Error::UserError {
+                details: String::from(error),
+            });
+        }
+
+        match response["errors"].as_array() {
+            Some(errors) if !errors.is_empty() => Err(error::Error::UserError {
+                details: errors
+                    .iter()
+                    .map(|e| e["message"].as_str().unwrap_or("unknown error").to_string())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            }),
+            _ => Ok(response["data"].clone()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let opt = Opt::from_args();
+    let logger = Logger::root(Discard, o!());
+
+    let code = run(opt, logger).await.unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        EXIT_ERROR
+    });
+    process::exit(code);
+}
+
+async fn run(opt: Opt, logger: Logger) -> Result<i32, error::Error> {
+    // Validation does not need a server or a database, so we only connect when needed.
+    let (server, token, config) = (opt.server, opt.token, opt.config);
+    let backend = || {
+        Backend::new(
+            server.clone(),
+            token.clone(),
+            config.clone(),
+            logger.clone(),
+        )
+    };
+
+    match opt.cmd {
+        Command::Load { paths } => load(&backend().await?, &paths).await,
+        Command::Validate { paths } => validate(&paths).await,
+        Command::List => list(&backend().await?).await,
+        Command::Run { tags, bragi_url } => run_scenarios(&backend().await?, tags, bragi_url).await,
+        Command::Benchmark {
+            tags,
+            bragi_url,
+            iterations,
+            concurrency,
+            baseline,
+        } => {
+            benchmark(
+                &backend().await?,
+                tags,
+                bragi_url,
+                iterations,
+                concurrency,
+                baseline,
+            )
+            .await
+        }
+        Command::Compare {
+            baseline_url,
+            candidate_url,
+            tags,
+        } => compare(&backend().await?, baseline_url, candidate_url, tags).await,
+        Command::Flaky => flaky(&backend().await?).await,
+        Command::ImportLog { paths, output } => import_log(&paths, output).await,
+        Command::LoadTest {
+            corpus,
+            bragi_url,
+            rate,
+            requests,
+        } => load_test(&corpus, &bragi_url, rate, requests).await,
+        Command::Report {
+            format,
+            run,
+            baseline,
+        } => report(&backend().await?, &format, run, baseline).await,
+        Command::Env {
+            cmd: EnvCommand::Status,
+        } => env_status(&backend().await?).await,
+        Command::Token { cmd } => tokens(&backend().await?, cmd).await,
+    }
+}
+
+// Read all the feature files designated by paths (files, or directories searched recursively).
+async fn read_sources(paths: &[PathBuf]) -> Result<Vec<FeatureSource>, error::Error> {
+    let mut sources = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found = import::read_feature_files(path.clone()).await?;
+            for source in found.iter_mut() {
+                source.filename = path.join(&source.filename).display().to_string();
+            }
+            sources.append(&mut found);
+        } else {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .context(error::TokioIOError)?;
+            sources.push(FeatureSource {
+                filename: path.display().to_string(),
+                content,
+            });
+        }
+    }
+    Ok(sources)
+}
+
+async fn load(backend: &Backend, paths: &[PathBuf]) -> Result<i32, error::Error> {
+    let mut code = EXIT_SUCCESS;
+    for source in read_sources(paths).await? {
+        let res = backend
+            .query(
+                "mutation($feature: String!) { loadFeature(feature: $feature) { id name } }",
+                json!({ "feature": source.content }),
+            )
+            .await;
+        match res {
+            Ok(data) => println!(
+                "{}: loaded '{}' ({})",
+                source.filename,
+                data["loadFeature"]["name"].as_str().unwrap_or(""),
+                data["loadFeature"]["id"].as_str().unwrap_or("")
+            ),
+            Err(err) => {
+                println!("{}: {}", source.filename, err);
+                code = EXIT_FAILURE;
+            }
+        }
+    }
+    Ok(code)
+}
+
+async fn validate(paths: &[PathBuf]) -> Result<i32, error::Error> {
+    let mut code = EXIT_SUCCESS;
+    for source in read_sources(paths).await? {
+        let feature = match gherkin_rust::Feature::parse(source.content) {
+            Ok(feature) => feature,
+            Err(err) => {
+                println!("{}: {}", source.filename, err);
+                code = EXIT_FAILURE;
+                continue;
+            }
+        };
+
+        let background = feature.background.iter().map(|b| ("Background", &b.steps));
+        let scenarios = feature
+            .scenarios
+            .iter()
+            .map(|s| (s.name.as_str(), &s.steps));
+        let mut undefined = 0;
+        for (name, steps) in background.chain(scenarios) {
+            for step in steps.iter() {
+                if steps::parse_step(&step.value).is_none() {
+                    println!(
+                        "{}: {}: undefined step '{}'",
+                        source.filename, name, step.value
+                    );
+                    undefined += 1;
+                }
+            }
+        }
+
+        if undefined == 0 {
+            println!("{}: ok", source.filename);
+        } else {
+            code = EXIT_FAILURE;
+        }
+    }
+    Ok(code)
+}
+
+async fn list(backend: &Backend) -> Result<i32, error::Error> {
+    let data = backend
+        .query("{ features { id name tags } }", json!({}))
+        .await?;
+    for feature in data["features"].as_array().into_iter().flatten() {
+        let id = feature["id"].as_str().unwrap_or("");
+        println!(
+            "{} {}{}",
+            id,
+            feature["name"].as_str().unwrap_or(""),
+            tags(&feature["tags"])
+        );
+        let scenarios = backend
+            .query(
+                "query($id: Uuid!) { scenarios(id: $id) { name tags } }",
+                json!({ "id": id }),
+            )
+            .await?;
+        for scenario in scenarios["scenarios"].as_array().into_iter().flatten() {
+            println!(
+                "    {}{}",
+                scenario["name"].as_str().unwrap_or(""),
+                tags(&scenario["tags"])
+            );
+        }
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+async fn flaky(backend: &Backend) -> Result<i32, error::Error> {
+    let data = backend
+        .query(
+            "{ flakyScenarios { featureName scenarioName environment geocoderVersion passed failed retried } }",
+            json!({}),
+        )
+        .await?;
+    for scenario in data["flakyScenarios"].as_array().into_iter().flatten() {
+        println!(
+            "{} / {}: {} passed ({} at a later attempt), {} failed, environment {}, bragi {}",
+            scenario["featureName"].as_str().unwrap_or(""),
+            scenario["scenarioName"].as_str().unwrap_or(""),
+            scenario["passed"],
+            scenario["retried"],
+            scenario["failed"],
+            scenario["environment"].as_str().unwrap_or("none"),
+            scenario["geocoderVersion"].as_str().unwrap_or("unknown")
+        );
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+// Format a JSON list of tags for display.
+fn tags(tags: &serde_json::Value) -> String {
+    tags.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| tag.as_str())
+        .map(|tag| format!(" @{}", tag.trim_start_matches('@')))
+        .collect()
+}
+
+async fn run_scenarios(
+    backend: &Backend,
+    tags: Vec<String>,
+    bragi_url: Option<String>,
+) -> Result<i32, error::Error> {
+    let data = backend
+        .query(
+            "mutation($tags: [String!], $bragiUrl: String) { runScenarios(tags: $tags, bragiUrl: $bragiUrl) { id status passed failed skipped } }",
+            json!({ "tags": tags, "bragiUrl": bragi_url }),
+        )
+        .await?;
+    Ok(print_run(&data["runScenarios"]))
+}
+
+// Print the outcome of the run, and return the matching exit code.
+fn print_run(run: &serde_json::Value) -> i32 {
+    println!(
+        "Run {}: {} ({} passed, {} failed, {} skipped)",
+        run["id"].as_str().unwrap_or(""),
+        run["status"].as_str().unwrap_or(""),
+        run["passed"],
+        run["failed"],
+        run["skipped"]
+    );
+    match run["status"].as_str() {
+        Some("PASSED") => EXIT_SUCCESS,
+        Some("FAILED") => EXIT_FAILURE,
+        _ => EXIT_ERROR,
+    }
+}
+
+// With a baseline, the comparison report follows the latencies.
+async fn benchmark(
+    backend: &Backend,
+    tags: Vec<String>,
+    bragi_url: Option<String>,
+    iterations: i32,
+    concurrency: i32,
+    baseline: Option<String>,
+) -> Result<i32, error::Error> {
+    let data = backend
+        .query(
+            "mutation($tags: [String!], $bragiUrl: String, $iterations: Int, $concurrency: Int) { runBenchmark(tags: $tags, bragiUrl: $bragiUrl, iterations: $iterations, concurrency: $concurrency) { id status passed failed skipped } }",
+            json!({ "tags": tags, "bragiUrl": bragi_url, "iterations": iterations, "concurrency": concurrency }),
+        )
+        .await?;
+    let run = &data["runBenchmark"];
+    let code = print_run(run);
+    // Without a run, there are no latencies to report.
+    let id = match run["id"].as_str() {
+        Some(id) => String::from(id),
+        None => return Ok(EXIT_ERROR),
+    };
+
+    let data = backend
+        .query(
+            "query($run: Uuid!) { latencyMetrics(runId: $run) { featureName scenarioName requests errorRate p50 p95 p99 throughput } }",
+            json!({ "run": id }),
+        )
+        .await?;
+    for m in data["latencyMetrics"].as_array().into_iter().flatten() {
+        println!(
+            "    {} / {}: p50 {:.1} ms, p95 {:.1} ms, p99 {:.1} ms, {:.1} req/s, {} requests, {:.1}% errors",
+            m["featureName"].as_str().unwrap_or(""),
+            m["scenarioName"].as_str().unwrap_or(""),
+            m["p50"].as_f64().unwrap_or(0.0),
+            m["p95"].as_f64().unwrap_or(0.0),
+            m["p99"].as_f64().unwrap_or(0.0),
+            m["throughput"].as_f64().unwrap_or(0.0),
+            m["requests"],
+            m["errorRate"].as_f64().unwrap_or(0.0) * 100.0
+        );
+    }
+
+    if baseline.is_some() {
+        report(backend, "comparison", Some(id), baseline).await?;
+    }
+    Ok(code)
+}
+
+// Scenarios newly failing against the candidate make the command fail.
+async fn compare(
+    backend: &Backend,
+    baseline_url: String,
+    candidate_url: String,
+    tags: Vec<String>,
+) -> Result<i32, error::Error> {
+    let data = backend
+        .query(
+            "mutation($tags: [String!], $baseline: String!, $candidate: String!) { compareBragi(tags: $tags, baselineUrl: $baseline, candidateUrl: $candidate) { baseline { id } candidate { id } newlyFailing { scenarioName } } }",
+            json!({ "tags": tags, "baseline": baseline_url, "candidate": candidate_url }),
+        )
+        .await?;
+    let comparison = &data["compareBragi"];
+    report(
+        backend,
+        "comparison",
+        comparison["candidate"]["id"].as_str().map(String::from),
+        comparison["baseline"]["id"].as_str().map(String::from),
+    )
+    .await?;
+    match comparison["newlyFailing"].as_array() {
+        Some(failing) if !failing.is_empty() => Ok(EXIT_FAILURE),
+        _ => Ok(EXIT_SUCCESS),
+    }
+}
+
+async fn report(
+    backend: &Backend,
+    format: &str,
+    run: Option<String>,
+    baseline: Option<String>,
+) -> Result<i32, error::Error> {
+    let format = match format {
+        "cucumber-json" => "CUCUMBER_JSON",
+        "comparison" => "COMPARISON",
+        _ => "JUNIT",
+    };
+    let data = backend
+        .query(
+            "query($run: Uuid, $format: ReportFormat!, $baseline: Uuid) { report(runId: $run, format: $format, baseline: $baseline) }",
+            json!({ "run": run, "format": format, "baseline": baseline }),
+        )
+        .await?;
+    println!("{}", data["report"].as_str().unwrap_or(""));
+    Ok(EXIT_SUCCESS)
+}
+
+// Lines which are not searches are listed on stderr, so that the corpus can go to stdout.
+async fn import_log(paths: &[PathBuf], output: Option<PathBuf>) -> Result<i32, error::Error> {
+    let mut queries = Vec::new();
+    for path in paths {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .context(error::TokioIOError)?;
+        let log = corpus::parse_log(&text);
+        for skipped in &log.skipped {
+            eprintln!("{}:{}: {}", path.display(), skipped.line, skipped.reason);
+        }
+        eprintln!(
+            "{}: {} searches, {} lines skipped",
+            path.display(),
+            log.queries.len(),
+            log.skipped.len()
+        );
+        queries.extend(log.queries);
+    }
+
+    let text = corpus::write_corpus(&queries)?;
+    match output {
+        Some(output) => tokio::fs::write(&output, text)
+            .await
+            .context(error::TokioIOError)?,
+        None => print!("{}", text),
+    }
+    Ok(if queries.is_empty() {
+        EXIT_FAILURE
+    } else {
+        EXIT_SUCCESS
+    })
+}
+
+// Requests which failed make the command fail.
+async fn load_test(
+    path: &Path,
+    bragi_url: &str,
+    rate: f64,
+    requests: Option<usize>,
+) -> Result<i32, error::Error> {
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .context(error::TokioIOError)?;
+    let log = corpus::parse_log(&text);
+    if !log.skipped.is_empty() {
+        eprintln!(
+            "{}: {} lines are not searches",
+            path.display(),
+            log.skipped.len()
+        );
+    }
+    let test = load::LoadTest {
+        rate,
+        requests: requests.unwrap_or(log.queries.len()),
+    };
+    let report = load::run(&log.queries, bragi_url, &test).await?;
+    print!("{}", load::render(&report));
+    Ok(if report.errors > 0 {
+        EXIT_FAILURE
+    } else {
+        EXIT_SUCCESS
+    })
+}
+
+async fn env_status(backend: &Backend) -> Result<i32, error::Error> {
+    let data = backend
+        .query("{ environments { id signature status } }", json!({}))
+        .await?;
+    let mut code = EXIT_SUCCESS;
+    for env in data["environments"].as_array().into_iter().flatten() {
+        let status = env["status"].as_str().unwrap_or("");
+        println!(
+            "{} {} {}",
+            env["id"].as_str().unwrap_or(""),
+            env["signature"].as_str().unwrap_or(""),
+            status
+        );
+        if status != "AVAILABLE" {
+            code = EXIT_FAILURE;
+        }
+    }
+    Ok(code)
+}
+
+async fn tokens(backend: &Backend, cmd: TokenCommand) -> Result<i32, error::Error> {
+    match cmd {
+        TokenCommand::Create { name, role } => {
+            let data = backend
+                .query(
+                    "mutation($name: String!, $role: Role!) { createApiToken(name: $name, role: $role) { id token } }",
+                    json!({ "name": name, "role": role.to_uppercase() }),
+                )
+                .await?;
+            let token = &data["createApiToken"];
+            eprintln!(
+                "Created token {} for '{}' ({}). Store it now, it cannot be retrieved later:",
+                token["id"].as_str().unwrap_or(""),
+                name,
+                role
+            );
+            println!("{}", token["token"].as_str().unwrap_or(""));
+        }
+        TokenCommand::List => {
+            let data = backend
+                .query(
+                    "{ apiTokens { id name role lastUsedAt revokedAt } }",
+                    json!({}),
+                )
+                .await?;
+            for token in data["apiTokens"].as_array().into_iter().flatten() {
+                println!(
+                    "{} {} {} last used: {}{}",
+                    token["id"].as_str().unwrap_or(""),
+                    token["name"].as_str().unwrap_or(""),
+                    token["role"].as_str().unwrap_or(""),
+                    token["lastUsedAt"].as_str().unwrap_or("never"),
+                    token["revokedAt"]
+                        .as_str()
+                        .map(|at| format!(" revoked: {}", at))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        TokenCommand::Revoke { id } => {
+            let data = backend
+                .query(
+                    "mutation($id: Uuid!) { revokeApiToken(id: $id) { id name } }",
+                    json!({ "id": id }),
+                )
+                .await?;
+            println!(
+                "Revoked token {} ({})",
+                id,
+                data["revokeApiToken"]["name"].as_str().unwrap_or("")
+            );
+        }
+    }
+    Ok(EXIT_SUCCESS)
+}
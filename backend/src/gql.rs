@@ -1,16 +1,44 @@
-use super::model::{environments, features};
-use crate::get_connstr;
-use futures::Stream;
+use super::model::{audit, environments, features, runs};
+use crate::{
+    auth::{self, Principal, Role},
+    error, report, runner,
+    settings::{self, Settings},
+    shutdown::Shutdown,
+    store::Store,
+};
+use futures::{Stream, StreamExt};
 use juniper::{FieldError, FieldResult, IntoFieldError, RootNode};
 use slog::{debug, info, Logger};
+use snafu::ResultExt;
 use sqlx::postgres::{PgListener, PgPool};
-use std::pin::Pin;
+use std::{pin::Pin, sync::Arc};
 use uuid::Uuid;
 
 #[derive(Debug, Clone)]
 pub struct Context {
-    pub pool: PgPool,
+    /// None when the context only uses the in-memory store.
+    pub pool: Option<PgPool>,
     pub logger: Logger,
+    pub settings: Arc<Settings>,
+    pub principal: Principal,
+    pub shutdown: Shutdown,
+    pub store: Store,
+}
+
+impl Context {
+    /// Return the connection pool, or an error if the context has no database.
+    pub fn pool(&self) -> Result<&PgPool, error::Error> {
+        self.pool.as_ref().ok_or_else(|| error::Error::ConfigError {
+            details: String::from("No database configured"),
+        })
+    }
+
+    /// Succeeds if the principal making the request has at least the given role.
+    pub fn authorize(&self, role: Role) -> FieldResult<()> {
+        self.principal
+            .authorize(role)
+            .map_err(IntoFieldError::into_field_error)
+    }
 }
 
 impl juniper::Context for Context {}
@@ -19,10 +47,46 @@ pub struct Query;
 
 #[juniper::graphql_object(Context = Context)]
 impl Query {
+    /// Return the principal making the request.
+    async fn whoami(&self, context: &Context) -> Principal {
+        context.principal.clone()
+    }
+
+    /// Return all the API tokens (admin only).
+    async fn api_tokens(&self, context: &Context) -> FieldResult<Vec<auth::token::ApiToken>> {
+        debug!(context.logger, "Fetching API tokens");
+        context.authorize(Role::Admin)?;
+        context
+            .store
+            .tokens
+            .fetch_api_tokens(context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the entries of the audit log matching the filter, most recent first (admin only).
+    async fn audit_log(
+        &self,
+        filter: Option<audit::AuditFilter>,
+        context: &Context,
+    ) -> FieldResult<Vec<audit::AuditEntry>> {
+        debug!(context.logger, "Fetching audit log");
+        context.authorize(Role::Admin)?;
+        context
+            .store
+            .audit
+            .fetch_audit_log(filter.unwrap_or_default(), context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
     /// Return a list of all features
     async fn features(&self, context: &Context) -> FieldResult<Vec<features::feature::Feature>> {
         debug!(context.logger, "Fetching All Features");
-        features::feature::fetch_all_features(&context)
+        context
+            .store
+            .features
+            .fetch_all_features(context)
             .await
             .map_err(IntoFieldError::into_field_error)
     }
@@ -40,6 +104,34 @@ impl Query {
     //         .map_err(IntoFieldError::into_field_error)
     // }
 
+    /// Return the revisions of the feature specified by the given id, most recent first.
+    /// Only features loaded from the features repository have revisions.
+    async fn feature_revisions(
+        &self,
+        id: Uuid,
+        context: &Context,
+    ) -> FieldResult<Vec<features::repository::FeatureRevision>> {
+        debug!(context.logger, "Fetching revisions of feature id '{}'", id);
+        context
+            .store
+            .repository
+            .fetch_feature_revisions(&id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the configuration of the features repository, and its last synchronisation.
+    async fn repository_status(
+        &self,
+        context: &Context,
+    ) -> FieldResult<features::repository::RepositoryStatus> {
+        debug!(context.logger, "Fetching repository status");
+        let config = features::repository::RepositoryConfig::from_settings(&context.settings).ok();
+        features::repository::fetch_repository_status(config.as_ref(), context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
     /// Return the scenarios belonging to the feature specified by the given id.
     async fn scenarios(
         &self,
@@ -50,7 +142,10 @@ impl Query {
             context.logger,
             "Fetching scenarios from feature id '{}'", id
         );
-        features::scenario::fetch_scenarios_by_feature_id(&id, &context)
+        context
+            .store
+            .features
+            .fetch_scenarios_by_feature_id(&id, context)
             .await
             .map_err(IntoFieldError::into_field_error)
     }
@@ -65,7 +160,10 @@ impl Query {
             context.logger,
             "Fetching background from feature id '{}'", id
         );
-        features::background::fetch_background_by_feature_id(&id, &context)
+        context
+            .store
+            .features
+            .fetch_background_by_feature_id(&id, context)
             .await
             .map_err(IntoFieldError::into_field_error)
     }
@@ -80,13 +178,19 @@ impl Query {
         match src {
             features::SourceType::Scenario => {
                 debug!(context.logger, "Fetching steps from scenario id '{}'", id);
-                features::step::fetch_steps_by_scenario_id(&id, &context)
+                context
+                    .store
+                    .features
+                    .fetch_steps_by_scenario_id(&id, context)
                     .await
                     .map_err(IntoFieldError::into_field_error)
             }
             features::SourceType::Background => {
                 debug!(context.logger, "Fetching steps from background id '{}'", id);
-                features::step::fetch_steps_by_background_id(&id, &context)
+                context
+                    .store
+                    .features
+                    .fetch_steps_by_background_id(&id, context)
                     .await
                     .map_err(IntoFieldError::into_field_error)
             }
@@ -114,10 +218,13 @@ impl Query {
                     context.logger,
                     "Fetching environment from background id '{}'", id
                 );
-                features::background::fetch_background_environment(&id, &context)
+                context
+                    .store
+                    .environments
+                    .fetch_background_environment(&id, context)
                     .await
                     .map_err(IntoFieldError::into_field_error)
-                    .map(|e| Some(e))
+                    .map(Some)
             }
         }
     }
@@ -128,7 +235,10 @@ impl Query {
         context: &Context,
     ) -> FieldResult<Vec<environments::environment::Environment>> {
         debug!(context.logger, "Fetching All Environments");
-        environments::environment::fetch_all_environments(&context)
+        context
+            .store
+            .environments
+            .fetch_all_environments(context)
             .await
             .map_err(IntoFieldError::into_field_error)
     }
@@ -143,7 +253,174 @@ impl Query {
             context.logger,
             "Fetching indexes from environment id '{}'", id
         );
-        environments::index::fetch_indexes_by_environment_id(&id, &context)
+        context
+            .store
+            .environments
+            .fetch_indexes_by_environment_id(&id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return a list of all runs, most recent first.
+    async fn runs(&self, context: &Context) -> FieldResult<Vec<runs::run::Run>> {
+        debug!(context.logger, "Fetching All Runs");
+        context
+            .store
+            .runs
+            .fetch_all_runs(context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the run corresponding to the given id.
+    async fn run(&self, id: Uuid, context: &Context) -> FieldResult<runs::run::Run> {
+        debug!(context.logger, "Fetching run with id '{}'", id);
+        context
+            .store
+            .runs
+            .fetch_run_by_id(&id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the results of the scenarios executed during the run specified by the given id.
+    async fn scenario_results(
+        &self,
+        id: Uuid,
+        context: &Context,
+    ) -> FieldResult<Vec<runs::run::ScenarioResult>> {
+        debug!(
+            context.logger,
+            "Fetching scenario results from run id '{}'", id
+        );
+        context
+            .store
+            .runs
+            .fetch_scenario_results_by_run_id(&id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the results of the steps belonging to the scenario result specified by the given id.
+    async fn step_results(
+        &self,
+        id: Uuid,
+        context: &Context,
+    ) -> FieldResult<Vec<runs::run::StepResult>> {
+        debug!(
+            context.logger,
+            "Fetching step results from scenario result id '{}'", id
+        );
+        context
+            .store
+            .runs
+            .fetch_step_results_by_scenario_result_id(&id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the search quality metrics (MRR, recall@k, nDCG) of the run specified by the
+    /// given id, for the whole run, or by feature, tag or index type.
+    async fn quality_metrics(
+        &self,
+        run_id: Uuid,
+        group_by: runs::quality::QualityGroup,
+        context: &Context,
+    ) -> FieldResult<Vec<runs::quality::QualityMetrics>> {
+        debug!(
+            context.logger,
+            "Fetching quality metrics of run '{}' by {:?}", run_id, group_by
+        );
+        context
+            .store
+            .runs
+            .fetch_quality_metrics(&run_id, group_by, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the search quality metrics of the group (the whole run if no name is given) in
+    /// the last runs (20 by default), oldest first.
+    async fn quality_trend(
+        &self,
+        group_by: runs::quality::QualityGroup,
+        group_name: Option<String>,
+        limit: Option<i32>,
+        context: &Context,
+    ) -> FieldResult<Vec<runs::quality::QualityMetrics>> {
+        let group_name = group_name.unwrap_or_default();
+        debug!(
+            context.logger,
+            "Fetching quality trend of {:?} '{}'", group_by, group_name
+        );
+        context
+            .store
+            .runs
+            .fetch_quality_trend(group_by, &group_name, limit.unwrap_or(20), context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the latency (p50, p95, p99, throughput and error rate) of the searches of each
+    /// scenario of the benchmark run specified by the given id.
+    async fn latency_metrics(
+        &self,
+        run_id: Uuid,
+        context: &Context,
+    ) -> FieldResult<Vec<runs::latency::LatencyMetrics>> {
+        debug!(
+            context.logger,
+            "Fetching latency metrics of run '{}'", run_id
+        );
+        context
+            .store
+            .runs
+            .fetch_latency_metrics(&run_id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the scenarios which both passed and failed against the same environment and the
+    /// same version of bragi, or which only passed once attempted again.
+    async fn flaky_scenarios(
+        &self,
+        context: &Context,
+    ) -> FieldResult<Vec<runs::flaky::FlakyScenario>> {
+        debug!(context.logger, "Fetching flaky scenarios");
+        context
+            .store
+            .runs
+            .fetch_flaky_scenarios(context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the differences between the results of run b and those of run a: scenarios
+    /// newly passing and newly failing, expected results found at a different rank, and
+    /// searches whose first results changed.
+    async fn compare_runs(
+        &self,
+        a: Uuid,
+        b: Uuid,
+        context: &Context,
+    ) -> FieldResult<report::compare::RunComparison> {
+        debug!(context.logger, "Comparing run '{}' with run '{}'", b, a);
+        report::compare::compare_runs(&a, &b, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)
+    }
+
+    /// Return the report of the run specified by the given id (the last run if no id is given),
+    /// in the given format. The comparison format needs the id of the baseline run.
+    async fn report(
+        &self,
+        run_id: Option<Uuid>,
+        format: report::ReportFormat,
+        baseline: Option<Uuid>,
+        context: &Context,
+    ) -> FieldResult<String> {
+        debug!(context.logger, "Generating {:?} report", format);
+        report::generate_report(run_id, format, baseline, context)
             .await
             .map_err(IntoFieldError::into_field_error)
     }
@@ -160,10 +437,17 @@ impl Mutation {
         context: &Context,
     ) -> FieldResult<features::feature::Feature> {
         debug!(context.logger, "Adding Feature {}", name);
+        context.authorize(Role::Editor)?;
 
-        features::feature::create_or_replace_feature(name, description, tags, &context)
+        let feature = context
+            .store
+            .features
+            .create_or_replace_feature(name, description, tags, context)
             .await
-            .map_err(IntoFieldError::into_field_error)
+            .map_err(IntoFieldError::into_field_error)?;
+        let id = feature.id.to_string();
+        audit::record("add_feature", "feature", &id, None, Some(&feature), context).await;
+        Ok(feature)
     }
 
     async fn load_feature(
@@ -171,21 +455,263 @@ impl Mutation {
         context: &Context,
     ) -> FieldResult<features::feature::Feature> {
         debug!(context.logger, "Loading Feature from string");
+        context.authorize(Role::Editor)?;
+
+        let feature =
+            features::feature::parse_feature(feature).map_err(IntoFieldError::into_field_error)?;
+        let feature = context
+            .store
+            .features
+            .create_or_replace_feature_from_gherkin(feature, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)?;
+        let id = feature.id.to_string();
+        audit::record(
+            "load_feature",
+            "feature",
+            &id,
+            None,
+            Some(&feature),
+            context,
+        )
+        .await;
+        Ok(feature)
+    }
+
+    /// Load all the '.feature' files found under the given directory, on the server. The
+    /// directory must be within one of the configured import paths.
+    /// In atomic mode, either all the features are loaded, or none are.
+    async fn import_features(
+        path: String,
+        atomic: Option<bool>,
+        context: &Context,
+    ) -> FieldResult<features::import::ImportReport> {
+        debug!(context.logger, "Importing Features from '{}'", path);
+        context.authorize(Role::Editor)?;
+
+        let report =
+            features::import::import_features_from_dir(&path, atomic.unwrap_or(false), context)
+                .await
+                .map_err(IntoFieldError::into_field_error)?;
+        audit::record(
+            "import_features",
+            "directory",
+            &path,
+            None,
+            Some(&report),
+            context,
+        )
+        .await;
+        Ok(report)
+    }
+
+    /// Synchronise the features with the features repository: features are loaded from every
+    /// '.feature' file, and the features whose file was removed are deleted.
+    async fn sync_repository(
+        context: &Context,
+    ) -> FieldResult<features::repository::RepositorySync> {
+        debug!(context.logger, "Synchronising features repository");
+        context.authorize(Role::Operator)?;
+        let config = features::repository::RepositoryConfig::from_settings(&context.settings)
+            .map_err(IntoFieldError::into_field_error)?;
+
+        let sync = features::repository::sync_repository(&config, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)?;
+        let url = settings::redact_url(&config.url);
+        audit::record(
+            "sync_repository",
+            "repository",
+            &url,
+            None,
+            Some(&sync),
+            context,
+        )
+        .await;
+        Ok(sync)
+    }
+
+    /// Run the scenarios matching any of the given tags (all of them if there are no tags)
+    /// against bragi (the configured one if no url is given), and record their results.
+    async fn run_scenarios(
+        tags: Option<Vec<String>>,
+        bragi_url: Option<String>,
+        context: &Context,
+    ) -> FieldResult<runs::run::Run> {
+        debug!(context.logger, "Running scenarios with tags {:?}", tags);
+        context.authorize(Role::Operator)?;
+
+        let run = runner::run_scenarios(tags.unwrap_or_default(), bragi_url, None, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)?;
+        let id = run.id.to_string();
+        audit::record("run_scenarios", "run", &id, None, Some(&run), context).await;
+        Ok(run)
+    }
+
+    /// Run the scenarios matching any of the given tags as a benchmark: each search is replayed
+    /// the given number of times (10 by default), with at most concurrency requests at a time (1
+    /// by default), and the latency of the searches of each scenario is recorded.
+    async fn run_benchmark(
+        tags: Option<Vec<String>>,
+        bragi_url: Option<String>,
+        iterations: Option<i32>,
+        concurrency: Option<i32>,
+        context: &Context,
+    ) -> FieldResult<runs::run::Run> {
+        debug!(context.logger, "Running benchmark with tags {:?}", tags);
+        context.authorize(Role::Operator)?;
+
+        let benchmark = runner::benchmark::Benchmark::new(iterations, concurrency)
+            .map_err(IntoFieldError::into_field_error)?;
+        let run = runner::run_scenarios(
+            tags.unwrap_or_default(),
+            bragi_url,
+            Some(benchmark),
+            context,
+        )
+        .await
+        .map_err(IntoFieldError::into_field_error)?;
+        let id = run.id.to_string();
+        audit::record("run_benchmark", "run", &id, None, Some(&run), context).await;
+        Ok(run)
+    }
+
+    /// Run the scenarios matching any of the given tags against two bragi, one after the
+    /// other, and compare the results of the candidate with those of the baseline.
+    async fn compare_bragi(
+        tags: Option<Vec<String>>,
+        baseline_url: String,
+        candidate_url: String,
+        context: &Context,
+    ) -> FieldResult<report::compare::RunComparison> {
+        debug!(
+            context.logger,
+            "Comparing {} with {} on tags {:?}", candidate_url, baseline_url, tags
+        );
+        context.authorize(Role::Operator)?;
 
-        features::feature::create_or_replace_feature_from_string(feature, &context)
+        let tags = tags.unwrap_or_default();
+        let mut runs = Vec::new();
+        for bragi_url in &[baseline_url, candidate_url] {
+            let run = runner::run_scenarios(tags.clone(), Some(bragi_url.clone()), None, context)
+                .await
+                .map_err(IntoFieldError::into_field_error)?;
+            let id = run.id.to_string();
+            audit::record("run_scenarios", "run", &id, None, Some(&run), context).await;
+            runs.push(run.id);
+        }
+        report::compare::compare_runs(&runs[0], &runs[1], context)
             .await
             .map_err(IntoFieldError::into_field_error)
     }
 
+    /// Accept the results found by the step result specified by the given id, which did not
+    /// match the snapshot of its step, as the new snapshot.
+    async fn accept_snapshot(
+        step_id: Uuid,
+        context: &Context,
+    ) -> FieldResult<runs::snapshot::Snapshot> {
+        debug!(context.logger, "Accepting snapshot from step '{}'", step_id);
+        context.authorize(Role::Editor)?;
+
+        let snapshot = context
+            .store
+            .runs
+            .accept_snapshot(&step_id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)?;
+        let id = step_id.to_string();
+        audit::record(
+            "accept_snapshot",
+            "snapshot",
+            &id,
+            None,
+            Some(&snapshot),
+            context,
+        )
+        .await;
+        Ok(snapshot)
+    }
+
+    /// Create an API token with the given role (admin only). The token is only returned once.
+    async fn create_api_token(
+        name: String,
+        role: Role,
+        context: &Context,
+    ) -> FieldResult<auth::token::NewApiToken> {
+        debug!(context.logger, "Creating API token '{}'", name);
+        context.authorize(Role::Admin)?;
+
+        let token = context
+            .store
+            .tokens
+            .create_api_token(&name, role, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)?;
+        // The token itself must not be recorded.
+        let id = token.id.to_string();
+        let after = serde_json::json!({ "id": token.id, "name": token.name, "role": token.role });
+        audit::record(
+            "create_api_token",
+            "api_token",
+            &id,
+            None,
+            Some(&after),
+            context,
+        )
+        .await;
+        Ok(token)
+    }
+
+    /// Revoke the API token specified by the given id (admin only).
+    async fn revoke_api_token(id: Uuid, context: &Context) -> FieldResult<auth::token::ApiToken> {
+        debug!(context.logger, "Revoking API token '{}'", id);
+        context.authorize(Role::Admin)?;
+
+        let token = context
+            .store
+            .tokens
+            .revoke_api_token(&id, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)?;
+        let id = id.to_string();
+        audit::record(
+            "revoke_api_token",
+            "api_token",
+            &id,
+            None,
+            Some(&token),
+            context,
+        )
+        .await;
+        Ok(token)
+    }
+
     async fn delete_feature(
         id: Uuid,
         context: &Context,
     ) -> FieldResult<features::feature::Feature> {
         debug!(context.logger, "Dropping Feature '{}'", id);
+        context.authorize(Role::Editor)?;
 
-        features::feature::delete_feature_by_id(id, &context)
+        let feature = context
+            .store
+            .features
+            .delete_feature_by_id(id, context)
             .await
-            .map_err(IntoFieldError::into_field_error)
+            .map_err(IntoFieldError::into_field_error)?;
+        let id = id.to_string();
+        audit::record(
+            "delete_feature",
+            "feature",
+            &id,
+            Some(&feature),
+            None,
+            context,
+        )
+        .await;
+        Ok(feature)
     }
 
     // This function returns the environment that correspond to the background specified by 'id'
@@ -195,9 +721,54 @@ impl Mutation {
         context: &Context,
     ) -> FieldResult<environments::environment::Environment> {
         debug!(context.logger, "Retrieving Background Environment '{}'", id);
-        features::background::fetch_background_environment(&id, &context)
+        context.authorize(Role::Operator)?;
+        let environment = context
+            .store
+            .environments
+            .fetch_background_environment(&id, context)
             .await
-            .map_err(IntoFieldError::into_field_error)
+            .map_err(IntoFieldError::into_field_error)?;
+        let env_id = environment.id.to_string();
+        audit::record(
+            "background_environment",
+            "environment",
+            &env_id,
+            None,
+            Some(&environment),
+            context,
+        )
+        .await;
+        Ok(environment)
+    }
+
+    /// Start downloading the item of the dataset from the location configured for its data
+    /// source. The item is returned while its download is in progress: its status tells when
+    /// the file is available, or if the download failed.
+    async fn download_dataset_item(
+        dataset: String,
+        item: String,
+        context: &Context,
+    ) -> FieldResult<environments::dataset::Item> {
+        debug!(
+            context.logger,
+            "Downloading item '{}' of dataset '{}'", item, dataset
+        );
+        context.authorize(Role::Operator)?;
+
+        let item = environments::dataset::download_dataset_item(&dataset, &item, context)
+            .await
+            .map_err(IntoFieldError::into_field_error)?;
+        let id = format!("{}/{}", item.data_source, item.id);
+        audit::record(
+            "download_dataset_item",
+            "dataset_item",
+            &id,
+            None,
+            Some(&item),
+            context,
+        )
+        .await;
+        Ok(item)
     }
 }
 
@@ -209,25 +780,49 @@ pub struct Subscription;
 impl Subscription {
     async fn notifications(context: &Context) -> PayloadStream {
         info!(context.logger, "Subscribing to database notifications");
-        let connstr = get_connstr(context.logger.clone())
-            .await
-            .expect("connection");
-        let mut listener = PgListener::new(&connstr).await.unwrap();
-        listener.listen("notifications").await.unwrap();
-        let logger = context.logger.clone();
-        let stream = listener.into_stream().map(move |i| {
-            let n = i.unwrap(); // FIXME
-            info!(logger, "Received Postgres Notification {}", n.payload());
-            // let feature: features::feature::Feature = serde_json::from_str(n.payload())
-            //     .expect("Pg Notification should send deseriazable message");
-            Ok(String::from(n.payload()))
-        });
-
-        Box::pin(stream)
+        let listening = match context.authorize(Role::Viewer) {
+            Ok(()) => listen(context)
+                .await
+                .map_err(IntoFieldError::into_field_error),
+            Err(err) => Err(err),
+        };
+        match listening {
+            Ok(stream) => stream,
+            Err(err) => Box::pin(futures::stream::once(async { Err(err) })),
+        }
     }
 }
 
-type Schema = RootNode<'static, Query, Mutation, Subscription>;
+// Listen to the notifications sent by the database. Not being able to listen, or to receive
+// a notification, means the database is unavailable, which is reported to the subscriber.
+async fn listen(context: &Context) -> Result<PayloadStream, error::Error> {
+    let connstr = context.settings.database.url()?;
+    let mut listener = PgListener::new(connstr)
+        .await
+        .context(error::NotificationError {
+            details: "Could not connect to the database",
+        })?;
+    listener
+        .listen("notifications")
+        .await
+        .context(error::NotificationError {
+            details: "Could not listen to notifications",
+        })?;
+    let logger = context.logger.clone();
+    let stream = listener.into_stream().map(move |n| {
+        let n = n
+            .context(error::NotificationError {
+                details: "Could not receive notification",
+            })
+            .map_err(IntoFieldError::into_field_error)?;
+        info!(logger, "Received Postgres Notification {}", n.payload());
+        Ok(String::from(n.payload()))
+    });
+
+    Ok(Box::pin(stream))
+}
+
+pub type Schema = RootNode<'static, Query, Mutation, Subscription>;
 
 pub fn schema() -> Schema {
     Schema::new(Query, Mutation, Subscription)
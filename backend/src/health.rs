@@ -0,0 +1,128 @@
+use crate::{settings::Settings, utils::timing::Timed};
+use serde::Serialize;
+use slog::{info, warn, Logger};
+use sqlx::postgres::{PgListener, PgPool};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// How long to wait before reconnecting the notifications listener.
+const LISTENER_RETRY: Duration = Duration::from_secs(5);
+
+/// The result of one readiness check.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub message: Option<String>,
+}
+
+impl Check {
+    fn new(name: &str, res: Result<(), String>) -> Self {
+        Check {
+            name: String::from(name),
+            ok: res.is_ok(),
+            message: res.err(),
+        }
+    }
+}
+
+/// Whether mjolnir can serve requests, and the checks it is based on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Tracks whether we are connected to the database notifications, which the subscriptions rely on.
+#[derive(Debug, Clone, Default)]
+pub struct ListenerStatus(Arc<AtomicBool>);
+
+impl ListenerStatus {
+    pub fn is_connected(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn set_connected(&self, connected: bool) {
+        self.0.store(connected, Ordering::SeqCst)
+    }
+}
+
+/// Keep a listener on the notifications channel, reconnecting when the connection is lost,
+/// and report its state in status.
+pub async fn monitor_listener(url: String, status: ListenerStatus, logger: Logger) {
+    loop {
+        match PgListener::new(&url).await {
+            Ok(mut listener) => match listener.listen("notifications").await {
+                Ok(()) => {
+                    info!(logger, "Listening to database notifications");
+                    status.set_connected(true);
+                    // Notifications are consumed by each subscription, this only checks
+                    // the connection is alive.
+                    while let Ok(_notification) = listener.recv().await {}
+                    warn!(logger, "Lost connection to database notifications");
+                }
+                Err(err) => warn!(logger, "Could not listen to notifications: {}", err),
+            },
+            Err(err) => warn!(
+                logger,
+                "Could not connect to database notifications: {}", err
+            ),
+        }
+        status.set_connected(false);
+        tokio::time::delay_for(LISTENER_RETRY).await;
+    }
+}
+
+/// Check the database is reachable, the work dir (if any) is writable, and the notifications
+/// listener is connected.
+pub async fn readiness(
+    pool: &PgPool,
+    settings: &Settings,
+    listener: &ListenerStatus,
+    logger: &Logger,
+) -> Readiness {
+    let database = sqlx::query("SELECT 1")
+        .execute(pool)
+        .timed(logger, "health::readiness")
+        .await
+        .map(|_| ())
+        .map_err(|err| format!("database is not reachable: {}", err));
+
+    // Without a work dir, nothing is downloaded or checked out.
+    let work_dir = match &settings.work_dir {
+        Some(work_dir) => check_writable(work_dir).await,
+        None => Ok(()),
+    };
+
+    let notifications = if listener.is_connected() {
+        Ok(())
+    } else {
+        Err(String::from("not listening to database notifications"))
+    };
+
+    let checks = vec![
+        Check::new("database", database),
+        Check::new("work_dir", work_dir),
+        Check::new("notifications", notifications),
+    ];
+    Readiness {
+        ready: checks.iter().all(|check| check.ok),
+        checks,
+    }
+}
+
+// Check we can create a file in the directory.
+async fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(format!(".mjolnir-ready-{}", uuid::Uuid::new_v4()));
+    tokio::fs::write(&probe, b"")
+        .await
+        .map_err(|err| format!("work dir '{}' is not writable: {}", dir.display(), err))?;
+    let _ = tokio::fs::remove_file(&probe).await;
+    Ok(())
+}
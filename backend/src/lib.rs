@@ -1,31 +1,72 @@
-use slog::{error, Logger};
+use slog::{info, o, Drain, Logger};
 use snafu::ResultExt;
 use sqlx::postgres::PgPool;
 
+pub mod auth;
 pub mod error;
 pub mod gql;
+pub mod health;
+pub mod load;
+pub mod metrics;
+pub mod migrations;
 pub mod model;
+pub mod report;
+pub mod runner;
+pub mod settings;
+pub mod shutdown;
+pub mod store;
 pub mod utils;
 
-pub async fn read_dotenv(_log: Logger) -> Result<(), error::Error> {
-    dotenv::dotenv().context(error::EnvError {
-        details: "Cannot find .env",
-    })?;
-    Ok(())
+/// Build the root logger, writing to stderr in the format given by the settings:
+/// 'json' for one JSON object per line (eg for log collectors), and otherwise human readable.
+pub fn build_logger(settings: &settings::Settings) -> Logger {
+    let level = settings.log_level();
+    if settings.log_format == "json" {
+        let drain = slog_json::Json::new(std::io::stderr())
+            .add_default_keys()
+            .build()
+            .fuse();
+        let drain = slog::LevelFilter::new(drain, level).fuse();
+        let drain = slog_async::Async::new(drain).build().fuse();
+        Logger::root(drain, o!())
+    } else {
+        let decorator = slog_term::TermDecorator::new().build();
+        let drain = slog_term::FullFormat::new(decorator).build().fuse();
+        let drain = slog::LevelFilter::new(drain, level).fuse();
+        let drain = slog_async::Async::new(drain).build().fuse();
+        Logger::root(drain, o!())
+    }
 }
 
-pub async fn get_connstr(_log: Logger) -> Result<String, error::Error> {
-    dotenv::var("DATABASE_URL").context(error::EnvError {
-        details: "Cannot find 'DATABASE_URL' in .env",
-    })
+/// Load the environment from .env, if there is one.
+pub async fn read_dotenv(log: Logger) -> Result<(), error::Error> {
+    match dotenv::dotenv() {
+        Ok(path) => {
+            info!(log, "Loaded environment from {}", path.display());
+            Ok(())
+        }
+        Err(dotenv::Error::Io(ref err)) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).context(error::EnvError {
+            details: "Cannot read .env",
+        }),
+    }
 }
 
-pub async fn connect_db(connstr: String, _log: Logger) -> Result<PgPool, error::Error> {
+pub async fn connect_db(
+    settings: &settings::DatabaseSettings,
+    _log: Logger,
+) -> Result<PgPool, error::Error> {
+    let connstr = settings.url()?;
     PgPool::builder()
-        .max_size(5)
-        .build(&connstr)
+        .max_size(settings.pool_size)
+        .connect_timeout(settings.connect_timeout())
+        .idle_timeout(settings.idle_timeout())
+        .build(connstr)
         .await
         .context(error::DBError {
-            details: format!("Could not create a PgPool with connection {}", connstr),
+            details: format!(
+                "Could not create a PgPool with connection {}",
+                settings::redact_url(connstr)
+            ),
         })
 }
@@ -0,0 +1,199 @@
+use crate::{
+    error,
+    runner::bragi::{self, Coord, SearchParams},
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+/// A search made by a user, as found in bragi's access logs. A corpus is a list of such
+/// searches, written as JSON lines, which is also a log the importer reads.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorpusQuery {
+    pub q: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub datasets: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+}
+
+impl CorpusQuery {
+    pub fn params(&self) -> SearchParams {
+        SearchParams {
+            focus: match (self.lat, self.lon) {
+                (Some(lat), Some(lon)) => Some(Coord { lat, lon }),
+                _ => None,
+            },
+            types: self.types.clone(),
+            datasets: self.datasets.clone(),
+            limit: self.limit,
+            lang: self.lang.clone(),
+            debug: false,
+        }
+    }
+
+    /// The autocomplete request replaying the search against the given bragi.
+    pub fn url(&self, bragi_url: &str) -> Result<reqwest::Url, error::Error> {
+        bragi::search_url(bragi_url, &self.q, &self.params())
+    }
+}
+
+/// A line of the log which did not give a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedLine {
+    pub line: usize, // from 1
+    pub reason: String,
+}
+
+/// The searches found in a log, in the order they were made.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImportedLog {
+    pub queries: Vec<CorpusQuery>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+/// Parse a log, where each line is either in the common log format, with the request to
+/// /autocomplete, or a JSON object with `q`, the coordinates (`lat` and `lon`, or `coord`), and
+/// the parameters (`type`, `pt_dataset`, `limit`, `lang`, at the top level or in `params`).
+/// Empty lines are ignored, and the others which are not a search are skipped.
+pub fn parse_log(text: &str) -> ImportedLog {
+    let mut log = ImportedLog::default();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let query = if line.starts_with('{') {
+            parse_json_line(line)
+        } else {
+            parse_common_log_line(line)
+        };
+        match query {
+            Ok(query) => log.queries.push(query),
+            Err(reason) => log.skipped.push(SkippedLine {
+                line: index + 1,
+                reason,
+            }),
+        }
+    }
+    log
+}
+
+/// The corpus as JSON lines, one search per line.
+pub fn write_corpus(queries: &[CorpusQuery]) -> Result<String, error::Error> {
+    let mut text = String::new();
+    for query in queries {
+        text.push_str(
+            &serde_json::to_string(query).context(error::SerdeJsonError {
+                details: "Could not serialize the corpus",
+            })?,
+        );
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+// 127.0.0.1 - - [10/Oct/2020:13:55:36 +0200] "GET /autocomplete?q=paris HTTP/1.1" 200 2326
+fn parse_common_log_line(line: &str) -> Result<CorpusQuery, String> {
+    lazy_static! {
+        static ref COMMON_LOG: Regex =
+            Regex::new(r#"^\S+ \S+ \S+ \[[^\]]+\] "([A-Z]+) (\S+)(?: [^"]*)?" [0-9]{3} \S+"#)
+                .unwrap();
+    }
+    let caps = COMMON_LOG
+        .captures(line)
+        .ok_or_else(|| String::from("not in the common log format"))?;
+    if &caps[1] != "GET" {
+        return Err(format!("not a search ({})", &caps[1]));
+    }
+    // Only the path and the query matter.
+    let url = reqwest::Url::parse("http://localhost/")
+        .and_then(|base| base.join(&caps[2]))
+        .map_err(|err| format!("invalid request '{}': {}", &caps[2], err))?;
+    if !url.path().ends_with("/autocomplete") {
+        return Err(format!("not a search ({})", url.path()));
+    }
+
+    let mut query = CorpusQuery {
+        q: String::new(),
+        lat: None,
+        lon: None,
+        types: Vec::new(),
+        datasets: Vec::new(),
+        limit: None,
+        lang: None,
+    };
+    let mut q = None;
+    for (key, value) in url.query_pairs() {
+        match &*key {
+            "q" => q = Some(value.into_owned()),
+            "lat" => query.lat = Some(number(&value, "lat")?),
+            "lon" => query.lon = Some(number(&value, "lon")?),
+            "type[]" => query.types.push(value.into_owned()),
+            "pt_dataset[]" => query.datasets.push(value.into_owned()),
+            "limit" => query.limit = Some(number(&value, "limit")?),
+            "lang" => query.lang = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+    query.q = q.ok_or_else(|| String::from("no query"))?;
+    Ok(query)
+}
+
+// {"q": "paris", "lat": 48.85, "lon": 2.35, "params": {"type": ["poi"], "limit": 5}}
+fn parse_json_line(line: &str) -> Result<CorpusQuery, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(line).map_err(|err| format!("invalid JSON: {}", err))?;
+    // Parameters may be at the top level, or in 'params'.
+    let field = |names: &[&str]| {
+        names.iter().find_map(|name| {
+            json.get(name)
+                .or_else(|| json.get("params").and_then(|params| params.get(name)))
+                .filter(|value| !value.is_null())
+        })
+    };
+    let text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let list = |names: &[&str]| match field(names) {
+        Some(serde_json::Value::Array(values)) => values.iter().map(text).collect(),
+        Some(value) => vec![text(value)],
+        None => Vec::new(),
+    };
+    let coord = |name: &str| -> Result<Option<f64>, String> {
+        field(&[name])
+            .or_else(|| json.get("coord").and_then(|coord| coord.get(name)))
+            .map(|value| number(&text(value), name))
+            .transpose()
+    };
+
+    Ok(CorpusQuery {
+        q: field(&["q"])
+            .map(text)
+            .ok_or_else(|| String::from("no query"))?,
+        lat: coord("lat")?,
+        lon: coord("lon")?,
+        types: list(&["type", "types", "type[]"]),
+        datasets: list(&["pt_dataset", "datasets", "pt_dataset[]"]),
+        limit: field(&["limit"])
+            .map(|value| number(&text(value), "limit"))
+            .transpose()?,
+        lang: field(&["lang"]).map(text),
+    })
+}
+
+fn number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {} '{}'", name, value))
+}
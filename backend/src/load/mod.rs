@@ -0,0 +1,186 @@
+use crate::{error, model::runs::latency};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+pub mod corpus;
+
+use corpus::CorpusQuery;
+
+// Upper bounds of the buckets of the latency histogram, in milliseconds. The last bucket has
+// no bound.
+const BUCKETS: [f64; 10] = [
+    5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+];
+
+/// How a corpus is replayed: requests are sent at the given rate (per second), whether the
+/// previous ones got a response or not, until there were as many as asked, going through the
+/// corpus again if need be.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadTest {
+    pub rate: f64,
+    pub requests: usize,
+}
+
+/// What a load test measured. Latencies are in milliseconds, and only those of the requests
+/// which succeeded count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadReport {
+    pub requests: usize,
+    pub errors: usize,
+    pub duration: f64, // seconds, from the first request to the last response
+    pub rate: f64,     // requests per second actually sent
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub histogram: Vec<Bucket>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub le: Option<f64>, // upper bound, None for the last bucket
+    pub count: usize,
+}
+
+/// Replay the corpus against the endpoint (a bragi url).
+pub async fn run(
+    queries: &[CorpusQuery],
+    endpoint: &str,
+    test: &LoadTest,
+) -> Result<LoadReport, error::Error> {
+    if queries.is_empty() {
+        return Err(error::Error::UserError {
+            details: String::from("The corpus has no query"),
+        });
+    }
+    if !test.rate.is_finite() || test.rate <= 0.0 {
+        return Err(error::Error::UserError {
+            details: format!("The rate must be positive, not {}", test.rate),
+        });
+    }
+    // Requests are at least a nanosecond apart: a shorter period rounds to zero, which the
+    // interval does not take.
+    let period = Duration::from_secs_f64(1.0 / test.rate);
+    if period == Duration::from_secs(0) {
+        return Err(error::Error::UserError {
+            details: format!(
+                "The rate must be at most one request per nanosecond, not {}",
+                test.rate
+            ),
+        });
+    }
+    let urls = queries
+        .iter()
+        .map(|query| query.url(endpoint))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let client = reqwest::Client::new();
+    let mut interval = tokio::time::interval(period);
+    let start = Instant::now();
+    let mut requests = Vec::with_capacity(test.requests);
+    for url in urls.iter().cycle().take(test.requests) {
+        interval.tick().await;
+        let request = client.get(url.clone());
+        requests.push(tokio::spawn(async move {
+            let start = Instant::now();
+            let response = request.send().await?.error_for_status()?;
+            response.bytes().await?;
+            Ok::<f64, reqwest::Error>(start.elapsed().as_secs_f64() * 1000.0)
+        }));
+    }
+    let sent = start.elapsed().as_secs_f64();
+
+    let mut latencies = Vec::new();
+    let mut errors = 0;
+    for request in requests {
+        match request.await {
+            Ok(Ok(latency)) => latencies.push(latency),
+            _ => errors += 1,
+        }
+    }
+    Ok(report(
+        &latencies,
+        errors,
+        start.elapsed().as_secs_f64(),
+        sent,
+    ))
+}
+
+// The rate is that of the requests sent, which only depends on how fast we sent them.
+fn report(latencies: &[f64], errors: usize, duration: f64, sent: f64) -> LoadReport {
+    let requests = latencies.len() + errors;
+    let at = |p: f64| latency::percentile(latencies, p).unwrap_or(0.0);
+    LoadReport {
+        requests,
+        errors,
+        duration,
+        rate: if sent > 0.0 {
+            requests as f64 / sent
+        } else {
+            requests as f64
+        },
+        p50: at(50.0),
+        p95: at(95.0),
+        p99: at(99.0),
+        max: latencies.iter().cloned().fold(0.0, f64::max),
+        histogram: histogram(latencies),
+    }
+}
+
+/// How many latencies fall in each bucket (not cumulative).
+pub fn histogram(latencies: &[f64]) -> Vec<Bucket> {
+    let mut buckets: Vec<Bucket> = BUCKETS
+        .iter()
+        .map(|le| Bucket {
+            le: Some(*le),
+            count: 0,
+        })
+        .chain(std::iter::once(Bucket { le: None, count: 0 }))
+        .collect();
+    for latency in latencies {
+        let index = BUCKETS
+            .iter()
+            .position(|le| latency <= le)
+            .unwrap_or(BUCKETS.len());
+        buckets[index].count += 1;
+    }
+    buckets
+}
+
+/// Render the report as text, with a bar for each bucket of the histogram.
+pub fn render(report: &LoadReport) -> String {
+    const WIDTH: usize = 40;
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "{} requests in {:.1} s ({:.1} per second), {} errors",
+        report.requests, report.duration, report.rate, report.errors
+    );
+    let _ = writeln!(
+        text,
+        "p50 {:.1} ms, p95 {:.1} ms, p99 {:.1} ms, max {:.1} ms\n",
+        report.p50, report.p95, report.p99, report.max
+    );
+    let largest = report
+        .histogram
+        .iter()
+        .map(|bucket| bucket.count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for bucket in &report.histogram {
+        let bound = match bucket.le {
+            Some(le) => format!("<= {} ms", le),
+            None => format!("> {} ms", BUCKETS[BUCKETS.len() - 1]),
+        };
+        let _ = writeln!(
+            text,
+            "{:>12} {:>6} {}",
+            bound,
+            bucket.count,
+            "#".repeat(bucket.count * WIDTH / largest)
+        );
+    }
+    text
+}
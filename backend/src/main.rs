@@ -1,59 +1,327 @@
-use futures::{Future, FutureExt, TryFutureExt};
+use bytes::Buf;
+use futures::{
+    future::{self, Either},
+    Future, FutureExt, TryStreamExt,
+};
+use juniper::http::GraphQLBatchRequest;
 use juniper_subscriptions::Coordinator;
 use juniper_warp::subscriptions::graphql_subscriptions;
-use slog::{error, info, o, Drain, Logger};
-use std::{pin::Pin, sync::Arc};
-use warp::{self, Filter};
+use serde::Deserialize;
+use slog::{debug, error, info, o, warn, Logger};
+use sqlx::postgres::PgPool;
+use std::{path::PathBuf, pin::Pin, process, sync::Arc, time::Duration};
+use structopt::StructOpt;
+use uuid::Uuid;
+use warp::{
+    self,
+    filters::BoxedFilter,
+    http::StatusCode,
+    multipart::{FormData, Part},
+    Filter, Rejection,
+};
 
-use mjolnir::{self, error, gql};
+use mjolnir::{
+    self,
+    auth::{Authenticator, Principal, Role},
+    error, gql,
+    health::{self, ListenerStatus},
+    metrics, migrations,
+    model::{
+        audit,
+        features::import::{self, ImportReport},
+        recovery,
+    },
+    settings::Settings,
+    shutdown::{self, Shutdown},
+    store::Store,
+    utils::archive,
+};
+
+// Maximum size of an upload to the import endpoint (features, or archives of features)
+const MAX_UPLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+// How often old entries are removed from the audit log, when a retention is configured.
+const AUDIT_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Command line flags. They override the configuration file and the environment.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "mjolnir", about = "Mjolnir server")]
+struct Opt {
+    /// Configuration file (TOML or YAML)
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Address to listen on (eg 127.0.0.1:3030)
+    #[structopt(short, long)]
+    listen: Option<String>,
+
+    /// Directory holding the frontend
+    #[structopt(long, parse(from_os_str))]
+    static_dir: Option<PathBuf>,
+
+    /// Database url
+    #[structopt(long)]
+    database_url: Option<String>,
+
+    /// Maximum number of database connections
+    #[structopt(long)]
+    pool_size: Option<u32>,
+
+    /// Directory for downloads and checkouts
+    #[structopt(long, parse(from_os_str))]
+    work_dir: Option<PathBuf>,
+
+    /// Bragi against which scenarios are run
+    #[structopt(long)]
+    bragi_url: Option<String>,
+
+    /// Directories from which features may be imported (can be repeated)
+    #[structopt(long = "import-path", parse(from_os_str))]
+    import_paths: Vec<PathBuf>,
+
+    /// Log level (critical, error, warn, info, debug, trace)
+    #[structopt(long)]
+    log_level: Option<String>,
+
+    /// Log format (term or json)
+    #[structopt(long)]
+    log_format: Option<String>,
+
+    /// Print the configuration, and exit
+    #[structopt(long)]
+    print_config: bool,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Apply the pending database migrations, and exit
+    Migrate,
+    /// Put back the work left in progress by servers which did not stop cleanly, and exit.
+    /// No server must be using the database meanwhile.
+    Recover,
+}
+
+impl Opt {
+    fn apply(self, settings: &mut Settings) {
+        if let Some(listen) = self.listen {
+            settings.server.listen = listen;
+        }
+        if let Some(static_dir) = self.static_dir {
+            settings.server.static_dir = static_dir;
+        }
+        if let Some(url) = self.database_url {
+            settings.database.url = Some(url);
+        }
+        if let Some(pool_size) = self.pool_size {
+            settings.database.pool_size = pool_size;
+        }
+        if let Some(work_dir) = self.work_dir {
+            settings.work_dir = Some(work_dir);
+        }
+        if let Some(bragi_url) = self.bragi_url {
+            settings.bragi_url = Some(bragi_url);
+        }
+        if !self.import_paths.is_empty() {
+            settings.import.paths = self.import_paths;
+        }
+        if let Some(log_level) = self.log_level {
+            settings.log_level = log_level;
+        }
+        if let Some(log_format) = self.log_format {
+            settings.log_format = log_format;
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    let decorator = slog_term::TermDecorator::new().build();
-    let drain = slog_term::FullFormat::new(decorator).build().fuse();
-    let drain = slog_async::Async::new(drain).build().fuse();
+    let mut opt = Opt::from_args();
+    let command = opt.command.take();
+
+    // Until we know the log level, we can only report problems on stderr.
+    let settings = configure(opt).await.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    let log = mjolnir::build_logger(&settings);
+    match command {
+        Some(Command::Migrate) => migrate(settings, log).await,
+        Some(Command::Recover) => recover(settings, log).await,
+        None => run(settings, log).await,
+    }
+}
+
+// Build and validate the settings. With --print-config, print them and exit.
+async fn configure(opt: Opt) -> Result<Settings, error::Error> {
+    let discard = Logger::root(slog::Discard, o!());
+    mjolnir::read_dotenv(discard).await?;
+
+    let print_config = opt.print_config;
+    let mut settings = Settings::new(opt.config.as_deref())?;
+    opt.apply(&mut settings);
+
+    if print_config {
+        print!("{}", settings.to_toml()?);
+        process::exit(0);
+    }
 
-    let log = slog::Logger::root(drain, o!());
-    run(log).await;
+    settings.validate()?;
+    Ok(settings)
 }
 
-async fn run(log: Logger) {
-    run_error(log.clone()).await.unwrap_or_else(|err| {
+async fn migrate(settings: Settings, log: Logger) {
+    let res = async {
+        let pool = mjolnir::connect_db(&settings.database, log.clone()).await?;
+        migrations::migrate(&pool, &log).await
+    }
+    .await;
+    if let Err(err) = res {
         error!(log, "{}", err);
-    })
+        process::exit(1);
+    }
 }
 
-async fn run_error(log: Logger) -> Result<(), error::Error> {
+async fn recover(settings: Settings, log: Logger) {
+    let res = async {
+        let pool = mjolnir::connect_db(&settings.database, log.clone()).await?;
+        recovery::recover_interrupted(&pool, &log).await
+    }
+    .await;
+    if let Err(err) = res {
+        error!(log, "{}", err);
+        process::exit(1);
+    }
+}
+
+async fn run(settings: Settings, log: Logger) {
+    run_error(settings, log.clone())
+        .await
+        .unwrap_or_else(|err| {
+            error!(log, "{}", err);
+        })
+}
+
+async fn run_error(settings: Settings, log: Logger) -> Result<(), error::Error> {
     let root_logger = log.new(o!());
+    let settings = Arc::new(settings);
+    let addr = settings.listen_addr()?;
 
-    mjolnir::read_dotenv(log).await?;
+    let authenticator = Arc::new(Authenticator::new(&settings.auth)?);
 
-    let pool = mjolnir::get_connstr(root_logger.clone())
-        .and_then(|connstr| mjolnir::connect_db(connstr, root_logger.clone()))
-        .await?;
+    let pool = mjolnir::connect_db(&settings.database, root_logger.clone()).await?;
 
-    let logger1 = root_logger.clone();
-    let pool1 = pool.clone();
-    let state = warp::any().map(move || gql::Context {
-        pool: pool1.clone(),
-        logger: logger1.clone(),
-    });
+    if settings.database.migrate {
+        migrations::migrate(&pool, &root_logger).await?;
+    }
+
+    let shutdown = Shutdown::default();
+
+    if let Some(days) = settings.audit.retention_days {
+        tokio::spawn(prune_audit_log(days, pool.clone(), root_logger.clone()));
+    }
+
+    let listener = ListenerStatus::default();
+    tokio::spawn(health::monitor_listener(
+        settings.database.url()?.to_string(),
+        listener.clone(),
+        root_logger.clone(),
+    ));
+
+    let state = with_context(
+        warp::header::optional::<String>("authorization").boxed(),
+        pool.clone(),
+        root_logger.clone(),
+        settings.clone(),
+        authenticator.clone(),
+        shutdown.clone(),
+    );
 
     let graphiql = warp::path("graphiql")
         .and(warp::path::end())
         .and(warp::get())
         .and(juniper_warp::graphiql_filter("/graphql", None));
 
-    let graphql_filter = juniper_warp::make_graphql_filter(gql::schema(), state.boxed());
+    // POST requests are handled here, so that they can be measured by operation. The others
+    // are left to juniper.
+    let schema = Arc::new(gql::schema());
+    let graphql_post = warp::post()
+        .and(warp::body::json())
+        .and(state.clone())
+        .and(warp::any().map(move || schema.clone()))
+        .and_then(graphql_handler);
+    let graphql_filter = juniper_warp::make_graphql_filter(gql::schema(), state.clone().boxed());
     /* This is ApiRoutes.Base */
-    let graphql = warp::path!("graphql").and(graphql_filter);
+    let graphql = warp::path!("graphql")
+        .and(graphql_post.or(graphql_filter))
+        .recover(handle_unauthorized);
 
-    let logger2 = root_logger.clone();
-    let pool2 = pool.clone();
-    let substate = warp::any().map(move || gql::Context {
-        pool: pool2.clone(),
-        logger: logger2.clone(),
-    });
+    // Probes and metrics are not authenticated.
+    let healthz = warp::path!("healthz")
+        .and(warp::get())
+        .map(|| warp::reply::with_status("ok", StatusCode::OK));
+
+    let readyz = {
+        let pool = pool.clone();
+        let settings = settings.clone();
+        let logger = root_logger.clone();
+        warp::path!("readyz").and(warp::get()).and_then(move || {
+            let pool = pool.clone();
+            let settings = settings.clone();
+            let listener = listener.clone();
+            let logger = logger.clone();
+            async move {
+                let readiness = health::readiness(&pool, &settings, &listener, &logger).await;
+                let status = if readiness.ready {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                };
+                Ok::<_, Rejection>(warp::reply::with_status(
+                    warp::reply::json(&readiness),
+                    status,
+                ))
+            }
+        })
+    };
+
+    let metrics = {
+        let pool = pool.clone();
+        warp::path!("metrics").and(warp::get()).map(move || {
+            warp::reply::with_header(
+                metrics::render(&pool),
+                "Content-Type",
+                "text/plain; version=0.0.4",
+            )
+        })
+    };
+
+    // Bulk import of features: a multipart form with any number of '.feature' files, or
+    // '.tar.gz' / '.zip' archives of features. Use '?atomic=true' for an all or nothing import.
+    let import = warp::path!("import")
+        .and(warp::post())
+        .and(warp::query::<ImportOptions>())
+        .and(warp::multipart::form().max_length(MAX_UPLOAD_SIZE))
+        .and(state)
+        .and_then(import_handler)
+        .recover(handle_unauthorized);
+
+    // Browsers cannot set headers on websockets, so the token can also be given in the query.
+    let subcredentials = warp::header::optional::<String>("authorization")
+        .and(warp::query::<TokenQuery>())
+        .map(|header: Option<String>, query: TokenQuery| header.or(query.token))
+        .boxed();
+    let substate = with_context(
+        subcredentials,
+        pool.clone(),
+        root_logger.clone(),
+        settings.clone(),
+        authenticator,
+        shutdown.clone(),
+    );
 
     let coordinator = Arc::new(juniper_subscriptions::Coordinator::new(gql::schema()));
 
@@ -66,28 +334,268 @@ async fn run_error(log: Logger) -> Result<(), error::Error> {
              context: gql::Context,
              coordinator: Arc<Coordinator<'static, _, _, _, _, _>>| {
                 ws.on_upgrade(|websocket| -> Pin<Box<dyn Future<Output = ()> + Send>> {
-                    println!("On upgrade");
-                    graphql_subscriptions(websocket, coordinator, context)
-                        .map(|r| {
-                            println!("r: {:?}", r);
-                            if let Err(err) = r {
-                                println!("Websocket Error: {}", err);
-                            }
-                        })
-                        .boxed()
+                    let logger = context.logger.clone();
+                    let shutdown = context.shutdown.clone();
+                    debug!(logger, "Websocket connection upgraded");
+                    let subscriptions = graphql_subscriptions(websocket, coordinator, context).map(
+                        move |r| match r {
+                            Ok(()) => debug!(logger, "Websocket connection closed"),
+                            Err(err) => warn!(logger, "Websocket error: {}", err),
+                        },
+                    );
+                    // The websocket is closed (by dropping it) when the server shuts down.
+                    async move {
+                        future::select(Box::pin(subscriptions), Box::pin(shutdown.wait())).await;
+                    }
+                    .boxed()
                 })
             },
         ))
-    .map(|reply| warp::reply::with_header(reply, "Sec-Websocket-Protocol", "graphql-ws"));
+    .map(|reply| warp::reply::with_header(reply, "Sec-Websocket-Protocol", "graphql-ws"))
+    .recover(handle_unauthorized);
 
-    let index = warp::fs::file("dist/index.html");
+    let index = warp::fs::file(settings.server.static_dir.join("index.html"));
 
-    let dir = warp::fs::dir("dist");
+    let dir = warp::fs::dir(settings.server.static_dir.clone());
 
-    let routes = graphiql.or(graphql).or(notifications).or(dir).or(index);
+    let routes = healthz
+        .or(readyz)
+        .or(metrics)
+        .or(graphiql)
+        .or(graphql)
+        .or(import)
+        .or(notifications)
+        .or(dir)
+        .or(index);
 
-    info!(root_logger.clone(), "Serving Mjolnir on 127.0.0.1:3030");
-    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+    // On SIGTERM or SIGINT, we stop accepting connections, and give the requests in progress
+    // some time to finish. Runs stop after their current scenario, and subscriptions are closed.
+    let (addr, server) = warp::serve(routes).bind_with_graceful_shutdown(addr, {
+        let shutdown = shutdown.clone();
+        async move { shutdown.wait().await }
+    });
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        let logger = root_logger.clone();
+        async move {
+            shutdown::signal().await;
+            info!(logger, "Shutting down");
+            shutdown.trigger();
+        }
+    });
+
+    info!(root_logger.clone(), "Serving Mjolnir on {}", addr);
+    let timeout = settings.shutdown_timeout();
+    let grace = {
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown.wait().await;
+            tokio::time::delay_for(timeout).await;
+        }
+    };
+    match future::select(Box::pin(server), Box::pin(grace)).await {
+        Either::Left(_) => info!(root_logger, "Server stopped"),
+        Either::Right(_) => warn!(
+            root_logger,
+            "Requests still in progress after {}s were abandoned",
+            timeout.as_secs()
+        ),
+    }
+
+    // Downloads stop when the shutdown is triggered, once they have recorded how far they got.
+    if tokio::time::timeout(timeout, shutdown.drain())
+        .await
+        .is_err()
+    {
+        warn!(
+            root_logger,
+            "Downloads still in progress after {}s were abandoned (see mjolnir recover)",
+            timeout.as_secs()
+        );
+    }
 
     Ok(())
 }
+
+async fn graphql_handler(
+    request: GraphQLBatchRequest,
+    context: gql::Context,
+    schema: Arc<gql::Schema>,
+) -> Result<impl warp::Reply, Rejection> {
+    let operation = match &request {
+        GraphQLBatchRequest::Single(request) => request.operation_name(),
+        GraphQLBatchRequest::Batch(_) => Some("batch"),
+    };
+    let timer = metrics::GraphQLTimer::start(operation);
+    let response = request.execute(&schema, &context).await;
+    timer.finish(response.is_ok());
+    let status = if response.is_ok() {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        status,
+    ))
+}
+
+// Periodically remove the audit log entries older than the retention.
+async fn prune_audit_log(retention_days: u32, pool: PgPool, logger: Logger) {
+    let mut interval = tokio::time::interval(AUDIT_PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = audit::prune_audit_log(retention_days, &pool, &logger).await {
+            warn!(logger, "{}", err);
+        }
+    }
+}
+
+// The credentials, for websockets.
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+// Rejection of requests whose credentials are not valid.
+#[derive(Debug)]
+struct Unauthorized(String);
+
+impl warp::reject::Reject for Unauthorized {}
+
+// Authenticate the credentials extracted by the given filter, and build the context of the
+// request for the resulting principal.
+// The logger of the context carries a request id (the X-Request-Id header, if given), so that
+// everything done for the request, including the jobs it starts, can be traced back to it.
+fn with_context(
+    credentials: BoxedFilter<(Option<String>,)>,
+    pool: PgPool,
+    logger: Logger,
+    settings: Arc<Settings>,
+    authenticator: Arc<Authenticator>,
+    shutdown: Shutdown,
+) -> BoxedFilter<(gql::Context,)> {
+    credentials
+        .and(warp::header::optional::<String>("x-request-id"))
+        .and_then(
+            move |credentials: Option<String>, request_id: Option<String>| {
+                let pool = pool.clone();
+                let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+                let logger = logger.new(o!("request_id" => request_id));
+                let settings = settings.clone();
+                let authenticator = authenticator.clone();
+                let shutdown = shutdown.clone();
+                async move {
+                    // The principal is only known once the credentials are authenticated.
+                    let mut context = gql::Context {
+                        pool: Some(pool),
+                        logger,
+                        settings,
+                        principal: Principal::anonymous(),
+                        shutdown,
+                        store: Store::postgres(),
+                    };
+                    match authenticator
+                        .authenticate(credentials.as_deref(), &context)
+                        .await
+                    {
+                        Ok(principal) => {
+                            context.logger = context
+                                .logger
+                                .new(o!("principal" => principal.name.clone()));
+                            context.principal = principal;
+                            Ok(context)
+                        }
+                        Err(err) => {
+                            warn!(context.logger, "Rejecting request: {}", err);
+                            Err(warp::reject::custom(Unauthorized(format!("{}", err))))
+                        }
+                    }
+                }
+            },
+        )
+        .boxed()
+}
+
+async fn handle_unauthorized(err: Rejection) -> Result<impl warp::Reply, Rejection> {
+    match err.find::<Unauthorized>() {
+        Some(Unauthorized(msg)) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": msg })),
+            StatusCode::UNAUTHORIZED,
+        )),
+        None => Err(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportOptions {
+    atomic: Option<bool>,
+}
+
+async fn import_handler(
+    options: ImportOptions,
+    form: FormData,
+    context: gql::Context,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(err) = context.principal.authorize(Role::Editor) {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": format!("{}", err) })),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    match import_form(options, form, &context).await {
+        Ok(report) => {
+            audit::record(
+                "import_features",
+                "upload",
+                "",
+                None,
+                Some(&report),
+                &context,
+            )
+            .await;
+            Ok(warp::reply::with_status(
+                warp::reply::json(&report),
+                StatusCode::OK,
+            ))
+        }
+        Err(err) => {
+            error!(context.logger, "Could not import features: {}", err);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": format!("{}", err) })),
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    }
+}
+
+async fn import_form(
+    options: ImportOptions,
+    form: FormData,
+    context: &gql::Context,
+) -> Result<ImportReport, error::Error> {
+    let parts: Vec<Part> = form
+        .try_collect()
+        .await
+        .map_err(|err| error::Error::UserError {
+            details: format!("Could not read multipart form: {}", err),
+        })?;
+
+    let mut sources = Vec::new();
+    for mut part in parts {
+        let filename = part
+            .filename()
+            .map(String::from)
+            .unwrap_or_else(|| String::from(part.name()));
+        let mut bytes: Vec<u8> = Vec::new();
+        while let Some(chunk) = part.data().await {
+            let chunk = chunk.map_err(|err| error::Error::UserError {
+                details: format!("Could not read '{}': {}", filename, err),
+            })?;
+            bytes.extend_from_slice(chunk.bytes());
+        }
+        sources.extend(archive::extract_features(&filename, &bytes)?);
+    }
+
+    import::import_features(sources, options.atomic.unwrap_or(false), context).await
+}
@@ -0,0 +1,182 @@
+use crate::model::runs::ResultStatus;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, TextEncoder,
+};
+use sqlx::postgres::PgPool;
+use std::time::Instant;
+
+lazy_static! {
+    static ref GRAPHQL_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "mjolnir_graphql_requests_total",
+        "Number of GraphQL requests, by operation and outcome",
+        &["operation", "outcome"]
+    )
+    .unwrap();
+    static ref GRAPHQL_DURATION: HistogramVec = register_histogram_vec!(
+        "mjolnir_graphql_request_duration_seconds",
+        "Duration of GraphQL requests, by operation",
+        &["operation"]
+    )
+    .unwrap();
+    static ref POOL_CONNECTIONS: IntGauge = register_int_gauge!(
+        "mjolnir_db_pool_connections",
+        "Number of connections opened by the database pool"
+    )
+    .unwrap();
+    static ref POOL_IDLE: IntGauge = register_int_gauge!(
+        "mjolnir_db_pool_idle_connections",
+        "Number of idle connections in the database pool"
+    )
+    .unwrap();
+    static ref POOL_MAX: IntGauge = register_int_gauge!(
+        "mjolnir_db_pool_max_connections",
+        "Maximum number of connections of the database pool"
+    )
+    .unwrap();
+    static ref JOBS_IN_PROGRESS: IntGaugeVec = register_int_gauge_vec!(
+        "mjolnir_jobs_in_progress",
+        "Number of jobs (runs, downloads) in progress, by kind",
+        &["kind"]
+    )
+    .unwrap();
+    static ref DOWNLOAD_BYTES: IntCounter = register_int_counter!(
+        "mjolnir_download_bytes_total",
+        "Number of bytes downloaded (eg BANO files)"
+    )
+    .unwrap();
+    static ref SCENARIOS: IntCounterVec = register_int_counter_vec!(
+        "mjolnir_scenarios_total",
+        "Number of scenarios executed, by status",
+        &["status"]
+    )
+    .unwrap();
+}
+
+/// The root fields of the schema, as clients name them. The operation name of a request is chosen
+/// by the client, so it only labels the metrics when it is one of these: any other name would
+/// create new series.
+pub const OPERATIONS: &[&str] = &[
+    // queries
+    "whoami",
+    "apiTokens",
+    "auditLog",
+    "features",
+    "featureRevisions",
+    "repositoryStatus",
+    "scenarios",
+    "background",
+    "steps",
+    "environment",
+    "environments",
+    "indexes",
+    "runs",
+    "run",
+    "scenarioResults",
+    "stepResults",
+    "qualityMetrics",
+    "qualityTrend",
+    "latencyMetrics",
+    "flakyScenarios",
+    "compareRuns",
+    "report",
+    // mutations
+    "addFeature",
+    "loadFeature",
+    "importFeatures",
+    "syncRepository",
+    "runScenarios",
+    "runBenchmark",
+    "compareBragi",
+    "acceptSnapshot",
+    "createApiToken",
+    "revokeApiToken",
+    "deleteFeature",
+    "backgroundEnvironment",
+    "downloadDatasetItem",
+];
+
+/// Times a GraphQL request. Call `finish` with its outcome once it has been executed.
+pub struct GraphQLTimer {
+    operation: &'static str,
+    start: Instant,
+}
+
+impl GraphQLTimer {
+    /// Anonymous operations are recorded as 'anonymous', batches as 'batch', and operations which
+    /// are not named after a root field (see OPERATIONS) as 'other'.
+    pub fn start(operation: Option<&str>) -> Self {
+        let operation = match operation {
+            None => "anonymous",
+            Some("batch") => "batch",
+            Some(name) => OPERATIONS
+                .iter()
+                .find(|known| **known == name)
+                .copied()
+                .unwrap_or("other"),
+        };
+        GraphQLTimer {
+            operation,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn finish(self, ok: bool) {
+        let outcome = if ok { "ok" } else { "error" };
+        GRAPHQL_REQUESTS
+            .with_label_values(&[self.operation, outcome])
+            .inc();
+        GRAPHQL_DURATION
+            .with_label_values(&[self.operation])
+            .observe(self.start.elapsed().as_secs_f64());
+    }
+}
+
+/// Counts a job as in progress, until it is dropped.
+pub struct JobGuard {
+    kind: &'static str,
+}
+
+impl JobGuard {
+    pub fn new(kind: &'static str) -> Self {
+        JOBS_IN_PROGRESS.with_label_values(&[kind]).inc();
+        JobGuard { kind }
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        JOBS_IN_PROGRESS.with_label_values(&[self.kind]).dec();
+    }
+}
+
+pub fn record_download(bytes: usize) {
+    DOWNLOAD_BYTES.inc_by(bytes as i64);
+}
+
+pub fn record_scenario(status: ResultStatus) {
+    let status = match status {
+        ResultStatus::Passed => "passed",
+        ResultStatus::Failed => "failed",
+        ResultStatus::Skipped => "skipped",
+        ResultStatus::Undefined => "undefined",
+        ResultStatus::Error => "error",
+    };
+    SCENARIOS.with_label_values(&[status]).inc();
+}
+
+/// Return all the metrics, in the Prometheus text format.
+/// Gauges which are not updated as they change (eg the pool) are updated first.
+pub fn render(pool: &PgPool) -> String {
+    POOL_CONNECTIONS.set(pool.size() as i64);
+    POOL_IDLE.set(pool.idle() as i64);
+    POOL_MAX.set(pool.max_size() as i64);
+
+    let mut buffer = Vec::new();
+    let encoder = TextEncoder::new();
+    // Encoding into a Vec cannot fail, unless metrics are invalid, which they are not.
+    let _ = encoder.encode(&prometheus::gather(), &mut buffer);
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -0,0 +1,311 @@
+use md5::{Digest, Md5};
+use slog::{info, Logger};
+use snafu::ResultExt;
+use sqlx::{
+    pool::PoolConnection,
+    postgres::{PgConnection, PgPool, PgQueryAs},
+    Connection, Executor,
+};
+
+use crate::{error, utils::timing::Timed};
+
+/// A schema change, embedded in the binary. Once released, a migration must not be modified:
+/// further changes go in a new file, added at the end of MIGRATIONS with the next version.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// Detects migrations modified after they were applied.
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Md5::digest(self.sql.as_bytes()))
+    }
+}
+
+macro_rules! migration {
+    ($version:expr, $path:expr) => {
+        Migration {
+            version: $version,
+            name: $path,
+            sql: include_str!(concat!("../../database/", $path)),
+        }
+    };
+}
+
+/// In the order they are applied. Up to BASELINE, this is the order used by
+/// database/provision.sh before migrations were introduced.
+pub static MIGRATIONS: &[Migration] = &[
+    migration!(1, "functions/11-create-extensions.sql"),
+    migration!(2, "functions/15-utils.sql"),
+    migration!(3, "functions/20-schema.sql"),
+    migration!(4, "functions/22-utils.sql"),
+    migration!(5, "functions/25-gherkin.sql"),
+    migration!(6, "functions/30-environment.sql"),
+    migration!(7, "api/background.sql"),
+    migration!(8, "api/environment.sql"),
+    migration!(9, "api/feature.sql"),
+    migration!(10, "api/scenario.sql"),
+    migration!(11, "api/step.sql"),
+    migration!(12, "provision.sql"),
+    migration!(13, "migrations/101-repository.sql"),
+    migration!(14, "migrations/102-runs.sql"),
+    migration!(15, "migrations/103-auth.sql"),
+    migration!(16, "migrations/104-audit.sql"),
+    migration!(17, "migrations/105-recovery.sql"),
+    migration!(18, "migrations/106-bano.sql"),
+    migration!(19, "migrations/107-datasets.sql"),
+    migration!(20, "migrations/108-step-scores.sql"),
+    migration!(21, "migrations/109-step-request-urls.sql"),
+    migration!(22, "migrations/110-quality-metrics.sql"),
+    migration!(23, "migrations/111-step-results.sql"),
+    migration!(24, "migrations/112-snapshots.sql"),
+    migration!(25, "migrations/113-latency-metrics.sql"),
+    migration!(26, "migrations/114-flaky-scenarios.sql"),
+    migration!(27, "migrations/115-revoke-unknown-token.sql"),
+    migration!(28, "migrations/116-feature-errors.sql"),
+];
+
+/// The last migration included in databases built by provision.sh before database/migrations
+/// existed. Such databases have the schema up to there, but no record of the migrations.
+pub const BASELINE: i32 = 12;
+
+// Any number will do, as long as no one else uses it for an advisory lock on this database.
+const MIGRATION_LOCK: i64 = 0x006d_6a6f_6c6e_6972;
+
+/// The latest version known to this binary.
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// The versions recorded as applied, in order.
+pub async fn applied_versions(pool: &PgPool, logger: &Logger) -> Result<Vec<i32>, error::Error> {
+    let mut conn = acquire(pool).await?;
+    create_migrations_table(&mut conn, logger).await?;
+    let applied = fetch_applied(&mut conn, logger).await?;
+    Ok(applied.into_iter().map(|(version, _)| version).collect())
+}
+
+/// Apply the migrations which have not been applied yet, each in its own transaction, and
+/// return their versions. Fails, without applying anything, if a migration was modified
+/// after it was applied, or if the database was migrated by a more recent version.
+/// Several servers may start at the same time: each migration is applied only once.
+pub async fn migrate(pool: &PgPool, logger: &Logger) -> Result<Vec<i32>, error::Error> {
+    let mut conn = acquire(pool).await?;
+    create_migrations_table(&mut conn, logger).await?;
+    baseline(&mut conn, logger).await?;
+
+    let applied = fetch_applied(&mut conn, logger).await?;
+    check_applied(&applied)?;
+
+    let mut versions = Vec::new();
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| !applied.iter().any(|(version, _)| *version == m.version))
+    {
+        let (returned, applied) = apply(migration, conn, logger).await?;
+        conn = returned;
+        if applied {
+            info!(
+                logger,
+                "Applied migration {} ({})", migration.version, migration.name
+            );
+            versions.push(migration.version);
+        }
+    }
+
+    // The connection goes back to the pool: do not leave it with the settings of the migrations.
+    conn.execute("RESET ALL").await.context(error::DBError {
+        details: "Could not reset the connection",
+    })?;
+
+    if versions.is_empty() {
+        info!(
+            logger,
+            "Database schema is up to date (version {})",
+            latest_version()
+        );
+    }
+    Ok(versions)
+}
+
+async fn acquire(pool: &PgPool) -> Result<PoolConnection<PgConnection>, error::Error> {
+    pool.acquire().await.context(error::DBError {
+        details: "Could not acquire a connection for migrations",
+    })
+}
+
+async fn create_migrations_table(
+    conn: &mut PoolConnection<PgConnection>,
+    logger: &Logger,
+) -> Result<(), error::Error> {
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS public.schema_migrations (
+             version INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             checksum TEXT NOT NULL,
+             applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+           )"#,
+    )
+    .execute(&mut *conn)
+    .timed(logger, "migrations::create_migrations_table")
+    .await
+    .context(error::DBError {
+        details: "Could not create the migrations table",
+    })?;
+    Ok(())
+}
+
+async fn fetch_applied(
+    conn: &mut PoolConnection<PgConnection>,
+    logger: &Logger,
+) -> Result<Vec<(i32, String)>, error::Error> {
+    sqlx::query_as("SELECT version, checksum FROM public.schema_migrations ORDER BY version")
+        .fetch_all(&mut *conn)
+        .timed(logger, "migrations::fetch_applied")
+        .await
+        .context(error::DBError {
+            details: "Could not fetch the applied migrations",
+        })
+}
+
+// Databases built by provision.sh already have the migrations up to the baseline, and nothing
+// after it: the files of later migrations were never part of it.
+async fn baseline(
+    conn: &mut PoolConnection<PgConnection>,
+    logger: &Logger,
+) -> Result<(), error::Error> {
+    let (recorded, provisioned): (i64, bool) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM public.schema_migrations), to_regclass('main.features') IS NOT NULL",
+    )
+    .fetch_one(&mut *conn)
+    .timed(logger, "migrations::baseline")
+    .await
+    .context(error::DBError {
+        details: "Could not check for a provisioned database",
+    })?;
+
+    if recorded > 0 || !provisioned {
+        return Ok(());
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= BASELINE) {
+        sqlx::query(
+            "INSERT INTO public.schema_migrations (version, name, checksum) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        )
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(migration.checksum())
+        .execute(&mut *conn)
+        .timed(logger, "migrations::baseline")
+        .await
+        .context(error::DBError {
+            details: format!("Could not record migration {}", migration.version),
+        })?;
+    }
+    info!(
+        logger,
+        "Database was provisioned without migrations: recorded it at version {}", BASELINE
+    );
+    Ok(())
+}
+
+fn check_applied(applied: &[(i32, String)]) -> Result<(), error::Error> {
+    for (version, checksum) in applied {
+        match MIGRATIONS.iter().find(|m| m.version == *version) {
+            None => {
+                return Err(error::Error::MigrationError {
+                    details: format!(
+                        "Database has migration {}, but this server only knows up to {}",
+                        version,
+                        latest_version()
+                    ),
+                })
+            }
+            Some(migration) if migration.checksum() != *checksum => {
+                return Err(error::Error::MigrationError {
+                    details: format!(
+                        "Migration {} ({}) was modified after it was applied",
+                        version, migration.name
+                    ),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+// Returns false if another server applied the migration while we were waiting for the lock.
+// The transaction takes the connection, which is given back once it is over.
+async fn apply(
+    migration: &Migration,
+    conn: PoolConnection<PgConnection>,
+    logger: &Logger,
+) -> Result<(PoolConnection<PgConnection>, bool), error::Error> {
+    let mut tx = conn.begin().await.context(error::DBError {
+        details: "Could not begin a transaction",
+    })?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MIGRATION_LOCK)
+        .execute(&mut tx)
+        .timed(logger, "migrations::apply")
+        .await
+        .context(error::DBError {
+            details: "Could not lock the migrations",
+        })?;
+
+    let (applied,): (bool,) =
+        sqlx::query_as("SELECT EXISTS (SELECT 1 FROM public.schema_migrations WHERE version = $1)")
+            .bind(migration.version)
+            .fetch_one(&mut tx)
+            .timed(logger, "migrations::apply")
+            .await
+            .context(error::DBError {
+                details: "Could not check the applied migrations",
+            })?;
+    if applied {
+        let conn = tx.rollback().await.context(error::DBError {
+            details: "Could not rollback the transaction",
+        })?;
+        return Ok((conn, false));
+    }
+
+    // Unqualified names go in main, as they did when provision.sh concatenated the files.
+    tx.execute("SET LOCAL search_path = main, public")
+        .await
+        .context(error::DBError {
+            details: "Could not set the search path",
+        })?;
+
+    tx.execute(migration.sql)
+        .timed(logger, "migrations::apply")
+        .await
+        .context(error::DBError {
+            details: format!(
+                "Could not apply migration {} ({})",
+                migration.version, migration.name
+            ),
+        })?;
+
+    sqlx::query(
+        "INSERT INTO public.schema_migrations (version, name, checksum) VALUES ($1, $2, $3)",
+    )
+    .bind(migration.version)
+    .bind(migration.name)
+    .bind(migration.checksum())
+    .execute(&mut tx)
+    .timed(logger, "migrations::apply")
+    .await
+    .context(error::DBError {
+        details: format!("Could not record migration {}", migration.version),
+    })?;
+
+    let conn = tx.commit().await.context(error::DBError {
+        details: "Could not commit the migration",
+    })?;
+    Ok((conn, true))
+}
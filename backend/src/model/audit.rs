@@ -0,0 +1,159 @@
+use crate::{error, gql, utils::timing::Timed};
+use chrono::prelude::*;
+use juniper::{GraphQLInputObject, GraphQLObject};
+use serde::{Deserialize, Serialize};
+use slog::{info, warn};
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgPool, PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+
+// The number of entries returned when the filter has no limit, and the most that can be asked.
+pub const DEFAULT_LIMIT: i32 = 100;
+pub const MAX_LIMIT: i32 = 1000;
+
+/// An entry of the audit log: who did what to what.
+/// 'before' and 'after' are the JSON representations of the target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct AuditEntry {
+    pub id: uuid::Uuid,
+    pub actor: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// This should match the main.return_audit_entry_type
+impl<'c> FromRow<'c, PgRow<'c>> for AuditEntry {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(AuditEntry {
+            id: row.get(0),
+            actor: row.get(1),
+            action: row.get(2),
+            target_type: row.get(3),
+            target_id: row.get(4),
+            before: row.get(5),
+            after: row.get(6),
+            created_at: row.get(7),
+        })
+    }
+}
+
+/// Criteria for selecting audit log entries. Missing criteria match anything.
+#[derive(Debug, Clone, Default, GraphQLInputObject)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i32>,
+}
+
+/// Record an action of the principal making the request.
+/// The action has already been made, so failing to record it is logged rather than returned.
+pub async fn record<T: Serialize>(
+    action: &str,
+    target_type: &str,
+    target_id: &str,
+    before: Option<&T>,
+    after: Option<&T>,
+    context: &gql::Context,
+) {
+    let to_json = |value: Option<&T>| value.and_then(|v| serde_json::to_value(v).ok());
+    let res = context
+        .store
+        .audit
+        .add_audit_entry(
+            action,
+            target_type,
+            target_id,
+            to_json(before),
+            to_json(after),
+            context,
+        )
+        .await;
+    if let Err(err) = res {
+        warn!(
+            context.logger,
+            "Could not record '{}' on {} '{}' in the audit log: {}",
+            action,
+            target_type,
+            target_id,
+            err
+        );
+    }
+}
+
+/// Add an entry made by the principal making the request.
+pub async fn add_audit_entry(
+    action: &str,
+    target_type: &str,
+    target_id: &str,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+    context: &gql::Context,
+) -> Result<AuditEntry, error::Error> {
+    sqlx::query_as("SELECT * FROM main.add_audit_entry($1, $2, $3, $4, $5, $6)")
+        .bind(context.principal.name.as_str())
+        .bind(action)
+        .bind(target_type)
+        .bind(target_id)
+        .bind(before)
+        .bind(after)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "audit::add_audit_entry")
+        .await
+        .context(error::DBError {
+            details: format!("Could not add '{}' to the audit log", action),
+        })
+}
+
+pub async fn fetch_audit_log(
+    filter: AuditFilter,
+    context: &gql::Context,
+) -> Result<Vec<AuditEntry>, error::Error> {
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT).clamp(0, MAX_LIMIT);
+    sqlx::query_as("SELECT * FROM main.fetch_audit_log($1, $2, $3, $4, $5, $6, $7)")
+        .bind(filter.actor)
+        .bind(filter.action)
+        .bind(filter.target_type)
+        .bind(filter.target_id)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(limit)
+        .fetch_all(context.pool()?)
+        .timed(&context.logger, "audit::fetch_audit_log")
+        .await
+        .context(error::DBError {
+            details: "Could not retrieve the audit log",
+        })
+}
+
+/// Remove the entries older than the given number of days, and return how many were removed.
+pub async fn prune_audit_log(
+    retention_days: u32,
+    pool: &PgPool,
+    logger: &slog::Logger,
+) -> Result<i64, error::Error> {
+    let (count,): (i64,) = sqlx::query_as("SELECT main.prune_audit_log($1)")
+        .bind(retention_days as i32)
+        .fetch_one(pool)
+        .timed(logger, "audit::prune_audit_log")
+        .await
+        .context(error::DBError {
+            details: "Could not prune the audit log",
+        })?;
+    if count > 0 {
+        info!(
+            logger,
+            "Removed {} audit log entries older than {} days", count, retention_days
+        );
+    }
+    Ok(count)
+}
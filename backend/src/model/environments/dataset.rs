@@ -0,0 +1,597 @@
+use crate::{
+    error, gql, metrics,
+    model::{FileStatus, PgTx},
+    shutdown::Shutdown,
+    utils::timing::Timed,
+};
+use chrono::prelude::*;
+use futures::{
+    future::{self, Either},
+    stream::{self, TryStreamExt},
+};
+use juniper::GraphQLObject;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use slog::{info, o, warn, Logger};
+use snafu::ResultExt;
+use sqlx::postgres::PgPool;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::prelude::*;
+
+/// A dataset is a named collection of source files (items) of a data source, eg BANO
+/// departments, OSM regions or cosmogony extracts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct Dataset {
+    pub id: String,
+    pub data_source: String,
+    pub description: String,
+    pub items: Vec<Item>,
+}
+
+impl<'c> FromRow<'c, PgRow<'c>> for Dataset {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(Dataset {
+            id: row.get(0),
+            data_source: row.get(1),
+            description: row.get(2),
+            items: vec![],
+        })
+    }
+}
+
+/// This is a single source file of a data source. It may belong to several datasets of that
+/// data source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct Item {
+    pub id: String,
+    pub data_source: String,
+    pub filename: String,
+    pub md5: String,   // hex formatted digest
+    pub filesize: f64, // file size in Kilobytes (this is because GraphQLType only supports f64 or i32)
+    pub filestatus: FileStatus,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Item {
+    pub fn new(data_source: String, id: String) -> Self {
+        Item {
+            id,
+            data_source,
+            filename: String::from(""),
+            md5: String::from(""),
+            filesize: 0.0,
+            filestatus: FileStatus::NotAvailable,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+impl<'c> FromRow<'c, PgRow<'c>> for Item {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(Item {
+            id: row.get(0),
+            data_source: row.get(1),
+            filename: row.get(2),
+            md5: row.get(3),
+            filesize: row.get(4),
+            filestatus: row.get(5),
+            updated_at: row.get(6),
+        })
+    }
+}
+
+/// Start downloading an item of the dataset, from the location configured for its data source,
+/// and return the item, whose download is in progress.
+pub async fn download_dataset_item(
+    dataset_id: &str,
+    item_id: &str,
+    context: &gql::Context,
+) -> Result<Item, error::Error> {
+    let item = fetch_dataset_item(dataset_id, item_id, context)
+        .await?
+        .ok_or_else(|| error::Error::UserError {
+            details: format!("Unknown item {} in dataset {}", item_id, dataset_id),
+        })?;
+    let url = context
+        .settings
+        .datasets
+        .location(&item.data_source, &item.id)?;
+    let workdir = context.settings.work_dir()?.to_path_buf();
+
+    let item = update_item_status(
+        &item,
+        FileStatus::DownloadInProgress,
+        context.pool()?,
+        &context.logger,
+    )
+    .await?;
+
+    let pool = context.pool()?.clone();
+    let logger = context.logger.clone();
+    let shutdown = context.shutdown.clone();
+    context.shutdown.spawn({
+        let item = item.clone();
+        async move {
+            // The outcome is recorded in the status of the item.
+            let _ = download_dataset_item_task(item, url, workdir, pool, logger, shutdown).await;
+        }
+    });
+
+    Ok(item)
+}
+
+/// Download the item from the url, and record its file, checksum and size. If the download
+/// fails, the item is marked as such. If the server shuts down first, the download stops, and
+/// the item is marked as not available, as recovery would.
+pub async fn download_dataset_item_task(
+    item: Item,
+    url: String,
+    workdir: PathBuf,
+    pool: PgPool,
+    logger: Logger,
+    shutdown: Shutdown,
+) -> Result<Item, error::Error> {
+    let logger =
+        logger.new(o!("data_source" => item.data_source.clone(), "item" => item.id.clone()));
+    let download = download_item_file(&item, &url, workdir, &pool, &logger);
+    let res = match future::select(Box::pin(download), Box::pin(shutdown.wait())).await {
+        Either::Left((res, _)) => res,
+        Either::Right(_) => {
+            info!(logger, "Download of {} stopped by the shutdown", url);
+            return update_item_status(&item, FileStatus::NotAvailable, &pool, &logger).await;
+        }
+    };
+    match res {
+        Ok(item) => Ok(item),
+        Err(err) => {
+            warn!(logger, "Could not download {}: {}", url, err);
+            update_item_status(&item, FileStatus::DownloadError, &pool, &logger).await?;
+            Err(err)
+        }
+    }
+}
+
+async fn download_item_file(
+    item: &Item,
+    url: &str,
+    workdir: PathBuf,
+    pool: &PgPool,
+    logger: &Logger,
+) -> Result<Item, error::Error> {
+    // Items are shared by the datasets of their data source, so they are stored by data source.
+    let mut path: PathBuf = workdir.join("datasets").join(&item.data_source);
+    fs::create_dir_all(path.clone())
+        .await
+        .context(error::TokioIOError)?;
+
+    let filename = url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .unwrap_or_else(|| item.id.clone());
+    path.push(&filename);
+    info!(logger, "Downloading {} to {}", url, path.display());
+    let _job = metrics::JobGuard::new("download");
+
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .context(error::ReqwestError {
+            details: format!("Could not download {} from {}", filename, url),
+        })?;
+
+    // The body is written to the file as it arrives, rather than held in memory, since items
+    // can be several gigabytes large.
+    let mut file = fs::File::create(path).await.context(error::TokioIOError)?;
+    let mut hasher = Md5::new();
+    let mut size = 0;
+    while let Some(chunk) = response.chunk().await.context(error::ReqwestError {
+        details: format!("Could not download {} from {}", filename, url),
+    })? {
+        hasher.input(&chunk);
+        size += chunk.len();
+        file.write_all(&chunk).await.context(error::TokioIOError)?;
+    }
+    file.flush().await.context(error::TokioIOError)?;
+    info!(logger, "... download ok");
+    metrics::record_download(size);
+
+    let digest = format!("{:x}", hasher.result());
+    let filesize = size as f64 / 1024.0;
+    sqlx::query_as(
+        "UPDATE main.dataset_items
+            SET (filename, md5, filesize, filestatus, updated_at) = ($1, $2, $3, $4, $5)
+            WHERE data_source = $6 AND id = $7
+            RETURNING *",
+    )
+    .bind(filename)
+    .bind(digest)
+    .bind(filesize)
+    .bind(FileStatus::Available)
+    .bind(Utc::now())
+    .bind(&item.data_source)
+    .bind(&item.id)
+    .fetch_one(pool)
+    .timed(logger, "dataset::download_item_file")
+    .await
+    .context(error::DBError {
+        details: "Could not update dataset item",
+    })
+}
+
+async fn update_item_status(
+    item: &Item,
+    status: FileStatus,
+    pool: &PgPool,
+    logger: &Logger,
+) -> Result<Item, error::Error> {
+    sqlx::query_as(
+        "UPDATE main.dataset_items
+        SET (filestatus, updated_at) = ($1, $2)
+        WHERE data_source = $3 AND id = $4
+        RETURNING *",
+    )
+    .bind(status)
+    .bind(Utc::now())
+    .bind(&item.data_source)
+    .bind(&item.id)
+    .fetch_one(pool)
+    .timed(logger, "dataset::update_item_status")
+    .await
+    .context(error::DBError {
+        details: "Could not update dataset item",
+    })
+}
+
+/// Return a dataset, without its items, or None if not found.
+pub async fn fetch_dataset(
+    id: &str,
+    context: &gql::Context,
+) -> Result<Option<Dataset>, error::Error> {
+    sqlx::query_as("SELECT * FROM main.datasets WHERE id = $1")
+        .bind(id)
+        .fetch_optional(context.pool()?)
+        .timed(&context.logger, "dataset::fetch_dataset")
+        .await
+        .context(error::DBError {
+            details: "Could not retrieve dataset",
+        })
+}
+
+/// Return an item identified by its dataset id, and its id.
+pub async fn fetch_dataset_item(
+    dataset_id: &str,
+    item_id: &str,
+    context: &gql::Context,
+) -> Result<Option<Item>, error::Error> {
+    sqlx::query_as(
+        "SELECT item.* FROM main.dataset_item_map AS map
+            INNER JOIN main.dataset_items AS item
+            ON map.data_source = item.data_source AND map.item = item.id
+            WHERE map.dataset = $1 AND item.id = $2",
+    )
+    .bind(dataset_id)
+    .bind(item_id)
+    .fetch_optional(context.pool()?)
+    .timed(&context.logger, "dataset::fetch_dataset_item")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve dataset item",
+    })
+}
+
+/// Return the items of a dataset.
+pub async fn fetch_dataset_items(
+    dataset_id: &str,
+    context: &gql::Context,
+) -> Result<Vec<Item>, error::Error> {
+    sqlx::query_as(
+        "SELECT item.* FROM main.dataset_item_map AS map
+            INNER JOIN main.dataset_items AS item
+            ON map.data_source = item.data_source AND map.item = item.id
+            WHERE map.dataset = $1
+            ORDER BY item.id",
+    )
+    .bind(dataset_id)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "dataset::fetch_dataset_items")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve dataset items",
+    })
+}
+
+/// Return the datasets, with their items, of the data source if one is given, or of all of
+/// them otherwise.
+pub async fn fetch_datasets(
+    data_source: Option<&str>,
+    context: &gql::Context,
+) -> Result<Vec<Dataset>, error::Error> {
+    let datasets = sqlx::query_as(
+        "SELECT * FROM main.datasets
+        WHERE $1::TEXT IS NULL OR data_source = $1
+        ORDER BY id",
+    )
+    .bind(data_source)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "dataset::fetch_datasets")
+    .await
+    .map(|rows| {
+        let datasets: Vec<Dataset> = rows;
+        // We need to wrap vector elements in a Result to make it compatible with try_fold
+        // used below.
+        datasets.into_iter().map(Ok)
+    })
+    .context(error::DBError {
+        details: "Could not retrieve datasets",
+    })?;
+
+    stream::iter(datasets)
+        .try_fold(vec![], |mut acc, dataset| async move {
+            let items = fetch_dataset_items(&dataset.id, context).await?;
+            acc.push(Dataset { items, ..dataset });
+            Ok(acc)
+        })
+        .await
+}
+
+/// Insert a dataset of the data source.
+pub async fn insert_dataset(
+    id: &str,
+    data_source: &str,
+    description: &str,
+    context: &gql::Context,
+) -> Result<Dataset, error::Error> {
+    sqlx::query_as(
+        "INSERT INTO main.datasets (id, data_source, description) VALUES ($1, $2, $3) RETURNING *",
+    )
+    .bind(id)
+    .bind(data_source)
+    .bind(description)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "dataset::insert_dataset")
+    .await
+    .context(error::DBError {
+        details: format!("Could not insert dataset {}", id),
+    })
+}
+
+/// Insert an item in the dataset.
+pub async fn insert_dataset_item(
+    dataset: &Dataset,
+    item_id: &str,
+    context: &gql::Context,
+) -> Result<Item, error::Error> {
+    // The item and its mapping to the dataset are inserted together, so that we don't end up
+    // with an item which does not belong to any dataset.
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    let item = insert_dataset_item_tx(dataset, item_id, &mut tx, context).await?;
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit dataset item",
+    })?;
+
+    Ok(item)
+}
+
+// Same as insert_dataset_item, but within a transaction owned by the caller. If the item
+// already belongs to the dataset, this is an error, and the caller should roll back.
+pub async fn insert_dataset_item_tx(
+    dataset: &Dataset,
+    item_id: &str,
+    tx: &mut PgTx,
+    context: &gql::Context,
+) -> Result<Item, error::Error> {
+    // The item may already belong to another dataset, in which case it is shared.
+    let item = sqlx::query_as(
+        "INSERT INTO main.dataset_items (id, data_source) VALUES ($1, $2)
+        ON CONFLICT (data_source, id) DO UPDATE SET id = EXCLUDED.id
+        RETURNING *",
+    )
+    .bind(item_id)
+    .bind(&dataset.data_source)
+    .fetch_one(&mut *tx)
+    .timed(&context.logger, "dataset::insert_dataset_item_tx")
+    .await
+    .context(error::DBError {
+        details: format!("Could not insert dataset item {}", item_id),
+    })?;
+
+    let mapped = sqlx::query(
+        "INSERT INTO main.dataset_item_map (dataset, data_source, item) VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(&dataset.id)
+    .bind(&dataset.data_source)
+    .bind(item_id)
+    .execute(&mut *tx)
+    .timed(&context.logger, "dataset::insert_dataset_item_tx")
+    .await
+    .context(error::DBError {
+        details: format!("Could not map dataset item {} to {}", item_id, dataset.id),
+    })?;
+
+    if mapped == 0 {
+        return Err(error::Error::UserError {
+            details: String::from("already a dataset item with that id"),
+        });
+    }
+
+    Ok(item)
+}
+
+/// Remove an item identified by its dataset id, and its id.
+pub async fn remove_dataset_item(
+    dataset_id: &str,
+    item_id: &str,
+    context: &gql::Context,
+) -> Result<(), error::Error> {
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    remove_dataset_item_tx(dataset_id, item_id, &mut tx, context).await?;
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit dataset item removal",
+    })?;
+
+    Ok(())
+}
+
+// Same as remove_dataset_item, but within a transaction owned by the caller.
+pub async fn remove_dataset_item_tx(
+    dataset_id: &str,
+    item_id: &str,
+    tx: &mut PgTx,
+    context: &gql::Context,
+) -> Result<(), error::Error> {
+    sqlx::query("DELETE FROM main.dataset_item_map WHERE dataset = $1 AND item = $2")
+        .bind(dataset_id)
+        .bind(item_id)
+        .execute(&mut *tx)
+        .timed(&context.logger, "dataset::remove_dataset_item_tx")
+        .await
+        .context(error::DBError {
+            details: format!(
+                "Could not remove dataset item {} from {}",
+                item_id, dataset_id
+            ),
+        })?;
+
+    remove_orphan_items_tx(tx, context).await
+}
+
+// Items can be shared by several datasets, so they are only removed once no dataset uses them.
+async fn remove_orphan_items_tx(tx: &mut PgTx, context: &gql::Context) -> Result<(), error::Error> {
+    sqlx::query(
+        "DELETE FROM main.dataset_items AS item
+        WHERE NOT EXISTS (
+            SELECT FROM main.dataset_item_map AS map
+            WHERE map.data_source = item.data_source AND map.item = item.id
+            )",
+    )
+    .execute(&mut *tx)
+    .timed(&context.logger, "dataset::remove_orphan_items_tx")
+    .await
+    .context(error::DBError {
+        details: "Could not remove orphan dataset items",
+    })?;
+    Ok(())
+}
+
+/// Remove a dataset identified by its id, along with the items no other dataset uses.
+pub async fn remove_dataset(id: &str, context: &gql::Context) -> Result<Dataset, error::Error> {
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    let dataset = remove_dataset_tx(id, &mut tx, context).await?;
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit dataset removal",
+    })?;
+
+    Ok(dataset)
+}
+
+// Same as remove_dataset, but within a transaction owned by the caller.
+pub async fn remove_dataset_tx(
+    id: &str,
+    tx: &mut PgTx,
+    context: &gql::Context,
+) -> Result<Dataset, error::Error> {
+    // The mapping to the items goes along with the dataset.
+    let dataset = sqlx::query_as("DELETE FROM main.datasets WHERE id = $1 RETURNING *")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .timed(&context.logger, "dataset::remove_dataset_tx")
+        .await
+        .context(error::DBError {
+            details: format!("Could not remove dataset {}", id),
+        })?;
+
+    remove_orphan_items_tx(tx, context).await?;
+
+    Ok(dataset)
+}
+
+/// Insert an item in the dataset, if there is a dataset with that id, and the item does not
+/// already belong to it.
+pub async fn check_and_insert_dataset_item(
+    dataset_id: &str,
+    item_id: &str,
+    context: &gql::Context,
+) -> Result<Item, error::Error> {
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    // The dataset is locked, so that it is not removed until the item is inserted.
+    let dataset: Option<Dataset> =
+        sqlx::query_as("SELECT * FROM main.datasets WHERE id = $1 FOR SHARE")
+            .bind(dataset_id)
+            .fetch_optional(&mut tx)
+            .timed(&context.logger, "dataset::check_and_insert_dataset_item")
+            .await
+            .context(error::DBError {
+                details: "Could not retrieve dataset",
+            })?;
+    let dataset = match dataset {
+        Some(dataset) => dataset,
+        None => {
+            info!(context.logger, "unknown id {}", dataset_id);
+            return Err(error::Error::UserError {
+                details: String::from("Unknown dataset id"),
+            });
+        }
+    };
+
+    // Dropping the transaction on error rolls it back.
+    let item = insert_dataset_item_tx(&dataset, item_id, &mut tx, context).await?;
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit dataset item",
+    })?;
+
+    Ok(item)
+}
+
+/// Insert a dataset of the data source, if there is no dataset with that id.
+pub async fn check_and_insert_dataset(
+    id: &str,
+    data_source: &str,
+    description: &str,
+    context: &gql::Context,
+) -> Result<Dataset, error::Error> {
+    let dataset = sqlx::query_as(
+        "INSERT INTO main.datasets (id, data_source, description) VALUES ($1, $2, $3)
+        ON CONFLICT (id) DO NOTHING
+        RETURNING *",
+    )
+    .bind(id)
+    .bind(data_source)
+    .bind(description)
+    .fetch_optional(context.pool()?)
+    .timed(&context.logger, "dataset::check_and_insert_dataset")
+    .await
+    .context(error::DBError {
+        details: format!("Could not insert dataset {}", id),
+    })?;
+
+    dataset.ok_or_else(|| {
+        info!(context.logger, "dataset already exists id {}", id);
+        error::Error::UserError {
+            details: String::from("duplicate dataset id"),
+        }
+    })
+}
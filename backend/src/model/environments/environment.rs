@@ -1,6 +1,6 @@
 // use super::scenario::{self, Scenario};
 use super::index::IndexStatus;
-use crate::{error, gql};
+use crate::{error, gql, utils::timing::Timed};
 use chrono::prelude::*;
 // use futures::stream::{self, TryStreamExt};
 use juniper::GraphQLObject;
@@ -13,7 +13,7 @@ use sqlx::{
 };
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
 pub struct Environment {
     pub id: Uuid,
     pub signature: String,
@@ -39,9 +39,9 @@ pub async fn fetch_all_environments(
 ) -> Result<Vec<Environment>, error::Error> {
     debug!(context.logger, "Retrieving all environments");
     sqlx::query_as("SELECT id, signature, status, created_at, updated_at FROM main.environments")
-        .fetch_all(&context.pool)
+        .fetch_all(context.pool()?)
+        .timed(&context.logger, "environment::fetch_all_environments")
         .await
-        .map(Into::<Vec<Environment>>::into)
         .context(error::DBError {
             details: "Could not retrieve environments",
         })
@@ -56,7 +56,8 @@ pub async fn fetch_environment_by_id(
         "SELECT id, signature, status, created_at, updated_at FROM main.environments WHERE id=$1",
     )
     .bind(id)
-    .fetch_one(&context.pool)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "environment::fetch_environment_by_id")
     .await
     .context(error::DBError {
         details: "Could not retrieve environment with id '{}', id",
@@ -77,7 +78,11 @@ pub async fn create_or_replace_environment(
         .bind(id)
         .bind(signature.clone())
         .bind(status)
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(
+            &context.logger,
+            "environment::create_or_replace_environment",
+        )
         .await
         .context(error::DBError {
             details: format!("Could not create or replace environment '{}'", signature),
@@ -91,7 +96,8 @@ pub async fn delete_enviroment_by_id(
     debug!(context.logger, "Deleting environment with id '{}'", id);
     sqlx::query_as("SELECT * FROM main.delete_environment($1)")
         .bind(id)
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "environment::delete_enviroment_by_id")
         .await
         .context(error::DBError {
             details: "Could not delete environment",
@@ -1,5 +1,5 @@
 // use super::scenario::{self, Scenario};
-use crate::{error, gql};
+use crate::{error, gql, utils::timing::Timed};
 use chrono::prelude::*;
 // use futures::stream::{self, TryStreamExt};
 use juniper::{GraphQLEnum, GraphQLObject};
@@ -12,7 +12,7 @@ use sqlx::{
 };
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, sqlx::Type, GraphQLEnum)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type, GraphQLEnum)]
 #[sqlx(rename = "index_status")]
 #[serde(rename_all = "lowercase")]
 pub enum IndexStatus {
@@ -38,7 +38,7 @@ pub enum IndexStatus {
     Available,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
 pub struct Index {
     pub id: Uuid,
     pub signature: String,
@@ -79,9 +79,9 @@ pub async fn fetch_indexes_by_environment_id(
         WHERE m.environment = $1",
     )
     .bind(id)
-    .fetch_all(&context.pool)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "index::fetch_indexes_by_environment_id")
     .await
-    .map(Into::<Vec<Index>>::into)
     .context(error::DBError {
         details: "Could not retrieve indexes",
     })
@@ -95,7 +95,8 @@ pub async fn validate_index_type(
     let _res = sqlx::query("SELECT id FROM main.index_types WHERE id = $1")
         .bind(index_type)
         .try_map(|row: PgRow| row.try_get::<String, _>(0))
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "index::validate_index_type")
         .await
         .context(error::DBError {
             details: format!("Could not retrieve index type '{}'", index_type),
@@ -111,7 +112,8 @@ pub async fn validate_data_source(
     let _res = sqlx::query("SELECT id FROM main.data_sources WHERE id = $1")
         .bind(data_source)
         .try_map(|row: PgRow| row.try_get::<String, _>(0))
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "index::validate_data_source")
         .await
         .context(error::DBError {
             details: format!("Could not retrieve data source '{}'", data_source),
@@ -130,7 +132,8 @@ pub async fn validate_data_source_with_index_type(
         .bind(index_type)
         .bind(data_source)
         .try_map(|row: PgRow| row.try_get::<String, _>(0))
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "index::validate_data_source_with_index_type")
         .await
         //.map(Into::<Vec<Environment>>::into)
         .context(error::DBError {
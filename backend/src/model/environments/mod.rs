@@ -1,2 +1,3 @@
+pub mod dataset;
 pub mod environment;
 pub mod index;
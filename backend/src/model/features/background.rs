@@ -1,5 +1,9 @@
 use super::{step, SourceType};
-use crate::{error, gql, model::environments};
+use crate::{
+    error, gql,
+    model::{environments, PgTx},
+    utils::timing::Timed,
+};
 use chrono::prelude::*;
 use futures::stream::{self, TryStreamExt};
 use juniper::GraphQLObject;
@@ -12,7 +16,7 @@ use sqlx::{
 };
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
 pub struct Background {
     pub id: Uuid,
     pub created_at: DateTime<Utc>,
@@ -39,7 +43,8 @@ pub async fn fetch_background_by_id(
     debug!(context.logger, "Fetching background '{}'", id);
     sqlx::query_as("SELECT id, created_at, updated_at FROM main.backgrounds WHERE id = $1")
         .bind(id)
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "background::fetch_background_by_id")
         .await
         .context(error::DBError {
             details: "Could not retrieve background",
@@ -55,7 +60,11 @@ pub async fn fetch_background_by_feature_id(
     // We select everything except search which is a created field.
     sqlx::query_as("SELECT id, created_at, updated_at FROM main.backgrounds WHERE feature = $1")
         .bind(id)
-        .fetch_optional(&context.pool)
+        .fetch_optional(context.pool()?)
+        .timed(
+            &context.logger,
+            "background::fetch_background_by_feature_id",
+        )
         .await
         .context(error::DBError {
             details: "Could not retrieve backgrounds",
@@ -65,34 +74,36 @@ pub async fn fetch_background_by_feature_id(
 pub async fn create_or_replace_background_from_gherkin(
     background: gherkin_rust::Background,
     feature: &Uuid,
+    tx: &mut PgTx,
     context: &gql::Context,
 ) -> Result<Background, error::Error> {
     debug!(context.logger, "Creating background from gherkin");
 
     let res: Background = sqlx::query_as("SELECT * FROM main.create_background($1)")
         .bind(feature)
-        .fetch_one(&context.pool)
+        .fetch_one(&mut *tx)
+        .timed(
+            &context.logger,
+            "background::create_or_replace_background_from_gherkin",
+        )
         .await
         .context(error::DBError {
-            details: format!("Could not create background"),
+            details: "Could not create background".to_string(),
         })?;
 
     let id = res.id;
 
-    // Here we're turning the background's steps into a stream of Result<Step, _>, on
-    // which we can use try_for_each and insert them in the database
-    stream::iter(background.steps.into_iter().map(|step| Ok(step)))
-        .try_for_each(|step| async {
-            let _step = step::create_or_replace_step_from_gherkin(
-                step,
-                &id,
-                SourceType::Background,
-                context,
-            )
-            .await?;
-            Ok(())
-        })
+    // The steps share the transaction, so they are inserted one after the other.
+    for step in background.steps {
+        let _step = step::create_or_replace_step_from_gherkin(
+            step,
+            &id,
+            SourceType::Background,
+            tx,
+            context,
+        )
         .await?;
+    }
 
     Ok(res)
 }
@@ -107,9 +118,11 @@ pub async fn fetch_background_steps(
     sqlx::query_as("SELECT s.id, s.step_type, s.value, s.docstring, s.created_at, s.updated_at FROM main.steps AS s
         INNER JOIN main.background_step_map AS m ON m.step = s.id
         INNER JOIN main.backgrounds AS b ON b.id = m.background
-        WHERE b.id = $1")
+        WHERE b.id = $1
+        ORDER BY m.position")
         .bind(id)
-        .fetch_all(&context.pool)
+        .fetch_all(context.pool()?)
+        .timed(&context.logger, "background::fetch_background_steps")
         .await
         .context(error::DBError {
             details: "Could not retrieve steps",
@@ -133,9 +146,9 @@ pub async fn fetch_background_environment(
     // - we use 'add_index_to_background' to associate that index to the current background.
     // Finally, we retrieve the environment corresponding to that background.
 
-    let steps = fetch_background_steps(&id, &context).await?;
+    let steps = fetch_background_steps(id, context).await?;
 
-    stream::iter(steps.into_iter().map(|step| Ok(step)))
+    stream::iter(steps.into_iter().map(Ok))
         .try_for_each(|step| async {
             info!(context.logger, "Working on step {}", step.value);
             // FIXME Do something if step_type is not 'GIVEN'
@@ -163,7 +176,8 @@ pub async fn fetch_background_environment(
                     .bind(index_type)
                     .bind(data_source)
                     .bind(regions)
-                    .fetch_one(&context.pool)
+                    .fetch_one(context.pool()?)
+                    .timed(&context.logger, "background::fetch_background_environment")
                     .await
                     .context(error::DBError {
                         details: "Could not create or replace index",
@@ -175,7 +189,8 @@ pub async fn fetch_background_environment(
                 sqlx::query_as("SELECT * FROM main.add_index_to_background($1, $2)")
                     .bind(index.id)
                     .bind(id)
-                    .fetch_one(&context.pool)
+                    .fetch_one(context.pool()?)
+                    .timed(&context.logger, "background::fetch_background_environment")
                     .await
                     .context(error::DBError {
                         details: "Could not add index to background",
@@ -188,7 +203,8 @@ pub async fn fetch_background_environment(
 
     sqlx::query_as("SELECT * FROM main.fetch_background_environment($1)")
         .bind(id)
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "background::fetch_background_environment")
         .await
         .context(error::DBError {
             details: format!("Could not fetch environment for background '{}'", id),
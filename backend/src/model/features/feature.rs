@@ -1,7 +1,6 @@
 use super::{background, scenario};
-use crate::{error, gql};
+use crate::{error, gql, model::PgTx, utils::timing::Timed};
 use chrono::prelude::*;
-use futures::stream::{self, TryStreamExt};
 use juniper::GraphQLObject;
 use serde::{Deserialize, Serialize};
 use slog::debug;
@@ -12,12 +11,14 @@ use sqlx::{
 };
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
 pub struct Feature {
     pub id: Uuid,
     pub name: String,
     pub description: String,
     pub tags: Vec<String>,
+    pub created_by: Option<String>, // principal who created the feature
+    pub updated_by: Option<String>, // principal who last modified the feature
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -29,8 +30,10 @@ impl<'c> FromRow<'c, PgRow<'c>> for Feature {
             name: row.get(1),
             description: row.get(2),
             tags: row.get(3),
-            created_at: row.get(4),
-            updated_at: row.get(5),
+            created_by: row.get(4),
+            updated_by: row.get(5),
+            created_at: row.get(6),
+            updated_at: row.get(7),
         })
     }
 }
@@ -38,13 +41,16 @@ impl<'c> FromRow<'c, PgRow<'c>> for Feature {
 pub async fn fetch_all_features(context: &gql::Context) -> Result<Vec<Feature>, error::Error> {
     debug!(context.logger, "Retrieving all features");
     // We select everything except search which is a created field.
-    sqlx::query_as("SELECT id, name, description, tags, created_at, updated_at FROM main.features")
-        .fetch_all(&context.pool)
-        .await
-        .map(Into::<Vec<Feature>>::into)
-        .context(error::DBError {
-            details: "Could not retrieve features",
-        })
+    sqlx::query_as(
+        "SELECT id, name, description, tags, created_by, updated_by, created_at, updated_at
+        FROM main.features",
+    )
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "feature::fetch_all_features")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve features",
+    })
 }
 
 // I'm not sure this function is useful, i've implemented it for testing loading -> fetching.
@@ -55,10 +61,12 @@ pub async fn fetch_feature_by_id(
     debug!(context.logger, "Fetching feature with id '{}'", id);
     // We select everything except search which is a created field.
     sqlx::query_as(
-        "SELECT id, name, description, tags, created_at, updated_at FROM main.features WHERE id=$1",
+        "SELECT id, name, description, tags, created_by, updated_by, created_at, updated_at
+        FROM main.features WHERE id=$1",
     )
     .bind(id)
-    .fetch_one(&context.pool)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "feature::fetch_feature_by_id")
     .await
     .context(error::DBError {
         details: "Could not retrieve features",
@@ -72,15 +80,19 @@ pub async fn create_or_replace_feature(
     context: &gql::Context,
 ) -> Result<Feature, error::Error> {
     debug!(context.logger, "Creating or Replacing Feature '{}'", name);
-    sqlx::query_as("SELECT * FROM main.create_or_replace_feature($1, $2, $3)")
-        .bind(name.clone())
-        .bind(description)
-        .bind(tags)
-        .fetch_one(&context.pool)
-        .await
-        .context(error::DBError {
-            details: format!("Could not create or replace feature '{}'", name),
-        })
+    sqlx::query_as(
+        "SELECT * FROM main.create_or_replace_feature($1, $2, $3, $4) WHERE id IS NOT NULL",
+    )
+    .bind(name.clone())
+    .bind(description)
+    .bind(tags)
+    .bind(context.principal.name.as_str())
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "feature::create_or_replace_feature")
+    .await
+    .context(error::DBError {
+        details: format!("Could not create or replace feature '{}'", name),
+    })
 }
 
 pub async fn create_or_replace_feature_from_string(
@@ -89,44 +101,110 @@ pub async fn create_or_replace_feature_from_string(
 ) -> Result<Feature, error::Error> {
     debug!(context.logger, "Creating or Replacing Feature from string");
 
-    let feature = gherkin_rust::Feature::parse(feature).context(error::GherkinError {
-        details: String::from("Could not parse feature"),
-    })?;
+    let feature = parse_feature(feature)?;
 
     create_or_replace_feature_from_gherkin(feature, context).await
 }
 
+pub fn parse_feature(feature: String) -> Result<gherkin_rust::Feature, error::Error> {
+    gherkin_rust::Feature::parse(feature).context(error::GherkinError {
+        details: String::from("Could not parse feature"),
+    })
+}
+
 pub async fn create_or_replace_feature_from_gherkin(
     feature: gherkin_rust::Feature,
     context: &gql::Context,
 ) -> Result<Feature, error::Error> {
     debug!(context.logger, "Creating or Replacing Feature from gherkin");
 
-    let res: Feature = sqlx::query_as("SELECT * FROM main.create_or_replace_feature($1, $2, $3)")
-        .bind(feature.name)
-        .bind(feature.description.unwrap_or(String::from("")))
-        .bind(feature.tags)
-        .fetch_one(&context.pool)
+    // The feature, its background, scenarios and steps are all inserted in a single
+    // transaction, so that we don't end up with half a feature in the database.
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    let res = create_or_replace_feature_from_gherkin_tx(feature, &mut tx, context).await?;
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit feature",
+    })?;
+
+    Ok(res)
+}
+
+/// Scenarios are identified by their name within a feature, so they must be unique.
+/// Return the names of the scenarios, sorted.
+pub fn check_scenario_names(feature: &gherkin_rust::Feature) -> Result<Vec<String>, error::Error> {
+    let mut names: Vec<String> = feature.scenarios.iter().map(|s| s.name.clone()).collect();
+    names.sort();
+    if let Some(name) = names
+        .windows(2)
+        .find(|w| w[0] == w[1])
+        .map(|w| w[0].clone())
+    {
+        return Err(error::Error::UserError {
+            details: format!(
+                "Feature '{}' has multiple scenarios named '{}'",
+                feature.name, name
+            ),
+        });
+    }
+    Ok(names)
+}
+
+// Same as create_or_replace_feature_from_gherkin, but within a transaction owned by the caller.
+pub async fn create_or_replace_feature_from_gherkin_tx(
+    feature: gherkin_rust::Feature,
+    tx: &mut PgTx,
+    context: &gql::Context,
+) -> Result<Feature, error::Error> {
+    let names = check_scenario_names(&feature)?;
+
+    let res: Feature = sqlx::query_as(
+        "SELECT * FROM main.create_or_replace_feature($1, $2, $3, $4) WHERE id IS NOT NULL",
+    )
+    .bind(feature.name)
+    .bind(feature.description.unwrap_or(String::from("")))
+    .bind(feature.tags)
+    .bind(context.principal.name.as_str())
+    .fetch_one(&mut *tx)
+    .timed(
+        &context.logger,
+        "feature::create_or_replace_feature_from_gherkin_tx",
+    )
+    .await
+    .context(error::DBError {
+        details: "Could not create or replace feature",
+    })?;
+
+    let id = res.id;
+
+    // If the feature was already there, we remove what is not part of the new version.
+    sqlx::query("SELECT main.prune_feature($1, $2, $3)")
+        .bind(id)
+        .bind(names)
+        .bind(feature.background.is_some())
+        .execute(&mut *tx)
+        .timed(
+            &context.logger,
+            "feature::create_or_replace_feature_from_gherkin_tx",
+        )
         .await
         .context(error::DBError {
-            details: format!("Could not create or replace feature"),
+            details: format!("Could not prune feature '{}'", id),
         })?;
 
-    let id = res.id;
-
     if let Some(background) = feature.background {
         let _background =
-            background::create_or_replace_background_from_gherkin(background, &id, context).await?;
+            background::create_or_replace_background_from_gherkin(background, &id, tx, context)
+                .await?;
+    }
+
+    for scenario in feature.scenarios {
+        let _scenario =
+            scenario::create_or_replace_scenario_from_gherkin(scenario, &id, tx, context).await?;
     }
-    // Here we're turning the feature's scenarios into a stream of Result<Scenario, _>, on
-    // which we can use try_for_each and insert them in the database
-    stream::iter(feature.scenarios.into_iter().map(|scenario| Ok(scenario)))
-        .try_for_each(|scenario| async {
-            let _scenario =
-                scenario::create_or_replace_scenario_from_gherkin(scenario, &id, context).await?;
-            Ok(())
-        })
-        .await?;
 
     Ok(res)
 }
@@ -136,10 +214,12 @@ pub async fn delete_feature_by_id(
     context: &gql::Context,
 ) -> Result<Feature, error::Error> {
     debug!(context.logger, "Deleting feature with id '{}'", id);
-    // We select everything except search which is a created field.
-    sqlx::query_as("SELECT * FROM main.delete_feature($1)")
+    // We select everything except search which is a created field. Nothing is deleted when
+    // there is no such feature, and the function then returns a row of nulls.
+    sqlx::query_as("SELECT * FROM main.delete_feature($1) WHERE id IS NOT NULL")
         .bind(id)
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "feature::delete_feature_by_id")
         .await
         .context(error::DBError {
             details: "Could not delete feature",
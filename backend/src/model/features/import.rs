@@ -0,0 +1,254 @@
+use super::feature::{self, Feature};
+use crate::{error, gql, utils::archive::FeatureSource};
+use juniper::GraphQLObject;
+use serde::{Deserialize, Serialize};
+use slog::{debug, info, warn};
+use snafu::ResultExt;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// The outcome of importing a single feature file.
+/// Exactly one of feature and error is set.
+#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct FileReport {
+    pub filename: String,
+    pub feature: Option<Feature>,
+    pub error: Option<String>,
+}
+
+impl FileReport {
+    fn success(filename: String, feature: Feature) -> Self {
+        FileReport {
+            filename,
+            feature: Some(feature),
+            error: None,
+        }
+    }
+
+    fn failure(filename: String, error: String) -> Self {
+        FileReport {
+            filename,
+            feature: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// The outcome of importing a set of feature files.
+/// In atomic mode, either all the files are loaded, or none are.
+#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct ImportReport {
+    pub atomic: bool,
+    pub loaded: i32,
+    pub failed: i32,
+    pub files: Vec<FileReport>,
+}
+
+impl ImportReport {
+    fn new(atomic: bool, files: Vec<FileReport>) -> Self {
+        let loaded = files.iter().filter(|f| f.feature.is_some()).count() as i32;
+        ImportReport {
+            atomic,
+            loaded,
+            failed: files.len() as i32 - loaded,
+            files,
+        }
+    }
+}
+
+/// Import all the '.feature' files found (recursively) under the given directory, which must be
+/// within one of the configured import paths.
+pub async fn import_features_from_dir(
+    path: &str,
+    atomic: bool,
+    context: &gql::Context,
+) -> Result<ImportReport, error::Error> {
+    info!(context.logger, "Importing features from '{}'", path);
+    let path = check_import_path(path, &context.settings.import.paths).await?;
+    let sources = read_feature_files(path).await?;
+    import_features(sources, atomic, context).await
+}
+
+// Resolve the path, and make sure it is within one of the allowed directories. Without any,
+// nothing on the server can be imported.
+async fn check_import_path(path: &str, allowed: &[PathBuf]) -> Result<PathBuf, error::Error> {
+    if allowed.is_empty() {
+        return Err(error::Error::UserError {
+            details: format!(
+                "Cannot import from '{}': no import path is configured",
+                path
+            ),
+        });
+    }
+    let path = fs::canonicalize(path)
+        .await
+        .map_err(|err| error::Error::UserError {
+            details: format!("Cannot import from '{}': {}", path, err),
+        })?;
+    for dir in allowed {
+        if let Ok(dir) = fs::canonicalize(dir).await {
+            if path.starts_with(&dir) {
+                return Ok(path);
+            }
+        }
+    }
+    Err(error::Error::UserError {
+        details: format!(
+            "Cannot import from '{}': not within the configured import paths",
+            path.display()
+        ),
+    })
+}
+
+/// Import a list of features.
+/// All the features are parsed before anything is loaded in the database. Then,
+/// - in atomic mode, if any file fails to parse, nothing is loaded, otherwise all the features
+///   are loaded in a single transaction, which is rolled back on the first error.
+/// - otherwise, each feature is loaded on its own, and failures do not impact other files.
+pub async fn import_features(
+    sources: Vec<FeatureSource>,
+    atomic: bool,
+    context: &gql::Context,
+) -> Result<ImportReport, error::Error> {
+    debug!(
+        context.logger,
+        "Importing {} features (atomic: {})",
+        sources.len(),
+        atomic
+    );
+
+    let parsed: Vec<(String, Result<gherkin_rust::Feature, error::Error>)> = sources
+        .into_iter()
+        .map(|source| {
+            let feature =
+                gherkin_rust::Feature::parse(source.content).context(error::GherkinError {
+                    details: format!("Could not parse feature '{}'", source.filename),
+                });
+            (source.filename, feature)
+        })
+        .collect();
+
+    let files = if atomic {
+        import_atomic(parsed, context).await?
+    } else {
+        let mut files = Vec::new();
+        for (filename, feature) in parsed {
+            let report = match feature {
+                Ok(feature) => {
+                    match feature::create_or_replace_feature_from_gherkin(feature, context).await {
+                        Ok(feature) => FileReport::success(filename, feature),
+                        Err(err) => FileReport::failure(filename, format!("{}", err)),
+                    }
+                }
+                Err(err) => FileReport::failure(filename, format!("{}", err)),
+            };
+            files.push(report);
+        }
+        files
+    };
+
+    let report = ImportReport::new(atomic, files);
+    info!(
+        context.logger,
+        "Imported {} features ({} failed)", report.loaded, report.failed
+    );
+    Ok(report)
+}
+
+async fn import_atomic(
+    parsed: Vec<(String, Result<gherkin_rust::Feature, error::Error>)>,
+    context: &gql::Context,
+) -> Result<Vec<FileReport>, error::Error> {
+    // If anything fails to parse, we don't even start the transaction.
+    if parsed.iter().any(|(_, feature)| feature.is_err()) {
+        return Ok(parsed
+            .into_iter()
+            .map(|(filename, feature)| match feature {
+                Ok(_) => FileReport::failure(
+                    filename,
+                    String::from("Not loaded: another file in the import is invalid"),
+                ),
+                Err(err) => FileReport::failure(filename, format!("{}", err)),
+            })
+            .collect());
+    }
+
+    let features: Vec<(String, gherkin_rust::Feature)> = parsed
+        .into_iter()
+        .filter_map(|(filename, feature)| feature.ok().map(|feature| (filename, feature)))
+        .collect();
+
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    let mut files = Vec::new();
+    let mut rollback = false;
+    for (filename, feature) in features {
+        if rollback {
+            files.push(FileReport::failure(
+                filename,
+                String::from("Not loaded: the import was rolled back"),
+            ));
+            continue;
+        }
+        match feature::create_or_replace_feature_from_gherkin_tx(feature, &mut tx, context).await {
+            Ok(feature) => files.push(FileReport::success(filename, feature)),
+            Err(err) => {
+                warn!(context.logger, "Could not load '{}': {}", filename, err);
+                rollback = true;
+                files.push(FileReport::failure(filename, format!("{}", err)));
+            }
+        }
+    }
+
+    if rollback {
+        tx.rollback().await.context(error::DBError {
+            details: "Could not rollback import",
+        })?;
+        for file in files.iter_mut().filter(|f| f.feature.is_some()) {
+            file.feature = None;
+            file.error = Some(String::from("Not loaded: the import was rolled back"));
+        }
+    } else {
+        tx.commit().await.context(error::DBError {
+            details: "Could not commit import",
+        })?;
+    }
+
+    Ok(files)
+}
+
+// Collect all the feature files under root, with their path relative to root.
+// Hidden directories (eg .git) are skipped, and so are symlinks: following them could escape the
+// import paths checked by check_import_path, or loop forever.
+// The result is sorted by filename so that imports are reproducible.
+pub async fn read_feature_files(root: PathBuf) -> Result<Vec<FeatureSource>, error::Error> {
+    let mut dirs = vec![root.clone()];
+    let mut sources = Vec::new();
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await.context(error::TokioIOError)?;
+        while let Some(entry) = entries.next_entry().await.context(error::TokioIOError)? {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let file_type = entry.file_type().await.context(error::TokioIOError)?;
+            if file_type.is_dir() {
+                if !hidden {
+                    dirs.push(path);
+                }
+            } else if file_type.is_file() && path.extension().is_some_and(|ext| ext == "feature") {
+                let content = fs::read_to_string(&path)
+                    .await
+                    .context(error::TokioIOError)?;
+                let filename = path
+                    .strip_prefix(&root)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                sources.push(FeatureSource { filename, content });
+            }
+        }
+    }
+    sources.sort_by(|a, b| a.filename.cmp(&b.filename));
+    Ok(sources)
+}
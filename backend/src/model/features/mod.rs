@@ -9,6 +9,8 @@ use uuid::Uuid;
 
 pub mod background;
 pub mod feature;
+pub mod import;
+pub mod repository;
 pub mod scenario;
 pub mod step;
 
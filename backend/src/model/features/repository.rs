@@ -0,0 +1,341 @@
+use super::{feature, import};
+use crate::{
+    error, gql,
+    settings::Settings,
+    utils::{git, timing::Timed},
+};
+use chrono::prelude::*;
+use juniper::GraphQLObject;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use slog::{info, warn};
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+lazy_static! {
+    // Only one synchronisation can run at any given time, since they share the checkout.
+    static ref SYNC_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Where to find the features repository, and where to check it out.
+#[derive(Debug, Clone)]
+pub struct RepositoryConfig {
+    pub url: String,       // local path, or url understood by git (eg file://)
+    pub branch: String,    // branch to synchronise with
+    pub checkout: PathBuf, // local directory holding the checkout
+}
+
+impl RepositoryConfig {
+    /// Extract the configuration from the settings:
+    /// The repository url is mandatory, and the repository is checked out in
+    /// ${WORK_DIR}/repository.
+    pub fn from_settings(settings: &Settings) -> Result<Self, error::Error> {
+        let url = settings
+            .repository
+            .url
+            .clone()
+            .ok_or_else(|| error::Error::ConfigError {
+                details: String::from(
+                    "no features repository (set FEATURES_REPOSITORY, or repository.url)",
+                ),
+            })?;
+        Ok(RepositoryConfig {
+            url,
+            branch: settings.repository.branch.clone(),
+            checkout: settings.work_dir()?.join("repository"),
+        })
+    }
+}
+
+/// Records which file, in which commit, a feature was loaded from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct FeatureRevision {
+    pub id: Uuid,
+    pub feature: Uuid,
+    pub path: String,
+    pub commit_sha: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl<'c> FromRow<'c, PgRow<'c>> for FeatureRevision {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(FeatureRevision {
+            id: row.get(0),
+            feature: row.get(1),
+            path: row.get(2),
+            commit_sha: row.get(3),
+            created_at: row.get(4),
+        })
+    }
+}
+
+/// A synchronisation with the features repository.
+/// While it is in progress, finished_at is not set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct RepositorySync {
+    pub id: Uuid,
+    pub url: String,
+    pub branch: String,
+    pub commit_sha: Option<String>,
+    pub loaded: i32,
+    pub deleted: i32,
+    pub failures: Vec<String>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+// This should match the main.return_repository_sync_type
+impl<'c> FromRow<'c, PgRow<'c>> for RepositorySync {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(RepositorySync {
+            id: row.get(0),
+            url: row.get(1),
+            branch: row.get(2),
+            commit_sha: row.get(3),
+            loaded: row.get(4),
+            deleted: row.get(5),
+            failures: row.get(6),
+            error: row.get(7),
+            started_at: row.get(8),
+            finished_at: row.get(9),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct RepositoryStatus {
+    pub configured: bool,
+    pub url: Option<String>,
+    pub branch: Option<String>,
+    pub last_sync: Option<RepositorySync>,
+}
+
+// What happened during a synchronisation, before it is recorded.
+struct SyncOutcome {
+    commit_sha: String,
+    loaded: i32,
+    deleted: i32,
+    failures: Vec<String>,
+}
+
+pub async fn fetch_feature_revisions(
+    id: &Uuid,
+    context: &gql::Context,
+) -> Result<Vec<FeatureRevision>, error::Error> {
+    sqlx::query_as(
+        "SELECT id, feature, path, commit_sha, created_at FROM main.feature_revisions
+        WHERE feature = $1
+        ORDER BY created_at DESC",
+    )
+    .bind(id)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "repository::fetch_feature_revisions")
+    .await
+    .context(error::DBError {
+        details: format!("Could not retrieve revisions for feature '{}'", id),
+    })
+}
+
+/// The most recent synchronisation, whether it is finished or not.
+pub async fn fetch_last_sync(
+    context: &gql::Context,
+) -> Result<Option<RepositorySync>, error::Error> {
+    sqlx::query_as(
+        "SELECT id, url, branch, commit_sha, loaded, deleted, failures, error, started_at, finished_at
+        FROM main.repository_syncs
+        ORDER BY started_at DESC
+        LIMIT 1",
+    )
+    .fetch_optional(context.pool()?)
+    .timed(&context.logger, "repository::fetch_last_sync")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve last repository synchronisation",
+    })
+}
+
+pub async fn fetch_repository_status(
+    config: Option<&RepositoryConfig>,
+    context: &gql::Context,
+) -> Result<RepositoryStatus, error::Error> {
+    let last_sync = context.store.repository.fetch_last_sync(context).await?;
+    Ok(RepositoryStatus {
+        configured: config.is_some(),
+        url: config.map(|c| c.url.clone()),
+        branch: config.map(|c| c.branch.clone()),
+        last_sync,
+    })
+}
+
+/// Bring the features in line with the repository:
+/// - every '.feature' in the repository is loaded (or reloaded),
+/// - the features which were previously loaded from the repository, but are no longer in it,
+///   are deleted.
+///
+/// Files which cannot be loaded are reported in the failures, and the features they held, if
+/// any, are left untouched. All the changes happen in a single transaction.
+pub async fn sync_repository(
+    config: &RepositoryConfig,
+    context: &gql::Context,
+) -> Result<RepositorySync, error::Error> {
+    let _guard = SYNC_LOCK.try_lock().map_err(|_| error::Error::UserError {
+        details: String::from("A repository synchronisation is already in progress"),
+    })?;
+
+    let sync: RepositorySync = sqlx::query_as("SELECT * FROM main.start_repository_sync($1, $2)")
+        .bind(config.url.as_str())
+        .bind(config.branch.as_str())
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "repository::sync_repository")
+        .await
+        .context(error::DBError {
+            details: "Could not record repository synchronisation",
+        })?;
+
+    let outcome = sync_checkout(config, context).await;
+
+    let (commit_sha, loaded, deleted, failures, errmsg) = match &outcome {
+        Ok(outcome) => (
+            Some(outcome.commit_sha.clone()),
+            outcome.loaded,
+            outcome.deleted,
+            outcome.failures.clone(),
+            None,
+        ),
+        Err(err) => {
+            warn!(context.logger, "Repository synchronisation failed: {}", err);
+            (None, 0, 0, Vec::new(), Some(format!("{}", err)))
+        }
+    };
+
+    let sync: RepositorySync =
+        sqlx::query_as("SELECT * FROM main.finish_repository_sync($1, $2, $3, $4, $5, $6)")
+            .bind(sync.id)
+            .bind(commit_sha)
+            .bind(loaded)
+            .bind(deleted)
+            .bind(failures)
+            .bind(errmsg)
+            .fetch_one(context.pool()?)
+            .timed(&context.logger, "repository::sync_repository")
+            .await
+            .context(error::DBError {
+                details: "Could not record repository synchronisation",
+            })?;
+
+    outcome.map(|_| sync)
+}
+
+async fn sync_checkout(
+    config: &RepositoryConfig,
+    context: &gql::Context,
+) -> Result<SyncOutcome, error::Error> {
+    let commit_sha = git::clone_or_pull(
+        &config.url,
+        &config.branch,
+        &config.checkout,
+        &context.logger,
+    )
+    .await?;
+    info!(
+        context.logger,
+        "Synchronising features with '{}' at {}", config.url, commit_sha
+    );
+
+    let sources = import::read_feature_files(config.checkout.clone()).await?;
+
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    let mut loaded: Vec<Uuid> = Vec::new();
+    let mut failed_paths: Vec<String> = Vec::new();
+    let mut failures: Vec<String> = Vec::new();
+
+    for source in sources {
+        let path = source.filename;
+        let parsed = gherkin_rust::Feature::parse(source.content).context(error::GherkinError {
+            details: format!("Could not parse feature '{}'", path),
+        });
+        let res = match parsed {
+            Ok(parsed) => {
+                feature::create_or_replace_feature_from_gherkin_tx(parsed, &mut tx, context).await
+            }
+            Err(err) => Err(err),
+        };
+        match res {
+            Ok(feature) => {
+                let _revision: FeatureRevision =
+                    sqlx::query_as("SELECT * FROM main.add_feature_revision($1, $2, $3)")
+                        .bind(feature.id)
+                        .bind(path.as_str())
+                        .bind(commit_sha.as_str())
+                        .fetch_one(&mut tx)
+                        .timed(&context.logger, "repository::sync_checkout")
+                        .await
+                        .context(error::DBError {
+                            details: format!("Could not record revision of '{}'", path),
+                        })?;
+                loaded.push(feature.id);
+            }
+            // Invalid features are reported, but they don't stop the synchronisation. Anything
+            // else (eg a database error) aborts the transaction, so we give up.
+            Err(err) => match err {
+                error::Error::GherkinError { .. } | error::Error::UserError { .. } => {
+                    warn!(context.logger, "Could not load '{}': {}", path, err);
+                    failures.push(format!("{}: {}", path, err));
+                    failed_paths.push(path);
+                }
+                _ => return Err(err),
+            },
+        }
+    }
+
+    // Now we remove the features which are no longer in the repository.
+    let previous: Vec<(Uuid, String)> = sqlx::query("SELECT * FROM main.repository_features()")
+        .try_map(|row: PgRow| Ok((row.try_get::<Uuid, _>(0)?, row.try_get::<String, _>(1)?)))
+        .fetch_all(&mut tx)
+        .timed(&context.logger, "repository::sync_checkout")
+        .await
+        .context(error::DBError {
+            details: "Could not retrieve features loaded from the repository",
+        })?;
+
+    let mut deleted = 0;
+    for (id, path) in previous {
+        if loaded.contains(&id) || failed_paths.contains(&path) {
+            continue;
+        }
+        info!(
+            context.logger,
+            "Deleting feature '{}', since '{}' was removed from the repository", id, path
+        );
+        let _feature: feature::Feature = sqlx::query_as("SELECT * FROM main.delete_feature($1)")
+            .bind(id)
+            .fetch_one(&mut tx)
+            .timed(&context.logger, "repository::sync_checkout")
+            .await
+            .context(error::DBError {
+                details: format!("Could not delete feature '{}'", id),
+            })?;
+        deleted += 1;
+    }
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit repository synchronisation",
+    })?;
+
+    Ok(SyncOutcome {
+        commit_sha,
+        loaded: loaded.len() as i32,
+        deleted,
+        failures,
+    })
+}
@@ -1,7 +1,6 @@
 use super::{step, SourceType};
-use crate::{error, gql};
+use crate::{error, gql, model::PgTx, utils::timing::Timed};
 use chrono::prelude::*;
-use futures::stream::{self, TryStreamExt};
 use juniper::GraphQLObject;
 use serde::{Deserialize, Serialize};
 use slog::debug;
@@ -12,7 +11,7 @@ use sqlx::{
 };
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
 pub struct Scenario {
     pub id: Uuid,
     pub name: String,
@@ -44,7 +43,8 @@ pub async fn fetch_scenario_by_id(
         "SELECT id, name, tags, created_at, updated_at FROM main.scenarios WHERE id = $1",
     )
     .bind(id)
-    .fetch_one(&context.pool)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "scenario::fetch_scenario_by_id")
     .await
     .context(error::DBError {
         details: "Could not retrieve features",
@@ -61,9 +61,9 @@ pub async fn fetch_scenarios_by_feature_id(
         "SELECT id, name, tags, created_at, updated_at FROM main.scenarios WHERE feature = $1",
     )
     .bind(id)
-    .fetch_all(&context.pool)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "scenario::fetch_scenarios_by_feature_id")
     .await
-    .map(Into::<Vec<Scenario>>::into)
     .context(error::DBError {
         details: "Could not retrieve features",
     })
@@ -95,38 +95,37 @@ pub async fn fetch_scenarios_by_feature_id(
 pub async fn create_or_replace_scenario_from_gherkin(
     scenario: gherkin_rust::Scenario,
     feature: &Uuid,
+    tx: &mut PgTx,
     context: &gql::Context,
 ) -> Result<Scenario, error::Error> {
-    debug!(context.logger, "Creating Scenario from gherkin");
+    debug!(context.logger, "Creating or Replacing Scenario from gherkin");
 
-    let res: Scenario = sqlx::query_as("SELECT * FROM main.create_scenario($1, $2, $3)")
-        .bind(scenario.name.clone())
-        .bind(
-            scenario
-                .tags
-                .iter()
-                .map(|tag| tag.clone())
-                .collect::<Vec<String>>(),
-        )
-        .bind(feature)
-        .fetch_one(&context.pool)
-        .await
-        .context(error::DBError {
-            details: format!("Could not create scenario '{}'", scenario.name),
-        })?;
+    // The function returns a row of nulls if the scenario could not be stored: nothing is then
+    // returned, and the caller rolls back the feature rather than keeping half of it.
+    let res: Scenario = sqlx::query_as(
+        "SELECT * FROM main.create_or_replace_scenario($1, $2, $3) WHERE id IS NOT NULL",
+    )
+    .bind(scenario.name.clone())
+    .bind(scenario.tags.to_vec())
+    .bind(feature)
+    .fetch_one(&mut *tx)
+    .timed(
+        &context.logger,
+        "scenario::create_or_replace_scenario_from_gherkin",
+    )
+    .await
+    .context(error::DBError {
+        details: format!("Could not create or replace scenario '{}'", scenario.name),
+    })?;
 
     let id = res.id;
 
-    // Here we're turning the scenario's steps into a stream of Result<Step, _>, on
-    // which we can use try_for_each and insert them in the database
-    stream::iter(scenario.steps.into_iter().map(|step| Ok(step)))
-        .try_for_each(|step| async {
-            let _step =
-                step::create_or_replace_step_from_gherkin(step, &id, SourceType::Scenario, context)
-                    .await?;
-            Ok(())
-        })
-        .await?;
+    // The steps share the transaction, so they are inserted one after the other.
+    for step in scenario.steps {
+        let _step =
+            step::create_or_replace_step_from_gherkin(step, &id, SourceType::Scenario, tx, context)
+                .await?;
+    }
 
     Ok(res)
 }
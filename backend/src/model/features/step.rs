@@ -1,5 +1,5 @@
 use super::{IdTimestamp, SourceType};
-use crate::{error, gql};
+use crate::{error, gql, model::PgTx, utils::timing::Timed};
 use chrono::prelude::*;
 use juniper::{GraphQLEnum, GraphQLObject};
 use lazy_static::lazy_static;
@@ -35,7 +35,7 @@ impl From<gherkin_rust::StepType> for StepType {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, GraphQLObject)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
 pub struct Step {
     pub id: Uuid,
     pub step_type: StepType,
@@ -65,7 +65,8 @@ pub async fn fetch_step_by_id(id: &Uuid, context: &gql::Context) -> Result<Step,
         "SELECT id, step_type, value, docstring, created_at, updated_at FROM main.steps WHERE id = $1"
     )
     .bind(id)
-    .fetch_one(&context.pool)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "step::fetch_step_by_id")
     .await
     .context(error::DBError {
         details: "Could not retrieve features",
@@ -81,10 +82,12 @@ pub async fn fetch_steps_by_scenario_id(
         "SELECT st.id, st.step_type, st.value, st.docstring, st.created_at, st.updated_at FROM main.steps AS st
          INNER JOIN main.scenario_step_map AS map ON map.step = st.id
          INNER JOIN main.scenarios as sc ON map.scenario = sc.id
-         WHERE sc.id = $1"
+         WHERE sc.id = $1
+         ORDER BY map.position"
     )
     .bind(id)
-    .fetch_all(&context.pool)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "step::fetch_steps_by_scenario_id")
     .await
     .context(error::DBError {
         details: "Could not retrieve steps for scenario",
@@ -100,10 +103,12 @@ pub async fn fetch_steps_by_background_id(
         "SELECT st.id, st.step_type, st.value, st.docstring, st.created_at, st.updated_at FROM main.steps AS st
          INNER JOIN main.background_step_map AS map ON map.step = st.id
          INNER JOIN main.backgrounds as bk ON map.background = bk.id
-         WHERE bk.id = $1"
+         WHERE bk.id = $1
+         ORDER BY map.position"
     )
     .bind(id)
-    .fetch_all(&context.pool)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "step::fetch_steps_by_background_id")
     .await
     .context(error::DBError {
         details: "Could not retrieve steps for background",
@@ -119,7 +124,8 @@ pub async fn create_or_replace_step(
         .bind(step.step_type.clone())
         .bind(step.value.clone())
         .bind(step.docstring.clone())
-        .fetch_one(&context.pool)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "step::create_or_replace_step")
         .await
         .context(error::DBError {
             details: format!("Could not insert or update feature {}", step.id),
@@ -130,6 +136,7 @@ pub async fn create_or_replace_step_from_gherkin(
     step: gherkin_rust::Step,
     id: &Uuid,          // id of the source
     source: SourceType, // type of the source
+    tx: &mut PgTx,
     context: &gql::Context,
 ) -> Result<Step, error::Error> {
     info!(
@@ -147,7 +154,8 @@ pub async fn create_or_replace_step_from_gherkin(
         .bind(StepType::from(step.ty))
         .bind(step.value.clone())
         .bind(step.docstring.unwrap_or(String::from("")))
-        .fetch_one(&context.pool)
+        .fetch_one(&mut *tx)
+        .timed(&context.logger, "step::create_or_replace_step_from_gherkin")
         .await
         .context(error::DBError {
             details: format!("Could not insert or update step '{}'", step.value),
@@ -162,7 +170,8 @@ pub async fn create_or_replace_step_from_gherkin(
                 sqlx::query_as("SELECT * FROM main.add_step_to_scenario($1, $2)")
                     .bind(id)
                     .bind(step_id)
-                    .fetch_one(&context.pool)
+                    .fetch_one(&mut *tx)
+                    .timed(&context.logger, "step::create_or_replace_step_from_gherkin")
                     .await
                     .context(error::DBError {
                         details: format!(
@@ -180,7 +189,8 @@ pub async fn create_or_replace_step_from_gherkin(
                 sqlx::query_as("SELECT * FROM main.add_step_to_background($1, $2)")
                     .bind(id)
                     .bind(step_id)
-                    .fetch_one(&context.pool)
+                    .fetch_one(&mut *tx)
+                    .timed(&context.logger, "step::create_or_replace_step_from_gherkin")
                     .await
                     .context(error::DBError {
                         details: format!(
@@ -199,7 +209,7 @@ pub fn extract_index_from_step(step: String) -> Option<(String, String, Vec<Stri
             Regex::new("I am indexing ([A-Za-z_]+) with ([A-Za-z]+) from ([0-9A-Za-z-, ]+)")
                 .unwrap();
     }
-    RE.captures(&step).and_then(|caps| {
+    RE.captures(&step).map(|caps| {
         let index_type = String::from(caps.get(1).unwrap().as_str());
         let data_source = String::from(caps.get(2).unwrap().as_str());
         let regions = caps
@@ -209,6 +219,6 @@ pub fn extract_index_from_step(step: String) -> Option<(String, String, Vec<Stri
             .split(", ")
             .map(|r| r.to_string())
             .collect();
-        Some((index_type, data_source, regions))
+        (index_type, data_source, regions)
     })
 }
@@ -1,10 +1,18 @@
 use juniper::GraphQLEnum;
 use serde::{Deserialize, Serialize};
+use sqlx::{pool::PoolConnection, postgres::PgConnection, Transaction};
 
+pub mod audit;
 pub mod environments;
 pub mod features;
+pub mod recovery;
+pub mod runs;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, sqlx::Type, GraphQLEnum)]
+// A transaction on a pooled connection. Functions which must be grouped with others (eg loading
+// a feature with all its scenarios and steps) take this rather than the pool.
+pub type PgTx = Transaction<PoolConnection<PgConnection>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type, GraphQLEnum)]
 #[sqlx(rename = "file_status")]
 #[serde(rename_all = "lowercase")]
 pub enum FileStatus {
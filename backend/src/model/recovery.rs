@@ -0,0 +1,24 @@
+use crate::{error, utils::timing::Timed};
+use slog::{info, Logger};
+use snafu::ResultExt;
+use sqlx::postgres::{PgPool, PgQueryAs};
+
+/// Put back the work which was in progress when the server stopped (downloads, indexing, runs)
+/// in the last state it reached. Returns how many rows were recovered.
+/// This must only be called when no server is working on the database (see `mjolnir recover`):
+/// it cannot tell the work of a stopped server from the work of a running one.
+pub async fn recover_interrupted(pool: &PgPool, logger: &Logger) -> Result<i64, error::Error> {
+    let recovered: Vec<(String, i64)> =
+        sqlx::query_as("SELECT target, count FROM main.recover_interrupted()")
+            .fetch_all(pool)
+            .timed(logger, "recovery::recover_interrupted")
+            .await
+            .context(error::DBError {
+                details: "Could not recover interrupted work",
+            })?;
+
+    for (target, count) in recovered.iter().filter(|(_, count)| *count > 0) {
+        info!(logger, "Recovered {} interrupted {}", count, target);
+    }
+    Ok(recovered.iter().map(|(_, count)| count).sum())
+}
@@ -0,0 +1,125 @@
+use super::{run::ScenarioResult, ResultStatus};
+use crate::{error, gql, utils::timing::Timed};
+use chrono::prelude::*;
+use juniper::GraphQLObject;
+use serde::{Deserialize, Serialize};
+use slog::debug;
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+
+/// A scenario which both passed and failed against the same environment and the same version
+/// of bragi, or which only passed once attempted again. Results of scenarios which ran against
+/// different environments, or different versions, are not compared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct FlakyScenario {
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub environment: Option<String>,
+    pub geocoder_version: Option<String>,
+    pub passed: i32,
+    pub failed: i32,  // failed, or in error
+    pub retried: i32, // passed, but not at the first attempt
+    pub last_seen: DateTime<Utc>,
+}
+
+// This should match the columns of the query in fetch_flaky_scenarios
+impl<'c> FromRow<'c, PgRow<'c>> for FlakyScenario {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(FlakyScenario {
+            feature_name: row.get(0),
+            scenario_name: row.get(1),
+            environment: row.get(2),
+            geocoder_version: row.get(3),
+            passed: row.get(4),
+            failed: row.get(5),
+            retried: row.get(6),
+            last_seen: row.get(7),
+        })
+    }
+}
+
+/// Find the flaky scenarios in the results, in the same order as fetch_flaky_scenarios.
+pub fn detect_flaky(results: &[ScenarioResult]) -> Vec<FlakyScenario> {
+    let mut flaky: Vec<FlakyScenario> = Vec::new();
+    for result in results {
+        let index = match flaky.iter().position(|f| {
+            f.feature_name == result.feature_name
+                && f.scenario_name == result.scenario_name
+                && f.environment == result.environment
+                && f.geocoder_version == result.geocoder_version
+        }) {
+            Some(index) => index,
+            None => {
+                flaky.push(FlakyScenario {
+                    feature_name: result.feature_name.clone(),
+                    scenario_name: result.scenario_name.clone(),
+                    environment: result.environment.clone(),
+                    geocoder_version: result.geocoder_version.clone(),
+                    passed: 0,
+                    failed: 0,
+                    retried: 0,
+                    last_seen: result.created_at,
+                });
+                flaky.len() - 1
+            }
+        };
+        let scenario = &mut flaky[index];
+        match result.status {
+            ResultStatus::Passed => {
+                scenario.passed += 1;
+                if result.attempts > 1 {
+                    scenario.retried += 1;
+                }
+            }
+            ResultStatus::Failed | ResultStatus::Error => scenario.failed += 1,
+            ResultStatus::Skipped | ResultStatus::Undefined => {}
+        }
+        scenario.last_seen = scenario.last_seen.max(result.created_at);
+    }
+    flaky.retain(|f| (f.passed > 0 && f.failed > 0) || f.retried > 0);
+    flaky.sort_by(|a, b| {
+        (
+            &a.feature_name,
+            &a.scenario_name,
+            &a.environment,
+            &a.geocoder_version,
+        )
+            .cmp(&(
+                &b.feature_name,
+                &b.scenario_name,
+                &b.environment,
+                &b.geocoder_version,
+            ))
+    });
+    flaky
+}
+
+/// The flaky scenarios, found in the results of all the runs.
+pub async fn fetch_flaky_scenarios(
+    context: &gql::Context,
+) -> Result<Vec<FlakyScenario>, error::Error> {
+    debug!(context.logger, "Fetching flaky scenarios");
+    sqlx::query_as(
+        "SELECT feature_name, scenario_name, environment, geocoder_version, passed, failed, retried, last_seen
+        FROM (
+          SELECT feature_name, scenario_name, environment, geocoder_version
+            , COUNT(*) FILTER (WHERE status = 'passed')::INTEGER AS passed
+            , COUNT(*) FILTER (WHERE status IN ('failed', 'error'))::INTEGER AS failed
+            , COUNT(*) FILTER (WHERE status = 'passed' AND attempts > 1)::INTEGER AS retried
+            , MAX(created_at) AS last_seen
+          FROM main.scenario_results
+          GROUP BY feature_name, scenario_name, environment, geocoder_version
+        ) AS history
+        WHERE (passed > 0 AND failed > 0) OR retried > 0
+        ORDER BY feature_name, scenario_name, environment NULLS FIRST, geocoder_version NULLS FIRST",
+    )
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "flaky::fetch_flaky_scenarios")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve flaky scenarios",
+    })
+}
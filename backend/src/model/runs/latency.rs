@@ -0,0 +1,179 @@
+use crate::{error, gql, utils::timing::Timed};
+use chrono::prelude::*;
+use juniper::GraphQLObject;
+use serde::{Deserialize, Serialize};
+use slog::debug;
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+use uuid::Uuid;
+
+/// Latency of the searches of a scenario, in a benchmark run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct LatencyMetrics {
+    pub run: Uuid,
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub iterations: i32,  // times each search was replayed
+    pub concurrency: i32, // requests at a time
+    pub requests: i32,
+    pub errors: i32,
+    pub error_rate: f64, // share of requests which failed
+    pub p50: f64,        // milliseconds
+    pub p95: f64,
+    pub p99: f64,
+    pub throughput: f64, // requests per second
+    pub created_at: DateTime<Utc>,
+}
+
+// This should match the columns of main.latency_metrics
+impl<'c> FromRow<'c, PgRow<'c>> for LatencyMetrics {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(LatencyMetrics {
+            run: row.get(0),
+            feature_name: row.get(1),
+            scenario_name: row.get(2),
+            iterations: row.get(3),
+            concurrency: row.get(4),
+            requests: row.get(5),
+            errors: row.get(6),
+            error_rate: row.get(7),
+            p50: row.get(8),
+            p95: row.get(9),
+            p99: row.get(10),
+            throughput: row.get(11),
+            created_at: row.get(12),
+        })
+    }
+}
+
+/// The replays of a single search.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LatencySample {
+    pub latencies: Vec<f64>, // milliseconds, of the requests which succeeded
+    pub errors: usize,
+    pub elapsed: f64, // milliseconds, for all the requests
+}
+
+/// The latency under which the given share (between 0 and 100) of the latencies fall, using
+/// the nearest rank. None if there are no latencies.
+pub fn percentile(latencies: &[f64], percentile: f64) -> Option<f64> {
+    if latencies.is_empty() {
+        return None;
+    }
+    let mut sorted = latencies.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.max(1).min(sorted.len()) - 1])
+}
+
+/// The metrics of a scenario, from the replays of its searches. None if it has no search.
+pub fn compute_metrics(
+    run: &Uuid,
+    feature_name: &str,
+    scenario_name: &str,
+    iterations: usize,
+    concurrency: usize,
+    samples: &[LatencySample],
+) -> Option<LatencyMetrics> {
+    if samples.is_empty() {
+        return None;
+    }
+    let latencies: Vec<f64> = samples
+        .iter()
+        .flat_map(|sample| sample.latencies.iter().copied())
+        .collect();
+    let errors: usize = samples.iter().map(|sample| sample.errors).sum();
+    let requests = latencies.len() + errors;
+    let elapsed: f64 = samples.iter().map(|sample| sample.elapsed).sum();
+    // When all the requests failed, there is no latency to speak of.
+    let at = |p: f64| percentile(&latencies, p).unwrap_or(0.0);
+    Some(LatencyMetrics {
+        run: *run,
+        feature_name: String::from(feature_name),
+        scenario_name: String::from(scenario_name),
+        iterations: iterations as i32,
+        concurrency: concurrency as i32,
+        requests: requests as i32,
+        errors: errors as i32,
+        error_rate: if requests == 0 {
+            0.0
+        } else {
+            errors as f64 / requests as f64
+        },
+        p50: at(50.0),
+        p95: at(95.0),
+        p99: at(99.0),
+        throughput: if elapsed > 0.0 {
+            requests as f64 * 1000.0 / elapsed
+        } else {
+            0.0
+        },
+        created_at: Utc::now(),
+    })
+}
+
+pub async fn create_latency_metrics(
+    metrics: Vec<LatencyMetrics>,
+    context: &gql::Context,
+) -> Result<Vec<LatencyMetrics>, error::Error> {
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    let mut created = Vec::new();
+    for m in metrics {
+        let row: LatencyMetrics = sqlx::query_as(
+            "INSERT INTO main.latency_metrics
+             (run, feature_name, scenario_name, iterations, concurrency, requests, errors, error_rate, p50, p95, p99, throughput)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+             RETURNING run, feature_name, scenario_name, iterations, concurrency, requests, errors, error_rate, p50, p95, p99, throughput, created_at",
+        )
+        .bind(m.run)
+        .bind(m.feature_name.as_str())
+        .bind(m.scenario_name.as_str())
+        .bind(m.iterations)
+        .bind(m.concurrency)
+        .bind(m.requests)
+        .bind(m.errors)
+        .bind(m.error_rate)
+        .bind(m.p50)
+        .bind(m.p95)
+        .bind(m.p99)
+        .bind(m.throughput)
+        .fetch_one(&mut tx)
+        .timed(&context.logger, "latency::create_latency_metrics")
+        .await
+        .context(error::DBError {
+            details: format!("Could not record latency metrics of run '{}'", m.run),
+        })?;
+        created.push(row);
+    }
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit transaction",
+    })?;
+    Ok(created)
+}
+
+/// The metrics of the scenarios of the run, which are empty unless it is a benchmark run.
+pub async fn fetch_latency_metrics(
+    run: &Uuid,
+    context: &gql::Context,
+) -> Result<Vec<LatencyMetrics>, error::Error> {
+    debug!(context.logger, "Fetching latency metrics of run '{}'", run);
+    sqlx::query_as(
+        "SELECT run, feature_name, scenario_name, iterations, concurrency, requests, errors, error_rate, p50, p95, p99, throughput, created_at
+        FROM main.latency_metrics WHERE run = $1
+        ORDER BY feature_name, scenario_name",
+    )
+    .bind(run)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "latency::fetch_latency_metrics")
+    .await
+    .context(error::DBError {
+        details: format!("Could not retrieve latency metrics of run '{}'", run),
+    })
+}
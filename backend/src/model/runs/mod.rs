@@ -0,0 +1,41 @@
+use juniper::GraphQLEnum;
+use serde::{Deserialize, Serialize};
+
+pub mod flaky;
+pub mod latency;
+pub mod quality;
+pub mod run;
+pub mod snapshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, GraphQLEnum)]
+#[sqlx(rename = "run_status")]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    #[sqlx(rename = "running")]
+    Running,
+    #[sqlx(rename = "passed")]
+    Passed,
+    #[sqlx(rename = "failed")]
+    Failed,
+    #[sqlx(rename = "error")]
+    Error,
+    #[sqlx(rename = "interrupted")]
+    Interrupted,
+}
+
+// The status of a scenario or a step, once it has been executed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, GraphQLEnum)]
+#[sqlx(rename = "result_status")]
+#[serde(rename_all = "lowercase")]
+pub enum ResultStatus {
+    #[sqlx(rename = "passed")]
+    Passed,
+    #[sqlx(rename = "failed")]
+    Failed,
+    #[sqlx(rename = "skipped")]
+    Skipped,
+    #[sqlx(rename = "undefined")]
+    Undefined,
+    #[sqlx(rename = "error")]
+    Error,
+}
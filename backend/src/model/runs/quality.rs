@@ -0,0 +1,237 @@
+use crate::{error, gql, utils::timing::Timed};
+use chrono::prelude::*;
+use juniper::{GraphQLEnum, GraphQLObject};
+use serde::{Deserialize, Serialize};
+use slog::debug;
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+/// How the metrics of a run are grouped.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    GraphQLEnum,
+)]
+#[sqlx(rename = "quality_group")]
+#[serde(rename_all = "snake_case")]
+pub enum QualityGroup {
+    /// All the steps of the run, in a single group with an empty name.
+    #[sqlx(rename = "run")]
+    Run,
+    #[sqlx(rename = "feature")]
+    Feature,
+    /// A step belongs to the group of each tag of its scenario and feature.
+    #[sqlx(rename = "tag")]
+    Tag,
+    /// A step belongs to the group of each index type of its scenario (admins, streets, ...).
+    #[sqlx(rename = "index_type")]
+    IndexType,
+}
+
+/// Relevance of the results of a run, for a group of steps looking for an expected result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct QualityMetrics {
+    pub run: Uuid,
+    pub group_by: QualityGroup,
+    pub group_name: String,
+    pub queries: i32,      // count of steps
+    pub mrr: f64,          // mean reciprocal rank of the expected result
+    pub recall_at_1: f64,  // share of expected results found first
+    pub recall_at_5: f64,  // share of expected results found within the first 5
+    pub recall_at_10: f64, // share of expected results found within the first 10
+    pub ndcg: f64,         // normalized discounted cumulative gain
+    pub created_at: DateTime<Utc>,
+}
+
+// This should match the columns of main.quality_metrics
+impl<'c> FromRow<'c, PgRow<'c>> for QualityMetrics {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(QualityMetrics {
+            run: row.get(0),
+            group_by: row.get(1),
+            group_name: row.get(2),
+            queries: row.get(3),
+            mrr: row.get(4),
+            recall_at_1: row.get(5),
+            recall_at_5: row.get(6),
+            recall_at_10: row.get(7),
+            ndcg: row.get(8),
+            created_at: row.get(9),
+        })
+    }
+}
+
+/// Where a step found its expected result, with what it takes to group it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankSample {
+    pub feature: String,
+    pub tags: Vec<String>,
+    pub index_types: Vec<String>,
+    pub rank: Option<usize>, // from 1, None if it was not found
+}
+
+/// The metrics of the run, for the whole run, and for each feature, tag and index type.
+pub fn compute_metrics(run: &Uuid, samples: &[RankSample]) -> Vec<QualityMetrics> {
+    let mut groups: BTreeMap<(QualityGroup, String), Vec<Option<usize>>> = BTreeMap::new();
+    for sample in samples {
+        let tags: BTreeSet<&str> = sample
+            .tags
+            .iter()
+            .map(|tag| tag.trim_start_matches('@'))
+            .collect();
+        let index_types: BTreeSet<&str> = sample.index_types.iter().map(String::as_str).collect();
+        let keys = std::iter::once((QualityGroup::Run, ""))
+            .chain(std::iter::once((
+                QualityGroup::Feature,
+                sample.feature.as_str(),
+            )))
+            .chain(tags.into_iter().map(|tag| (QualityGroup::Tag, tag)))
+            .chain(
+                index_types
+                    .into_iter()
+                    .map(|index_type| (QualityGroup::IndexType, index_type)),
+            );
+        for (group_by, group_name) in keys {
+            groups
+                .entry((group_by, String::from(group_name)))
+                .or_default()
+                .push(sample.rank);
+        }
+    }
+
+    let created_at = Utc::now();
+    groups
+        .into_iter()
+        .map(|((group_by, group_name), ranks)| {
+            let mean = |f: &dyn Fn(usize) -> f64| {
+                ranks.iter().map(|rank| rank.map_or(0.0, f)).sum::<f64>() / ranks.len() as f64
+            };
+            let recall = |k: usize| mean(&|rank| if rank <= k { 1.0 } else { 0.0 });
+            QualityMetrics {
+                run: *run,
+                group_by,
+                group_name,
+                queries: ranks.len() as i32,
+                mrr: mean(&|rank| 1.0 / rank as f64),
+                recall_at_1: recall(1),
+                recall_at_5: recall(5),
+                recall_at_10: recall(10),
+                // With a single relevant result, the ideal DCG is 1.
+                ndcg: mean(&|rank| 1.0 / (rank as f64 + 1.0).log2()),
+                created_at,
+            }
+        })
+        .collect()
+}
+
+pub async fn create_quality_metrics(
+    metrics: Vec<QualityMetrics>,
+    context: &gql::Context,
+) -> Result<Vec<QualityMetrics>, error::Error> {
+    let mut tx = context.pool()?.begin().await.context(error::DBError {
+        details: "Could not start transaction",
+    })?;
+
+    let mut created = Vec::new();
+    for m in metrics {
+        let row: QualityMetrics = sqlx::query_as(
+            "INSERT INTO main.quality_metrics
+             (run, group_by, group_name, queries, mrr, recall_at_1, recall_at_5, recall_at_10, ndcg)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             RETURNING run, group_by, group_name, queries, mrr, recall_at_1, recall_at_5, recall_at_10, ndcg, created_at",
+        )
+        .bind(m.run)
+        .bind(m.group_by)
+        .bind(m.group_name.as_str())
+        .bind(m.queries)
+        .bind(m.mrr)
+        .bind(m.recall_at_1)
+        .bind(m.recall_at_5)
+        .bind(m.recall_at_10)
+        .bind(m.ndcg)
+        .fetch_one(&mut tx)
+        .timed(&context.logger, "quality::create_quality_metrics")
+        .await
+        .context(error::DBError {
+            details: format!("Could not record quality metrics of run '{}'", m.run),
+        })?;
+        created.push(row);
+    }
+
+    tx.commit().await.context(error::DBError {
+        details: "Could not commit transaction",
+    })?;
+    Ok(created)
+}
+
+/// The metrics of the run, by group name.
+pub async fn fetch_quality_metrics(
+    run: &Uuid,
+    group_by: QualityGroup,
+    context: &gql::Context,
+) -> Result<Vec<QualityMetrics>, error::Error> {
+    debug!(
+        context.logger,
+        "Fetching quality metrics of run '{}' by {:?}", run, group_by
+    );
+    sqlx::query_as(
+        "SELECT run, group_by, group_name, queries, mrr, recall_at_1, recall_at_5, recall_at_10, ndcg, created_at
+        FROM main.quality_metrics WHERE run = $1 AND group_by = $2
+        ORDER BY group_name",
+    )
+    .bind(run)
+    .bind(group_by)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "quality::fetch_quality_metrics")
+    .await
+    .context(error::DBError {
+        details: format!("Could not retrieve quality metrics of run '{}'", run),
+    })
+}
+
+/// The metrics of the group in the last runs which have some, oldest first.
+pub async fn fetch_quality_trend(
+    group_by: QualityGroup,
+    group_name: &str,
+    limit: i32,
+    context: &gql::Context,
+) -> Result<Vec<QualityMetrics>, error::Error> {
+    debug!(
+        context.logger,
+        "Fetching quality trend of {:?} '{}'", group_by, group_name
+    );
+    let mut metrics: Vec<QualityMetrics> = sqlx::query_as(
+        "SELECT q.run, q.group_by, q.group_name, q.queries, q.mrr, q.recall_at_1, q.recall_at_5, q.recall_at_10, q.ndcg, q.created_at
+        FROM main.quality_metrics AS q
+        INNER JOIN main.runs AS r ON q.run = r.id
+        WHERE q.group_by = $1 AND q.group_name = $2
+        ORDER BY r.started_at DESC LIMIT $3",
+    )
+    .bind(group_by)
+    .bind(group_name)
+    .bind(i64::from(limit))
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "quality::fetch_quality_trend")
+    .await
+    .context(error::DBError {
+        details: format!(
+            "Could not retrieve quality trend of {:?} '{}'",
+            group_by, group_name
+        ),
+    })?;
+    metrics.reverse();
+    Ok(metrics)
+}
@@ -0,0 +1,316 @@
+use super::{ResultStatus, RunStatus};
+use crate::{error, gql, model::features::step::StepType, utils::timing::Timed};
+use chrono::prelude::*;
+use juniper::GraphQLObject;
+use serde::{Deserialize, Serialize};
+use slog::debug;
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+use uuid::Uuid;
+
+/// A run is the execution of a selection of scenarios against bragi.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct Run {
+    pub id: Uuid,
+    pub tags: Vec<String>,
+    pub bragi_url: String,
+    pub status: RunStatus,
+    pub passed: i32,
+    pub failed: i32,
+    pub skipped: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+// This should match the main.return_run_type
+impl<'c> FromRow<'c, PgRow<'c>> for Run {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(Run {
+            id: row.get(0),
+            tags: row.get(1),
+            bragi_url: row.get(2),
+            status: row.get(3),
+            passed: row.get(4),
+            failed: row.get(5),
+            skipped: row.get(6),
+            started_at: row.get(7),
+            finished_at: row.get(8),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct ScenarioResult {
+    pub id: Uuid,
+    pub run: Uuid,
+    pub scenario: Option<Uuid>, // None if the scenario has since been removed
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub tags: Vec<String>,
+    pub status: ResultStatus,
+    pub duration: f64, // milliseconds
+    pub created_at: DateTime<Utc>,
+    pub environment: Option<String>, // signature of the environment of the feature, if any
+    pub geocoder_version: Option<String>, // version of bragi, if it gave one
+    pub attempts: i32,               // times the scenario ran: more than once if it failed at first
+    pub quarantined: bool,           // tagged @quarantine, so that it does not fail the run
+}
+
+// This should match the main.return_scenario_result_type
+impl<'c> FromRow<'c, PgRow<'c>> for ScenarioResult {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(ScenarioResult {
+            id: row.get(0),
+            run: row.get(1),
+            scenario: row.get(2),
+            feature_name: row.get(3),
+            scenario_name: row.get(4),
+            tags: row.get(5),
+            status: row.get(6),
+            duration: row.get(7),
+            created_at: row.get(8),
+            environment: row.get(9),
+            geocoder_version: row.get(10),
+            attempts: row.get(11),
+            quarantined: row.get(12),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct StepResult {
+    pub id: Uuid,
+    pub scenario_result: Uuid,
+    pub position: i32,
+    pub step_type: StepType,
+    pub value: String,
+    pub status: ResultStatus,
+    pub message: Option<String>,
+    pub duration: f64, // milliseconds
+    pub created_at: DateTime<Utc>,
+    pub score: Option<f64>, // how well the label found matched the one expected, between 0 and 1
+    pub request_url: Option<String>, // bragi request made by a search step
+    pub rank: Option<i32>,  // where the expected result was found (from 1), 0 if it was not found
+    pub results: Option<Vec<String>>, // labels of the first results of a search step
+}
+
+// This should match the main.return_step_result_type
+impl<'c> FromRow<'c, PgRow<'c>> for StepResult {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        Ok(StepResult {
+            id: row.get(0),
+            scenario_result: row.get(1),
+            position: row.get(2),
+            step_type: row.get(3),
+            value: row.get(4),
+            status: row.get(5),
+            message: row.get(6),
+            duration: row.get(7),
+            created_at: row.get(8),
+            score: row.get(9),
+            request_url: row.get(10),
+            rank: row.get(11),
+            results: row.get(12),
+        })
+    }
+}
+
+pub async fn create_run(
+    tags: Vec<String>,
+    bragi_url: &str,
+    context: &gql::Context,
+) -> Result<Run, error::Error> {
+    debug!(context.logger, "Creating run with tags {:?}", tags);
+    sqlx::query_as("SELECT * FROM main.create_run($1, $2)")
+        .bind(tags)
+        .bind(bragi_url)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "run::create_run")
+        .await
+        .context(error::DBError {
+            details: "Could not create run",
+        })
+}
+
+pub async fn finish_run(
+    id: &Uuid,
+    status: RunStatus,
+    context: &gql::Context,
+) -> Result<Run, error::Error> {
+    debug!(context.logger, "Finishing run '{}' with {:?}", id, status);
+    sqlx::query_as("SELECT * FROM main.finish_run($1, $2)")
+        .bind(id)
+        .bind(status)
+        .fetch_one(context.pool()?)
+        .timed(&context.logger, "run::finish_run")
+        .await
+        .context(error::DBError {
+            details: format!("Could not finish run '{}'", id),
+        })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_scenario_result(
+    run: &Uuid,
+    scenario: &Uuid,
+    feature_name: &str,
+    scenario_name: &str,
+    tags: Vec<String>,
+    status: ResultStatus,
+    duration: f64,
+    environment: Option<&str>,
+    geocoder_version: Option<&str>,
+    attempts: i32,
+    quarantined: bool,
+    context: &gql::Context,
+) -> Result<ScenarioResult, error::Error> {
+    sqlx::query_as(
+        "SELECT * FROM main.create_scenario_result($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+    )
+    .bind(run)
+    .bind(scenario)
+    .bind(feature_name)
+    .bind(scenario_name)
+    .bind(tags)
+    .bind(status)
+    .bind(duration)
+    .bind(environment)
+    .bind(geocoder_version)
+    .bind(attempts)
+    .bind(quarantined)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "run::create_scenario_result")
+    .await
+    .context(error::DBError {
+        details: format!("Could not record result of scenario '{}'", scenario_name),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_step_result(
+    scenario_result: &Uuid,
+    position: i32,
+    step_type: StepType,
+    value: &str,
+    status: ResultStatus,
+    message: Option<String>,
+    duration: f64,
+    score: Option<f64>,
+    request_url: Option<String>,
+    rank: Option<i32>,
+    results: Option<Vec<String>>,
+    context: &gql::Context,
+) -> Result<StepResult, error::Error> {
+    sqlx::query_as(
+        "SELECT * FROM main.create_step_result($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+    )
+    .bind(scenario_result)
+    .bind(position)
+    .bind(step_type)
+    .bind(value)
+    .bind(status)
+    .bind(message)
+    .bind(duration)
+    .bind(score)
+    .bind(request_url)
+    .bind(rank)
+    .bind(results)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "run::create_step_result")
+    .await
+    .context(error::DBError {
+        details: format!("Could not record result of step '{}'", value),
+    })
+}
+
+/// Return all the runs, most recent first.
+pub async fn fetch_all_runs(context: &gql::Context) -> Result<Vec<Run>, error::Error> {
+    debug!(context.logger, "Retrieving all runs");
+    sqlx::query_as(
+        "SELECT id, tags, bragi_url, status, passed, failed, skipped, started_at, finished_at
+        FROM main.runs ORDER BY started_at DESC",
+    )
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "run::fetch_all_runs")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve runs",
+    })
+}
+
+pub async fn fetch_run_by_id(id: &Uuid, context: &gql::Context) -> Result<Run, error::Error> {
+    debug!(context.logger, "Fetching run '{}'", id);
+    sqlx::query_as(
+        "SELECT id, tags, bragi_url, status, passed, failed, skipped, started_at, finished_at
+        FROM main.runs WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "run::fetch_run_by_id")
+    .await
+    .context(error::DBError {
+        details: format!("Could not retrieve run '{}'", id),
+    })
+}
+
+/// Return the most recent run, if there is one.
+pub async fn fetch_last_run(context: &gql::Context) -> Result<Option<Run>, error::Error> {
+    debug!(context.logger, "Fetching last run");
+    sqlx::query_as(
+        "SELECT id, tags, bragi_url, status, passed, failed, skipped, started_at, finished_at
+        FROM main.runs ORDER BY started_at DESC LIMIT 1",
+    )
+    .fetch_optional(context.pool()?)
+    .timed(&context.logger, "run::fetch_last_run")
+    .await
+    .context(error::DBError {
+        details: "Could not retrieve last run",
+    })
+}
+
+pub async fn fetch_scenario_results_by_run_id(
+    id: &Uuid,
+    context: &gql::Context,
+) -> Result<Vec<ScenarioResult>, error::Error> {
+    debug!(
+        context.logger,
+        "Fetching scenario results from run '{}'", id
+    );
+    sqlx::query_as(
+        "SELECT id, run, scenario, feature_name, scenario_name, tags, status, duration, created_at, environment, geocoder_version, attempts, quarantined
+        FROM main.scenario_results WHERE run = $1
+        ORDER BY feature_name, created_at",
+    )
+    .bind(id)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "run::fetch_scenario_results_by_run_id")
+    .await
+    .context(error::DBError {
+        details: format!("Could not retrieve scenario results for run '{}'", id),
+    })
+}
+
+pub async fn fetch_step_results_by_scenario_result_id(
+    id: &Uuid,
+    context: &gql::Context,
+) -> Result<Vec<StepResult>, error::Error> {
+    debug!(
+        context.logger,
+        "Fetching step results from scenario result '{}'", id
+    );
+    sqlx::query_as(
+        "SELECT id, scenario_result, position, step_type, value, status, message, duration, created_at, score, request_url, rank, results
+        FROM main.step_results WHERE scenario_result = $1
+        ORDER BY position",
+    )
+    .bind(id)
+    .fetch_all(context.pool()?)
+    .timed(&context.logger, "run::fetch_step_results_by_scenario_result_id")
+    .await
+    .context(error::DBError {
+        details: format!("Could not retrieve step results for scenario result '{}'", id),
+    })
+}
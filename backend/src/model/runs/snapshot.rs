@@ -0,0 +1,189 @@
+use crate::{error, gql, utils::timing::Timed};
+use chrono::prelude::*;
+use juniper::GraphQLObject;
+use serde::{Deserialize, Serialize};
+use slog::debug;
+use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgQueryAs, PgRow},
+    row::{FromRow, Row},
+};
+use uuid::Uuid;
+
+/// The results a snapshot step expects, recorded the first time it ran, or accepted since.
+/// The step is identified by its scenario, and its position in the scenario (background steps
+/// included).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct Snapshot {
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub position: i32,
+    pub results: Vec<SnapshotPlace>,
+    pub pending: Option<Vec<SnapshotPlace>>, // results of the last run, if they did not match
+    pub pending_step: Option<Uuid>,          // step result which found the pending results
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What a snapshot keeps of a result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct SnapshotPlace {
+    pub label: String,
+    pub place_type: String,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+}
+
+// The results are stored as JSON, and returned as text.
+fn parse_places(text: &str) -> Result<Vec<SnapshotPlace>, sqlx::Error> {
+    serde_json::from_str(text).map_err(|err| sqlx::Error::Decode(err.into()))
+}
+
+fn to_json(places: &[SnapshotPlace]) -> serde_json::Value {
+    serde_json::to_value(places).unwrap_or(serde_json::Value::Null)
+}
+
+// This should match the columns returned by the queries below
+impl<'c> FromRow<'c, PgRow<'c>> for Snapshot {
+    fn from_row(row: &PgRow<'c>) -> Result<Self, sqlx::Error> {
+        let results: String = row.get(3);
+        let pending: Option<String> = row.get(4);
+        Ok(Snapshot {
+            feature_name: row.get(0),
+            scenario_name: row.get(1),
+            position: row.get(2),
+            results: parse_places(&results)?,
+            pending: pending.as_deref().map(parse_places).transpose()?,
+            pending_step: row.get(5),
+            created_at: row.get(6),
+            updated_at: row.get(7),
+        })
+    }
+}
+
+/// The snapshot of the step, if it has one.
+pub async fn fetch_snapshot(
+    feature_name: &str,
+    scenario_name: &str,
+    position: i32,
+    context: &gql::Context,
+) -> Result<Option<Snapshot>, error::Error> {
+    debug!(
+        context.logger,
+        "Fetching snapshot of '{}' / '{}' at {}", feature_name, scenario_name, position
+    );
+    sqlx::query_as(
+        "SELECT feature_name, scenario_name, position, results::TEXT, pending::TEXT, pending_step, created_at, updated_at
+        FROM main.snapshots WHERE feature_name = $1 AND scenario_name = $2 AND position = $3",
+    )
+    .bind(feature_name)
+    .bind(scenario_name)
+    .bind(position)
+    .fetch_optional(context.pool()?)
+    .timed(&context.logger, "snapshot::fetch_snapshot")
+    .await
+    .context(error::DBError {
+        details: format!(
+            "Could not retrieve snapshot of '{}' / '{}'",
+            feature_name, scenario_name
+        ),
+    })
+}
+
+/// Record the results as the snapshot of the step, replacing the one it had, if any.
+pub async fn save_snapshot(
+    feature_name: &str,
+    scenario_name: &str,
+    position: i32,
+    results: Vec<SnapshotPlace>,
+    context: &gql::Context,
+) -> Result<Snapshot, error::Error> {
+    debug!(
+        context.logger,
+        "Saving snapshot of '{}' / '{}' at {}", feature_name, scenario_name, position
+    );
+    sqlx::query_as(
+        "INSERT INTO main.snapshots (feature_name, scenario_name, position, results)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (feature_name, scenario_name, position) DO
+          UPDATE SET results = EXCLUDED.results, pending = NULL, pending_step = NULL, updated_at = NOW()
+        RETURNING feature_name, scenario_name, position, results::TEXT, pending::TEXT, pending_step, created_at, updated_at",
+    )
+    .bind(feature_name)
+    .bind(scenario_name)
+    .bind(position)
+    .bind(to_json(&results))
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "snapshot::save_snapshot")
+    .await
+    .context(error::DBError {
+        details: format!(
+            "Could not save snapshot of '{}' / '{}'",
+            feature_name, scenario_name
+        ),
+    })
+}
+
+/// Record the results found by the step result, which do not match the snapshot, so that they
+/// can be accepted later.
+pub async fn propose_snapshot(
+    feature_name: &str,
+    scenario_name: &str,
+    position: i32,
+    results: Vec<SnapshotPlace>,
+    step: &Uuid,
+    context: &gql::Context,
+) -> Result<Snapshot, error::Error> {
+    debug!(
+        context.logger,
+        "Proposing snapshot of '{}' / '{}' at {} from step result '{}'",
+        feature_name,
+        scenario_name,
+        position,
+        step
+    );
+    sqlx::query_as(
+        "UPDATE main.snapshots SET pending = $4, pending_step = $5, updated_at = NOW()
+        WHERE feature_name = $1 AND scenario_name = $2 AND position = $3
+        RETURNING feature_name, scenario_name, position, results::TEXT, pending::TEXT, pending_step, created_at, updated_at",
+    )
+    .bind(feature_name)
+    .bind(scenario_name)
+    .bind(position)
+    .bind(to_json(&results))
+    .bind(step)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "snapshot::propose_snapshot")
+    .await
+    .context(error::DBError {
+        details: format!(
+            "Could not propose snapshot of '{}' / '{}'",
+            feature_name, scenario_name
+        ),
+    })
+}
+
+/// The results found by the step result become the snapshot of its step. Only the last results
+/// which did not match the snapshot can be accepted.
+pub async fn accept_snapshot(
+    step: &Uuid,
+    context: &gql::Context,
+) -> Result<Snapshot, error::Error> {
+    debug!(
+        context.logger,
+        "Accepting snapshot from step result '{}'", step
+    );
+    sqlx::query_as(
+        "UPDATE main.snapshots
+        SET results = pending, pending = NULL, pending_step = NULL, updated_at = NOW()
+        WHERE pending_step = $1
+        RETURNING feature_name, scenario_name, position, results::TEXT, pending::TEXT, pending_step, created_at, updated_at",
+    )
+    .bind(step)
+    .fetch_one(context.pool()?)
+    .timed(&context.logger, "snapshot::accept_snapshot")
+    .await
+    .context(error::DBError {
+        details: format!("No snapshot is waiting for the results of step '{}'", step),
+    })
+}
@@ -0,0 +1,286 @@
+use super::{fetch_run_results, RunResults};
+use crate::{
+    error, gql,
+    model::runs::{
+        latency::LatencyMetrics,
+        run::{Run, ScenarioResult, StepResult},
+        ResultStatus,
+    },
+};
+use juniper::GraphQLObject;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// How the results of a run (the candidate) differ from those of another run (the baseline),
+/// typically of the same scenarios against two builds of bragi. Scenarios are matched by feature
+/// and scenario name, and their steps by position and value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct RunComparison {
+    pub baseline: Run,
+    pub candidate: Run,
+    /// Scenarios which did not pass in the baseline, and pass in the candidate.
+    pub newly_passing: Vec<ScenarioChange>,
+    /// Scenarios which passed in the baseline, and do not pass in the candidate.
+    pub newly_failing: Vec<ScenarioChange>,
+    /// Steps which found their expected result at a different rank.
+    pub rank_changes: Vec<RankChange>,
+    /// Search steps whose first results are different, or in a different order.
+    pub result_changes: Vec<ResultsChange>,
+    /// Latency of the scenarios measured in both runs, when both are benchmark runs.
+    pub latency_changes: Vec<LatencyChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct ScenarioChange {
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub before: ResultStatus,
+    pub after: ResultStatus,
+    pub message: Option<String>, // message of the step which did not pass in the candidate
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct RankChange {
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub step: String,
+    pub before: i32, // from 1, 0 if the expected result was not found
+    pub after: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct ResultsChange {
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub step: String,
+    pub before: Vec<String>, // labels of the first results
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, GraphQLObject)]
+pub struct LatencyChange {
+    pub feature_name: String,
+    pub scenario_name: String,
+    pub before: LatencyMetrics,
+    pub after: LatencyMetrics,
+    pub p95_change: f64, // relative, eg 0.5 if p95 is 50% higher in the candidate
+}
+
+/// Fetch the results of both runs, and compare them.
+pub async fn compare_runs(
+    baseline: &Uuid,
+    candidate: &Uuid,
+    context: &gql::Context,
+) -> Result<RunComparison, error::Error> {
+    let baseline = fetch_run_results(Some(*baseline), context).await?;
+    let candidate = fetch_run_results(Some(*candidate), context).await?;
+    Ok(compare(&baseline, &candidate))
+}
+
+/// The changes are listed in the order of the candidate's scenarios. Scenarios which are only
+/// in one of the runs are left out.
+pub fn compare(baseline: &RunResults, candidate: &RunResults) -> RunComparison {
+    let mut comparison = RunComparison {
+        baseline: baseline.run.clone(),
+        candidate: candidate.run.clone(),
+        newly_passing: Vec::new(),
+        newly_failing: Vec::new(),
+        rank_changes: Vec::new(),
+        result_changes: Vec::new(),
+        latency_changes: Vec::new(),
+    };
+
+    for (after, after_steps) in &candidate.scenarios {
+        let (before, before_steps) = match baseline
+            .scenarios
+            .iter()
+            .find(|(before, _)| same_scenario(before, after))
+        {
+            Some(found) => found,
+            None => continue,
+        };
+
+        let change = || ScenarioChange {
+            feature_name: after.feature_name.clone(),
+            scenario_name: after.scenario_name.clone(),
+            before: before.status,
+            after: after.status,
+            message: after_steps
+                .iter()
+                .find(|step| step.status == after.status)
+                .and_then(|step| step.message.clone()),
+        };
+        match (before.status, after.status) {
+            (ResultStatus::Passed, ResultStatus::Passed) => {}
+            (_, ResultStatus::Passed) => comparison.newly_passing.push(change()),
+            (ResultStatus::Passed, _) => comparison.newly_failing.push(change()),
+            _ => {}
+        }
+
+        let latency = |results: &RunResults| {
+            results
+                .latency
+                .iter()
+                .find(|m| {
+                    m.feature_name == after.feature_name && m.scenario_name == after.scenario_name
+                })
+                .cloned()
+        };
+        if let (Some(before), Some(after)) = (latency(baseline), latency(candidate)) {
+            comparison.latency_changes.push(LatencyChange {
+                feature_name: after.feature_name.clone(),
+                scenario_name: after.scenario_name.clone(),
+                p95_change: if before.p95 > 0.0 {
+                    after.p95 / before.p95 - 1.0
+                } else {
+                    0.0
+                },
+                before,
+                after,
+            });
+        }
+
+        for (before_step, after_step) in before_steps.iter().zip(after_steps) {
+            if before_step.value != after_step.value {
+                break;
+            }
+            if let (Some(before_rank), Some(after_rank)) = (before_step.rank, after_step.rank) {
+                if before_rank != after_rank {
+                    comparison.rank_changes.push(RankChange {
+                        feature_name: after.feature_name.clone(),
+                        scenario_name: after.scenario_name.clone(),
+                        step: describe(after_step),
+                        before: before_rank,
+                        after: after_rank,
+                    });
+                }
+            }
+            if let (Some(before_results), Some(after_results)) =
+                (&before_step.results, &after_step.results)
+            {
+                if before_results != after_results {
+                    comparison.result_changes.push(ResultsChange {
+                        feature_name: after.feature_name.clone(),
+                        scenario_name: after.scenario_name.clone(),
+                        step: describe(after_step),
+                        before: before_results.clone(),
+                        after: after_results.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    comparison
+}
+
+// Scenarios are identified by name, as they may have been reloaded between the runs.
+fn same_scenario(a: &ScenarioResult, b: &ScenarioResult) -> bool {
+    a.feature_name == b.feature_name && a.scenario_name == b.scenario_name
+}
+
+fn describe(step: &StepResult) -> String {
+    format!("{:?} {}", step.step_type, step.value)
+}
+
+fn scenario(feature_name: &str, scenario_name: &str) -> String {
+    format!("{} / {}", feature_name, scenario_name)
+}
+
+// Ranks as written in the report, 0 meaning the result was not found.
+fn rank(rank: i32) -> String {
+    if rank == 0 {
+        String::from("not found")
+    } else {
+        rank.to_string()
+    }
+}
+
+/// Render the comparison as text, one section for each kind of change.
+pub fn render(comparison: &RunComparison) -> String {
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "Comparison of run {} ({}) with run {} ({})",
+        comparison.candidate.id,
+        comparison.candidate.bragi_url,
+        comparison.baseline.id,
+        comparison.baseline.bragi_url
+    );
+
+    let section = |title: &str, changes: &[ScenarioChange], text: &mut String| {
+        let _ = writeln!(text, "\n{} ({}):", title, changes.len());
+        for change in changes {
+            let _ = write!(
+                text,
+                "  {}: {:?} -> {:?}",
+                scenario(&change.feature_name, &change.scenario_name),
+                change.before,
+                change.after
+            );
+            match &change.message {
+                Some(message) => {
+                    let _ = writeln!(text, ": {}", message.lines().next().unwrap_or(""));
+                }
+                None => text.push('\n'),
+            }
+        }
+    };
+    section("Newly passing", &comparison.newly_passing, &mut text);
+    section("Newly failing", &comparison.newly_failing, &mut text);
+
+    let _ = writeln!(text, "\nRank changes ({}):", comparison.rank_changes.len());
+    for change in &comparison.rank_changes {
+        let _ = writeln!(
+            text,
+            "  {}: {}: {} -> {}",
+            scenario(&change.feature_name, &change.scenario_name),
+            change.step,
+            rank(change.before),
+            rank(change.after)
+        );
+    }
+
+    let _ = writeln!(
+        text,
+        "\nResult changes ({}):",
+        comparison.result_changes.len()
+    );
+    for change in &comparison.result_changes {
+        let _ = writeln!(
+            text,
+            "  {}: {}\n    - {}\n    + {}",
+            scenario(&change.feature_name, &change.scenario_name),
+            change.step,
+            change.before.join(" | "),
+            change.after.join(" | ")
+        );
+    }
+
+    // Only benchmark runs have latencies to compare.
+    if !comparison.latency_changes.is_empty() {
+        let _ = writeln!(text, "\nLatency ({}):", comparison.latency_changes.len());
+        for change in &comparison.latency_changes {
+            let (before, after) = (&change.before, &change.after);
+            let _ = writeln!(
+                text,
+                "  {}: p50 {:.1} -> {:.1} ms, p95 {:.1} -> {:.1} ms ({:+.0}%), p99 {:.1} -> {:.1} ms, {:.1} -> {:.1} req/s, errors {:.1}% -> {:.1}%",
+                scenario(&change.feature_name, &change.scenario_name),
+                before.p50,
+                after.p50,
+                before.p95,
+                after.p95,
+                change.p95_change * 100.0,
+                before.p99,
+                after.p99,
+                before.throughput,
+                after.throughput,
+                before.error_rate * 100.0,
+                after.error_rate * 100.0
+            );
+        }
+    }
+
+    text
+}
@@ -0,0 +1,86 @@
+use super::RunResults;
+use crate::{error, model::runs::ResultStatus};
+use serde_json::json;
+use snafu::ResultExt;
+
+fn status(status: ResultStatus) -> &'static str {
+    match status {
+        ResultStatus::Passed => "passed",
+        ResultStatus::Failed => "failed",
+        ResultStatus::Skipped => "skipped",
+        ResultStatus::Undefined => "undefined",
+        // Cucumber has no 'error' status, the closest is 'failed'.
+        ResultStatus::Error => "failed",
+    }
+}
+
+/// Render the results in the cucumber JSON format understood by most CI report plugins.
+/// Durations are in nanoseconds, as cucumber expects. The steps of quarantined scenarios which
+/// did not pass are reported as skipped, with their message, so that they do not fail the build.
+pub fn render(results: &RunResults) -> Result<String, error::Error> {
+    let mut features: Vec<serde_json::Value> = Vec::new();
+    let mut names: Vec<&str> = Vec::new();
+
+    for (scenario, steps) in &results.scenarios {
+        let steps: Vec<serde_json::Value> = steps
+            .iter()
+            .map(|step| {
+                let step_status = match step.status {
+                    ResultStatus::Passed => "passed",
+                    _ if scenario.quarantined => "skipped",
+                    other => status(other),
+                };
+                let mut result = json!({
+                    "status": step_status,
+                    "duration": (step.duration * 1_000_000.0) as u64,
+                });
+                if let Some(message) = &step.message {
+                    result["error_message"] = json!(message);
+                }
+                json!({
+                    "keyword": format!("{:?} ", step.step_type),
+                    "name": step.value,
+                    "line": step.position + 1,
+                    "result": result,
+                })
+            })
+            .collect();
+
+        let tags: Vec<serde_json::Value> = scenario
+            .tags
+            .iter()
+            .map(|tag| json!({ "name": format!("@{}", tag.trim_start_matches('@')) }))
+            .collect();
+
+        let element = json!({
+            "id": scenario.scenario_name.to_lowercase().replace(' ', "-"),
+            "keyword": "Scenario",
+            "type": "scenario",
+            "name": scenario.scenario_name,
+            "tags": tags,
+            "steps": steps,
+        });
+
+        match names.iter().position(|n| *n == scenario.feature_name) {
+            Some(i) => {
+                if let Some(elements) = features[i]["elements"].as_array_mut() {
+                    elements.push(element);
+                }
+            }
+            None => {
+                names.push(&scenario.feature_name);
+                features.push(json!({
+                    "id": scenario.feature_name.to_lowercase().replace(' ', "-"),
+                    "keyword": "Feature",
+                    "name": scenario.feature_name,
+                    "uri": scenario.feature_name,
+                    "elements": [element],
+                }));
+            }
+        }
+    }
+
+    serde_json::to_string_pretty(&features).context(error::SerdeJsonError {
+        details: "Could not serialize cucumber report",
+    })
+}
@@ -0,0 +1,123 @@
+use super::RunResults;
+use crate::model::runs::ResultStatus;
+use std::fmt::Write;
+
+// Escape the characters which are not allowed in XML attributes and text.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render the results as a JUnit XML report: one testsuite per feature, one testcase per
+/// scenario. Failures carry the message of the step which failed, except those of quarantined
+/// scenarios, which are reported as skipped so that they do not fail the build.
+pub fn render(results: &RunResults) -> String {
+    let mut features: Vec<&str> = Vec::new();
+    for (scenario, _) in &results.scenarios {
+        if !features.contains(&scenario.feature_name.as_str()) {
+            features.push(&scenario.feature_name);
+        }
+    }
+
+    let count = |status: ResultStatus| {
+        results
+            .scenarios
+            .iter()
+            .filter(|(s, _)| s.status == status && !s.quarantined)
+            .count()
+    };
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        results.run.id,
+        results.scenarios.len(),
+        count(ResultStatus::Failed),
+        count(ResultStatus::Error) + count(ResultStatus::Undefined),
+    );
+
+    for feature in features {
+        let scenarios: Vec<_> = results
+            .scenarios
+            .iter()
+            .filter(|(s, _)| s.feature_name == feature)
+            .collect();
+        let time: f64 = scenarios.iter().map(|(s, _)| s.duration).sum::<f64>() / 1000.0;
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" time=\"{:.3}\">",
+            escape(feature),
+            scenarios.len(),
+            time
+        );
+        for (scenario, steps) in scenarios {
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape(feature),
+                escape(&scenario.scenario_name),
+                scenario.duration / 1000.0
+            );
+            let step = steps.iter().find(|s| s.status == scenario.status);
+            let message = step
+                .map(|s| {
+                    format!(
+                        "{:?} {}: {}",
+                        s.step_type,
+                        s.value,
+                        s.message.as_deref().unwrap_or("")
+                    )
+                })
+                .unwrap_or_default();
+            match scenario.status {
+                ResultStatus::Passed if scenario.attempts > 1 => {
+                    let _ = write!(
+                        xml,
+                        ">\n      <system-out>Passed at attempt {}</system-out>\n    </testcase>\n",
+                        scenario.attempts
+                    );
+                }
+                ResultStatus::Passed => xml.push_str("/>\n"),
+                _ if scenario.quarantined => {
+                    let _ = write!(
+                        xml,
+                        ">\n      <skipped message=\"quarantined\">{}</skipped>\n    </testcase>\n",
+                        escape(&message)
+                    );
+                }
+                ResultStatus::Skipped => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+                ResultStatus::Failed => {
+                    let _ = write!(
+                        xml,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                        escape(step.map(|s| s.value.as_str()).unwrap_or("")),
+                        escape(&message)
+                    );
+                }
+                ResultStatus::Error | ResultStatus::Undefined => {
+                    let _ = write!(
+                        xml,
+                        ">\n      <error message=\"{}\">{}</error>\n    </testcase>\n",
+                        escape(step.map(|s| s.value.as_str()).unwrap_or("")),
+                        escape(&message)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
@@ -0,0 +1,89 @@
+use crate::{
+    error, gql,
+    model::runs::{
+        latency::LatencyMetrics,
+        run::{Run, ScenarioResult, StepResult},
+    },
+};
+use juniper::GraphQLEnum;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub mod compare;
+pub mod cucumber;
+pub mod junit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, GraphQLEnum)]
+pub enum ReportFormat {
+    Junit,
+    CucumberJson,
+    /// The changes since a baseline run, as text.
+    Comparison,
+}
+
+/// Everything recorded about a run, as needed to produce a report.
+pub struct RunResults {
+    pub run: Run,
+    pub scenarios: Vec<(ScenarioResult, Vec<StepResult>)>,
+    pub latency: Vec<LatencyMetrics>, // empty unless it is a benchmark run
+}
+
+/// Fetch the run (the last one if no id is given), with all its results.
+pub async fn fetch_run_results(
+    run_id: Option<Uuid>,
+    context: &gql::Context,
+) -> Result<RunResults, error::Error> {
+    let run = match run_id {
+        Some(id) => context.store.runs.fetch_run_by_id(&id, context).await?,
+        None => context
+            .store
+            .runs
+            .fetch_last_run(context)
+            .await?
+            .ok_or_else(|| error::Error::UserError {
+                details: String::from("There is no run to report on"),
+            })?,
+    };
+
+    let mut scenarios = Vec::new();
+    let runs = &context.store.runs;
+    for scenario in runs
+        .fetch_scenario_results_by_run_id(&run.id, context)
+        .await?
+    {
+        let steps = runs
+            .fetch_step_results_by_scenario_result_id(&scenario.id, context)
+            .await?;
+        scenarios.push((scenario, steps));
+    }
+
+    let latency = runs.fetch_latency_metrics(&run.id, context).await?;
+
+    Ok(RunResults {
+        run,
+        scenarios,
+        latency,
+    })
+}
+
+/// Return the report of a run (the last one if no id is given) in the given format. A
+/// comparison report needs the baseline run the run is compared with.
+pub async fn generate_report(
+    run_id: Option<Uuid>,
+    format: ReportFormat,
+    baseline: Option<Uuid>,
+    context: &gql::Context,
+) -> Result<String, error::Error> {
+    let results = fetch_run_results(run_id, context).await?;
+    match format {
+        ReportFormat::Junit => Ok(junit::render(&results)),
+        ReportFormat::CucumberJson => cucumber::render(&results),
+        ReportFormat::Comparison => {
+            let baseline = baseline.ok_or_else(|| error::Error::UserError {
+                details: String::from("A comparison report needs a baseline run"),
+            })?;
+            let baseline = fetch_run_results(Some(baseline), context).await?;
+            Ok(compare::render(&compare::compare(&baseline, &results)))
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use super::{
+    bragi::{Coord, Place},
+    describe_places,
+    matching::{LabelMatcher, MatchMode},
+};
+
+/// What a 'Then' step expects from the results of the last search.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Assertion {
+    /// I find '<label>' of type '<type>' within the first <n> results
+    Find {
+        label: String,
+        place_type: String,
+        limit: usize,
+    },
+    /// I do not find '<label>' [within the first <n> results]
+    NotFind { label: String, limit: Option<usize> },
+    /// I find at least <n> results, I find at most <n> results, I find between <n> and <m>
+    /// results, I find <n> results, I find no results
+    Count {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// I find '<label>' within <n> meters of <lat>, <lon>
+    Near {
+        label: String,
+        distance: f64, // meters
+        coord: Coord,
+    },
+    /// I find '<label>' in '<administrative region>'
+    InAdmin { label: String, admin: String },
+    /// I find '<label>' with zone type '<zone type>'
+    ZoneType { label: String, zone_type: String },
+    /// I find '<label>' with level <n>
+    Level { label: String, level: i64 },
+    /// I find '<label>' with postcode '<postcode>'
+    Postcode { label: String, postcode: String },
+    /// I find '<label>' with a house number, or with house number '<n>'
+    HouseNumber {
+        label: String,
+        housenumber: Option<String>,
+    },
+    /// '<label>' ranks above '<label>'
+    RanksAbove { above: String, below: String },
+    /// I find the address|street|admin '<name>', in the first result, which is the nearest when
+    /// reverse geocoding.
+    Nearest { part: AddressPart, name: String },
+}
+
+/// What part of the nearest result is checked after reverse geocoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressPart {
+    /// the label of an address (bragi's 'house')
+    Address,
+    /// the name of a street, or the street of an address
+    Street,
+    /// one of the administrative regions
+    Admin,
+}
+
+impl Assertion {
+    /// Check the assertion against the results of a search, comparing labels according to the
+    /// mode. Returns the score of the label found, if the assertion looks for one. The failure
+    /// message describes what was expected, and lists the results.
+    pub fn evaluate(&self, places: &[Place], mode: MatchMode) -> Result<Option<f64>, String> {
+        match self {
+            Assertion::Find {
+                label,
+                place_type,
+                limit,
+            } => {
+                let matcher = LabelMatcher::new(mode, label)?;
+                let best = places
+                    .iter()
+                    .take(*limit)
+                    .filter(|place| place.place_type == *place_type)
+                    .filter_map(|place| matcher.score(&place.label))
+                    .fold(None, |best: Option<f64>, score| {
+                        Some(best.map_or(score, |best| best.max(score)))
+                    });
+                match best {
+                    Some(score) => Ok(Some(score)),
+                    None => Err(failure(
+                        format!(
+                            "Could not find '{}' of type '{}' within the first {} results.{}",
+                            label,
+                            place_type,
+                            limit,
+                            closest(&matcher, mode, places)
+                        ),
+                        places,
+                    )),
+                }
+            }
+            Assertion::NotFind { label, limit } => {
+                let matcher = LabelMatcher::new(mode, label)?;
+                let limit = limit.unwrap_or_else(|| places.len());
+                match places
+                    .iter()
+                    .take(limit)
+                    .position(|place| matcher.score(&place.label).is_some())
+                {
+                    None => Ok(None),
+                    Some(position) => Err(failure(
+                        format!("Found '{}' at position {}.", label, position + 1),
+                        places,
+                    )),
+                }
+            }
+            Assertion::Count { min, max } => {
+                let count = places.len();
+                let ok = min.is_none_or(|min| count >= min) && max.is_none_or(|max| count <= max);
+                check(ok, places, || {
+                    let expected = match (min, max) {
+                        (Some(min), Some(max)) if min == max => format!("{}", min),
+                        (Some(min), Some(max)) => format!("between {} and {}", min, max),
+                        (Some(min), None) => format!("at least {}", min),
+                        (None, Some(max)) => format!("at most {}", max),
+                        (None, None) => String::from("any number of"),
+                    };
+                    format!("Expected {} results, found {}.", expected, count)
+                })
+                .map(|()| None)
+            }
+            Assertion::Near {
+                label,
+                distance,
+                coord,
+            } => {
+                let (position, place, score) = find(label, places, mode)?;
+                match place.coord {
+                    None => Err(failure(
+                        format!(
+                            "'{}' at position {} has no coordinates.",
+                            label,
+                            position + 1
+                        ),
+                        places,
+                    )),
+                    Some(actual) => {
+                        let actual_distance = haversine(&actual, coord);
+                        check(actual_distance <= *distance, places, || {
+                            format!(
+                                "'{}' at position {} is at {}, {}, {:.0} meters from {}, {} (expected within {} meters).",
+                                label,
+                                position + 1,
+                                actual.lat,
+                                actual.lon,
+                                actual_distance,
+                                coord.lat,
+                                coord.lon,
+                                distance
+                            )
+                        })
+                    }
+                }
+                .map(|()| Some(score))
+            }
+            Assertion::InAdmin { label, admin } => {
+                let (position, place, score) = find(label, places, mode)?;
+                let admin_matcher = LabelMatcher::new(mode, admin)?;
+                let admins = admin_names(place);
+                let found = admins
+                    .iter()
+                    .any(|name| admin_matcher.score(name).is_some());
+                check(found, places, || {
+                    format!(
+                        "'{}' at position {} is not in '{}', but in [{}].",
+                        label,
+                        position + 1,
+                        admin,
+                        admins.join(", ")
+                    )
+                })
+                .map(|()| Some(score))
+            }
+            Assertion::ZoneType { label, zone_type } => {
+                let (position, place, score) = find(label, places, mode)?;
+                let actual = place.geocoding["zone_type"].as_str();
+                check(actual == Some(zone_type.as_str()), places, || {
+                    format!(
+                        "'{}' at position {} has zone type {}, expected '{}'.",
+                        label,
+                        position + 1,
+                        describe(actual),
+                        zone_type
+                    )
+                })
+                .map(|()| Some(score))
+            }
+            Assertion::Level { label, level } => {
+                let (position, place, score) = find(label, places, mode)?;
+                let actual = place.geocoding["level"].as_i64();
+                check(actual == Some(*level), places, || {
+                    format!(
+                        "'{}' at position {} has level {}, expected {}.",
+                        label,
+                        position + 1,
+                        actual.map_or_else(|| String::from("none"), |level| level.to_string()),
+                        level
+                    )
+                })
+                .map(|()| Some(score))
+            }
+            Assertion::Postcode { label, postcode } => {
+                let (position, place, score) = find(label, places, mode)?;
+                // Places spanning several postcodes (eg cities) have them separated by ';'.
+                let actual = place.geocoding["postcode"].as_str();
+                let found = actual.is_some_and(|actual| actual.split(';').any(|p| p == postcode));
+                check(found, places, || {
+                    format!(
+                        "'{}' at position {} has postcode {}, expected '{}'.",
+                        label,
+                        position + 1,
+                        describe(actual),
+                        postcode
+                    )
+                })
+                .map(|()| Some(score))
+            }
+            Assertion::HouseNumber { label, housenumber } => {
+                let (position, place, score) = find(label, places, mode)?;
+                let actual = place.geocoding["housenumber"]
+                    .as_str()
+                    .filter(|number| !number.is_empty());
+                let ok = match housenumber {
+                    None => actual.is_some(),
+                    Some(housenumber) => actual == Some(housenumber.as_str()),
+                };
+                check(ok, places, || match housenumber {
+                    None => format!(
+                        "'{}' at position {} has no house number.",
+                        label,
+                        position + 1
+                    ),
+                    Some(housenumber) => format!(
+                        "'{}' at position {} has house number {}, expected '{}'.",
+                        label,
+                        position + 1,
+                        describe(actual),
+                        housenumber
+                    ),
+                })
+                .map(|()| Some(score))
+            }
+            Assertion::RanksAbove { above, below } => {
+                let (above_position, _, above_score) = find(above, places, mode)?;
+                let (below_position, _, below_score) = find(below, places, mode)?;
+                check(above_position < below_position, places, || {
+                    format!(
+                        "'{}' at position {} does not rank above '{}' at position {}.",
+                        above,
+                        above_position + 1,
+                        below,
+                        below_position + 1
+                    )
+                })
+                .map(|()| Some(above_score.min(below_score)))
+            }
+            Assertion::Nearest { part, name } => {
+                let matcher = LabelMatcher::new(mode, name)?;
+                let nearest = places.first().ok_or_else(|| {
+                    failure(format!("Could not find the {} '{}'.", part, name), places)
+                })?;
+                let (candidates, found) = match part {
+                    AddressPart::Address => {
+                        (vec![nearest.label.clone()], nearest.place_type == "house")
+                    }
+                    AddressPart::Street => {
+                        let field = if nearest.place_type == "street" {
+                            "name"
+                        } else {
+                            "street"
+                        };
+                        let street = nearest.geocoding[field].as_str().map(String::from);
+                        (street.into_iter().collect(), true)
+                    }
+                    AddressPart::Admin => (admin_names(nearest), true),
+                };
+                let score = candidates
+                    .iter()
+                    .filter_map(|candidate| matcher.score(candidate))
+                    .fold(None, |best: Option<f64>, score| {
+                        Some(best.map_or(score, |best| best.max(score)))
+                    });
+                match score {
+                    Some(score) if found => Ok(Some(score)),
+                    _ => Err(failure(
+                        format!(
+                            "The nearest result, '{}' of type '{}', does not have the {} '{}', but [{}].",
+                            nearest.label,
+                            nearest.place_type,
+                            part,
+                            name,
+                            candidates.join(", ")
+                        ),
+                        places,
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Where the result the assertion looks for is, among all the results, whether the assertion
+    /// holds or not: its position from 1, or 0 if it is not there. None if the assertion does
+    /// not look for a particular result.
+    pub fn rank(&self, places: &[Place], mode: MatchMode) -> Option<usize> {
+        match self {
+            Assertion::Find {
+                label, place_type, ..
+            } => {
+                let matcher = LabelMatcher::new(mode, label).ok()?;
+                Some(
+                    places
+                        .iter()
+                        .position(|place| {
+                            place.place_type == *place_type && matcher.score(&place.label).is_some()
+                        })
+                        .map_or(0, |position| position + 1),
+                )
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AddressPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressPart::Address => write!(f, "address"),
+            AddressPart::Street => write!(f, "street"),
+            AddressPart::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+// The first result with the label, along with its position and score.
+fn find<'a>(
+    label: &str,
+    places: &'a [Place],
+    mode: MatchMode,
+) -> Result<(usize, &'a Place, f64), String> {
+    let matcher = LabelMatcher::new(mode, label)?;
+    places
+        .iter()
+        .enumerate()
+        .find_map(|(position, place)| {
+            matcher
+                .score(&place.label)
+                .map(|score| (position, place, score))
+        })
+        .ok_or_else(|| {
+            failure(
+                format!(
+                    "Could not find '{}'.{}",
+                    label,
+                    closest(&matcher, mode, places)
+                ),
+                places,
+            )
+        })
+}
+
+// With a similarity mode, the result which came closest to the label, to help adjust thresholds.
+fn closest(matcher: &LabelMatcher, mode: MatchMode, places: &[Place]) -> String {
+    match mode {
+        MatchMode::Levenshtein(_) | MatchMode::JaroWinkler(_) | MatchMode::Tokens => places
+            .iter()
+            .enumerate()
+            .map(|(position, place)| (position, place, matcher.similarity(&place.label)))
+            .max_by(|(_, _, a), (_, _, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(position, place, score)| {
+                format!(
+                    " Closest was '{}' at position {}, with a score of {:.2}.",
+                    place.label,
+                    position + 1,
+                    score
+                )
+            })
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+// The names and labels of the administrative regions the place is in.
+fn admin_names(place: &Place) -> Vec<String> {
+    place.geocoding["administrative_regions"]
+        .as_array()
+        .map(|admins| {
+            admins
+                .iter()
+                .flat_map(|admin| vec![admin["name"].as_str(), admin["label"].as_str()])
+                .filter_map(|name| name.map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn check<F: FnOnce() -> String>(ok: bool, places: &[Place], message: F) -> Result<(), String> {
+    if ok {
+        Ok(())
+    } else {
+        Err(failure(message(), places))
+    }
+}
+
+fn failure(message: String, places: &[Place]) -> String {
+    format!("{} Results were:\n{}", message, describe_places(places))
+}
+
+fn describe(value: Option<&str>) -> String {
+    value.map_or_else(|| String::from("none"), |value| format!("'{}'", value))
+}
+
+/// The distance between two points, in meters.
+pub fn haversine(a: &Coord, b: &Coord) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0; // meters
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let dlat = (b.lat - a.lat).to_radians();
+    let dlon = (b.lon - a.lon).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
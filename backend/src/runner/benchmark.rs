@@ -0,0 +1,80 @@
+use super::bragi::Place;
+use crate::{error, model::runs::latency};
+use futures::{future::Future, stream, StreamExt};
+use std::time::Instant;
+
+// Unless the benchmark says otherwise.
+pub const ITERATIONS: usize = 10;
+pub const CONCURRENCY: usize = 1;
+
+/// How a benchmark run replays each search: as many times as there are iterations, with at most
+/// concurrency requests at a time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Benchmark {
+    pub iterations: usize,
+    pub concurrency: usize,
+}
+
+impl Benchmark {
+    pub fn new(iterations: Option<i32>, concurrency: Option<i32>) -> Result<Self, error::Error> {
+        let positive = |value: Option<i32>, default: usize, name: &str| match value {
+            None => Ok(default),
+            Some(value) if value > 0 => Ok(value as usize),
+            Some(value) => Err(error::Error::UserError {
+                details: format!("The {} must be positive, not {}", name, value),
+            }),
+        };
+        Ok(Benchmark {
+            iterations: positive(iterations, ITERATIONS, "iterations")?,
+            concurrency: positive(concurrency, CONCURRENCY, "concurrency")?,
+        })
+    }
+}
+
+/// Send the request as many times as the benchmark says, and time each of them.
+pub async fn replay<F, R>(request: F, benchmark: &Benchmark) -> latency::LatencySample
+where
+    F: Fn() -> R,
+    R: Future<Output = Result<Vec<Place>, error::Error>>,
+{
+    let start = Instant::now();
+    let requests = (0..benchmark.iterations).map(|_| {
+        let request = request();
+        async move {
+            let start = Instant::now();
+            request
+                .await
+                .map(|_| start.elapsed().as_secs_f64() * 1000.0)
+        }
+    });
+    let outcomes: Vec<_> = stream::iter(requests)
+        .buffer_unordered(benchmark.concurrency)
+        .collect()
+        .await;
+
+    let mut sample = latency::LatencySample::default();
+    for outcome in outcomes {
+        match outcome {
+            Ok(latency) => sample.latencies.push(latency),
+            Err(_) => sample.errors += 1,
+        }
+    }
+    sample.elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    sample
+}
+
+/// Check that the given percentile of the latencies (in milliseconds) of the last search is
+/// within the maximum.
+pub fn check_latency(latencies: &[f64], maximum: f64, percentile: f64) -> Result<(), String> {
+    match latency::percentile(latencies, percentile) {
+        None => Err(String::from("No request to bragi succeeded")),
+        Some(latency) if latency <= maximum => Ok(()),
+        Some(latency) => Err(format!(
+            "The search responded in {:.1} ms at p{} (over {} requests), more than {} ms",
+            latency,
+            percentile,
+            latencies.len(),
+            maximum
+        )),
+    }
+}
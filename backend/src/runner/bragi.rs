@@ -0,0 +1,192 @@
+use crate::error;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+/// A single result returned by bragi.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    pub id: String,
+    pub label: String,
+    pub place_type: String,
+    pub coord: Option<Coord>,
+    // The geocoding properties, as returned by bragi (name, postcode, administrative_regions, ...)
+    pub geocoding: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coord {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// What a search step asks bragi for, besides the query.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchParams {
+    pub focus: Option<Coord>,  // lat, lon
+    pub types: Vec<String>,    // type[]
+    pub datasets: Vec<String>, // pt_dataset[]
+    pub limit: Option<usize>,
+    pub lang: Option<String>,
+    pub debug: bool, // _debug
+}
+
+/// The autocomplete request for the query, which is what a search step records to reproduce it.
+pub fn search_url(
+    bragi_url: &str,
+    query: &str,
+    params: &SearchParams,
+) -> Result<reqwest::Url, error::Error> {
+    let mut url = endpoint(bragi_url, "autocomplete")?;
+    {
+        let mut pairs = url.query_pairs_mut();
+        pairs.append_pair("q", query);
+        if let Some(focus) = params.focus {
+            pairs.append_pair("lat", &focus.lat.to_string());
+            pairs.append_pair("lon", &focus.lon.to_string());
+        }
+        for place_type in &params.types {
+            pairs.append_pair("type[]", place_type);
+        }
+        for dataset in &params.datasets {
+            pairs.append_pair("pt_dataset[]", dataset);
+        }
+        if let Some(limit) = params.limit {
+            pairs.append_pair("limit", &limit.to_string());
+        }
+        if let Some(lang) = &params.lang {
+            pairs.append_pair("lang", lang);
+        }
+        if params.debug {
+            pairs.append_pair("_debug", "true");
+        }
+    }
+    Ok(url)
+}
+
+/// The reverse geocoding request for the coordinates.
+pub fn reverse_url(bragi_url: &str, coord: &Coord) -> Result<reqwest::Url, error::Error> {
+    let mut url = endpoint(bragi_url, "reverse")?;
+    url.query_pairs_mut()
+        .append_pair("lat", &coord.lat.to_string())
+        .append_pair("lon", &coord.lon.to_string());
+    Ok(url)
+}
+
+fn endpoint(bragi_url: &str, path: &str) -> Result<reqwest::Url, error::Error> {
+    reqwest::Url::parse(&format!("{}/{}", bragi_url.trim_end_matches('/'), path)).map_err(|err| {
+        error::Error::ConfigError {
+            details: format!("Invalid bragi url '{}': {}", bragi_url, err),
+        }
+    })
+}
+
+/// Search using bragi's autocomplete endpoint, with the url given by search_url. With a shape
+/// (a GeoJSON feature), the search is a POST, and results are restricted to the shape.
+pub async fn autocomplete(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+    shape: Option<&serde_json::Value>,
+) -> Result<Vec<Place>, error::Error> {
+    let request = match shape {
+        None => client.get(url.clone()),
+        Some(shape) => client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::json!({ "shape": shape }).to_string()),
+    };
+    fetch_places(request, url).await
+}
+
+/// The places nearest to the coordinates, using bragi's reverse endpoint, with the url given by
+/// reverse_url. The nearest comes first.
+pub async fn reverse(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+) -> Result<Vec<Place>, error::Error> {
+    fetch_places(client.get(url.clone()), url).await
+}
+
+/// The version of bragi, as given by its status endpoint (`{"bragi": {"version": ...}}`), if it
+/// gives one.
+pub async fn version(
+    client: &reqwest::Client,
+    bragi_url: &str,
+) -> Result<Option<String>, error::Error> {
+    let url = endpoint(bragi_url, "status")?;
+    let body = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context(error::ReqwestError {
+            details: format!("Could not query bragi with {}", url),
+        })?
+        .text()
+        .await
+        .context(error::ReqwestError {
+            details: format!("Could not read response from {}", url),
+        })?;
+    let json: serde_json::Value = serde_json::from_str(&body).context(error::SerdeJsonError {
+        details: "Could not parse bragi status",
+    })?;
+    Ok(json["bragi"]["version"]
+        .as_str()
+        .or_else(|| json["version"].as_str())
+        .map(String::from))
+}
+
+async fn fetch_places(
+    request: reqwest::RequestBuilder,
+    url: &reqwest::Url,
+) -> Result<Vec<Place>, error::Error> {
+    let body = request
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context(error::ReqwestError {
+            details: format!("Could not query bragi with {}", url),
+        })?
+        .text()
+        .await
+        .context(error::ReqwestError {
+            details: format!("Could not read response from {}", url),
+        })?;
+
+    parse_places(&body)
+}
+
+/// Turn bragi's response (a GeoJSON feature collection) into a list of places.
+pub fn parse_places(body: &str) -> Result<Vec<Place>, error::Error> {
+    let json: serde_json::Value = serde_json::from_str(body).context(error::SerdeJsonError {
+        details: "Could not parse bragi response",
+    })?;
+
+    let features = json["features"]
+        .as_array()
+        .ok_or_else(|| error::Error::UserError {
+            details: String::from("Unexpected bragi response: no features"),
+        })?;
+
+    Ok(features
+        .iter()
+        .map(|feature| {
+            let geocoding = &feature["properties"]["geocoding"];
+            let coord = feature["geometry"]["coordinates"]
+                .as_array()
+                .and_then(|coords| match (coords.first(), coords.get(1)) {
+                    (Some(lon), Some(lat)) => Some(Coord {
+                        lat: lat.as_f64()?,
+                        lon: lon.as_f64()?,
+                    }),
+                    _ => None,
+                });
+            Place {
+                id: String::from(geocoding["id"].as_str().unwrap_or("")),
+                label: String::from(geocoding["label"].as_str().unwrap_or("")),
+                place_type: String::from(geocoding["type"].as_str().unwrap_or("")),
+                coord,
+                geocoding: geocoding.clone(),
+            }
+        })
+        .collect())
+}
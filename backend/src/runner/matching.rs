@@ -0,0 +1,163 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// How the labels expected by a step are compared with the labels returned by bragi.
+/// A step picks its mode with a trailing 'using <mode> matching', otherwise it takes the mode
+/// of a '@match:<mode>' tag on its scenario or feature, otherwise labels must be identical.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MatchMode {
+    /// The labels are identical.
+    #[default]
+    Exact,
+    /// The labels are identical, ignoring case, accents and punctuation.
+    Normalized,
+    /// Every word of the expected label is in the result, in any order, after normalization.
+    Tokens,
+    /// The normalized Levenshtein similarity is at least the threshold (between 0 and 1).
+    Levenshtein(f64),
+    /// The Jaro-Winkler similarity of the normalized labels is at least the threshold.
+    JaroWinkler(f64),
+    /// The expected label is a regular expression found in the result.
+    Regex,
+}
+
+impl MatchMode {
+    /// The mode named in a step ('levenshtein 0.8') or a tag ('levenshtein:0.8'). The
+    /// similarity modes have a default threshold.
+    pub fn parse(name: &str, threshold: Option<&str>) -> Option<Self> {
+        let threshold = match threshold {
+            Some(threshold) => match threshold.parse::<f64>() {
+                Ok(t) if (0.0..=1.0).contains(&t) => Some(t),
+                _ => return None,
+            },
+            None => None,
+        };
+        match (name, threshold) {
+            ("exact", None) => Some(MatchMode::Exact),
+            ("normalized", None) | ("normalised", None) => Some(MatchMode::Normalized),
+            ("tokens", None) => Some(MatchMode::Tokens),
+            ("regex", None) => Some(MatchMode::Regex),
+            ("levenshtein", t) => Some(MatchMode::Levenshtein(t.unwrap_or(0.8))),
+            ("jaro-winkler", t) => Some(MatchMode::JaroWinkler(t.unwrap_or(0.9))),
+            _ => None,
+        }
+    }
+
+    /// The mode given by the last '@match:' tag, which is the scenario's if both the feature
+    /// and the scenario have one. Fails with the offending tag if it names no mode.
+    pub fn from_tags(tags: &[String]) -> Result<Self, String> {
+        lazy_static! {
+            static ref TAG: Regex = Regex::new(r"^@?match:([a-z-]+)(?::([0-9.]+))?$").unwrap();
+        }
+        match tags
+            .iter()
+            .rev()
+            .find(|tag| tag.trim_start_matches('@').starts_with("match:"))
+        {
+            None => Ok(MatchMode::default()),
+            Some(tag) => TAG
+                .captures(tag)
+                .and_then(|caps| MatchMode::parse(&caps[1], caps.get(2).map(|t| t.as_str())))
+                .ok_or_else(|| tag.clone()),
+        }
+    }
+}
+
+/// Compares the results with an expected label.
+#[derive(Debug, Clone)]
+pub struct LabelMatcher {
+    mode: MatchMode,
+    expected: String,
+    regex: Option<Regex>,
+}
+
+impl LabelMatcher {
+    /// Fails if the mode is Regex and the label is not a valid regular expression.
+    pub fn new(mode: MatchMode, expected: &str) -> Result<Self, String> {
+        let regex = match mode {
+            MatchMode::Regex => Some(
+                Regex::new(expected)
+                    .map_err(|err| format!("Invalid regular expression '{}': {}", expected, err))?,
+            ),
+            _ => None,
+        };
+        let expected = match mode {
+            MatchMode::Exact | MatchMode::Regex => String::from(expected),
+            _ => normalize(expected),
+        };
+        Ok(LabelMatcher {
+            mode,
+            expected,
+            regex,
+        })
+    }
+
+    /// The score of the label if it matches, between 0 and 1.
+    pub fn score(&self, actual: &str) -> Option<f64> {
+        let (score, matched) = self.compare(actual);
+        if matched {
+            Some(score)
+        } else {
+            None
+        }
+    }
+
+    /// The similarity of the label with the expected one, whether it matches or not.
+    pub fn similarity(&self, actual: &str) -> f64 {
+        self.compare(actual).0
+    }
+
+    fn compare(&self, actual: &str) -> (f64, bool) {
+        let exact = |matched: bool| (if matched { 1.0 } else { 0.0 }, matched);
+        match self.mode {
+            MatchMode::Exact => exact(self.expected == actual),
+            MatchMode::Normalized => exact(self.expected == normalize(actual)),
+            MatchMode::Regex => exact(self.regex.as_ref().unwrap().is_match(actual)),
+            MatchMode::Tokens => {
+                let actual = normalize(actual);
+                let expected: Vec<&str> = self.expected.split(' ').collect();
+                let actual: Vec<&str> = actual.split(' ').collect();
+                let common = expected.iter().filter(|t| actual.contains(t)).count();
+                let union =
+                    expected.len() + actual.iter().filter(|t| !expected.contains(t)).count();
+                (common as f64 / union as f64, common == expected.len())
+            }
+            MatchMode::Levenshtein(threshold) => {
+                let score = strsim::normalized_levenshtein(&self.expected, &normalize(actual));
+                (score, score >= threshold)
+            }
+            MatchMode::JaroWinkler(threshold) => {
+                let score = jaro_winkler(&self.expected, &normalize(actual));
+                (score, score >= threshold)
+            }
+        }
+    }
+}
+
+// strsim 0.10 does not cap the common prefix at 4 characters, which gives a score of 1 to
+// labels which only differ near the end.
+fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let jaro = strsim::jaro(a, b);
+    let prefix = a
+        .chars()
+        .zip(b.chars())
+        .take(4)
+        .take_while(|(x, y)| x == y)
+        .count();
+    jaro + 0.1 * prefix as f64 * (1.0 - jaro)
+}
+
+/// Lowercase, without accents, with words separated by a single space.
+/// "Rue Hector-Malot, Paris" becomes "rue hector malot paris".
+pub fn normalize(label: &str) -> String {
+    label
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,734 @@
+use crate::{
+    error, gql, metrics,
+    model::{
+        features::step,
+        runs::{latency, quality, run, snapshot::SnapshotPlace, ResultStatus, RunStatus},
+    },
+};
+use slog::{info, o, warn};
+use snafu::ResultExt;
+use std::time::Instant;
+use uuid::Uuid;
+
+pub mod assertions;
+pub mod benchmark;
+pub mod bragi;
+pub mod matching;
+pub mod snapshots;
+pub mod steps;
+
+use benchmark::Benchmark;
+use matching::MatchMode;
+use steps::StepKind;
+
+// How many results of each search are recorded with its step.
+const RECORDED_RESULTS: usize = 10;
+
+// Scenarios with this tag are reported, but do not fail the run.
+const QUARANTINE_TAG: &str = "quarantine";
+
+// The outcome of a single step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepOutcome {
+    pub status: ResultStatus,
+    pub message: Option<String>,
+    pub duration: f64,                        // milliseconds
+    pub score: Option<f64>,                   // how well the label found matched the one expected
+    pub request_url: Option<String>,          // bragi request made by a search
+    pub rank: Option<usize>, // position of the result expected, from 1, 0 if missing
+    pub results: Option<Vec<String>>, // labels of the first results found by a search
+    pub snapshot: Option<Vec<SnapshotPlace>>, // results which do not match the snapshot
+}
+
+impl StepOutcome {
+    fn new(status: ResultStatus, message: Option<String>) -> Self {
+        StepOutcome {
+            status,
+            message,
+            duration: 0.0,
+            score: None,
+            request_url: None,
+            rank: None,
+            results: None,
+            snapshot: None,
+        }
+    }
+
+    fn passed() -> Self {
+        StepOutcome::new(ResultStatus::Passed, None)
+    }
+
+    fn skipped() -> Self {
+        StepOutcome::new(ResultStatus::Skipped, None)
+    }
+
+    fn failed(message: String) -> Self {
+        StepOutcome::new(ResultStatus::Failed, Some(message))
+    }
+}
+
+// What the steps of a scenario share while it is running.
+#[derive(Debug)]
+struct ScenarioState<'a> {
+    feature_name: &'a str,
+    scenario_name: &'a str,
+    position: i32,                     // of the current step, background steps included
+    places: Option<Vec<bragi::Place>>, // results of the last search
+    mode: Result<MatchMode, String>,   // match mode given by the tags, or the invalid tag
+    benchmark: Option<Benchmark>,      // how searches are replayed, in a benchmark run
+    latencies: Vec<f64>,               // milliseconds, of the last search
+    samples: Vec<latency::LatencySample>, // replays of all the searches, in a benchmark run
+}
+
+impl<'a> ScenarioState<'a> {
+    fn new(
+        feature_name: &'a str,
+        scenario_name: &'a str,
+        tags: &[String],
+        benchmark: Option<Benchmark>,
+    ) -> Self {
+        ScenarioState {
+            feature_name,
+            scenario_name,
+            position: 0,
+            places: None,
+            mode: MatchMode::from_tags(tags),
+            benchmark,
+            latencies: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    // The mode of the step if it has one, otherwise that of the tags. An invalid tag fails
+    // the step, with the message returned.
+    fn mode(&self, mode: Option<MatchMode>) -> Result<MatchMode, String> {
+        match (mode, &self.mode) {
+            (Some(mode), _) => Ok(mode),
+            (None, Ok(mode)) => Ok(*mode),
+            (None, Err(tag)) => Err(format!("Unknown match mode in the tag '{}'", tag)),
+        }
+    }
+
+    // The results the step checks.
+    fn places(&self) -> Result<&[bragi::Place], String> {
+        self.places
+            .as_deref()
+            .ok_or_else(|| String::from("No search was performed before this step"))
+    }
+}
+
+/// Run all the scenarios matching the given tags (all of them if there are no tags) against the
+/// given bragi (the one found at BRAGI_URL by default), and record the results. A scenario which
+/// fails is attempted again, as many times as configured. A benchmark run also replays each
+/// search, and records the latency of the searches of each scenario.
+pub async fn run_scenarios(
+    tags: Vec<String>,
+    bragi_url: Option<String>,
+    benchmark: Option<Benchmark>,
+    context: &gql::Context,
+) -> Result<run::Run, error::Error> {
+    let bragi_url = match bragi_url {
+        Some(bragi_url) => bragi_url,
+        None => context.settings.bragi_url()?.to_string(),
+    };
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|tag| String::from(tag.trim_start_matches('@')))
+        .collect();
+
+    let run = context
+        .store
+        .runs
+        .create_run(tags.clone(), &bragi_url, context)
+        .await?;
+    let _job = metrics::JobGuard::new("run");
+    // Everything logged during the run can be traced back to it, and to the request.
+    let context = &gql::Context {
+        logger: context.logger.new(o!("run_id" => run.id.to_string())),
+        ..context.clone()
+    };
+    info!(
+        context.logger,
+        "Starting run '{}' with tags {:?} against {}", run.id, tags, bragi_url
+    );
+
+    if let Some(benchmark) = benchmark {
+        info!(
+            context.logger,
+            "Replaying each search {} times, {} at a time",
+            benchmark.iterations,
+            benchmark.concurrency
+        );
+    }
+
+    // All the requests of the run, benchmark replays included, share one client.
+    let client = reqwest::Client::new();
+
+    // Results are only compared with those found against the same version of bragi.
+    let geocoder_version = match bragi::version(&client, &bragi_url).await {
+        Ok(version) => version,
+        Err(err) => {
+            warn!(
+                context.logger,
+                "Could not find the version of bragi at {}: {}", bragi_url, err
+            );
+            None
+        }
+    };
+
+    let mut samples = Vec::new();
+    let mut latency = Vec::new();
+    let status = match execute_run(
+        &run,
+        &tags,
+        &bragi_url,
+        &client,
+        geocoder_version.as_deref(),
+        benchmark,
+        &mut samples,
+        &mut latency,
+        context,
+    )
+    .await
+    {
+        Ok(status) => status,
+        Err(err) => {
+            warn!(context.logger, "Run '{}' failed: {}", run.id, err);
+            RunStatus::Error
+        }
+    };
+
+    let metrics = quality::compute_metrics(&run.id, &samples);
+    if let Err(err) = context
+        .store
+        .runs
+        .create_quality_metrics(metrics, context)
+        .await
+    {
+        warn!(
+            context.logger,
+            "Could not record quality metrics of run '{}': {}", run.id, err
+        );
+    }
+
+    if !latency.is_empty() {
+        if let Err(err) = context
+            .store
+            .runs
+            .create_latency_metrics(latency, context)
+            .await
+        {
+            warn!(
+                context.logger,
+                "Could not record latency metrics of run '{}': {}", run.id, err
+            );
+        }
+    }
+
+    context
+        .store
+        .runs
+        .finish_run(&run.id, status, context)
+        .await
+}
+
+// Returns true if the scenario, tagged with scenario_tags, is selected by tags.
+fn matches_tags(scenario_tags: &[String], tags: &[String]) -> bool {
+    tags.is_empty()
+        || scenario_tags
+            .iter()
+            .any(|tag| tags.iter().any(|t| t == tag.trim_start_matches('@')))
+}
+
+// Returns true if the scenario, tagged with scenario_tags, is quarantined.
+fn is_quarantined(scenario_tags: &[String]) -> bool {
+    scenario_tags
+        .iter()
+        .any(|tag| tag.trim_start_matches('@') == QUARANTINE_TAG)
+}
+
+// Returns Passed if all the scenarios passed, quarantined ones aside. If the server is shutting
+// down, the run stops after the current scenario, whose results are recorded, and it is
+// Interrupted. The rank of the result expected by each step which looks for one is added to
+// samples, and, in a benchmark run, the latency metrics of each scenario with searches are added
+// to latency. Those come from the last attempt of each scenario.
+#[allow(clippy::too_many_arguments)]
+async fn execute_run(
+    run: &run::Run,
+    tags: &[String],
+    bragi_url: &str,
+    client: &reqwest::Client,
+    geocoder_version: Option<&str>,
+    benchmark: Option<Benchmark>,
+    samples: &mut Vec<quality::RankSample>,
+    latency: &mut Vec<latency::LatencyMetrics>,
+    context: &gql::Context,
+) -> Result<RunStatus, error::Error> {
+    let mut all_passed = true;
+    let attempts = context.settings.runner.attempts.max(1) as i32;
+
+    let store = &context.store;
+    for feature in store.features.fetch_all_features(context).await? {
+        let (background_steps, environment) = match store
+            .features
+            .fetch_background_by_feature_id(&feature.id, context)
+            .await?
+        {
+            Some(background) => {
+                let steps = store
+                    .features
+                    .fetch_steps_by_background_id(&background.id, context)
+                    .await?;
+                let environment = background_environment(&background.id, &steps, context).await;
+                (steps, environment)
+            }
+            None => (Vec::new(), None),
+        };
+
+        for scenario in store
+            .features
+            .fetch_scenarios_by_feature_id(&feature.id, context)
+            .await?
+        {
+            let mut scenario_tags = feature.tags.clone();
+            scenario_tags.extend(scenario.tags.iter().cloned());
+            if !matches_tags(&scenario_tags, tags) {
+                continue;
+            }
+            if context.shutdown.is_triggered() {
+                warn!(context.logger, "Interrupting run '{}'", run.id);
+                return Ok(RunStatus::Interrupted);
+            }
+
+            let steps = store
+                .features
+                .fetch_steps_by_scenario_id(&scenario.id, context)
+                .await?;
+            let quarantined = is_quarantined(&scenario_tags);
+            let start = Instant::now();
+            let mut attempt = 0;
+            let (state, outcomes, status) = loop {
+                attempt += 1;
+                let mut state =
+                    ScenarioState::new(&feature.name, &scenario.name, &scenario_tags, benchmark);
+                let outcomes = execute_steps(
+                    background_steps.iter().chain(steps.iter()),
+                    &mut state,
+                    bragi_url,
+                    client,
+                    context,
+                )
+                .await;
+                // The scenario takes the status of its first step which did not pass.
+                let status = outcomes
+                    .iter()
+                    .map(|(_, outcome)| outcome.status)
+                    .find(|status| *status != ResultStatus::Passed)
+                    .unwrap_or(ResultStatus::Passed);
+                // Undefined steps will not be defined the next time either.
+                let failed = status == ResultStatus::Failed || status == ResultStatus::Error;
+                if !failed || attempt >= attempts || context.shutdown.is_triggered() {
+                    break (state, outcomes, status);
+                }
+                info!(
+                    context.logger,
+                    "Scenario '{}' / '{}': {:?} at attempt {} of {}, trying again",
+                    feature.name,
+                    scenario.name,
+                    status,
+                    attempt,
+                    attempts
+                );
+            };
+            let duration = start.elapsed().as_secs_f64() * 1000.0;
+
+            all_passed &= status == ResultStatus::Passed || quarantined;
+            metrics::record_scenario(status);
+
+            if status == ResultStatus::Passed && attempt > 1 {
+                warn!(
+                    context.logger,
+                    "Scenario '{}' / '{}' is flaky: it passed at attempt {}",
+                    feature.name,
+                    scenario.name,
+                    attempt
+                );
+            }
+            info!(
+                context.logger,
+                "Scenario '{}' / '{}': {:?}{}",
+                feature.name,
+                scenario.name,
+                status,
+                if quarantined { " (quarantined)" } else { "" }
+            );
+
+            let index_types: Vec<String> = background_steps
+                .iter()
+                .chain(steps.iter())
+                .filter_map(|step| match steps::parse_step(&step.value) {
+                    Some(StepKind::Index { index_type, .. }) => Some(index_type),
+                    _ => None,
+                })
+                .collect();
+            samples.extend(outcomes.iter().filter_map(|(_, outcome)| {
+                outcome.rank.map(|rank| quality::RankSample {
+                    feature: feature.name.clone(),
+                    tags: scenario_tags.clone(),
+                    index_types: index_types.clone(),
+                    rank: if rank == 0 { None } else { Some(rank) },
+                })
+            }));
+            if let Some(benchmark) = benchmark {
+                latency.extend(latency::compute_metrics(
+                    &run.id,
+                    &feature.name,
+                    &scenario.name,
+                    benchmark.iterations,
+                    benchmark.concurrency,
+                    &state.samples,
+                ));
+            }
+
+            let result = store
+                .runs
+                .create_scenario_result(
+                    &run.id,
+                    &scenario.id,
+                    &feature.name,
+                    &scenario.name,
+                    scenario_tags,
+                    status,
+                    duration,
+                    environment.as_deref(),
+                    geocoder_version,
+                    attempt,
+                    quarantined,
+                    context,
+                )
+                .await?;
+
+            for (position, (step, mut outcome)) in outcomes.into_iter().enumerate() {
+                let snapshot = outcome.snapshot.take();
+                let step_result = store
+                    .runs
+                    .create_step_result(
+                        &result.id,
+                        position as i32,
+                        step.step_type.clone(),
+                        &step.value,
+                        outcome.status,
+                        outcome.message,
+                        outcome.duration,
+                        outcome.score,
+                        outcome.request_url,
+                        outcome.rank.map(|rank| rank as i32),
+                        outcome.results,
+                        context,
+                    )
+                    .await?;
+                // The results can then be accepted as the new snapshot.
+                if let Some(results) = snapshot {
+                    store
+                        .runs
+                        .propose_snapshot(
+                            &feature.name,
+                            &scenario.name,
+                            position as i32,
+                            results,
+                            &step_result.id,
+                            context,
+                        )
+                        .await?;
+                }
+            }
+        }
+    }
+
+    Ok(if all_passed {
+        RunStatus::Passed
+    } else {
+        RunStatus::Failed
+    })
+}
+
+// The signature of the environment the background describes, if all its steps are index steps.
+// Scenarios of features without an environment are compared with each other regardless.
+async fn background_environment(
+    background: &Uuid,
+    steps: &[step::Step],
+    context: &gql::Context,
+) -> Option<String> {
+    let indexes = steps
+        .iter()
+        .all(|step| matches!(steps::parse_step(&step.value), Some(StepKind::Index { .. })));
+    if steps.is_empty() || !indexes {
+        return None;
+    }
+    match context
+        .store
+        .environments
+        .fetch_background_environment(background, context)
+        .await
+    {
+        Ok(environment) => Some(environment.signature),
+        Err(err) => {
+            warn!(
+                context.logger,
+                "Could not find the environment of background '{}': {}", background, err
+            );
+            None
+        }
+    }
+}
+
+// Execute the steps in order. Once a step did not pass, the following ones are skipped.
+// The state starts with the tags of the scenario and its feature.
+async fn execute_steps<'a, I>(
+    steps: I,
+    state: &mut ScenarioState<'_>,
+    bragi_url: &str,
+    client: &reqwest::Client,
+    context: &gql::Context,
+) -> Vec<(&'a step::Step, StepOutcome)>
+where
+    I: Iterator<Item = &'a step::Step>,
+{
+    let mut outcomes = Vec::new();
+    let mut skip = false;
+
+    for (position, step) in steps.enumerate() {
+        state.position = position as i32;
+        let outcome = if skip {
+            StepOutcome::skipped()
+        } else {
+            let start = Instant::now();
+            let mut outcome = execute_step(step, state, bragi_url, client, context).await;
+            outcome.duration = start.elapsed().as_secs_f64() * 1000.0;
+            outcome
+        };
+        skip = outcome.status != ResultStatus::Passed;
+        outcomes.push((step, outcome));
+    }
+
+    outcomes
+}
+
+async fn execute_step(
+    step: &step::Step,
+    state: &mut ScenarioState<'_>,
+    bragi_url: &str,
+    client: &reqwest::Client,
+    context: &gql::Context,
+) -> StepOutcome {
+    match steps::parse_step(&step.value) {
+        None => StepOutcome::new(
+            ResultStatus::Undefined,
+            Some(format!("No step definition matches '{}'", step.value)),
+        ),
+        // The environment is prepared before the run, so there is nothing left to do.
+        Some(StepKind::Index { .. }) => StepOutcome::passed(),
+        Some(StepKind::Search {
+            query,
+            params,
+            within_shape,
+        }) => {
+            let url = match bragi::search_url(bragi_url, &query, &params) {
+                Ok(url) => url,
+                Err(err) => return StepOutcome::new(ResultStatus::Error, Some(format!("{}", err))),
+            };
+            let shape = match parse_shape(within_shape, &step.docstring) {
+                Ok(shape) => shape,
+                Err(err) => return record_places(Err(err), url, state, context),
+            };
+            let (request, shape) = (&url, shape.as_ref());
+            let places = measure(move || bragi::autocomplete(client, request, shape), state).await;
+            record_places(places, url, state, context)
+        }
+        Some(StepKind::Reverse { coord }) => {
+            let url = match bragi::reverse_url(bragi_url, &coord) {
+                Ok(url) => url,
+                Err(err) => return StepOutcome::new(ResultStatus::Error, Some(format!("{}", err))),
+            };
+            let request = &url;
+            let places = measure(move || bragi::reverse(client, request), state).await;
+            record_places(places, url, state, context)
+        }
+        Some(StepKind::Snapshot { limit, distance }) => {
+            match check_snapshot(limit, distance, state, context).await {
+                Ok(outcome) | Err(outcome) => outcome,
+            }
+        }
+        Some(StepKind::Latency {
+            maximum,
+            percentile,
+        }) => {
+            if let Err(message) = state.places() {
+                return StepOutcome::failed(message);
+            }
+            match benchmark::check_latency(&state.latencies, maximum, percentile) {
+                Ok(()) => StepOutcome::passed(),
+                Err(message) => StepOutcome::failed(message),
+            }
+        }
+        Some(StepKind::Assert { assertion, mode }) => {
+            let mode = match state.mode(mode) {
+                Ok(mode) => mode,
+                Err(message) => return StepOutcome::failed(message),
+            };
+            let places = match state.places() {
+                Ok(places) => places,
+                Err(message) => return StepOutcome::failed(message),
+            };
+            let outcome = match assertion.evaluate(places, mode) {
+                Ok(score) => StepOutcome {
+                    score,
+                    ..StepOutcome::passed()
+                },
+                Err(message) => StepOutcome::failed(message),
+            };
+            StepOutcome {
+                rank: assertion.rank(places, mode),
+                ..outcome
+            }
+        }
+    }
+}
+
+// The first time the step runs, the results become its snapshot. Afterwards, results which do
+// not match are kept with the outcome, so that they can be accepted.
+async fn check_snapshot(
+    limit: usize,
+    distance: f64,
+    state: &ScenarioState<'_>,
+    context: &gql::Context,
+) -> Result<StepOutcome, StepOutcome> {
+    let mode = state.mode(None).map_err(StepOutcome::failed)?;
+    let results = snapshots::snapshot_places(state.places().map_err(StepOutcome::failed)?, limit);
+    let error = |err: error::Error| StepOutcome::new(ResultStatus::Error, Some(format!("{}", err)));
+    let runs = &context.store.runs;
+    let snapshot = runs
+        .fetch_snapshot(
+            state.feature_name,
+            state.scenario_name,
+            state.position,
+            context,
+        )
+        .await
+        .map_err(error)?;
+    match snapshot {
+        None => {
+            let count = results.len();
+            runs.save_snapshot(
+                state.feature_name,
+                state.scenario_name,
+                state.position,
+                results,
+                context,
+            )
+            .await
+            .map_err(error)?;
+            Ok(StepOutcome::new(
+                ResultStatus::Passed,
+                Some(format!("Recorded a new snapshot of {} results", count)),
+            ))
+        }
+        Some(snapshot) => {
+            let expected = &snapshot.results[..limit.min(snapshot.results.len())];
+            match snapshots::compare(expected, &results, mode, distance) {
+                Ok(()) => Ok(StepOutcome::passed()),
+                Err(message) => Ok(StepOutcome {
+                    snapshot: Some(results),
+                    ..StepOutcome::failed(message)
+                }),
+            }
+        }
+    }
+}
+
+// The places found by bragi are those the following steps check. Whether the request succeeded
+// or not, the outcome has its url, so that it can be reproduced. It has the labels of the first
+// results, so that runs can be compared.
+fn record_places(
+    places: Result<Vec<bragi::Place>, error::Error>,
+    url: reqwest::Url,
+    state: &mut ScenarioState,
+    context: &gql::Context,
+) -> StepOutcome {
+    let outcome = match places {
+        Ok(places) => {
+            let results = places
+                .iter()
+                .take(RECORDED_RESULTS)
+                .map(|place| place.label.clone())
+                .collect();
+            state.places = Some(places);
+            StepOutcome {
+                results: Some(results),
+                ..StepOutcome::passed()
+            }
+        }
+        Err(err) => {
+            warn!(context.logger, "Request {} failed: {}", url, err);
+            StepOutcome::new(ResultStatus::Error, Some(format!("{}", err)))
+        }
+    };
+    StepOutcome {
+        request_url: Some(url.to_string()),
+        ..outcome
+    }
+}
+
+// The shape, if the search is restricted to one, is the GeoJSON feature in the docstring.
+fn parse_shape(
+    within_shape: bool,
+    docstring: &str,
+) -> Result<Option<serde_json::Value>, error::Error> {
+    if !within_shape {
+        return Ok(None);
+    }
+    serde_json::from_str(docstring)
+        .map(Some)
+        .context(error::SerdeJsonError {
+            details: "Could not parse the shape in the docstring",
+        })
+}
+
+// Send the request to bragi, and record its latency. In a benchmark run, a request which
+// succeeded is replayed, and the latencies are those of the replays.
+async fn measure<F, R>(
+    request: F,
+    state: &mut ScenarioState<'_>,
+) -> Result<Vec<bragi::Place>, error::Error>
+where
+    F: Fn() -> R,
+    R: std::future::Future<Output = Result<Vec<bragi::Place>, error::Error>>,
+{
+    let start = Instant::now();
+    let places = request().await;
+    let latency = start.elapsed().as_secs_f64() * 1000.0;
+    state.latencies = match (&places, state.benchmark) {
+        (Err(_), _) => Vec::new(),
+        (Ok(_), None) => vec![latency],
+        (Ok(_), Some(benchmark)) => {
+            let sample = benchmark::replay(&request, &benchmark).await;
+            let latencies = sample.latencies.clone();
+            state.samples.push(sample);
+            latencies
+        }
+    };
+    places
+}
+
+// A numbered list of the places, for failure messages.
+pub fn describe_places(places: &[bragi::Place]) -> String {
+    if places.is_empty() {
+        return String::from("  (no results)");
+    }
+    places
+        .iter()
+        .enumerate()
+        .map(|(i, place)| format!("  {}. {} ({})", i + 1, place.label, place.place_type))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,84 @@
+use super::{
+    assertions::haversine,
+    bragi::{Coord, Place},
+    matching::{LabelMatcher, MatchMode},
+};
+use crate::model::runs::snapshot::SnapshotPlace;
+
+/// What a snapshot keeps of the first results.
+pub fn snapshot_places(places: &[Place], limit: usize) -> Vec<SnapshotPlace> {
+    places
+        .iter()
+        .take(limit)
+        .map(|place| SnapshotPlace {
+            label: place.label.clone(),
+            place_type: place.place_type.clone(),
+            lat: place.coord.map(|coord| coord.lat),
+            lon: place.coord.map(|coord| coord.lon),
+        })
+        .collect()
+}
+
+/// Check the results against the snapshot, position by position: labels are compared
+/// according to the mode, types must be identical, and coordinates within the distance (in
+/// meters). The failure message lists the differences.
+pub fn compare(
+    expected: &[SnapshotPlace],
+    actual: &[SnapshotPlace],
+    mode: MatchMode,
+    distance: f64,
+) -> Result<(), String> {
+    let mut differences = Vec::new();
+    for position in 0..expected.len().max(actual.len()) {
+        let difference = match (expected.get(position), actual.get(position)) {
+            (Some(expected), Some(actual)) => {
+                let matcher = LabelMatcher::new(mode, &expected.label)?;
+                if matcher.score(&actual.label).is_none()
+                    || expected.place_type != actual.place_type
+                {
+                    Some(format!(
+                        "expected '{}' ({}), found '{}' ({})",
+                        expected.label, expected.place_type, actual.label, actual.place_type
+                    ))
+                } else {
+                    match (coord(expected), coord(actual)) {
+                        (Some(a), Some(b)) if haversine(&a, &b) > distance => Some(format!(
+                            "'{}' moved {:.0} meters",
+                            actual.label,
+                            haversine(&a, &b)
+                        )),
+                        (Some(_), None) => Some(format!("'{}' has no coordinates", actual.label)),
+                        _ => None,
+                    }
+                }
+            }
+            (Some(expected), None) => Some(format!(
+                "'{}' ({}) is missing",
+                expected.label, expected.place_type
+            )),
+            (None, Some(actual)) => {
+                Some(format!("'{}' ({}) is new", actual.label, actual.place_type))
+            }
+            (None, None) => None,
+        };
+        if let Some(difference) = difference {
+            differences.push(format!("  {}. {}", position + 1, difference));
+        }
+    }
+
+    if differences.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "The results differ from the snapshot:\n{}",
+            differences.join("\n")
+        ))
+    }
+}
+
+fn coord(place: &SnapshotPlace) -> Option<Coord> {
+    match (place.lat, place.lon) {
+        (Some(lat), Some(lon)) => Some(Coord { lat, lon }),
+        _ => None,
+    }
+}
@@ -0,0 +1,341 @@
+use super::{
+    assertions::{AddressPart, Assertion},
+    bragi::{Coord, SearchParams},
+    matching::MatchMode,
+};
+use crate::model::features::step;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// What a snapshot step compares, unless it says otherwise.
+const SNAPSHOT_LIMIT: usize = 10;
+const SNAPSHOT_DISTANCE: f64 = 100.0; // meters
+
+/// What a step means to the runner.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepKind {
+    /// I am indexing <index type> with <data source> from <regions>
+    Index {
+        index_type: String,
+        data_source: String,
+        regions: Vec<String>,
+    },
+    /// I search for '<query>' [near <lat>, <lon>] [within the shape] [with <parameters>], where
+    /// the parameters are any of: type(s) '<types>', dataset(s) '<datasets>', limit <n>,
+    /// lang '<lang>' and debug, separated by ', ' or ' and '. The shape is the step's docstring.
+    Search {
+        query: String,
+        params: SearchParams,
+        within_shape: bool,
+    },
+    /// I reverse geocode <lat>, <lon>
+    Reverse { coord: Coord },
+    /// the [first <n>] results match the snapshot [within <n> meters]: the first results (10 by
+    /// default) are those recorded the first time the step ran, and have not moved further
+    /// than the distance (100 meters by default).
+    Snapshot { limit: usize, distance: f64 },
+    /// the search responds within <n> ms at p<percentile>: in a benchmark run, the percentile
+    /// of the latencies of the replays of the last search, otherwise the latency of the search.
+    Latency { maximum: f64, percentile: f64 },
+    /// I find ..., I do not find ..., '<label>' ranks above '<label>', optionally followed by
+    /// 'using <mode> matching', which overrides the mode given by the tags.
+    Assert {
+        assertion: Assertion,
+        mode: Option<MatchMode>,
+    },
+}
+
+/// Return what the step means, or None if the runner does not know this step.
+pub fn parse_step(value: &str) -> Option<StepKind> {
+    lazy_static! {
+        static ref SEARCH: Regex = Regex::new(
+            r"^I search for '(.*?)'(?: near (-?[0-9]+(?:\.[0-9]+)?), ?(-?[0-9]+(?:\.[0-9]+)?))?( within the shape)?(?: with (.+))?$"
+        )
+        .unwrap();
+        static ref REVERSE: Regex = Regex::new(
+            r"^I reverse geocode (-?[0-9]+(?:\.[0-9]+)?), ?(-?[0-9]+(?:\.[0-9]+)?)$"
+        )
+        .unwrap();
+        static ref SNAPSHOT: Regex = Regex::new(
+            r"^the (?:first ([0-9]+) )?results match the snapshot(?: within ([0-9]+(?:\.[0-9]+)?) m(?:eters)?)?$"
+        )
+        .unwrap();
+        static ref LATENCY: Regex = Regex::new(
+            r"^the search responds within ([0-9]+(?:\.[0-9]+)?) ms at p([1-9][0-9]?)$"
+        )
+        .unwrap();
+        static ref MATCHING: Regex =
+            Regex::new(r"^(.*) using ([a-z-]+)(?: ([0-9.]+))? matching$").unwrap();
+    }
+    let value = value.trim();
+
+    if let Some((index_type, data_source, regions)) =
+        step::extract_index_from_step(String::from(value))
+    {
+        return Some(StepKind::Index {
+            index_type,
+            data_source,
+            regions,
+        });
+    }
+
+    if let Some(caps) = SEARCH.captures(value) {
+        let mut params = match caps.get(5) {
+            Some(params) => parse_search_params(params.as_str())?,
+            None => SearchParams::default(),
+        };
+        if let (Some(lat), Some(lon)) = (caps.get(2), caps.get(3)) {
+            params.focus = Some(Coord {
+                lat: lat.as_str().parse().ok()?,
+                lon: lon.as_str().parse().ok()?,
+            });
+        }
+        return Some(StepKind::Search {
+            query: String::from(&caps[1]),
+            params,
+            within_shape: caps.get(4).is_some(),
+        });
+    }
+
+    if let Some(caps) = REVERSE.captures(value) {
+        return Some(StepKind::Reverse {
+            coord: Coord {
+                lat: caps[1].parse().ok()?,
+                lon: caps[2].parse().ok()?,
+            },
+        });
+    }
+
+    if let Some(caps) = SNAPSHOT.captures(value) {
+        return Some(StepKind::Snapshot {
+            limit: match caps.get(1) {
+                Some(limit) => limit.as_str().parse().ok()?,
+                None => SNAPSHOT_LIMIT,
+            },
+            distance: match caps.get(2) {
+                Some(distance) => distance.as_str().parse().ok()?,
+                None => SNAPSHOT_DISTANCE,
+            },
+        });
+    }
+
+    if let Some(caps) = LATENCY.captures(value) {
+        return Some(StepKind::Latency {
+            maximum: caps[1].parse().ok()?,
+            percentile: caps[2].parse().ok()?,
+        });
+    }
+
+    match MATCHING.captures(value) {
+        Some(caps) => {
+            let mode = MatchMode::parse(&caps[2], caps.get(3).map(|t| t.as_str()))?;
+            parse_assertion(&caps[1]).map(|assertion| StepKind::Assert {
+                assertion,
+                mode: Some(mode),
+            })
+        }
+        None => parse_assertion(value).map(|assertion| StepKind::Assert {
+            assertion,
+            mode: None,
+        }),
+    }
+}
+
+// "types 'poi, street', dataset 'stif' and limit 5". None if anything is left unparsed.
+fn parse_search_params(value: &str) -> Option<SearchParams> {
+    lazy_static! {
+        static ref PARAM: Regex =
+            Regex::new(r"(types?|datasets?) '([^']*)'|limit ([0-9]+)|lang '([A-Za-z_-]+)'|debug")
+                .unwrap();
+    }
+    let mut params = SearchParams::default();
+    let mut end = 0;
+    for caps in PARAM.captures_iter(value) {
+        let param = caps.get(0).unwrap();
+        let separator = &value[end..param.start()];
+        let separated = if end == 0 {
+            separator.is_empty()
+        } else {
+            separator == ", " || separator == " and "
+        };
+        if !separated {
+            return None;
+        }
+        end = param.end();
+
+        let list = |m: regex::Match| {
+            m.as_str()
+                .split(',')
+                .map(|item| String::from(item.trim()))
+                .filter(|item| !item.is_empty())
+                .collect::<Vec<_>>()
+        };
+        match (caps.get(1).map(|m| m.as_str()), caps.get(2)) {
+            (Some("type"), Some(types)) | (Some("types"), Some(types)) => {
+                params.types.extend(list(types))
+            }
+            (Some(_), Some(datasets)) => params.datasets.extend(list(datasets)),
+            _ => {
+                if let Some(limit) = caps.get(3) {
+                    params.limit = Some(limit.as_str().parse().ok()?);
+                } else if let Some(lang) = caps.get(4) {
+                    params.lang = Some(String::from(lang.as_str()));
+                } else {
+                    params.debug = true;
+                }
+            }
+        }
+    }
+    if end == 0 || end != value.len() {
+        return None;
+    }
+    Some(params)
+}
+
+// The more specific patterns come first: "I find 'A' in 'B'" would also match "I find '(.*)'".
+fn parse_assertion(value: &str) -> Option<Assertion> {
+    lazy_static! {
+        static ref FIND: Regex = Regex::new(
+            r"^I find '(.*)' of type '([A-Za-z_]+)' within the first ([0-9]+) results?$"
+        )
+        .unwrap();
+        static ref NOT_FIND: Regex =
+            Regex::new(r"^I do not find '(.*)'(?: within the first ([0-9]+) results?)?$").unwrap();
+        static ref NO_RESULTS: Regex = Regex::new(r"^I find no results?$").unwrap();
+        static ref COUNT: Regex =
+            Regex::new(r"^I find (at least |at most )?([0-9]+) results?$").unwrap();
+        static ref COUNT_BETWEEN: Regex =
+            Regex::new(r"^I find between ([0-9]+) and ([0-9]+) results?$").unwrap();
+        static ref NEAR: Regex = Regex::new(
+            r"^I find '(.*)' within ([0-9]+(?:\.[0-9]+)?) m(?:eters)? of (-?[0-9]+(?:\.[0-9]+)?), ?(-?[0-9]+(?:\.[0-9]+)?)$"
+        )
+        .unwrap();
+        static ref ZONE_TYPE: Regex =
+            Regex::new(r"^I find '(.*)' with zone type '([A-Za-z_]+)'$").unwrap();
+        static ref LEVEL: Regex = Regex::new(r"^I find '(.*)' with level ([0-9]+)$").unwrap();
+        static ref POSTCODE: Regex =
+            Regex::new(r"^I find '(.*)' with postcode '([^']*)'$").unwrap();
+        static ref HOUSE_NUMBER: Regex =
+            Regex::new(r"^I find '(.*)' with (?:a house number|house number '([^']*)')$").unwrap();
+        static ref IN_ADMIN: Regex = Regex::new(r"^I find '(.*)' in '([^']*)'$").unwrap();
+        static ref NEAREST: Regex =
+            Regex::new(r"^I find the (address|street|admin) '(.*)'$").unwrap();
+        static ref RANKS_ABOVE: Regex = Regex::new(r"^'(.*)' ranks above '(.*)'$").unwrap();
+    }
+
+    if let Some(caps) = FIND.captures(value) {
+        return caps[3].parse().ok().map(|limit| Assertion::Find {
+            label: String::from(&caps[1]),
+            place_type: String::from(&caps[2]),
+            limit,
+        });
+    }
+
+    if let Some(caps) = NOT_FIND.captures(value) {
+        let limit = match caps.get(2) {
+            Some(limit) => Some(limit.as_str().parse().ok()?),
+            None => None,
+        };
+        return Some(Assertion::NotFind {
+            label: String::from(&caps[1]),
+            limit,
+        });
+    }
+
+    if NO_RESULTS.is_match(value) {
+        return Some(Assertion::Count {
+            min: None,
+            max: Some(0),
+        });
+    }
+
+    if let Some(caps) = COUNT.captures(value) {
+        let count = caps[2].parse().ok()?;
+        return Some(match caps.get(1).map(|m| m.as_str()) {
+            Some("at least ") => Assertion::Count {
+                min: Some(count),
+                max: None,
+            },
+            Some(_) => Assertion::Count {
+                min: None,
+                max: Some(count),
+            },
+            None => Assertion::Count {
+                min: Some(count),
+                max: Some(count),
+            },
+        });
+    }
+
+    if let Some(caps) = COUNT_BETWEEN.captures(value) {
+        return Some(Assertion::Count {
+            min: Some(caps[1].parse().ok()?),
+            max: Some(caps[2].parse().ok()?),
+        });
+    }
+
+    if let Some(caps) = NEAR.captures(value) {
+        return Some(Assertion::Near {
+            label: String::from(&caps[1]),
+            distance: caps[2].parse().ok()?,
+            coord: Coord {
+                lat: caps[3].parse().ok()?,
+                lon: caps[4].parse().ok()?,
+            },
+        });
+    }
+
+    if let Some(caps) = ZONE_TYPE.captures(value) {
+        return Some(Assertion::ZoneType {
+            label: String::from(&caps[1]),
+            zone_type: String::from(&caps[2]),
+        });
+    }
+
+    if let Some(caps) = LEVEL.captures(value) {
+        return Some(Assertion::Level {
+            label: String::from(&caps[1]),
+            level: caps[2].parse().ok()?,
+        });
+    }
+
+    if let Some(caps) = POSTCODE.captures(value) {
+        return Some(Assertion::Postcode {
+            label: String::from(&caps[1]),
+            postcode: String::from(&caps[2]),
+        });
+    }
+
+    if let Some(caps) = HOUSE_NUMBER.captures(value) {
+        return Some(Assertion::HouseNumber {
+            label: String::from(&caps[1]),
+            housenumber: caps.get(2).map(|m| String::from(m.as_str())),
+        });
+    }
+
+    if let Some(caps) = NEAREST.captures(value) {
+        let part = match &caps[1] {
+            "address" => AddressPart::Address,
+            "street" => AddressPart::Street,
+            _ => AddressPart::Admin,
+        };
+        return Some(Assertion::Nearest {
+            part,
+            name: String::from(&caps[2]),
+        });
+    }
+
+    if let Some(caps) = IN_ADMIN.captures(value) {
+        return Some(Assertion::InAdmin {
+            label: String::from(&caps[1]),
+            admin: String::from(&caps[2]),
+        });
+    }
+
+    RANKS_ABOVE
+        .captures(value)
+        .map(|caps| Assertion::RanksAbove {
+            above: String::from(&caps[1]),
+            below: String::from(&caps[2]),
+        })
+}
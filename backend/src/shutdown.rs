@@ -0,0 +1,104 @@
+use futures::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{mpsc, watch};
+
+/// Tells long running tasks (runs, subscriptions, downloads) that the server is shutting down,
+/// so that they can stop at a point where they can record how far they got.
+/// Clones share the same state, so triggering one triggers them all.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+    // Each task spawned with `spawn` holds a clone of the tasks sender, so once `drain` has dropped
+    // the sender, the receiver is closed when the last of them finishes.
+    tasks: Arc<Mutex<Option<mpsc::Sender<()>>>>,
+    finished: Arc<Mutex<Option<mpsc::Receiver<()>>>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        let (tasks, finished) = mpsc::channel(1);
+        Shutdown {
+            sender: Arc::new(sender),
+            receiver,
+            tasks: Arc::new(Mutex::new(Some(tasks))),
+            finished: Arc::new(Mutex::new(Some(finished))),
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn trigger(&self) {
+        let _ = self.sender.broadcast(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once the shutdown has been triggered.
+    pub async fn wait(&self) {
+        let mut receiver = self.receiver.clone();
+        while !*receiver.borrow() {
+            if receiver.recv().await.is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Spawn a task which outlives the request starting it (eg a download), and which the server
+    /// waits for when it shuts down (see `drain`). The task should stop when the shutdown is
+    /// triggered.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let guard = lock(&self.tasks).clone();
+        tokio::spawn(async move {
+            task.await;
+            drop(guard);
+        });
+    }
+
+    /// Resolves once all the tasks spawned with `spawn` have finished. Tasks spawned afterwards
+    /// are not waited for.
+    pub async fn drain(&self) {
+        lock(&self.tasks).take();
+        let finished = lock(&self.finished).take();
+        if let Some(mut finished) = finished {
+            // Nothing is ever sent: this returns None once all the senders are dropped.
+            finished.recv().await;
+        }
+    }
+}
+
+// The locks are only held to take or clone what they protect, which cannot be left half done by
+// a panic, so we keep going.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Resolves on SIGTERM or SIGINT (Ctrl-C).
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                futures::future::select(
+                    Box::pin(sigterm.recv()),
+                    Box::pin(tokio::signal::ctrl_c()),
+                )
+                .await;
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
@@ -0,0 +1,1111 @@
+use super::{
+    AuditStore, DatasetStore, EnvironmentStore, FeatureStore, RepositoryStore, RunStore, TokenStore,
+};
+use crate::{
+    auth::{
+        token::{ApiToken, NewApiToken},
+        Role,
+    },
+    error, gql,
+    model::{
+        audit::{self, AuditEntry, AuditFilter},
+        environments::{
+            dataset::{Dataset, Item},
+            environment::Environment,
+            index::{Index, IndexStatus},
+        },
+        features::{
+            background::Background,
+            feature::{self, Feature},
+            repository::{FeatureRevision, RepositorySync},
+            scenario::Scenario,
+            step::{self, Step, StepType},
+        },
+        runs::{
+            flaky::{self, FlakyScenario},
+            latency::LatencyMetrics,
+            quality::{QualityGroup, QualityMetrics},
+            run::{Run, ScenarioResult, StepResult},
+            snapshot::{Snapshot, SnapshotPlace},
+            ResultStatus, RunStatus,
+        },
+    },
+};
+use async_trait::async_trait;
+use chrono::prelude::*;
+use md5::{Digest, Md5};
+use snafu::ResultExt;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+use uuid::Uuid;
+
+/// Keeps everything in memory, behind a single lock which is never held across an await.
+/// It follows the rules of the database functions closely enough for the GraphQL layer to be
+/// tested without a database, but does not validate index types and data sources.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    features: Vec<Feature>,
+    backgrounds: HashMap<Uuid, Background>,  // by feature id
+    scenarios: HashMap<Uuid, Vec<Scenario>>, // by feature id
+    steps: HashMap<Uuid, Vec<Step>>,         // by scenario or background id
+    environments: Vec<Environment>,
+    indexes: Vec<Index>,
+    environment_indexes: HashMap<Uuid, Vec<Uuid>>, // index ids by environment id
+    datasets: Vec<Dataset>,                        // without their items
+    items: Vec<Item>,
+    dataset_items: Vec<(String, String)>, // (dataset id, item id)
+    runs: Vec<Run>,                       // in the order they were started
+    scenario_results: Vec<ScenarioResult>,
+    step_results: Vec<StepResult>,
+    quality_metrics: Vec<QualityMetrics>,
+    latency_metrics: Vec<LatencyMetrics>,
+    snapshots: Vec<Snapshot>,
+    api_tokens: Vec<(ApiToken, String)>, // with the hash of the token
+    audit_log: Vec<AuditEntry>,          // in the order they were added
+}
+
+impl MemoryStore {
+    // A panic while holding the lock cannot leave the state half updated, so we keep going.
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+// Missing rows are reported like the database would, so that they get the same error code.
+fn not_found<T>(details: String) -> Result<T, error::Error> {
+    Err(sqlx::Error::RowNotFound).context(error::DBError { details })
+}
+
+fn digest(parts: &[String]) -> String {
+    format!("{:x}", Md5::digest(parts.join("/").as_bytes()))
+}
+
+impl State {
+    fn create_or_replace_feature(
+        &mut self,
+        name: String,
+        description: String,
+        tags: Vec<String>,
+        principal: &str,
+    ) -> Feature {
+        let now = Utc::now();
+        match self.features.iter_mut().find(|f| f.name == name) {
+            Some(feature) => {
+                feature.description = description;
+                feature.tags = tags;
+                feature.updated_by = Some(String::from(principal));
+                feature.updated_at = now;
+                feature.clone()
+            }
+            None => {
+                let feature = Feature {
+                    id: Uuid::new_v4(),
+                    name,
+                    description,
+                    tags,
+                    created_by: Some(String::from(principal)),
+                    updated_by: Some(String::from(principal)),
+                    created_at: now,
+                    updated_at: now,
+                };
+                self.features.push(feature.clone());
+                feature
+            }
+        }
+    }
+
+    // Replace the steps of a scenario or a background.
+    fn replace_steps(&mut self, id: Uuid, steps: Vec<gherkin_rust::Step>) {
+        let now = Utc::now();
+        let steps = steps
+            .into_iter()
+            .map(|step| Step {
+                id: Uuid::new_v4(),
+                step_type: StepType::from(step.ty),
+                value: step.value,
+                docstring: step.docstring.unwrap_or_default(),
+                created_at: now,
+                updated_at: now,
+            })
+            .collect();
+        self.steps.insert(id, steps);
+    }
+
+    fn remove_orphan_items(&mut self) {
+        let datasets = &self.datasets;
+        let dataset_items = &self.dataset_items;
+        self.items.retain(|item| {
+            dataset_items.iter().any(|(dataset, id)| {
+                *id == item.id
+                    && datasets
+                        .iter()
+                        .any(|d| d.id == *dataset && d.data_source == item.data_source)
+            })
+        });
+    }
+
+    fn dataset_items(&self, dataset_id: &str) -> Vec<Item> {
+        let data_source = match self.datasets.iter().find(|d| d.id == dataset_id) {
+            Some(dataset) => &dataset.data_source,
+            None => return Vec::new(),
+        };
+        let mut items: Vec<Item> = self
+            .dataset_items
+            .iter()
+            .filter(|(dataset, _)| dataset == dataset_id)
+            .filter_map(|(_, item)| {
+                self.items
+                    .iter()
+                    .find(|i| i.id == *item && i.data_source == *data_source)
+            })
+            .cloned()
+            .collect();
+        items.sort_by(|a, b| a.id.cmp(&b.id));
+        items
+    }
+}
+
+#[async_trait]
+impl FeatureStore for MemoryStore {
+    async fn fetch_all_features(
+        &self,
+        _context: &gql::Context,
+    ) -> Result<Vec<Feature>, error::Error> {
+        Ok(self.state().features.clone())
+    }
+
+    async fn fetch_feature_by_id(
+        &self,
+        id: Uuid,
+        _context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        match self.state().features.iter().find(|f| f.id == id) {
+            Some(feature) => Ok(feature.clone()),
+            None => not_found(format!("Could not retrieve feature '{}'", id)),
+        }
+    }
+
+    async fn create_or_replace_feature(
+        &self,
+        name: String,
+        description: String,
+        tags: Vec<String>,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        Ok(self
+            .state()
+            .create_or_replace_feature(name, description, tags, &context.principal.name))
+    }
+
+    async fn create_or_replace_feature_from_gherkin(
+        &self,
+        feature: gherkin_rust::Feature,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        feature::check_scenario_names(&feature)?;
+
+        let mut state = self.state();
+        let res = state.create_or_replace_feature(
+            feature.name,
+            feature.description.unwrap_or_default(),
+            feature.tags,
+            &context.principal.name,
+        );
+        let now = Utc::now();
+
+        // Scenarios keep their id, as long as they keep their name.
+        let previous = state.scenarios.remove(&res.id).unwrap_or_default();
+        let mut scenarios = Vec::new();
+        for scenario in feature.scenarios {
+            let id = match previous.iter().find(|s| s.name == scenario.name) {
+                Some(previous) => previous.id,
+                None => Uuid::new_v4(),
+            };
+            scenarios.push(Scenario {
+                id,
+                name: scenario.name,
+                tags: scenario.tags,
+                created_at: now,
+                updated_at: now,
+            });
+            state.replace_steps(id, scenario.steps);
+        }
+        for scenario in previous {
+            if !scenarios.iter().any(|s| s.id == scenario.id) {
+                state.steps.remove(&scenario.id);
+            }
+        }
+        state.scenarios.insert(res.id, scenarios);
+
+        match feature.background {
+            Some(background) => {
+                let id = match state.backgrounds.get(&res.id) {
+                    Some(previous) => previous.id,
+                    None => Uuid::new_v4(),
+                };
+                state.backgrounds.insert(
+                    res.id,
+                    Background {
+                        id,
+                        created_at: now,
+                        updated_at: now,
+                    },
+                );
+                state.replace_steps(id, background.steps);
+            }
+            None => {
+                if let Some(previous) = state.backgrounds.remove(&res.id) {
+                    state.steps.remove(&previous.id);
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    async fn delete_feature_by_id(
+        &self,
+        id: Uuid,
+        _context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        let mut state = self.state();
+        let position = match state.features.iter().position(|f| f.id == id) {
+            Some(position) => position,
+            None => return not_found(format!("Could not delete feature '{}'", id)),
+        };
+        let feature = state.features.remove(position);
+        for scenario in state.scenarios.remove(&id).unwrap_or_default() {
+            state.steps.remove(&scenario.id);
+        }
+        if let Some(background) = state.backgrounds.remove(&id) {
+            state.steps.remove(&background.id);
+        }
+        Ok(feature)
+    }
+
+    async fn fetch_scenarios_by_feature_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<Scenario>, error::Error> {
+        Ok(self.state().scenarios.get(id).cloned().unwrap_or_default())
+    }
+
+    async fn fetch_background_by_feature_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Option<Background>, error::Error> {
+        Ok(self.state().backgrounds.get(id).cloned())
+    }
+
+    async fn fetch_steps_by_scenario_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<Step>, error::Error> {
+        Ok(self.state().steps.get(id).cloned().unwrap_or_default())
+    }
+
+    async fn fetch_steps_by_background_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<Step>, error::Error> {
+        Ok(self.state().steps.get(id).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl EnvironmentStore for MemoryStore {
+    async fn fetch_all_environments(
+        &self,
+        _context: &gql::Context,
+    ) -> Result<Vec<Environment>, error::Error> {
+        Ok(self.state().environments.clone())
+    }
+
+    async fn fetch_environment_by_id(
+        &self,
+        id: Uuid,
+        _context: &gql::Context,
+    ) -> Result<Environment, error::Error> {
+        match self.state().environments.iter().find(|e| e.id == id) {
+            Some(environment) => Ok(environment.clone()),
+            None => not_found(format!("Could not retrieve environment '{}'", id)),
+        }
+    }
+
+    async fn fetch_indexes_by_environment_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<Index>, error::Error> {
+        let state = self.state();
+        let ids = state
+            .environment_indexes
+            .get(id)
+            .cloned()
+            .unwrap_or_default();
+        Ok(state
+            .indexes
+            .iter()
+            .filter(|index| ids.contains(&index.id))
+            .cloned()
+            .collect())
+    }
+
+    async fn fetch_background_environment(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Environment, error::Error> {
+        let mut state = self.state();
+        let steps = state.steps.get(id).cloned().unwrap_or_default();
+        let now = Utc::now();
+
+        // Indexes are identified by their type, data source and regions.
+        let mut ids = Vec::new();
+        for step in steps {
+            let (index_type, data_source, regions) =
+                step::extract_index_from_step(step.value.clone()).ok_or_else(|| {
+                    error::Error::UserError {
+                        details: format!("Could not extract an index from '{}'", step.value),
+                    }
+                })?;
+            let mut parts = vec![index_type.clone(), data_source.clone()];
+            parts.extend(regions.iter().cloned());
+            let signature = digest(&parts);
+            let index_id = match state.indexes.iter().find(|i| i.signature == signature) {
+                Some(index) => index.id,
+                None => {
+                    let index = Index {
+                        id: Uuid::new_v4(),
+                        signature,
+                        index_type,
+                        data_source,
+                        regions,
+                        filepath: None,
+                        status: IndexStatus::NotAvailable,
+                        created_at: now,
+                        updated_at: now,
+                    };
+                    let index_id = index.id;
+                    state.indexes.push(index);
+                    index_id
+                }
+            };
+            ids.push(index_id);
+        }
+
+        // The environment is identified by the signatures of its indexes.
+        let mut signatures: Vec<String> = state
+            .indexes
+            .iter()
+            .filter(|index| ids.contains(&index.id))
+            .map(|index| index.signature.clone())
+            .collect();
+        signatures.sort();
+        let signature = digest(&signatures);
+        let environment = match state.environments.iter().find(|e| e.signature == signature) {
+            Some(environment) => environment.clone(),
+            None => {
+                let environment = Environment {
+                    id: Uuid::new_v4(),
+                    signature,
+                    status: IndexStatus::NotAvailable,
+                    created_at: now,
+                    updated_at: now,
+                };
+                state.environments.push(environment.clone());
+                environment
+            }
+        };
+        state.environment_indexes.insert(environment.id, ids);
+        Ok(environment)
+    }
+}
+
+#[async_trait]
+impl DatasetStore for MemoryStore {
+    async fn fetch_datasets(
+        &self,
+        data_source: Option<&str>,
+        _context: &gql::Context,
+    ) -> Result<Vec<Dataset>, error::Error> {
+        let state = self.state();
+        let mut datasets: Vec<Dataset> = state
+            .datasets
+            .iter()
+            .filter(|dataset| data_source.is_none_or(|source| dataset.data_source == source))
+            .map(|dataset| Dataset {
+                items: state.dataset_items(&dataset.id),
+                ..dataset.clone()
+            })
+            .collect();
+        datasets.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(datasets)
+    }
+
+    async fn fetch_dataset(
+        &self,
+        id: &str,
+        _context: &gql::Context,
+    ) -> Result<Option<Dataset>, error::Error> {
+        Ok(self.state().datasets.iter().find(|d| d.id == id).cloned())
+    }
+
+    async fn fetch_dataset_items(
+        &self,
+        dataset_id: &str,
+        _context: &gql::Context,
+    ) -> Result<Vec<Item>, error::Error> {
+        Ok(self.state().dataset_items(dataset_id))
+    }
+
+    async fn fetch_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        _context: &gql::Context,
+    ) -> Result<Option<Item>, error::Error> {
+        Ok(self
+            .state()
+            .dataset_items(dataset_id)
+            .into_iter()
+            .find(|item| item.id == item_id))
+    }
+
+    async fn check_and_insert_dataset(
+        &self,
+        id: &str,
+        data_source: &str,
+        description: &str,
+        _context: &gql::Context,
+    ) -> Result<Dataset, error::Error> {
+        let mut state = self.state();
+        if state.datasets.iter().any(|d| d.id == id) {
+            return Err(error::Error::UserError {
+                details: String::from("duplicate dataset id"),
+            });
+        }
+        let dataset = Dataset {
+            id: String::from(id),
+            data_source: String::from(data_source),
+            description: String::from(description),
+            items: vec![],
+        };
+        state.datasets.push(dataset.clone());
+        Ok(dataset)
+    }
+
+    async fn check_and_insert_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        _context: &gql::Context,
+    ) -> Result<Item, error::Error> {
+        let mut state = self.state();
+        let data_source = match state.datasets.iter().find(|d| d.id == dataset_id) {
+            Some(dataset) => dataset.data_source.clone(),
+            None => {
+                return Err(error::Error::UserError {
+                    details: String::from("Unknown dataset id"),
+                })
+            }
+        };
+        if state
+            .dataset_items(dataset_id)
+            .iter()
+            .any(|i| i.id == item_id)
+        {
+            return Err(error::Error::UserError {
+                details: String::from("already a dataset item with that id"),
+            });
+        }
+        // The item may already belong to another dataset, in which case it is shared.
+        let item = match state
+            .items
+            .iter()
+            .find(|i| i.id == item_id && i.data_source == data_source)
+        {
+            Some(item) => item.clone(),
+            None => {
+                let item = Item::new(data_source, String::from(item_id));
+                state.items.push(item.clone());
+                item
+            }
+        };
+        state
+            .dataset_items
+            .push((String::from(dataset_id), String::from(item_id)));
+        Ok(item)
+    }
+
+    async fn remove_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        _context: &gql::Context,
+    ) -> Result<(), error::Error> {
+        let mut state = self.state();
+        state
+            .dataset_items
+            .retain(|(dataset, item)| !(dataset == dataset_id && item == item_id));
+        state.remove_orphan_items();
+        Ok(())
+    }
+
+    async fn remove_dataset(
+        &self,
+        id: &str,
+        _context: &gql::Context,
+    ) -> Result<Dataset, error::Error> {
+        let mut state = self.state();
+        let position = match state.datasets.iter().position(|d| d.id == id) {
+            Some(position) => position,
+            None => return not_found(format!("Could not remove dataset {}", id)),
+        };
+        let dataset = state.datasets.remove(position);
+        state.dataset_items.retain(|(dataset, _)| dataset != id);
+        state.remove_orphan_items();
+        Ok(dataset)
+    }
+}
+
+#[async_trait]
+impl RunStore for MemoryStore {
+    async fn create_run(
+        &self,
+        tags: Vec<String>,
+        bragi_url: &str,
+        _context: &gql::Context,
+    ) -> Result<Run, error::Error> {
+        let run = Run {
+            id: Uuid::new_v4(),
+            tags,
+            bragi_url: String::from(bragi_url),
+            status: RunStatus::Running,
+            passed: 0,
+            failed: 0,
+            skipped: 0,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        self.state().runs.push(run.clone());
+        Ok(run)
+    }
+
+    async fn finish_run(
+        &self,
+        id: &Uuid,
+        status: RunStatus,
+        _context: &gql::Context,
+    ) -> Result<Run, error::Error> {
+        let mut state = self.state();
+        let count = |statuses: &[ResultStatus]| {
+            state
+                .scenario_results
+                .iter()
+                .filter(|result| result.run == *id && statuses.contains(&result.status))
+                .count() as i32
+        };
+        let passed = count(&[ResultStatus::Passed]);
+        let failed = count(&[ResultStatus::Failed, ResultStatus::Error]);
+        let skipped = count(&[ResultStatus::Skipped, ResultStatus::Undefined]);
+        match state.runs.iter_mut().find(|run| run.id == *id) {
+            Some(run) => {
+                run.status = status;
+                run.passed = passed;
+                run.failed = failed;
+                run.skipped = skipped;
+                run.finished_at = Some(Utc::now());
+                Ok(run.clone())
+            }
+            None => not_found(format!("Could not finish run '{}'", id)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn create_scenario_result(
+        &self,
+        run: &Uuid,
+        scenario: &Uuid,
+        feature_name: &str,
+        scenario_name: &str,
+        tags: Vec<String>,
+        status: ResultStatus,
+        duration: f64,
+        environment: Option<&str>,
+        geocoder_version: Option<&str>,
+        attempts: i32,
+        quarantined: bool,
+        _context: &gql::Context,
+    ) -> Result<ScenarioResult, error::Error> {
+        let result = ScenarioResult {
+            id: Uuid::new_v4(),
+            run: *run,
+            scenario: Some(*scenario),
+            feature_name: String::from(feature_name),
+            scenario_name: String::from(scenario_name),
+            tags,
+            status,
+            duration,
+            created_at: Utc::now(),
+            environment: environment.map(String::from),
+            geocoder_version: geocoder_version.map(String::from),
+            attempts,
+            quarantined,
+        };
+        self.state().scenario_results.push(result.clone());
+        Ok(result)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn create_step_result(
+        &self,
+        scenario_result: &Uuid,
+        position: i32,
+        step_type: StepType,
+        value: &str,
+        status: ResultStatus,
+        message: Option<String>,
+        duration: f64,
+        score: Option<f64>,
+        request_url: Option<String>,
+        rank: Option<i32>,
+        results: Option<Vec<String>>,
+        _context: &gql::Context,
+    ) -> Result<StepResult, error::Error> {
+        let result = StepResult {
+            id: Uuid::new_v4(),
+            scenario_result: *scenario_result,
+            position,
+            step_type,
+            value: String::from(value),
+            status,
+            message,
+            duration,
+            created_at: Utc::now(),
+            score,
+            request_url,
+            rank,
+            results,
+        };
+        self.state().step_results.push(result.clone());
+        Ok(result)
+    }
+
+    async fn fetch_all_runs(&self, _context: &gql::Context) -> Result<Vec<Run>, error::Error> {
+        Ok(self.state().runs.iter().rev().cloned().collect())
+    }
+
+    async fn fetch_run_by_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Run, error::Error> {
+        match self.state().runs.iter().find(|run| run.id == *id) {
+            Some(run) => Ok(run.clone()),
+            None => not_found(format!("Could not retrieve run '{}'", id)),
+        }
+    }
+
+    async fn fetch_last_run(&self, _context: &gql::Context) -> Result<Option<Run>, error::Error> {
+        Ok(self.state().runs.last().cloned())
+    }
+
+    async fn fetch_scenario_results_by_run_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<ScenarioResult>, error::Error> {
+        Ok(self
+            .state()
+            .scenario_results
+            .iter()
+            .filter(|result| result.run == *id)
+            .cloned()
+            .collect())
+    }
+
+    async fn fetch_step_results_by_scenario_result_id(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<StepResult>, error::Error> {
+        let mut results: Vec<StepResult> = self
+            .state()
+            .step_results
+            .iter()
+            .filter(|result| result.scenario_result == *id)
+            .cloned()
+            .collect();
+        results.sort_by_key(|result| result.position);
+        Ok(results)
+    }
+
+    async fn create_quality_metrics(
+        &self,
+        metrics: Vec<QualityMetrics>,
+        _context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error> {
+        self.state().quality_metrics.extend(metrics.iter().cloned());
+        Ok(metrics)
+    }
+
+    async fn fetch_quality_metrics(
+        &self,
+        run: &Uuid,
+        group_by: QualityGroup,
+        _context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error> {
+        let mut metrics: Vec<QualityMetrics> = self
+            .state()
+            .quality_metrics
+            .iter()
+            .filter(|m| m.run == *run && m.group_by == group_by)
+            .cloned()
+            .collect();
+        metrics.sort_by(|a, b| a.group_name.cmp(&b.group_name));
+        Ok(metrics)
+    }
+
+    async fn fetch_quality_trend(
+        &self,
+        group_by: QualityGroup,
+        group_name: &str,
+        limit: i32,
+        _context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error> {
+        let state = self.state();
+        // Runs are kept in the order they were started.
+        let mut metrics: Vec<QualityMetrics> = state
+            .runs
+            .iter()
+            .rev()
+            .filter_map(|run| {
+                state.quality_metrics.iter().find(|m| {
+                    m.run == run.id && m.group_by == group_by && m.group_name == group_name
+                })
+            })
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+        metrics.reverse();
+        Ok(metrics)
+    }
+
+    async fn create_latency_metrics(
+        &self,
+        metrics: Vec<LatencyMetrics>,
+        _context: &gql::Context,
+    ) -> Result<Vec<LatencyMetrics>, error::Error> {
+        self.state().latency_metrics.extend(metrics.iter().cloned());
+        Ok(metrics)
+    }
+
+    async fn fetch_latency_metrics(
+        &self,
+        run: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<LatencyMetrics>, error::Error> {
+        let mut metrics: Vec<LatencyMetrics> = self
+            .state()
+            .latency_metrics
+            .iter()
+            .filter(|m| m.run == *run)
+            .cloned()
+            .collect();
+        metrics.sort_by(|a, b| {
+            (&a.feature_name, &a.scenario_name).cmp(&(&b.feature_name, &b.scenario_name))
+        });
+        Ok(metrics)
+    }
+
+    async fn fetch_flaky_scenarios(
+        &self,
+        _context: &gql::Context,
+    ) -> Result<Vec<FlakyScenario>, error::Error> {
+        Ok(flaky::detect_flaky(&self.state().scenario_results))
+    }
+
+    async fn fetch_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        _context: &gql::Context,
+    ) -> Result<Option<Snapshot>, error::Error> {
+        Ok(self
+            .state()
+            .snapshots
+            .iter()
+            .find(|s| {
+                s.feature_name == feature_name
+                    && s.scenario_name == scenario_name
+                    && s.position == position
+            })
+            .cloned())
+    }
+
+    async fn save_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        results: Vec<SnapshotPlace>,
+        _context: &gql::Context,
+    ) -> Result<Snapshot, error::Error> {
+        let mut state = self.state();
+        let now = Utc::now();
+        match state.snapshots.iter_mut().find(|s| {
+            s.feature_name == feature_name
+                && s.scenario_name == scenario_name
+                && s.position == position
+        }) {
+            Some(snapshot) => {
+                snapshot.results = results;
+                snapshot.pending = None;
+                snapshot.pending_step = None;
+                snapshot.updated_at = now;
+                Ok(snapshot.clone())
+            }
+            None => {
+                let snapshot = Snapshot {
+                    feature_name: String::from(feature_name),
+                    scenario_name: String::from(scenario_name),
+                    position,
+                    results,
+                    pending: None,
+                    pending_step: None,
+                    created_at: now,
+                    updated_at: now,
+                };
+                state.snapshots.push(snapshot.clone());
+                Ok(snapshot)
+            }
+        }
+    }
+
+    async fn propose_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        results: Vec<SnapshotPlace>,
+        step: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Snapshot, error::Error> {
+        let mut state = self.state();
+        match state.snapshots.iter_mut().find(|s| {
+            s.feature_name == feature_name
+                && s.scenario_name == scenario_name
+                && s.position == position
+        }) {
+            Some(snapshot) => {
+                snapshot.pending = Some(results);
+                snapshot.pending_step = Some(*step);
+                snapshot.updated_at = Utc::now();
+                Ok(snapshot.clone())
+            }
+            None => not_found(format!(
+                "Could not propose snapshot of '{}' / '{}'",
+                feature_name, scenario_name
+            )),
+        }
+    }
+
+    async fn accept_snapshot(
+        &self,
+        step: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Snapshot, error::Error> {
+        let mut state = self.state();
+        match state
+            .snapshots
+            .iter_mut()
+            .find(|s| s.pending_step == Some(*step))
+        {
+            Some(snapshot) => {
+                snapshot.results = snapshot.pending.take().unwrap_or_default();
+                snapshot.pending_step = None;
+                snapshot.updated_at = Utc::now();
+                Ok(snapshot.clone())
+            }
+            None => not_found(format!(
+                "No snapshot is waiting for the results of step '{}'",
+                step
+            )),
+        }
+    }
+}
+
+// The features repository can only be synchronised with Postgres, so there is nothing to report.
+#[async_trait]
+impl RepositoryStore for MemoryStore {
+    async fn fetch_feature_revisions(
+        &self,
+        _id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<Vec<FeatureRevision>, error::Error> {
+        Ok(Vec::new())
+    }
+
+    async fn fetch_last_sync(
+        &self,
+        _context: &gql::Context,
+    ) -> Result<Option<RepositorySync>, error::Error> {
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl TokenStore for MemoryStore {
+    async fn create_api_token(
+        &self,
+        name: &str,
+        role: Role,
+        _context: &gql::Context,
+    ) -> Result<NewApiToken, error::Error> {
+        let mut state = self.state();
+        if state.api_tokens.iter().any(|(t, _)| t.name == name) {
+            return Err(error::Error::UserError {
+                details: format!("already an API token named '{}'", name),
+            });
+        }
+        let token = format!(
+            "mjr_{}{}",
+            Uuid::new_v4().to_simple(),
+            Uuid::new_v4().to_simple()
+        );
+        let api_token = ApiToken {
+            id: Uuid::new_v4(),
+            name: String::from(name),
+            role,
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        let new_token = NewApiToken {
+            id: api_token.id,
+            name: api_token.name.clone(),
+            role,
+            token: token.clone(),
+            created_at: api_token.created_at,
+        };
+        state.api_tokens.push((api_token, digest(&[token])));
+        Ok(new_token)
+    }
+
+    async fn revoke_api_token(
+        &self,
+        id: &Uuid,
+        _context: &gql::Context,
+    ) -> Result<ApiToken, error::Error> {
+        match self
+            .state()
+            .api_tokens
+            .iter_mut()
+            .find(|(t, _)| t.id == *id)
+        {
+            Some((token, _)) => {
+                token.revoked_at = token.revoked_at.or_else(|| Some(Utc::now()));
+                Ok(token.clone())
+            }
+            None => not_found(format!("Could not find API token '{}'", id)),
+        }
+    }
+
+    async fn fetch_api_tokens(
+        &self,
+        _context: &gql::Context,
+    ) -> Result<Vec<ApiToken>, error::Error> {
+        let mut tokens: Vec<ApiToken> = self
+            .state()
+            .api_tokens
+            .iter()
+            .map(|(t, _)| t.clone())
+            .collect();
+        tokens.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(tokens)
+    }
+
+    async fn authenticate_api_token(
+        &self,
+        token: &str,
+        _context: &gql::Context,
+    ) -> Result<Option<ApiToken>, error::Error> {
+        let hash = digest(&[String::from(token)]);
+        Ok(self
+            .state()
+            .api_tokens
+            .iter_mut()
+            .find(|(t, h)| *h == hash && t.revoked_at.is_none())
+            .map(|(token, _)| {
+                token.last_used_at = Some(Utc::now());
+                token.clone()
+            }))
+    }
+}
+
+#[async_trait]
+impl AuditStore for MemoryStore {
+    async fn add_audit_entry(
+        &self,
+        action: &str,
+        target_type: &str,
+        target_id: &str,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+        context: &gql::Context,
+    ) -> Result<AuditEntry, error::Error> {
+        let entry = AuditEntry {
+            id: Uuid::new_v4(),
+            actor: context.principal.name.clone(),
+            action: String::from(action),
+            target_type: String::from(target_type),
+            target_id: Some(String::from(target_id)),
+            before: before.map(|v| v.to_string()),
+            after: after.map(|v| v.to_string()),
+            created_at: Utc::now(),
+        };
+        self.state().audit_log.push(entry.clone());
+        Ok(entry)
+    }
+
+    async fn fetch_audit_log(
+        &self,
+        filter: AuditFilter,
+        _context: &gql::Context,
+    ) -> Result<Vec<AuditEntry>, error::Error> {
+        let limit = filter
+            .limit
+            .unwrap_or(audit::DEFAULT_LIMIT)
+            .clamp(0, audit::MAX_LIMIT) as usize;
+        let matches = |criterion: &Option<String>, value: Option<&String>| {
+            criterion.is_none() || criterion.as_ref() == value
+        };
+        Ok(self
+            .state()
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|e| matches(&filter.actor, Some(&e.actor)))
+            .filter(|e| matches(&filter.action, Some(&e.action)))
+            .filter(|e| matches(&filter.target_type, Some(&e.target_type)))
+            .filter(|e| matches(&filter.target_id, e.target_id.as_ref()))
+            .filter(|e| filter.since.is_none() || filter.since <= Some(e.created_at))
+            .filter(|e| filter.until.is_none() || Some(e.created_at) < filter.until)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
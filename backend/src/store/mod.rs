@@ -0,0 +1,468 @@
+use crate::{
+    auth::{
+        token::{ApiToken, NewApiToken},
+        Role,
+    },
+    error, gql,
+    model::{
+        audit::{AuditEntry, AuditFilter},
+        environments::{
+            dataset::{Dataset, Item},
+            environment::Environment,
+            index::Index,
+        },
+        features::{
+            background::Background,
+            feature::Feature,
+            repository::{FeatureRevision, RepositorySync},
+            scenario::Scenario,
+            step::{Step, StepType},
+        },
+        runs::{
+            flaky::FlakyScenario,
+            latency::LatencyMetrics,
+            quality::{QualityGroup, QualityMetrics},
+            run::{Run, ScenarioResult, StepResult},
+            snapshot::{Snapshot, SnapshotPlace},
+            ResultStatus, RunStatus,
+        },
+    },
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub mod memory;
+pub mod postgres;
+
+pub use self::{memory::MemoryStore, postgres::PostgresStore};
+
+// The stores take the context, like the model functions, for logging and for the principal
+// making the request. The Postgres store also uses its pool.
+
+/// Features, along with their background, scenarios and steps.
+#[async_trait]
+pub trait FeatureStore: Send + Sync {
+    async fn fetch_all_features(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Vec<Feature>, error::Error>;
+
+    async fn fetch_feature_by_id(
+        &self,
+        id: Uuid,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error>;
+
+    /// Create the feature, or replace the one with the same name.
+    async fn create_or_replace_feature(
+        &self,
+        name: String,
+        description: String,
+        tags: Vec<String>,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error>;
+
+    /// Create the feature, or replace the one with the same name, along with its background,
+    /// scenarios and steps.
+    async fn create_or_replace_feature_from_gherkin(
+        &self,
+        feature: gherkin_rust::Feature,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error>;
+
+    async fn delete_feature_by_id(
+        &self,
+        id: Uuid,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error>;
+
+    async fn fetch_scenarios_by_feature_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Scenario>, error::Error>;
+
+    async fn fetch_background_by_feature_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Option<Background>, error::Error>;
+
+    async fn fetch_steps_by_scenario_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Step>, error::Error>;
+
+    async fn fetch_steps_by_background_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Step>, error::Error>;
+}
+
+/// Environments, and the indexes they are made of.
+#[async_trait]
+pub trait EnvironmentStore: Send + Sync {
+    async fn fetch_all_environments(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Vec<Environment>, error::Error>;
+
+    async fn fetch_environment_by_id(
+        &self,
+        id: Uuid,
+        context: &gql::Context,
+    ) -> Result<Environment, error::Error>;
+
+    async fn fetch_indexes_by_environment_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Index>, error::Error>;
+
+    /// Return the environment described by the steps of the background, creating it, along with
+    /// its indexes, if needed.
+    async fn fetch_background_environment(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Environment, error::Error>;
+}
+
+/// Datasets of any data source, and the items (files) they are made of.
+#[async_trait]
+pub trait DatasetStore: Send + Sync {
+    /// The datasets of the data source if one is given, or of all of them, with their items.
+    async fn fetch_datasets(
+        &self,
+        data_source: Option<&str>,
+        context: &gql::Context,
+    ) -> Result<Vec<Dataset>, error::Error>;
+
+    async fn fetch_dataset(
+        &self,
+        id: &str,
+        context: &gql::Context,
+    ) -> Result<Option<Dataset>, error::Error>;
+
+    async fn fetch_dataset_items(
+        &self,
+        dataset_id: &str,
+        context: &gql::Context,
+    ) -> Result<Vec<Item>, error::Error>;
+
+    async fn fetch_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        context: &gql::Context,
+    ) -> Result<Option<Item>, error::Error>;
+
+    /// Fails if there is already a dataset with that id.
+    async fn check_and_insert_dataset(
+        &self,
+        id: &str,
+        data_source: &str,
+        description: &str,
+        context: &gql::Context,
+    ) -> Result<Dataset, error::Error>;
+
+    /// Fails if there is no dataset with that id, or if it already has that item. Items are
+    /// shared by the datasets of their data source.
+    async fn check_and_insert_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        context: &gql::Context,
+    ) -> Result<Item, error::Error>;
+
+    /// Items which no longer belong to any dataset are removed too.
+    async fn remove_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        context: &gql::Context,
+    ) -> Result<(), error::Error>;
+
+    /// Items which no longer belong to any dataset are removed too.
+    async fn remove_dataset(
+        &self,
+        id: &str,
+        context: &gql::Context,
+    ) -> Result<Dataset, error::Error>;
+}
+
+/// Runs, and the results of their scenarios and steps.
+#[async_trait]
+pub trait RunStore: Send + Sync {
+    async fn create_run(
+        &self,
+        tags: Vec<String>,
+        bragi_url: &str,
+        context: &gql::Context,
+    ) -> Result<Run, error::Error>;
+
+    /// Record the final status of the run, and count its scenarios by status.
+    async fn finish_run(
+        &self,
+        id: &Uuid,
+        status: RunStatus,
+        context: &gql::Context,
+    ) -> Result<Run, error::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn create_scenario_result(
+        &self,
+        run: &Uuid,
+        scenario: &Uuid,
+        feature_name: &str,
+        scenario_name: &str,
+        tags: Vec<String>,
+        status: ResultStatus,
+        duration: f64,
+        environment: Option<&str>,
+        geocoder_version: Option<&str>,
+        attempts: i32,
+        quarantined: bool,
+        context: &gql::Context,
+    ) -> Result<ScenarioResult, error::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn create_step_result(
+        &self,
+        scenario_result: &Uuid,
+        position: i32,
+        step_type: StepType,
+        value: &str,
+        status: ResultStatus,
+        message: Option<String>,
+        duration: f64,
+        score: Option<f64>,
+        request_url: Option<String>,
+        rank: Option<i32>,
+        results: Option<Vec<String>>,
+        context: &gql::Context,
+    ) -> Result<StepResult, error::Error>;
+
+    /// Most recent first.
+    async fn fetch_all_runs(&self, context: &gql::Context) -> Result<Vec<Run>, error::Error>;
+
+    async fn fetch_run_by_id(&self, id: &Uuid, context: &gql::Context)
+        -> Result<Run, error::Error>;
+
+    async fn fetch_last_run(&self, context: &gql::Context) -> Result<Option<Run>, error::Error>;
+
+    async fn fetch_scenario_results_by_run_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<ScenarioResult>, error::Error>;
+
+    async fn fetch_step_results_by_scenario_result_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<StepResult>, error::Error>;
+
+    async fn create_quality_metrics(
+        &self,
+        metrics: Vec<QualityMetrics>,
+        context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error>;
+
+    /// The metrics of the run, by group name.
+    async fn fetch_quality_metrics(
+        &self,
+        run: &Uuid,
+        group_by: QualityGroup,
+        context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error>;
+
+    /// The metrics of the group in the last runs which have some, oldest first.
+    async fn fetch_quality_trend(
+        &self,
+        group_by: QualityGroup,
+        group_name: &str,
+        limit: i32,
+        context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error>;
+
+    async fn create_latency_metrics(
+        &self,
+        metrics: Vec<LatencyMetrics>,
+        context: &gql::Context,
+    ) -> Result<Vec<LatencyMetrics>, error::Error>;
+
+    /// The metrics of the scenarios of the run, by feature and scenario name.
+    async fn fetch_latency_metrics(
+        &self,
+        run: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<LatencyMetrics>, error::Error>;
+
+    /// The scenarios whose results differ against the same environment and version of bragi,
+    /// by feature and scenario name.
+    async fn fetch_flaky_scenarios(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Vec<FlakyScenario>, error::Error>;
+
+    async fn fetch_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        context: &gql::Context,
+    ) -> Result<Option<Snapshot>, error::Error>;
+
+    /// Replaces the snapshot of the step, and what was pending.
+    async fn save_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        results: Vec<SnapshotPlace>,
+        context: &gql::Context,
+    ) -> Result<Snapshot, error::Error>;
+
+    /// Keeps the results which did not match the snapshot, until they are accepted.
+    async fn propose_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        results: Vec<SnapshotPlace>,
+        step: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Snapshot, error::Error>;
+
+    /// The pending results found by the step result become the snapshot.
+    async fn accept_snapshot(
+        &self,
+        step: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Snapshot, error::Error>;
+}
+
+/// What was recorded of the synchronisations with the features repository.
+#[async_trait]
+pub trait RepositoryStore: Send + Sync {
+    /// Most recent first.
+    async fn fetch_feature_revisions(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<FeatureRevision>, error::Error>;
+
+    /// The most recent synchronisation, whether it is finished or not.
+    async fn fetch_last_sync(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Option<RepositorySync>, error::Error>;
+}
+
+/// API tokens. Only a hash of the token is kept, the token itself is given once, at creation.
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    /// Fails if there is already a token with that name.
+    async fn create_api_token(
+        &self,
+        name: &str,
+        role: Role,
+        context: &gql::Context,
+    ) -> Result<NewApiToken, error::Error>;
+
+    /// Revoking a token twice keeps the time it was first revoked.
+    async fn revoke_api_token(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<ApiToken, error::Error>;
+
+    /// By name.
+    async fn fetch_api_tokens(&self, context: &gql::Context)
+        -> Result<Vec<ApiToken>, error::Error>;
+
+    /// The token matching the given (clear) token, if it has not been revoked. Records that it
+    /// was used.
+    async fn authenticate_api_token(
+        &self,
+        token: &str,
+        context: &gql::Context,
+    ) -> Result<Option<ApiToken>, error::Error>;
+}
+
+/// The audit log of the mutations.
+#[async_trait]
+pub trait AuditStore: Send + Sync {
+    /// Add an entry made by the principal of the context.
+    async fn add_audit_entry(
+        &self,
+        action: &str,
+        target_type: &str,
+        target_id: &str,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+        context: &gql::Context,
+    ) -> Result<AuditEntry, error::Error>;
+
+    /// The entries matching the filter, most recent first.
+    async fn fetch_audit_log(
+        &self,
+        filter: AuditFilter,
+        context: &gql::Context,
+    ) -> Result<Vec<AuditEntry>, error::Error>;
+}
+
+/// Where the GraphQL layer and the runner find what they need. Synchronising the features
+/// repository, and the maintenance of the server (migrations, recovery, pruning the audit log)
+/// still need Postgres.
+#[derive(Clone)]
+pub struct Store {
+    pub features: Arc<dyn FeatureStore>,
+    pub environments: Arc<dyn EnvironmentStore>,
+    pub datasets: Arc<dyn DatasetStore>,
+    pub runs: Arc<dyn RunStore>,
+    pub repository: Arc<dyn RepositoryStore>,
+    pub tokens: Arc<dyn TokenStore>,
+    pub audit: Arc<dyn AuditStore>,
+}
+
+impl Store {
+    /// Everything is stored in the database of the context.
+    pub fn postgres() -> Self {
+        let store = Arc::new(PostgresStore);
+        Store {
+            features: store.clone(),
+            environments: store.clone(),
+            datasets: store.clone(),
+            runs: store.clone(),
+            repository: store.clone(),
+            tokens: store.clone(),
+            audit: store,
+        }
+    }
+
+    /// Everything is kept in memory, and lost once the store is dropped. This is meant for tests
+    /// which should not depend on a database.
+    pub fn memory() -> Self {
+        let store = Arc::new(MemoryStore::default());
+        Store {
+            features: store.clone(),
+            environments: store.clone(),
+            datasets: store.clone(),
+            runs: store.clone(),
+            repository: store.clone(),
+            tokens: store.clone(),
+            audit: store,
+        }
+    }
+}
+
+impl std::fmt::Debug for Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Store").finish()
+    }
+}
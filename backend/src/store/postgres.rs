@@ -0,0 +1,515 @@
+use super::{
+    AuditStore, DatasetStore, EnvironmentStore, FeatureStore, RepositoryStore, RunStore, TokenStore,
+};
+use crate::{
+    auth::{
+        token::{self, ApiToken, NewApiToken},
+        Role,
+    },
+    error, gql,
+    model::{
+        audit::{self, AuditEntry, AuditFilter},
+        environments::{
+            dataset::{self, Dataset, Item},
+            environment::{self, Environment},
+            index::{self, Index},
+        },
+        features::{
+            background::{self, Background},
+            feature::{self, Feature},
+            repository::{self, FeatureRevision, RepositorySync},
+            scenario::{self, Scenario},
+            step::{self, Step, StepType},
+        },
+        runs::{
+            flaky::{self, FlakyScenario},
+            latency::{self, LatencyMetrics},
+            quality::{self, QualityGroup, QualityMetrics},
+            run::{self, Run, ScenarioResult, StepResult},
+            snapshot::{self, Snapshot, SnapshotPlace},
+            ResultStatus, RunStatus,
+        },
+    },
+};
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// Stores everything in the database of the context, using the model functions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostgresStore;
+
+#[async_trait]
+impl FeatureStore for PostgresStore {
+    async fn fetch_all_features(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Vec<Feature>, error::Error> {
+        feature::fetch_all_features(context).await
+    }
+
+    async fn fetch_feature_by_id(
+        &self,
+        id: Uuid,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        feature::fetch_feature_by_id(id, context).await
+    }
+
+    async fn create_or_replace_feature(
+        &self,
+        name: String,
+        description: String,
+        tags: Vec<String>,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        feature::create_or_replace_feature(name, description, tags, context).await
+    }
+
+    async fn create_or_replace_feature_from_gherkin(
+        &self,
+        feature: gherkin_rust::Feature,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        feature::create_or_replace_feature_from_gherkin(feature, context).await
+    }
+
+    async fn delete_feature_by_id(
+        &self,
+        id: Uuid,
+        context: &gql::Context,
+    ) -> Result<Feature, error::Error> {
+        feature::delete_feature_by_id(id, context).await
+    }
+
+    async fn fetch_scenarios_by_feature_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Scenario>, error::Error> {
+        scenario::fetch_scenarios_by_feature_id(id, context).await
+    }
+
+    async fn fetch_background_by_feature_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Option<Background>, error::Error> {
+        background::fetch_background_by_feature_id(id, context).await
+    }
+
+    async fn fetch_steps_by_scenario_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Step>, error::Error> {
+        step::fetch_steps_by_scenario_id(id, context).await
+    }
+
+    async fn fetch_steps_by_background_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Step>, error::Error> {
+        step::fetch_steps_by_background_id(id, context).await
+    }
+}
+
+#[async_trait]
+impl EnvironmentStore for PostgresStore {
+    async fn fetch_all_environments(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Vec<Environment>, error::Error> {
+        environment::fetch_all_environments(context).await
+    }
+
+    async fn fetch_environment_by_id(
+        &self,
+        id: Uuid,
+        context: &gql::Context,
+    ) -> Result<Environment, error::Error> {
+        environment::fetch_environment_by_id(id, context).await
+    }
+
+    async fn fetch_indexes_by_environment_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<Index>, error::Error> {
+        index::fetch_indexes_by_environment_id(id, context).await
+    }
+
+    async fn fetch_background_environment(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Environment, error::Error> {
+        background::fetch_background_environment(id, context).await
+    }
+}
+
+#[async_trait]
+impl DatasetStore for PostgresStore {
+    async fn fetch_datasets(
+        &self,
+        data_source: Option<&str>,
+        context: &gql::Context,
+    ) -> Result<Vec<Dataset>, error::Error> {
+        dataset::fetch_datasets(data_source, context).await
+    }
+
+    async fn fetch_dataset(
+        &self,
+        id: &str,
+        context: &gql::Context,
+    ) -> Result<Option<Dataset>, error::Error> {
+        dataset::fetch_dataset(id, context).await
+    }
+
+    async fn fetch_dataset_items(
+        &self,
+        dataset_id: &str,
+        context: &gql::Context,
+    ) -> Result<Vec<Item>, error::Error> {
+        dataset::fetch_dataset_items(dataset_id, context).await
+    }
+
+    async fn fetch_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        context: &gql::Context,
+    ) -> Result<Option<Item>, error::Error> {
+        dataset::fetch_dataset_item(dataset_id, item_id, context).await
+    }
+
+    async fn check_and_insert_dataset(
+        &self,
+        id: &str,
+        data_source: &str,
+        description: &str,
+        context: &gql::Context,
+    ) -> Result<Dataset, error::Error> {
+        dataset::check_and_insert_dataset(id, data_source, description, context).await
+    }
+
+    async fn check_and_insert_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        context: &gql::Context,
+    ) -> Result<Item, error::Error> {
+        dataset::check_and_insert_dataset_item(dataset_id, item_id, context).await
+    }
+
+    async fn remove_dataset_item(
+        &self,
+        dataset_id: &str,
+        item_id: &str,
+        context: &gql::Context,
+    ) -> Result<(), error::Error> {
+        dataset::remove_dataset_item(dataset_id, item_id, context).await
+    }
+
+    async fn remove_dataset(
+        &self,
+        id: &str,
+        context: &gql::Context,
+    ) -> Result<Dataset, error::Error> {
+        dataset::remove_dataset(id, context).await
+    }
+}
+
+#[async_trait]
+impl RunStore for PostgresStore {
+    async fn create_run(
+        &self,
+        tags: Vec<String>,
+        bragi_url: &str,
+        context: &gql::Context,
+    ) -> Result<Run, error::Error> {
+        run::create_run(tags, bragi_url, context).await
+    }
+
+    async fn finish_run(
+        &self,
+        id: &Uuid,
+        status: RunStatus,
+        context: &gql::Context,
+    ) -> Result<Run, error::Error> {
+        run::finish_run(id, status, context).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn create_scenario_result(
+        &self,
+        run: &Uuid,
+        scenario: &Uuid,
+        feature_name: &str,
+        scenario_name: &str,
+        tags: Vec<String>,
+        status: ResultStatus,
+        duration: f64,
+        environment: Option<&str>,
+        geocoder_version: Option<&str>,
+        attempts: i32,
+        quarantined: bool,
+        context: &gql::Context,
+    ) -> Result<ScenarioResult, error::Error> {
+        run::create_scenario_result(
+            run,
+            scenario,
+            feature_name,
+            scenario_name,
+            tags,
+            status,
+            duration,
+            environment,
+            geocoder_version,
+            attempts,
+            quarantined,
+            context,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn create_step_result(
+        &self,
+        scenario_result: &Uuid,
+        position: i32,
+        step_type: StepType,
+        value: &str,
+        status: ResultStatus,
+        message: Option<String>,
+        duration: f64,
+        score: Option<f64>,
+        request_url: Option<String>,
+        rank: Option<i32>,
+        results: Option<Vec<String>>,
+        context: &gql::Context,
+    ) -> Result<StepResult, error::Error> {
+        run::create_step_result(
+            scenario_result,
+            position,
+            step_type,
+            value,
+            status,
+            message,
+            duration,
+            score,
+            request_url,
+            rank,
+            results,
+            context,
+        )
+        .await
+    }
+
+    async fn fetch_all_runs(&self, context: &gql::Context) -> Result<Vec<Run>, error::Error> {
+        run::fetch_all_runs(context).await
+    }
+
+    async fn fetch_run_by_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Run, error::Error> {
+        run::fetch_run_by_id(id, context).await
+    }
+
+    async fn fetch_last_run(&self, context: &gql::Context) -> Result<Option<Run>, error::Error> {
+        run::fetch_last_run(context).await
+    }
+
+    async fn fetch_scenario_results_by_run_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<ScenarioResult>, error::Error> {
+        run::fetch_scenario_results_by_run_id(id, context).await
+    }
+
+    async fn fetch_step_results_by_scenario_result_id(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<StepResult>, error::Error> {
+        run::fetch_step_results_by_scenario_result_id(id, context).await
+    }
+
+    async fn create_quality_metrics(
+        &self,
+        metrics: Vec<QualityMetrics>,
+        context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error> {
+        quality::create_quality_metrics(metrics, context).await
+    }
+
+    async fn fetch_quality_metrics(
+        &self,
+        run: &Uuid,
+        group_by: QualityGroup,
+        context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error> {
+        quality::fetch_quality_metrics(run, group_by, context).await
+    }
+
+    async fn fetch_quality_trend(
+        &self,
+        group_by: QualityGroup,
+        group_name: &str,
+        limit: i32,
+        context: &gql::Context,
+    ) -> Result<Vec<QualityMetrics>, error::Error> {
+        quality::fetch_quality_trend(group_by, group_name, limit, context).await
+    }
+
+    async fn create_latency_metrics(
+        &self,
+        metrics: Vec<LatencyMetrics>,
+        context: &gql::Context,
+    ) -> Result<Vec<LatencyMetrics>, error::Error> {
+        latency::create_latency_metrics(metrics, context).await
+    }
+
+    async fn fetch_latency_metrics(
+        &self,
+        run: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<LatencyMetrics>, error::Error> {
+        latency::fetch_latency_metrics(run, context).await
+    }
+
+    async fn fetch_flaky_scenarios(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Vec<FlakyScenario>, error::Error> {
+        flaky::fetch_flaky_scenarios(context).await
+    }
+
+    async fn fetch_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        context: &gql::Context,
+    ) -> Result<Option<Snapshot>, error::Error> {
+        snapshot::fetch_snapshot(feature_name, scenario_name, position, context).await
+    }
+
+    async fn save_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        results: Vec<SnapshotPlace>,
+        context: &gql::Context,
+    ) -> Result<Snapshot, error::Error> {
+        snapshot::save_snapshot(feature_name, scenario_name, position, results, context).await
+    }
+
+    async fn propose_snapshot(
+        &self,
+        feature_name: &str,
+        scenario_name: &str,
+        position: i32,
+        results: Vec<SnapshotPlace>,
+        step: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Snapshot, error::Error> {
+        snapshot::propose_snapshot(
+            feature_name,
+            scenario_name,
+            position,
+            results,
+            step,
+            context,
+        )
+        .await
+    }
+
+    async fn accept_snapshot(
+        &self,
+        step: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Snapshot, error::Error> {
+        snapshot::accept_snapshot(step, context).await
+    }
+}
+
+#[async_trait]
+impl RepositoryStore for PostgresStore {
+    async fn fetch_feature_revisions(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<Vec<FeatureRevision>, error::Error> {
+        repository::fetch_feature_revisions(id, context).await
+    }
+
+    async fn fetch_last_sync(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Option<RepositorySync>, error::Error> {
+        repository::fetch_last_sync(context).await
+    }
+}
+
+#[async_trait]
+impl TokenStore for PostgresStore {
+    async fn create_api_token(
+        &self,
+        name: &str,
+        role: Role,
+        context: &gql::Context,
+    ) -> Result<NewApiToken, error::Error> {
+        token::create_api_token(name, role, context).await
+    }
+
+    async fn revoke_api_token(
+        &self,
+        id: &Uuid,
+        context: &gql::Context,
+    ) -> Result<ApiToken, error::Error> {
+        token::revoke_api_token(id, context).await
+    }
+
+    async fn fetch_api_tokens(
+        &self,
+        context: &gql::Context,
+    ) -> Result<Vec<ApiToken>, error::Error> {
+        token::fetch_api_tokens(context).await
+    }
+
+    async fn authenticate_api_token(
+        &self,
+        token: &str,
+        context: &gql::Context,
+    ) -> Result<Option<ApiToken>, error::Error> {
+        token::authenticate_api_token(token, context).await
+    }
+}
+
+#[async_trait]
+impl AuditStore for PostgresStore {
+    async fn add_audit_entry(
+        &self,
+        action: &str,
+        target_type: &str,
+        target_id: &str,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+        context: &gql::Context,
+    ) -> Result<AuditEntry, error::Error> {
+        audit::add_audit_entry(action, target_type, target_id, before, after, context).await
+    }
+
+    async fn fetch_audit_log(
+        &self,
+        filter: AuditFilter,
+        context: &gql::Context,
+    ) -> Result<Vec<AuditEntry>, error::Error> {
+        audit::fetch_audit_log(filter, context).await
+    }
+}
@@ -0,0 +1,149 @@
+use crate::error;
+use flate2::read::GzDecoder;
+use snafu::ResultExt;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+// Archives are small, but what they decompress to may not be: each feature, and all the features
+// of an archive, are read up to these sizes.
+const MAX_FEATURE_SIZE: u64 = 4 * 1024 * 1024;
+const MAX_EXTRACTED_SIZE: u64 = 128 * 1024 * 1024;
+
+/// A feature file, as read from disk, an upload or an archive, before it is parsed.
+#[derive(Debug, Clone)]
+pub struct FeatureSource {
+    pub filename: String,
+    pub content: String,
+}
+
+fn is_feature_file(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext == "feature")
+}
+
+/// Extract the feature files from an uploaded file.
+/// The file can be a single '.feature', or a '.tar.gz' / '.tgz' / '.zip' archive, in which case
+/// all the '.feature' files it contains are returned, and everything else is ignored.
+pub fn extract_features(filename: &str, bytes: &[u8]) -> Result<Vec<FeatureSource>, error::Error> {
+    if is_feature_file(filename) {
+        let content = String::from_utf8(bytes.to_vec()).map_err(|_| error::Error::UserError {
+            details: format!("Feature '{}' is not valid UTF-8", filename),
+        })?;
+        Ok(vec![FeatureSource {
+            filename: String::from(filename),
+            content,
+        }])
+    } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+        extract_tar_gz(filename, bytes)
+    } else if filename.ends_with(".zip") {
+        extract_zip(filename, bytes)
+    } else {
+        Err(error::Error::UserError {
+            details: format!(
+                "Unsupported file '{}': expected a .feature, .tar.gz or .zip",
+                filename
+            ),
+        })
+    }
+}
+
+fn extract_tar_gz(filename: &str, bytes: &[u8]) -> Result<Vec<FeatureSource>, error::Error> {
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut sources = Vec::new();
+    let mut extracted = 0;
+    let entries = archive.entries().context(error::ArchiveError {
+        details: format!("Could not read archive '{}'", filename),
+    })?;
+    for entry in entries {
+        let entry = entry.context(error::ArchiveError {
+            details: format!("Could not read entry in archive '{}'", filename),
+        })?;
+        let path = entry
+            .path()
+            .context(error::ArchiveError {
+                details: format!("Invalid path in archive '{}'", filename),
+            })?
+            .display()
+            .to_string();
+        // Symlinks, hard links, devices and the like are skipped.
+        if !entry.header().entry_type().is_file() || !is_feature_file(&path) {
+            continue;
+        }
+        let content = read_feature(entry, &path, filename, &mut extracted)?;
+        sources.push(FeatureSource {
+            filename: path,
+            content,
+        });
+    }
+    Ok(sources)
+}
+
+fn extract_zip(filename: &str, bytes: &[u8]) -> Result<Vec<FeatureSource>, error::Error> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).context(error::ZipError {
+        details: format!("Could not read archive '{}'", filename),
+    })?;
+    let mut sources = Vec::new();
+    let mut extracted = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).context(error::ZipError {
+            details: format!("Could not read entry in archive '{}'", filename),
+        })?;
+        let path = String::from(file.name());
+        // Directories, and entries whose unix mode is not that of a regular file (eg symlinks),
+        // are skipped.
+        let regular = file
+            .unix_mode()
+            .is_none_or(|mode| matches!(mode & 0o170000, 0 | 0o100000));
+        if !file.is_file() || !regular || !is_feature_file(&path) {
+            continue;
+        }
+        let content = read_feature(file, &path, filename, &mut extracted)?;
+        sources.push(FeatureSource {
+            filename: path,
+            content,
+        });
+    }
+    Ok(sources)
+}
+
+// Read a feature from an archive, failing if it is larger than MAX_FEATURE_SIZE, or if it brings
+// what was extracted from the archive so far over MAX_EXTRACTED_SIZE.
+fn read_feature<R: Read>(
+    entry: R,
+    path: &str,
+    filename: &str,
+    extracted: &mut u64,
+) -> Result<String, error::Error> {
+    let mut bytes = Vec::new();
+    entry
+        .take(MAX_FEATURE_SIZE + 1)
+        .read_to_end(&mut bytes)
+        .context(error::ArchiveError {
+            details: format!("Could not read '{}' in archive '{}'", path, filename),
+        })?;
+    let size = bytes.len() as u64;
+    if size > MAX_FEATURE_SIZE {
+        return Err(error::Error::UserError {
+            details: format!(
+                "Feature '{}' in archive '{}' is larger than {} bytes",
+                path, filename, MAX_FEATURE_SIZE
+            ),
+        });
+    }
+    *extracted += size;
+    if *extracted > MAX_EXTRACTED_SIZE {
+        return Err(error::Error::UserError {
+            details: format!(
+                "Archive '{}' holds more than {} bytes of features",
+                filename, MAX_EXTRACTED_SIZE
+            ),
+        });
+    }
+    String::from_utf8(bytes).map_err(|_| error::Error::UserError {
+        details: format!(
+            "Feature '{}' in archive '{}' is not valid UTF-8",
+            path, filename
+        ),
+    })
+}
@@ -0,0 +1,68 @@
+use crate::error;
+use slog::{debug, info, Logger};
+use snafu::ResultExt;
+use std::path::Path;
+use tokio::{fs, process::Command};
+
+// Run git with the given arguments, and return its standard output.
+async fn git(args: &[&str], logger: &Logger) -> Result<String, error::Error> {
+    debug!(logger, "git {}", args.join(" "));
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .await
+        .context(error::TokioIOError)?;
+    if !output.status.success() {
+        return Err(error::Error::GitError {
+            details: format!(
+                "'git {}' failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Make sure the directory 'checkout' contains an up to date copy of the given branch of the
+/// repository at 'url', which can be a local path, or any url understood by git (eg file://).
+/// Returns the SHA of the commit which is checked out.
+pub async fn clone_or_pull(
+    url: &str,
+    branch: &str,
+    checkout: &Path,
+    logger: &Logger,
+) -> Result<String, error::Error> {
+    let dir = checkout.display().to_string();
+
+    if checkout.join(".git").exists() {
+        // If the configured repository changed, we start from a fresh clone.
+        let origin = git(&["-C", &dir, "remote", "get-url", "origin"], logger).await?;
+        if origin != url {
+            info!(
+                logger,
+                "Repository changed from '{}' to '{}', removing {}", origin, url, dir
+            );
+            fs::remove_dir_all(checkout)
+                .await
+                .context(error::TokioIOError)?;
+        }
+    }
+
+    if checkout.join(".git").exists() {
+        info!(logger, "Pulling '{}' ({}) into {}", url, branch, dir);
+        git(&["-C", &dir, "fetch", "--prune", "origin", branch], logger).await?;
+        git(&["-C", &dir, "checkout", "--force", "FETCH_HEAD"], logger).await?;
+        git(&["-C", &dir, "clean", "-fdx"], logger).await?;
+    } else {
+        info!(logger, "Cloning '{}' ({}) into {}", url, branch, dir);
+        if let Some(parent) = checkout.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .context(error::TokioIOError)?;
+        }
+        git(&["clone", "--branch", branch, url, &dir], logger).await?;
+    }
+
+    git(&["-C", &dir, "rev-parse", "HEAD"], logger).await
+}
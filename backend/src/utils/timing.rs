@@ -0,0 +1,25 @@
+use futures::future::{BoxFuture, Future, FutureExt};
+use slog::{debug, Logger};
+use std::time::Instant;
+
+/// Times a future (eg a database query) and logs how long it took, with the given label.
+pub trait Timed: Future + Sized + Send {
+    fn timed<'a>(self, logger: &Logger, label: &'static str) -> BoxFuture<'a, Self::Output>
+    where
+        Self: 'a,
+    {
+        let logger = logger.clone();
+        async move {
+            let start = Instant::now();
+            let res = self.await;
+            debug!(logger, "query {}", label;
+                "query" => label,
+                "duration_ms" => start.elapsed().as_secs_f64() * 1000.0
+            );
+            res
+        }
+        .boxed()
+    }
+}
+
+impl<F: Future + Send> Timed for F {}
@@ -1,17 +1,156 @@
 use cucumber_rust::{after, before, cucumber};
-use futures::TryFutureExt;
 use gherkin_rust::Feature;
+use mjolnir::{
+    auth::{token::NewApiToken, Principal, Role},
+    health::Readiness,
+    load::{corpus::ImportedLog, LoadReport},
+    migrations,
+    model::{
+        audit::AuditEntry,
+        features::{
+            import::ImportReport,
+            repository::{RepositoryConfig, RepositorySync},
+        },
+        runs::{
+            flaky::FlakyScenario,
+            latency::{LatencyMetrics, LatencySample},
+            quality::{QualityMetrics, RankSample},
+            run::Run,
+            snapshot::Snapshot,
+        },
+    },
+    report::compare::RunComparison,
+    runner::bragi::Place,
+    settings::Settings,
+    shutdown::Shutdown,
+    store::Store,
+};
+use once_cell::sync::OnceCell;
 use slog::{o, warn, Drain};
+use sqlx::{postgres::PgQueryAs, Connect, Executor};
+use std::{path::PathBuf, sync::Arc};
 use tokio::runtime::Runtime;
 use uuid::Uuid;
 
+lazy_static::lazy_static! {
+    // Steps all run on the one runtime: the connections of a pool are bound to the runtime which
+    // opened them, and fail with 'reactor gone' once it is dropped.
+    static ref RUNTIME: Runtime = Runtime::new().unwrap();
+}
+
+// What steps block on, in place of a runtime of their own.
+struct StepRuntime;
+
+impl StepRuntime {
+    fn block_on<F: std::future::Future>(&mut self, future: F) -> F::Output {
+        RUNTIME
+            .handle()
+            .enter(|| futures::executor::block_on(future))
+    }
+}
+
+fn runtime() -> StepRuntime {
+    StepRuntime
+}
+
 pub struct MyWorld {
-    context: mjolnir::gql::Context,
+    context: ScenarioContext,
     feature: Feature,      // feature, as read from file.
     id: Option<Uuid>,      // id of the feature returned by the loading operation.
     name: String,          // name of the feature returned by fetching the feature back.
     scenario_count: usize, // count of scenarios returned by fetching scenarios.
     step_count: usize,
+    errors: Vec<serde_json::Value>, // GraphQL errors of the last request.
+    cli: Option<std::process::Output>, // output of the last command line client invocation.
+    scratch: Vec<PathBuf>,          // directories created for the scenario, removed with the world.
+    config: ConfigWorld,
+    repository: RepositoryWorld,
+    auth: AuthWorld,
+    server: ServerWorld,
+    datasets: DatasetWorld,
+    search: SearchWorld,
+    runs: RunWorld,
+    load: LoadWorld,
+    import: ImportWorld,
+}
+
+/// Reading the configuration.
+#[derive(Default)]
+pub struct ConfigWorld {
+    settings: Option<Settings>, // settings read from a configuration file.
+    error: Option<String>,      // why the settings could not be read, if they could not.
+    env_var: Option<(String, String)>, // environment variable set while reading the settings.
+}
+
+/// Synchronising with a features repository.
+#[derive(Default)]
+pub struct RepositoryWorld {
+    config: Option<RepositoryConfig>, // repository created for the scenario, if any.
+    sync: Option<RepositorySync>,     // result of the last synchronisation.
+}
+
+/// Authentication, and what the audit log recorded.
+#[derive(Default)]
+pub struct AuthWorld {
+    token: Option<NewApiToken>, // API token created for the scenario, if any.
+    principal: Option<Result<Principal, String>>, // outcome of the last authentication.
+    audit: Option<AuditEntry>,  // audit log entry looked up by the scenario.
+}
+
+/// Operating the server: health, metrics and migrations.
+#[derive(Default)]
+pub struct ServerWorld {
+    readiness: Option<Readiness>, // result of the last readiness check.
+    metrics: Option<String>,      // metrics, as rendered for Prometheus.
+    migrated: Option<Vec<i32>>,   // versions applied by the last migration.
+}
+
+/// Datasets, and the downloads of their items.
+#[derive(Default)]
+pub struct DatasetWorld {
+    ids: Vec<String>,                      // dataset ids created for the scenario.
+    silent: Option<std::net::TcpListener>, // server which never answers the downloads.
+    served: Option<StubServer>,            // server which answers the downloads...
+    content: Vec<u8>,                      // ... with this file.
+}
+
+/// Checking the steps of a scenario against bragi results.
+#[derive(Default)]
+pub struct SearchWorld {
+    places: Option<Vec<Place>>, // bragi results the steps are checked against.
+    checked: Option<Result<Option<f64>, String>>, // outcome, and score, of the last step checked.
+    request: Option<(String, bool)>, // bragi request of a search step, and whether it has a shape.
+}
+
+/// Runs, and what is computed from them.
+#[derive(Default)]
+pub struct RunWorld {
+    run: Option<Run>,                  // run created for the scenario, if any.
+    named: Vec<(String, Uuid)>,        // runs created by the scenario, by name.
+    samples: Vec<RankSample>,          // ranks the quality metrics are computed from.
+    quality: Vec<QualityMetrics>,      // quality metrics computed, or fetched, by the scenario.
+    comparison: Option<RunComparison>, // result of the last comparison of runs.
+    snapshot: Option<Snapshot>,        // snapshot recorded, proposed or accepted by the scenario.
+    bragi: Option<StubServer>,         // bragi the scenario runs its features against.
+    flaky: Vec<FlakyScenario>,         // flaky scenarios found by the scenario.
+}
+
+/// Benchmarks and load tests.
+#[derive(Default)]
+pub struct LoadWorld {
+    replays: Vec<LatencySample>, // replays of the searches of a benchmarked scenario.
+    latency: Option<LatencyMetrics>, // latency metrics computed by the scenario.
+    corpus: Option<ImportedLog>, // searches imported from a query log.
+    stub: Option<load_steps::Stub>, // bragi stub the scenario replays searches against.
+    report: Option<Result<LoadReport, String>>, // outcome of the last load test.
+}
+
+/// Importing features.
+#[derive(Default)]
+pub struct ImportWorld {
+    archive: Option<import_steps::Archive>, // archive the scenario imports.
+    report: Option<Result<ImportReport, String>>, // outcome of the last import.
+    dir: Option<PathBuf>,                   // directory on the server the scenario imports.
 }
 
 impl cucumber_rust::World for MyWorld {}
@@ -19,7 +158,6 @@ impl cucumber_rust::World for MyWorld {}
 impl std::default::Default for MyWorld {
     /// This function is called every time a new scenario is started
     fn default() -> MyWorld {
-        let context = get_gql_context();
         MyWorld {
             feature: Feature {
                 name: String::from(""),
@@ -30,19 +168,34 @@ impl std::default::Default for MyWorld {
                 tags: Vec::new(),
                 span: (0, 0),
             },
-            context,
+            context: ScenarioContext::postgres(),
             id: None, // feature id
             name: String::new(),
             scenario_count: 0,
             step_count: 0,
+            errors: Vec::new(),
+            cli: None,
+            scratch: Vec::new(),
+            config: ConfigWorld::default(),
+            repository: RepositoryWorld::default(),
+            auth: AuthWorld::default(),
+            server: ServerWorld::default(),
+            datasets: DatasetWorld::default(),
+            search: SearchWorld::default(),
+            runs: RunWorld::default(),
+            load: LoadWorld::default(),
+            import: ImportWorld::default(),
         }
     }
 }
 
 impl Drop for MyWorld {
     fn drop(&mut self) {
+        for dir in &self.scratch {
+            let _ = std::fs::remove_dir_all(dir);
+        }
         // Before dropping MyWorld, we'll remove the feature from the database.
-        if self.id.is_none() {
+        if self.id.is_none() || !self.context.is_built() {
             return;
         }
         let mut variables: juniper::Variables<juniper::DefaultScalarValue> =
@@ -53,7 +206,7 @@ impl Drop for MyWorld {
         );
 
         // Again we use a runtime for running async code
-        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let mut rt = crate::runtime();
         rt.block_on(async {
             let (_res, errs) = juniper::execute(
                 r#"mutation($id: Uuid!) {
@@ -62,7 +215,7 @@ impl Drop for MyWorld {
                     }
                 }"#,
                 None,
-                &mjolnir::schema(),
+                &mjolnir::gql::schema(),
                 &variables,
                 &self.context,
             )
@@ -110,13 +263,106 @@ after!(an_after_fn => |_scenario| {
 });
 
 // A setup function to be called before everything else
-fn setup() {}
+fn setup() {
+    let mut rt = runtime();
+    rt.block_on(async {
+        let logger = slog::Logger::root(slog::Discard, o!());
+        mjolnir::read_dotenv(logger.clone()).await.unwrap();
+    });
+}
+
+lazy_static::lazy_static! {
+    // Each run gets a database of its own, created with the migrations, so that tests start from
+    // an empty schema and do not depend on what previous runs left behind. It is only created
+    // once a scenario needs it: scenarios using the in-memory store do not need Postgres at all.
+    static ref DATABASE_URL: String = on_own_thread(|| {
+        let mut rt = runtime();
+        rt.block_on(async {
+            let logger = slog::Logger::root(slog::Discard, o!());
+            let settings = Settings::new(None).unwrap();
+            let url = create_throwaway_database(settings.database.url().unwrap()).await;
+
+            let mut database = settings.database.clone();
+            database.url = Some(url.clone());
+            let pool = mjolnir::connect_db(&database, logger.clone())
+                .await
+                .unwrap();
+            migrations::migrate(&pool, &logger).await.unwrap();
+            pool.close().await;
+            url
+        })
+    });
+}
+
+// The url of the database of the run.
+fn database_url() -> &'static str {
+    &DATABASE_URL
+}
+
+// Steps block on the runtime, and cannot do it again while they are at it: what needs to block
+// while a step may already be blocking is done on a thread of its own.
+fn on_own_thread<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::spawn(f).join().unwrap()
+}
+
+// Create a database named after a random id, on the server of the given url, and return its url.
+// Databases left by previous runs are dropped first: there is no hook at the end of a run, and
+// those still in use by another run cannot be dropped anyway.
+async fn create_throwaway_database(url: &str) -> String {
+    let mut conn = sqlx::PgConnection::connect(url).await.unwrap();
+
+    let leftovers: Vec<(String,)> =
+        sqlx::query_as("SELECT datname FROM pg_database WHERE datname LIKE 'mjolnir\\_test\\_%'")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+    for (name,) in leftovers {
+        let _ = conn
+            .execute(format!(r#"DROP DATABASE "{}""#, name).as_str())
+            .await;
+    }
+
+    create_database(url).await
+}
+
+// Create a database named after a random id, which the next run drops, and return its url.
+async fn create_database(url: &str) -> String {
+    let mut conn = sqlx::PgConnection::connect(url).await.unwrap();
+    let name = format!("mjolnir_test_{}", Uuid::new_v4().to_simple());
+    conn.execute(format!(r#"CREATE DATABASE "{}""#, name).as_str())
+        .await
+        .unwrap();
+
+    let mut url = reqwest::Url::parse(url).unwrap();
+    url.set_path(&format!("/{}", name));
+    url.to_string()
+}
 
 cucumber! {
     features: "./features",
     world: ::MyWorld,
     steps: &[
-        example_steps::steps
+        example_steps::steps,
+        repository_steps::steps,
+        settings_steps::steps,
+        auth_steps::steps,
+        audit_steps::steps,
+        health_steps::steps,
+        recovery_steps::steps,
+        error_steps::steps,
+        dataset_steps::steps,
+        store_steps::steps,
+        migration_steps::steps,
+        assertion_steps::steps,
+        search_steps::steps,
+        quality_steps::steps,
+        comparison_steps::steps,
+        snapshot_steps::steps,
+        latency_steps::steps,
+        load_steps::steps,
+        flaky_steps::steps,
+        import_steps::steps,
+        cli_steps::steps
     ],
     setup: setup,
     before: &[a_before_fn],
@@ -143,7 +389,7 @@ mod example_steps {
             variables.insert(String::from("feature"), juniper::InputValue::scalar(feature));
 
             // Using GraphQL is in the async world, so we need a runtime...
-            let mut rt = tokio::runtime::Runtime::new().unwrap();
+            let mut rt = crate::runtime();
             rt.block_on(async {
                 // execute the sql statement.
                 let (res, errs) = juniper::execute(
@@ -153,7 +399,7 @@ mod example_steps {
                         }
                     }"#,
                     None,
-                    &mjolnir::schema(),
+                    &mjolnir::gql::schema(),
                     &variables,
                     &world.context
                     ).await.unwrap();
@@ -162,7 +408,7 @@ mod example_steps {
                     for err in errs {
                       warn!(world.context.logger, "{:?}", err);
                     }
-                    assert!(false, "errors occured while executing a graphql statement for adding a feature")
+                    panic!("errors occured while executing a graphql statement for adding a feature")
                 }
 
                 // and extract the id of the feature to store it in the world
@@ -191,7 +437,7 @@ mod example_steps {
             variables.insert(String::from("feature"), juniper::InputValue::scalar(feature));
 
             // Using GraphQL is in the async world, so we need a runtime...
-            let mut rt = tokio::runtime::Runtime::new().unwrap();
+            let mut rt = crate::runtime();
             rt.block_on(async {
                 // execute the graphql statement.
                 let (_res, errs) = juniper::execute(
@@ -201,7 +447,7 @@ mod example_steps {
                         }
                     }"#,
                     None,
-                    &mjolnir::schema(),
+                    &mjolnir::gql::schema(),
                     &variables,
                     &world.context
                     ).await.unwrap();
@@ -212,20 +458,20 @@ mod example_steps {
         };
 
         when r#"I search for the feature by id"# |world, _step| {
-            // Now we use the id we got in the 'given' step as a key for searching the feature
-            let mut variables: juniper::Variables<juniper::DefaultScalarValue> = juniper::Variables::new();
-            variables.insert(String::from("id"), juniper::InputValue::scalar(world.id.unwrap().to_string()));
+            // Now we use the id we got in the 'given' step as a key for searching the feature,
+            // among all of them since there is no query for a single feature.
+            let variables: juniper::Variables<juniper::DefaultScalarValue> = juniper::Variables::new();
             // Again we use a runtime for running async code
-            let mut rt = tokio::runtime::Runtime::new().unwrap();
+            let mut rt = crate::runtime();
             rt.block_on(async {
                 let (res, errs) = juniper::execute(
-                    r#"query($id: Uuid!) {
-                        feature(id: $id) {
+                    r#"query {
+                        features {
                             id, name
                         }
                     }"#,
                     None,
-                    &mjolnir::schema(),
+                    &mjolnir::gql::schema(),
                     &variables,
                     &world.context
                     ).await.unwrap();
@@ -234,13 +480,19 @@ mod example_steps {
                     for err in errs {
                       warn!(world.context.logger, "{:?}", err);
                     }
-                    assert!(false, "errors occured while executing a graphql statement for searching a feature")
+                    panic!("errors occured while executing a graphql statement for searching a feature")
                 }
 
                 // this time we store the name of the feature
-                world.name = String::from(res.as_object_value().unwrap()
-                    .get_field_value("feature").unwrap()
-                    .as_object_value().unwrap()
+                let id = world.id.unwrap().to_string();
+                let feature = res.as_object_value().unwrap()
+                    .get_field_value("features").unwrap()
+                    .as_list_value().unwrap()
+                    .iter()
+                    .map(|feature| feature.as_object_value().unwrap())
+                    .find(|feature| feature.get_field_value("id").unwrap().as_string_value() == Some(&id))
+                    .unwrap();
+                world.name = String::from(feature
                     .get_field_value("name").unwrap()
                     .as_string_value().unwrap());
 
@@ -252,7 +504,7 @@ mod example_steps {
             let mut variables: juniper::Variables<juniper::DefaultScalarValue> = juniper::Variables::new();
             variables.insert(String::from("id"), juniper::InputValue::scalar(world.id.unwrap().to_string()));
             // Again we use a runtime for running async code
-            let mut rt = tokio::runtime::Runtime::new().unwrap();
+            let mut rt = crate::runtime();
             rt.block_on(async {
                 let (res, errs) = juniper::execute(
                     r#"query($id: Uuid!) {
@@ -261,7 +513,7 @@ mod example_steps {
                         }
                     }"#,
                     None,
-                    &mjolnir::schema(),
+                    &mjolnir::gql::schema(),
                     &variables,
                     &world.context
                     ).await.unwrap();
@@ -270,7 +522,7 @@ mod example_steps {
                     for err in errs {
                       warn!(world.context.logger, "{:?}", err);
                     }
-                    assert!(false, "errors occured while executing a graphql statement for searching scenarios")
+                    panic!("errors occured while executing a graphql statement for searching scenarios")
                 }
 
                 // this time we store the name of the feature
@@ -287,7 +539,7 @@ mod example_steps {
             let mut variables: juniper::Variables<juniper::DefaultScalarValue> = juniper::Variables::new();
             variables.insert(String::from("id"), juniper::InputValue::scalar(world.id.unwrap().to_string()));
             // Again we use a runtime for running async code
-            let mut rt = tokio::runtime::Runtime::new().unwrap();
+            let mut rt = crate::runtime();
             rt.block_on(async {
                 let (res, errs) = juniper::execute(
                     r#"query($id: Uuid!) {
@@ -296,7 +548,7 @@ mod example_steps {
                         }
                     }"#,
                     None,
-                    &mjolnir::schema(),
+                    &mjolnir::gql::schema(),
                     &variables,
                     &world.context
                     ).await.unwrap();
@@ -305,7 +557,7 @@ mod example_steps {
                     for err in errs {
                       warn!(world.context.logger, "{:?}", err);
                     }
-                    assert!(false, "errors occured while executing a graphql statement for searching scenarios")
+                    panic!("errors occured while executing a graphql statement for searching scenarios")
                 }
 
                 // Here we extract the id of the first scenario.
@@ -323,12 +575,12 @@ mod example_steps {
 
                 let (res, errs) = juniper::execute(
                     r#"query($id: Uuid!) {
-                        steps(id: $id) {
+                        steps(id: $id, src: SCENARIO) {
                             id, value
                         }
                     }"#,
                     None,
-                    &mjolnir::schema(),
+                    &mjolnir::gql::schema(),
                     &variables,
                     &world.context
                     ).await.unwrap();
@@ -337,7 +589,7 @@ mod example_steps {
                     for err in errs {
                       warn!(world.context.logger, "{:?}", err);
                     }
-                    assert!(false, "errors occured while executing a graphql statement for searching steps")
+                    panic!("errors occured while executing a graphql statement for searching steps")
                 }
 
                 // this time we store the count of steps
@@ -363,27 +615,2199 @@ mod example_steps {
     });
 }
 
-fn get_gql_context() -> mjolnir::gql::Context {
-    let mut rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        let decorator = slog_term::TermDecorator::new().build();
-        let drain = slog_term::FullFormat::new(decorator).build().fuse();
-        let drain = slog_async::Async::new(drain).build().fuse();
+mod repository_steps {
+    use cucumber_rust::steps;
+    use mjolnir::model::features::repository::{self, RepositoryConfig};
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
 
-        let log = slog::Logger::root(drain, o!());
+    // Run git in the given directory, and return its output.
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .expect("git");
+        assert!(output.status.success(), "git {} failed", args.join(" "));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
 
-        let logger = log.new(o!());
+    fn sync(world: &mut crate::MyWorld) {
+        let config = world.repository.config.clone().unwrap();
+        let mut rt = crate::runtime();
+        world.repository.sync = Some(rt.block_on(async {
+            repository::sync_repository(&config, &world.context)
+                .await
+                .unwrap()
+        }));
+    }
 
-        mjolnir::read_dotenv(logger.clone()).await.unwrap();
+    // Create a git repository, unique to this scenario, holding the given feature.
+    fn init_repository(world: &mut crate::MyWorld, content: &str) {
+        let root = crate::scratch_dir(world);
+        let origin = root.join("origin");
+        std::fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "-q"]);
+        git(&origin, &["checkout", "-q", "-b", "master"]);
+        std::fs::write(origin.join("example.feature"), content).unwrap();
+        git(&origin, &["add", "."]);
+        git(
+            &origin,
+            &[
+                "-c",
+                "user.name=mjolnir",
+                "-c",
+                "user.email=mjolnir@localhost",
+                "commit",
+                "-q",
+                "-m",
+                "add feature",
+            ],
+        );
 
-        let pool = mjolnir::get_connstr(logger.clone())
-            .and_then(|connstr| mjolnir::connect_db(connstr, logger.clone()))
-            .await
-            .unwrap();
+        world.feature = gherkin_rust::Feature::parse(String::from(content)).unwrap();
+        world.repository.config = Some(RepositoryConfig {
+            url: format!("file://{}", origin.display()),
+            branch: String::from("master"),
+            checkout: root.join("checkout"),
+        });
+    }
 
-        mjolnir::gql::Context {
-            pool: pool.clone(),
-            logger: logger.clone(),
-        }
-    })
+    steps!(crate::MyWorld => {
+        given regex r#"^I have a git repository containing the feature '(.*)'$"# (String) |world, filename, _step| {
+            let content = std::fs::read_to_string(PathBuf::from(filename)).unwrap();
+            init_repository(world, &content);
+        };
+
+        given regex r#"^I have a git repository containing the feature '(.*)' with a scenario named with (\d+) characters$"# (String, usize) |world, name, length, _step| {
+            let content = format!(
+                "Feature: {}\n\n  Scenario: {}\n    Given I search for 'Paris'\n",
+                name,
+                "x".repeat(length)
+            );
+            init_repository(world, &content);
+        };
+
+        when r#"I synchronise the features with the repository, which fails"# |world, _step| {
+            let config = world.repository.config.clone().unwrap();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                assert!(repository::sync_repository(&config, &world.context).await.is_err());
+                let status = repository::fetch_repository_status(Some(&config), &world.context).await.unwrap();
+                world.repository.sync = status.last_sync;
+            });
+        };
+
+        when r#"I synchronise the features with the repository"# |world, _step| {
+            sync(world);
+        };
+
+        when r#"I ask for the status of the features repository"# |world, _step| {
+            let mut rt = crate::runtime();
+            let status = rt.block_on(async {
+                repository::fetch_repository_status(None, &world.context).await.unwrap()
+            });
+            assert!(!status.configured);
+            world.repository.sync = status.last_sync;
+        };
+
+        when r#"I remove the feature from the repository and synchronise again"# |world, _step| {
+            let origin = PathBuf::from(world.repository.config.as_ref().unwrap().url.trim_start_matches("file://"));
+            git(&origin, &["rm", "-q", "example.feature"]);
+            git(&origin, &["-c", "user.name=mjolnir", "-c", "user.email=mjolnir@localhost", "commit", "-q", "-m", "remove feature"]);
+            sync(world);
+        };
+
+        then r#"I find that the feature from the repository is loaded at the latest commit"# |world, _step| {
+            let sync = world.repository.sync.as_ref().unwrap();
+            assert_eq!(sync.loaded, 1);
+            assert!(sync.failures.is_empty());
+
+            let origin = PathBuf::from(world.repository.config.as_ref().unwrap().url.trim_start_matches("file://"));
+            let head = git(&origin, &["rev-parse", "HEAD"]);
+            assert_eq!(sync.commit_sha, Some(head.clone()));
+
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let features = mjolnir::model::features::feature::fetch_all_features(&world.context).await.unwrap();
+                let feature = features.into_iter().find(|f| f.name == world.feature.name).expect("feature loaded");
+                let revisions = repository::fetch_feature_revisions(&feature.id, &world.context).await.unwrap();
+                assert_eq!(revisions[0].commit_sha, head);
+                world.id = Some(feature.id);
+            });
+        };
+
+        then r#"I find that nothing of the feature from the repository is loaded"# |world, _step| {
+            let sync = world.repository.sync.as_ref().unwrap();
+            assert!(sync.error.as_ref().unwrap().contains("Could not create or replace scenario"), "{:?}", sync.error);
+            assert_eq!(sync.loaded, 0);
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let features = mjolnir::model::features::feature::fetch_all_features(&world.context).await.unwrap();
+                assert!(features.iter().all(|f| f.name != world.feature.name));
+            });
+        };
+
+        then r#"I find that the features repository was never synchronised"# |world, _step| {
+            assert!(world.repository.sync.is_none(), "{:?}", world.repository.sync);
+        };
+
+        then r#"I find that the feature from the repository is deleted"# |world, _step| {
+            assert_eq!(world.repository.sync.as_ref().unwrap().deleted, 1);
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let features = mjolnir::model::features::feature::fetch_all_features(&world.context).await.unwrap();
+                assert!(features.iter().all(|f| f.name != world.feature.name));
+            });
+            world.id = None;
+        };
+    });
+}
+
+mod settings_steps {
+    use cucumber_rust::steps;
+    use mjolnir::settings::Settings;
+    use std::path::PathBuf;
+
+    steps!(crate::MyWorld => {
+        given regex r#"^I read the configuration file '(.*)'$"# (String) |world, filename, _step| {
+            world.config.settings = Some(Settings::from_file(&PathBuf::from(filename)).unwrap());
+        };
+
+        then regex r#"^I find that the server listens on '(.*)' with a pool of ([0-9]+) connections$"# (String, u32) |world, listen, pool_size, _step| {
+            let settings = world.config.settings.as_ref().unwrap();
+            assert_eq!(settings.server.listen, listen);
+            assert_eq!(settings.database.pool_size, pool_size);
+            // What is not in the file keeps its default value.
+            assert_eq!(settings.server.static_dir, PathBuf::from("dist"));
+        };
+
+        then regex r#"^I find that the logs are written as '(.*)'$"# (String) |world, format, _step| {
+            assert_eq!(world.config.settings.as_ref().unwrap().log_format, format);
+        };
+
+        then r#"I find that the configuration is valid"# |world, _step| {
+            assert!(world.config.settings.as_ref().unwrap().validate().is_ok());
+        };
+
+        given regex r#"^the environment variable '(.*)' is '(.*)'$"# (String, String) |world, name, value, _step| {
+            world.config.env_var = Some((name, value));
+        };
+
+        when r#"I read the configuration"# |world, _step| {
+            // The variable is only set while reading, so that it does not leak into other scenarios.
+            let (name, value) = world.config.env_var.take().unwrap();
+            std::env::set_var(&name, value);
+            let settings = Settings::new(None).map_err(|err| format!("{}", err));
+            std::env::remove_var(&name);
+            world.config.error = settings.err();
+        };
+
+        then r#"I find that the printed configuration hides the passwords"# |world, _step| {
+            let printed = world.config.settings.as_ref().unwrap().to_toml().unwrap();
+            assert!(!printed.contains("secret"), "{}", printed);
+            assert!(!printed.contains("hidden"), "{}", printed);
+            assert!(printed.contains("0.0.0.0:8080"), "{}", printed);
+        };
+
+        then regex r#"^I find that the configuration is rejected because of '(.*)'$"# (String) |world, key, _step| {
+            let err = world.config.error.as_ref().expect("the configuration was accepted");
+            assert!(err.contains(&key), "{}", err);
+        };
+
+        then regex r#"^I find that the configuration is invalid because of '(.*)', '(.*)' and '(.*)'$"# (String, String, String) |world, first, second, third, _step| {
+            let err = world.config.settings.as_ref().unwrap().validate().unwrap_err();
+            let msg = format!("{}", err);
+            // All the problems are reported at once.
+            assert!(msg.contains(&first), "{}", msg);
+            assert!(msg.contains(&second), "{}", msg);
+            assert!(msg.contains(&third), "{}", msg);
+        };
+    });
+}
+
+mod auth_steps {
+    use cucumber_rust::steps;
+    use mjolnir::auth::{Authenticator, Role};
+
+    fn authenticate(world: &mut crate::MyWorld, credentials: Option<String>) {
+        let authenticator = Authenticator::new(&world.context.settings.auth).unwrap();
+        let mut rt = crate::runtime();
+        world.auth.principal = Some(rt.block_on(async {
+            authenticator
+                .authenticate(credentials.as_deref(), &world.context)
+                .await
+                .map_err(|err| format!("{}", err))
+        }));
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^I have an API token with the role '(.*)'$"# (String) |world, role, _step| {
+            let role: Role = role.parse().unwrap();
+            // Token names are unique, so each scenario gets its own.
+            let name = format!("cucumber-{}", uuid::Uuid::new_v4());
+            let mut rt = crate::runtime();
+            world.auth.token = Some(rt.block_on(async {
+                let tokens = &world.context.store.tokens;
+                tokens.create_api_token(&name, role, &world.context).await.unwrap()
+            }));
+        };
+
+        when r#"I authenticate with the token"# |world, _step| {
+            let token = world.auth.token.as_ref().unwrap().token.clone();
+            authenticate(world, Some(format!("Bearer {}", token)));
+        };
+
+        when r#"I authenticate with an unknown token"# |world, _step| {
+            authenticate(world, Some(String::from("mjr_unknown")));
+        };
+
+        when r#"I revoke the token"# |world, _step| {
+            let id = world.auth.token.as_ref().unwrap().id;
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let tokens = &world.context.store.tokens;
+                tokens.revoke_api_token(&id, &world.context).await.unwrap()
+            });
+        };
+
+        then regex r#"^I find that I am authenticated with the role '(.*)'$"# (String) |world, role, _step| {
+            let principal = world.auth.principal.as_ref().unwrap().as_ref().unwrap();
+            assert_eq!(principal.name, world.auth.token.as_ref().unwrap().name);
+            assert_eq!(principal.role, role.parse::<Role>().unwrap());
+        };
+
+        then regex r#"^I find that I am allowed to act as '(.*)' but not as '(.*)'$"# (String, String) |world, allowed, denied, _step| {
+            let principal = world.auth.principal.as_ref().unwrap().as_ref().unwrap();
+            assert!(principal.authorize(allowed.parse().unwrap()).is_ok());
+            assert!(principal.authorize(denied.parse().unwrap()).is_err());
+        };
+
+        then r#"I find that the authentication is rejected"# |world, _step| {
+            assert!(world.auth.principal.as_ref().unwrap().is_err());
+        };
+    });
+}
+
+mod audit_steps {
+    use cucumber_rust::steps;
+    use mjolnir::model::audit::{self, AuditFilter};
+
+    // Execute a GraphQL query with the context of the world, and return its data.
+    fn execute(
+        world: &crate::MyWorld,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> serde_json::Value {
+        let response = crate::graphql(world, query, variables);
+        assert!(response.get("errors").is_none(), "{}", response);
+        response["data"].clone()
+    }
+
+    fn add_feature(world: &mut crate::MyWorld, name: String) {
+        let data = execute(
+            world,
+            r#"mutation($name: String!) {
+                addFeature(name: $name, description: "audited", tags: []) { id }
+            }"#,
+            serde_json::json!({ "name": name }),
+        );
+        world.id = Some(data["addFeature"]["id"].as_str().unwrap().parse().unwrap());
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^I have added the feature '(.*)' through the API$"# (String) |world, name, _step| {
+            add_feature(world, name);
+        };
+
+        when regex r#"^I add the feature '(.*)' through the API$"# (String) |world, name, _step| {
+            add_feature(world, name);
+        };
+
+        when r#"I delete the feature through the API"# |world, _step| {
+            let id = world.id.unwrap();
+            execute(
+                world,
+                r#"mutation($id: Uuid!) { deleteFeature(id: $id) { id } }"#,
+                serde_json::json!({ "id": id }),
+            );
+        };
+
+        when regex r#"^I prune the audit log with a retention of (\d+) days?$"# (u32) |world, days, _step| {
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                audit::prune_audit_log(days, world.context.pool().unwrap(), &world.context.logger)
+                    .await
+                    .unwrap()
+            });
+        };
+
+        then regex r#"^I find an? '(.*)' audit entry for the feature by '(.*)'$"# (String, String) |world, action, actor, _step| {
+            let filter = AuditFilter {
+                action: Some(action),
+                target_type: Some(String::from("feature")),
+                target_id: world.id.map(|id| id.to_string()),
+                ..AuditFilter::default()
+            };
+            let mut rt = crate::runtime();
+            let entries = rt.block_on(async {
+                let audit = &world.context.store.audit;
+                audit.fetch_audit_log(filter, &world.context).await.unwrap()
+            });
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].actor, actor);
+            world.auth.audit = entries.into_iter().next();
+        };
+
+        then regex r#"^I find that the audit entry records the feature (before|after) the change$"# (String) |world, when, _step| {
+            let entry = world.auth.audit.as_ref().unwrap();
+            let (recorded, missing) = if when == "before" {
+                (&entry.before, &entry.after)
+            } else {
+                (&entry.after, &entry.before)
+            };
+            let recorded: serde_json::Value = serde_json::from_str(recorded.as_ref().unwrap()).unwrap();
+            assert_eq!(recorded["id"], serde_json::json!(world.id.unwrap()));
+            assert!(missing.is_none());
+        };
+    });
+}
+
+mod health_steps {
+    use cucumber_rust::steps;
+    use mjolnir::{
+        health::{self, ListenerStatus},
+        metrics,
+    };
+
+    steps!(crate::MyWorld => {
+        when r#"I check the readiness"# |world, _step| {
+            // The notifications listener is not started here.
+            let listener = ListenerStatus::default();
+            let mut rt = crate::runtime();
+            world.server.readiness = Some(rt.block_on(async {
+                health::readiness(
+                    world.context.pool().unwrap(),
+                    &world.context.settings,
+                    &listener,
+                    &world.context.logger,
+                )
+                .await
+            }));
+        };
+
+        when regex r#"^I time the GraphQL operations '(.*)' and '(.*)'$"# (String, String) |_world, first, second, _step| {
+            metrics::GraphQLTimer::start(Some(&first)).finish(true);
+            metrics::GraphQLTimer::start(Some(&second)).finish(true);
+        };
+
+        when r#"I render the metrics"# |world, _step| {
+            world.server.metrics = Some(metrics::render(world.context.pool().unwrap()));
+        };
+
+        then regex r#"^I find that the '(.*)' check (passes|fails)$"# (String, String) |world, name, outcome, _step| {
+            let readiness = world.server.readiness.as_ref().unwrap();
+            let check = readiness.checks.iter().find(|check| check.name == name).unwrap();
+            assert_eq!(check.ok, outcome == "passes", "{:?}", check);
+        };
+
+        then r#"I find that mjolnir is not ready"# |world, _step| {
+            assert!(!world.server.readiness.as_ref().unwrap().ready);
+        };
+
+        then regex r#"^I find the metric '(.*)'$"# (String) |world, name, _step| {
+            let metrics = world.server.metrics.as_ref().unwrap();
+            assert!(metrics.lines().any(|line| line.starts_with(&name)), "{}", metrics);
+        };
+
+        then regex r#"^I find no metric about the operation '(.*)'$"# (String) |world, operation, _step| {
+            let metrics = world.server.metrics.as_ref().unwrap();
+            assert!(!metrics.contains(&operation), "{}", metrics);
+        };
+
+        then r#"I find that every root field of the schema is a known operation"# |world, _step| {
+            let response = crate::graphql(
+                world,
+                "{ __schema { queryType { fields { name } } mutationType { fields { name } } } }",
+                serde_json::json!({}),
+            );
+            let schema = &response["data"]["__schema"];
+            for root in &[&schema["queryType"], &schema["mutationType"]] {
+                for field in root["fields"].as_array().unwrap() {
+                    let name = field["name"].as_str().unwrap();
+                    assert!(metrics::OPERATIONS.contains(&name), "{} is not in OPERATIONS", name);
+                }
+            }
+        };
+    });
+}
+
+mod recovery_steps {
+    use cucumber_rust::steps;
+    use mjolnir::model::{environments::dataset, recovery, runs::run};
+    use sqlx::postgres::PgQueryAs;
+    use std::{sync::Arc, time::Duration};
+
+    steps!(crate::MyWorld => {
+        given regex r#"^I have a run left running against '(.*)'$"# (String) |world, bragi_url, _step| {
+            let mut rt = crate::runtime();
+            world.runs.run = Some(rt.block_on(async {
+                run::create_run(Vec::new(), &bragi_url, &world.context).await.unwrap()
+            }));
+        };
+
+        given regex r#"^I have the download of the item '(.*)' of '(.*)' left in progress$"# (String, String) |world, item, data_source, _step| {
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                sqlx::query(
+                    "INSERT INTO main.dataset_items (id, data_source, filestatus) VALUES ($1, $2, 'download_in_progress')
+                    ON CONFLICT (data_source, id) DO UPDATE SET filestatus = EXCLUDED.filestatus",
+                )
+                .bind(item)
+                .bind(data_source)
+                .execute(world.context.pool().unwrap())
+                .await
+                .unwrap();
+            });
+        };
+
+        given regex r#"^I have started the download of the item '(.*)' of '(.*)' from a server which does not answer$"# (String, String) |world, item, data_source, _step| {
+            // Connections are accepted by the system, but nothing is ever read or written.
+            let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let location = format!("http://{}/{{item}}.csv", silent.local_addr().unwrap());
+            let work_dir = crate::scratch_dir(world);
+            let mut settings = (*world.context.settings).clone();
+            settings.work_dir = Some(work_dir);
+            settings.datasets.locations.insert(data_source.clone(), location);
+            world.context.settings = Arc::new(settings);
+            world.datasets.silent = Some(silent);
+
+            let id = format!("download-{}", uuid::Uuid::new_v4());
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                datasets.check_and_insert_dataset(&id, &data_source, "download", &world.context).await.unwrap();
+                datasets.check_and_insert_dataset_item(&id, &item, &world.context).await.unwrap();
+                dataset::download_dataset_item(&id, &item, &world.context).await.unwrap();
+            });
+        };
+
+        when r#"the server shuts down"# |world, _step| {
+            world.context.shutdown.trigger();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                tokio::time::timeout(Duration::from_secs(10), world.context.shutdown.drain())
+                    .await
+                    .expect("the downloads did not stop");
+            });
+        };
+
+        when r#"I recover the interrupted work"# |world, _step| {
+            let mut rt = crate::runtime();
+            let recovered = rt.block_on(async {
+                recovery::recover_interrupted(world.context.pool().unwrap(), &world.context.logger)
+                    .await
+                    .unwrap()
+            });
+            assert!(recovered > 0);
+        };
+
+        then regex r#"^I find that the run is '(.*)'$"# (String) |world, status, _step| {
+            let id = world.runs.run.as_ref().unwrap().id;
+            let mut rt = crate::runtime();
+            let run = rt.block_on(async {
+                run::fetch_run_by_id(&id, &world.context).await.unwrap()
+            });
+            assert_eq!(serde_json::to_value(run.status).unwrap(), serde_json::json!(status));
+            assert!(run.finished_at.is_some());
+        };
+
+        then regex r#"^I find that the item '(.*)' of '(.*)' is '(.*)'$"# (String, String, String) |world, item, data_source, status, _step| {
+            let mut rt = crate::runtime();
+            let (found,): (String,) = rt.block_on(async {
+                sqlx::query_as("SELECT filestatus::TEXT FROM main.dataset_items WHERE id = $1 AND data_source = $2")
+                    .bind(item)
+                    .bind(data_source)
+                    .fetch_one(world.context.pool().unwrap())
+                    .await
+                    .unwrap()
+            });
+            assert_eq!(found, status);
+        };
+    });
+}
+
+mod error_steps {
+    use cucumber_rust::steps;
+    use mjolnir::error;
+
+    // Execute a GraphQL query with the context of the world, and keep its errors.
+    fn execute(world: &mut crate::MyWorld, query: &str, variables: serde_json::Value) {
+        let response = crate::graphql(world, query, variables);
+        world.errors = response["errors"].as_array().cloned().unwrap_or_default();
+    }
+
+    steps!(crate::MyWorld => {
+        when regex r#"^I query the run '(.*)'$"# (String) |world, id, _step| {
+            execute(
+                world,
+                r#"query($id: Uuid!) { run(id: $id) { id } }"#,
+                serde_json::json!({ "id": id }),
+            );
+        };
+
+        when regex r#"^I load the feature '(.*)'$"# (String) |world, feature, _step| {
+            execute(
+                world,
+                r#"mutation($feature: String!) { loadFeature(feature: $feature) { id } }"#,
+                serde_json::json!({ "feature": feature.replace("\\n", "\n") }),
+            );
+        };
+
+        when regex r#"^I add a feature whose name is (\d+) characters long$"# (usize) |world, length, _step| {
+            execute(
+                world,
+                r#"mutation($name: String!) { addFeature(name: $name, description: "", tags: []) { id } }"#,
+                serde_json::json!({ "name": "x".repeat(length) }),
+            );
+        };
+
+        when regex r#"^I revoke the API token '(.*)'$"# (String) |world, id, _step| {
+            execute(
+                world,
+                r#"mutation($id: Uuid!) { revokeApiToken(id: $id) { id } }"#,
+                serde_json::json!({ "id": id }),
+            );
+        };
+
+        when r#"I create an API token with the same name"# |world, _step| {
+            let name = world.auth.token.as_ref().unwrap().name.clone();
+            execute(
+                world,
+                r#"mutation($name: String!) { createApiToken(name: $name, role: VIEWER) { id } }"#,
+                serde_json::json!({ "name": name }),
+            );
+        };
+
+        then regex r#"^I find an error with the code '(.*)'$"# (String) |world, code, _step| {
+            assert_eq!(world.errors.len(), 1, "{:?}", world.errors);
+            assert_eq!(world.errors[0]["extensions"]["code"], serde_json::json!(code));
+        };
+
+        then regex r#"^I find that the error is located at line (\d+)$"# (i32) |world, line, _step| {
+            assert_eq!(world.errors[0]["extensions"]["line"], serde_json::json!(line));
+        };
+
+        then regex r#"^I find that the error is about the field '(.*)'$"# (String) |world, field, _step| {
+            assert_eq!(world.errors[0]["extensions"]["field"], serde_json::json!(field));
+        };
+
+        then regex r#"^I find that '(.*)' is redacted as '(.*)'$"# (String, String) |_world, msg, redacted, _step| {
+            assert_eq!(error::redact(&msg), redacted);
+        };
+    });
+}
+
+mod dataset_steps {
+    use cucumber_rust::steps;
+    use md5::{Digest, Md5};
+    use mjolnir::model::FileStatus;
+    use sqlx::postgres::PgQueryAs;
+    use std::{sync::Arc, time::Duration};
+    use warp::Filter;
+
+    // Fragments which would escape a query built by formatting ids into it.
+    const HOSTILE: &[&str] = &[
+        "'",
+        "''",
+        "\"",
+        ";",
+        "--",
+        "/*",
+        "\\",
+        "%",
+        "$1",
+        "')",
+        "'; DROP TABLE main.dataset_item_map; --",
+        "' OR '1'='1",
+        "é",
+        "日本",
+        "🦀",
+    ];
+
+    // An id made of hostile fragments picked at random, made unique by a suffix.
+    fn hostile_id() -> String {
+        let uuid = uuid::Uuid::new_v4();
+        let bytes = uuid.as_bytes();
+        let mut id: String = bytes[..4]
+            .iter()
+            .map(|b| HOSTILE[*b as usize % HOSTILE.len()])
+            .collect();
+        id.push_str(&uuid.to_simple().to_string()[..8]);
+        id
+    }
+
+    // The datasets are reached through the store of the context, so that these steps work with
+    // the in-memory store too.
+    steps!(crate::MyWorld => {
+        given r#"I have a dataset with an item"# |world, _step| {
+            let id = format!("sentinel-{}", uuid::Uuid::new_v4());
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                datasets.check_and_insert_dataset(&id, "bano", "sentinel", &world.context).await.unwrap();
+                datasets.check_and_insert_dataset_item(&id, &id, &world.context).await.unwrap();
+            });
+            world.datasets.ids.push(id);
+        };
+
+        when regex r#"^I create (\d+) datasets with hostile ids, each with an item of the same id$"# (usize) |world, count, _step| {
+            let ids: Vec<String> = (0..count).map(|_| hostile_id()).collect();
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                for id in &ids {
+                    let res = datasets.check_and_insert_dataset(id, "bano", "hostile", &world.context).await.unwrap();
+                    assert_eq!(&res.id, id);
+                    let item = datasets.check_and_insert_dataset_item(id, id, &world.context).await.unwrap();
+                    assert_eq!(&item.id, id);
+                }
+            });
+            world.datasets.ids.extend(ids);
+        };
+
+        then r#"I find each dataset with its item, with their ids unchanged"# |world, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                for id in &world.datasets.ids {
+                    let res = datasets.fetch_dataset(id, &world.context).await.unwrap();
+                    assert_eq!(res.map(|d| d.id).as_ref(), Some(id));
+                    let items = datasets.fetch_dataset_items(id, &world.context).await.unwrap();
+                    let items: Vec<&String> = items.iter().map(|item| &item.id).collect();
+                    assert_eq!(items, vec![id]);
+                }
+            });
+        };
+
+        when r#"I remove the hostile datasets and their items"# |world, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                // The first one is the dataset we are not supposed to touch.
+                for id in world.datasets.ids.iter().skip(1) {
+                    datasets.remove_dataset_item(id, id, &world.context).await.unwrap();
+                    let res = datasets.remove_dataset(id, &world.context).await.unwrap();
+                    assert_eq!(&res.id, id);
+                }
+            });
+        };
+
+        then r#"I find that only the first dataset and its item are left"# |world, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let sentinel = &world.datasets.ids[0];
+                let item = datasets.fetch_dataset_item(sentinel, sentinel, &world.context).await.unwrap();
+                assert!(item.is_some(), "the dataset which was not removed lost its item");
+                for id in world.datasets.ids.iter().skip(1) {
+                    assert!(datasets.fetch_dataset(id, &world.context).await.unwrap().is_none());
+                    assert!(datasets.fetch_dataset_items(id, &world.context).await.unwrap().is_empty());
+                }
+                datasets.remove_dataset(sentinel, &world.context).await.unwrap();
+            });
+        };
+
+        given regex r#"^I have created the (\w+) dataset '(.*)'$"# (String, String) |world, data_source, id, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            let res = rt.block_on(async {
+                datasets.check_and_insert_dataset(&id, &data_source, "created by cucumber", &world.context).await.unwrap()
+            });
+            assert_eq!(res.id, id);
+            assert_eq!(res.data_source, data_source);
+            assert!(res.items.is_empty());
+        };
+
+        given regex r#"^I have added the item '(.*)' to the dataset '(.*)'$"# (String, String) |world, item_id, dataset_id, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            let item = rt.block_on(async {
+                datasets.check_and_insert_dataset_item(&dataset_id, &item_id, &world.context).await.unwrap()
+            });
+            assert_eq!(item.id, item_id);
+        };
+
+        when regex r#"^I remove the item '(.*)' from the dataset '(.*)'$"# (String, String) |world, item_id, dataset_id, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                datasets.remove_dataset_item(&dataset_id, &item_id, &world.context).await.unwrap()
+            });
+        };
+
+        then regex r#"^I find that creating the (\w+) dataset '(.*)' fails$"# (String, String) |world, data_source, id, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            let res = rt.block_on(async {
+                datasets.check_and_insert_dataset(&id, &data_source, "not created", &world.context).await
+            });
+            assert!(res.is_err(), "the {} dataset '{}' was created", data_source, id);
+        };
+
+        then regex r#"^I find that adding the item '(.*)' to the dataset '(.*)' fails$"# (String, String) |world, item_id, dataset_id, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            let res = rt.block_on(async {
+                datasets.check_and_insert_dataset_item(&dataset_id, &item_id, &world.context).await
+            });
+            assert!(res.is_err(), "the item '{}' was added to '{}'", item_id, dataset_id);
+        };
+
+        // The items are given as a comma separated list, which may be empty.
+        then regex r#"^I find the dataset '(.*)' with the items '(.*)'$"# (String, String) |world, id, items, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            let all = rt.block_on(async {
+                datasets.fetch_datasets(None, &world.context).await.unwrap()
+            });
+            let res = all.into_iter().find(|d| d.id == id).expect("dataset not found");
+            let found: Vec<String> = res.items.into_iter().map(|item| item.id).collect();
+            let expected: Vec<String> = items
+                .split(',')
+                .map(|item| String::from(item.trim()))
+                .filter(|item| !item.is_empty())
+                .collect();
+            assert_eq!(found, expected);
+        };
+
+        then regex r#"^I find that the (\w+) item '(.*)' (still exists|no longer exists)$"# (String, String, String) |world, data_source, id, state, _step| {
+            let mut rt = crate::runtime();
+            let (count,): (i64,) = rt.block_on(async {
+                sqlx::query_as("SELECT COUNT(*) FROM main.dataset_items WHERE data_source = $1 AND id = $2")
+                    .bind(&data_source)
+                    .bind(&id)
+                    .fetch_one(world.context.pool().unwrap())
+                    .await
+                    .unwrap()
+            });
+            assert_eq!(count, if state == "still exists" { 1 } else { 0 });
+        };
+
+        // The file spans many chunks of the response, and its bytes are not all the same, so that
+        // a chunk which is lost, repeated or out of order shows in its checksum.
+        given regex r#"^a server which serves the (\w+) items, as (\d+) kilobytes files$"# (String, usize) |world, data_source, size, _step| {
+            let content: Vec<u8> = (0..size * 1024).map(|i| (i % 251) as u8).collect();
+            let served = content.clone();
+            let server = crate::StubServer::start(
+                warp::path!(String).map(move |_item: String| served.clone()),
+            );
+            let work_dir = crate::scratch_dir(world);
+            let mut settings = (*world.context.settings).clone();
+            settings.work_dir = Some(work_dir);
+            settings
+                .datasets
+                .locations
+                .insert(data_source, format!("{}/{{item}}.csv", server.url));
+            world.context.settings = Arc::new(settings);
+            world.datasets.served = Some(server);
+            world.datasets.content = content;
+        };
+
+        when regex r#"^I download the item '(.*)' of the dataset '(.*)'$"# (String, String) |world, item, dataset, _step| {
+            let response = crate::graphql(
+                world,
+                r#"mutation($dataset: String!, $item: String!) {
+                    downloadDatasetItem(dataset: $dataset, item: $item) { id filestatus }
+                }"#,
+                serde_json::json!({ "dataset": dataset, "item": item }),
+            );
+            assert!(response.get("errors").is_none(), "{}", response);
+            let item = &response["data"]["downloadDatasetItem"];
+            assert_eq!(item["filestatus"], "DOWNLOAD_IN_PROGRESS");
+        };
+
+        then regex r#"^I find that the item '(.*)' of the dataset '(.*)' is available, with the file served$"# (String, String) |world, item_id, dataset_id, _step| {
+            let datasets = world.context.store.datasets.clone();
+            let mut rt = crate::runtime();
+            let item = rt.block_on(async {
+                let wait = async {
+                    loop {
+                        let item = datasets
+                            .fetch_dataset_item(&dataset_id, &item_id, &world.context)
+                            .await
+                            .unwrap()
+                            .unwrap();
+                        if item.filestatus != FileStatus::DownloadInProgress {
+                            return item;
+                        }
+                        tokio::time::delay_for(Duration::from_millis(50)).await;
+                    }
+                };
+                tokio::time::timeout(Duration::from_secs(10), wait)
+                    .await
+                    .expect("the download did not finish")
+            });
+            let content = &world.datasets.content;
+            assert_eq!(item.filestatus, FileStatus::Available);
+            assert_eq!(item.filename, format!("{}.csv", item_id));
+            assert_eq!(item.md5, format!("{:x}", Md5::digest(content)));
+            assert_eq!(item.filesize, content.len() as f64 / 1024.0);
+
+            let path = world
+                .context
+                .settings
+                .work_dir()
+                .unwrap()
+                .join("datasets")
+                .join(&item.data_source)
+                .join(&item.filename);
+            assert!(std::fs::read(path).unwrap() == *content, "the file differs from the one served");
+        };
+    });
+}
+
+mod store_steps {
+    use cucumber_rust::steps;
+
+    steps!(crate::MyWorld => {
+        given r#"I am using the in-memory store"# |world, _step| {
+            world.context = crate::ScenarioContext::memory();
+        };
+    });
+}
+
+mod migration_steps {
+    use cucumber_rust::steps;
+    use mjolnir::migrations;
+    use sqlx::{postgres::PgQueryAs, Executor};
+
+    steps!(crate::MyWorld => {
+        // The scenario then uses that database. It is built as provision.sh did before there were
+        // migrations, with the files up to the baseline.
+        given regex r#"^a database built by provision.sh, holding the feature '(.*)'$"# (String) |world, name, _step| {
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let url = world.context.settings.database.url().unwrap().to_string();
+                let mut database = world.context.settings.database.clone();
+                database.url = Some(crate::create_database(&url).await);
+                let pool = mjolnir::connect_db(&database, world.context.logger.clone())
+                    .await
+                    .unwrap();
+                let mut conn = pool.acquire().await.unwrap();
+                for migration in migrations::MIGRATIONS.iter().filter(|m| m.version <= migrations::BASELINE) {
+                    conn.execute(migration.sql).await.unwrap();
+                }
+                let _feature: (uuid::Uuid,) = sqlx::query_as("SELECT id FROM main.create_or_replace_feature($1, '', '{}')")
+                    .bind(name)
+                    .fetch_one(&mut conn)
+                    .await
+                    .unwrap();
+                world.context.pool = Some(pool);
+            });
+        };
+
+        when r#"I apply the pending migrations"# |world, _step| {
+            let mut rt = crate::runtime();
+            world.server.migrated = Some(rt.block_on(async {
+                migrations::migrate(world.context.pool().unwrap(), &world.context.logger)
+                    .await
+                    .unwrap()
+            }));
+        };
+
+        then r#"I find that applying them again does nothing"# |world, _step| {
+            let mut rt = crate::runtime();
+            let migrated = rt.block_on(async {
+                migrations::migrate(world.context.pool().unwrap(), &world.context.logger)
+                    .await
+                    .unwrap()
+            });
+            assert!(migrated.is_empty(), "{:?}", migrated);
+        };
+
+        then r#"I find that the migrations after the baseline were applied"# |world, _step| {
+            let expected: Vec<i32> = (migrations::BASELINE + 1..=migrations::latest_version()).collect();
+            assert_eq!(world.server.migrated.as_ref().unwrap(), &expected);
+        };
+
+        then regex r#"^I find that the feature '(.*)' is kept$"# (String) |world, name, _step| {
+            let mut rt = crate::runtime();
+            let (count,): (i64,) = rt.block_on(async {
+                sqlx::query_as("SELECT COUNT(*) FROM main.features WHERE name = $1")
+                    .bind(name)
+                    .fetch_one(world.context.pool().unwrap())
+                    .await
+                    .unwrap()
+            });
+            assert_eq!(count, 1);
+        };
+
+        then r#"I find that the database is at the latest version"# |world, _step| {
+            let mut rt = crate::runtime();
+            let applied = rt.block_on(async {
+                migrations::applied_versions(world.context.pool().unwrap(), &world.context.logger)
+                    .await
+                    .unwrap()
+            });
+            let known: Vec<i32> = migrations::MIGRATIONS.iter().map(|m| m.version).collect();
+            assert_eq!(applied, known);
+            assert_eq!(applied.last(), Some(&migrations::latest_version()));
+        };
+    });
+}
+
+mod assertion_steps {
+    use cucumber_rust::steps;
+    use mjolnir::runner::{bragi, matching::MatchMode, steps::StepKind};
+
+    // Check the step as the runner would, in a scenario with the given tags.
+    fn check(world: &mut crate::MyWorld, value: &str, tags: &[String]) {
+        let (assertion, mode) = match mjolnir::runner::steps::parse_step(value) {
+            Some(StepKind::Assert { assertion, mode }) => (assertion, mode),
+            other => panic!("'{}' is not an assertion: {:?}", value, other),
+        };
+        let mode = mode.unwrap_or_else(|| MatchMode::from_tags(tags).unwrap());
+        world.search.checked =
+            Some(assertion.evaluate(world.search.places.as_ref().unwrap(), mode));
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^the bragi results in '(.*)'$"# (String) |world, path, _step| {
+            let body = std::fs::read_to_string(&path).unwrap();
+            world.search.places = Some(bragi::parse_places(&body).unwrap());
+        };
+
+        when regex r#"^I check the step "(.*)"$"# (String) |world, value, _step| {
+            check(world, &value, &[]);
+        };
+
+        when regex r#"^I check the step "(.*)" in a scenario tagged '(.*)'$"# (String, String) |world, value, tags, _step| {
+            let tags: Vec<String> = tags.split_whitespace().map(String::from).collect();
+            check(world, &value, &tags);
+        };
+
+        then r#"I find that the step passes"# |world, _step| {
+            let checked = world.search.checked.as_ref().unwrap();
+            assert!(checked.is_ok(), "{:?}", checked);
+        };
+
+        then regex r#"^I find that the step passes with a score of ([0-9.]+)$"# (String) |world, expected, _step| {
+            match world.search.checked.as_ref().unwrap() {
+                Ok(Some(score)) => assert_eq!(format!("{:.2}", score), expected),
+                other => panic!("Expected a score of {}, got {:?}", expected, other),
+            }
+        };
+
+        then regex r#"^I find that the step fails with a message containing '(.*)'$"# (String) |world, expected, _step| {
+            match world.search.checked.as_ref().unwrap() {
+                Ok(_) => panic!("The step passed"),
+                Err(message) => assert!(
+                    message.contains(&expected.replace("\\n", "\n")),
+                    "'{}' does not contain '{}'",
+                    message,
+                    expected
+                ),
+            }
+        };
+    });
+}
+
+mod search_steps {
+    use cucumber_rust::steps;
+    use mjolnir::runner::{bragi, steps::StepKind};
+
+    steps!(crate::MyWorld => {
+        when regex r#"^I prepare the search step "(.*)"$"# (String) |world, value, _step| {
+            world.search.request = match mjolnir::runner::steps::parse_step(&value) {
+                Some(StepKind::Search { query, params, within_shape }) => {
+                    let url = bragi::search_url("http://localhost:4000/", &query, &params).unwrap();
+                    Some((url.to_string(), within_shape))
+                }
+                Some(StepKind::Reverse { coord }) => {
+                    let url = bragi::reverse_url("http://localhost:4000/", &coord).unwrap();
+                    Some((url.to_string(), false))
+                }
+                _ => None,
+            };
+        };
+
+        then regex r#"^I find that it requests '(.*)'$"# (String) |world, expected, _step| {
+            let (url, _) = world.search.request.as_ref().expect("not a search step");
+            assert_eq!(url, &expected);
+        };
+
+        then regex r#"^I find that it (sends|does not send) a shape$"# (String) |world, sends, _step| {
+            let (_, within_shape) = world.search.request.as_ref().expect("not a search step");
+            assert_eq!(*within_shape, sends == "sends");
+        };
+
+        then r#"I find that it is not a search step"# |world, _step| {
+            assert!(world.search.request.is_none(), "{:?}", world.search.request);
+        };
+    });
+}
+
+mod quality_steps {
+    use cucumber_rust::steps;
+    use mjolnir::model::runs::quality::{self, QualityGroup, RankSample};
+    use mjolnir::runner::{matching::MatchMode, steps::StepKind};
+
+    fn group(name: &str) -> QualityGroup {
+        match name {
+            "run" => QualityGroup::Run,
+            "feature" => QualityGroup::Feature,
+            "tag" => QualityGroup::Tag,
+            _ => QualityGroup::IndexType,
+        }
+    }
+
+    steps!(crate::MyWorld => {
+        // Ranks are separated by commas, '-' standing for an expected result not found.
+        given regex r#"^the ranks '(.*)' in the feature '(.*)' tagged '(.*)' indexing '(.*)'$"# (String, String, String, String) |world, ranks, feature, tags, index_types, _step| {
+            for rank in ranks.split(',').map(str::trim) {
+                world.runs.samples.push(RankSample {
+                    feature: feature.clone(),
+                    tags: tags.split_whitespace().map(String::from).collect(),
+                    index_types: index_types.split_whitespace().map(String::from).collect(),
+                    rank: rank.parse().ok(),
+                });
+            }
+        };
+
+        when r#"I compute the quality metrics"# |world, _step| {
+            world.runs.quality = quality::compute_metrics(&uuid::Uuid::new_v4(), &world.runs.samples);
+        };
+
+        when regex r#"^I record the quality metrics of (\d+) runs?$"# (usize) |world, count, _step| {
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let runs = &world.context.store.runs;
+                for _ in 0..count {
+                    let run = runs
+                        .create_run(Vec::new(), "http://localhost:4000", &world.context)
+                        .await
+                        .unwrap();
+                    let metrics = quality::compute_metrics(&run.id, &world.runs.samples);
+                    runs.create_quality_metrics(metrics, &world.context).await.unwrap();
+                }
+            });
+        };
+
+        when regex r#"^I fetch the quality trend of the (run|feature|tag|index type) '(.*)' over (\d+) runs$"# (String, String, i32) |world, group_by, name, limit, _step| {
+            let mut rt = crate::runtime();
+            world.runs.quality = rt.block_on(async {
+                world
+                    .context
+                    .store
+                    .runs
+                    .fetch_quality_trend(group(&group_by), &name, limit, &world.context)
+                    .await
+                    .unwrap()
+            });
+        };
+
+        then regex r#"^I find (\d+) quality metrics$"# (usize) |world, count, _step| {
+            assert_eq!(world.runs.quality.len(), count, "{:?}", world.runs.quality);
+        };
+
+        then regex r#"^I find that the (run|feature|tag|index type) '(.*)' has (\d+) quer(?:y|ies), an MRR of ([0-9.]+), a recall of ([0-9.]+), ([0-9.]+) and ([0-9.]+) at 1, 5 and 10, and an nDCG of ([0-9.]+)$"# (String, String, i32, String, String, String, String, String) |world, group_by, name, queries, mrr, recall_1, recall_5, recall_10, ndcg, _step| {
+            let group_by = group(&group_by);
+            let metrics = world
+                .runs
+                .quality
+                .iter()
+                .find(|m| m.group_by == group_by && m.group_name == name)
+                .unwrap_or_else(|| panic!("No metrics for {:?} '{}': {:?}", group_by, name, world.runs.quality));
+            assert_eq!(metrics.queries, queries);
+            let actual = [metrics.mrr, metrics.recall_at_1, metrics.recall_at_5, metrics.recall_at_10, metrics.ndcg]
+                .iter()
+                .map(|value| format!("{:.3}", value))
+                .collect::<Vec<_>>();
+            assert_eq!(actual, vec![mrr, recall_1, recall_5, recall_10, ndcg]);
+        };
+
+        then regex r#"^I find that the step "(.*)" ranks the expected result (\d+)$"# (String, usize) |world, value, rank, _step| {
+            let assertion = match mjolnir::runner::steps::parse_step(&value) {
+                Some(StepKind::Assert { assertion, .. }) => assertion,
+                other => panic!("'{}' is not an assertion: {:?}", value, other),
+            };
+            let places = world.search.places.as_ref().unwrap();
+            assert_eq!(assertion.rank(places, MatchMode::Exact), Some(rank));
+        };
+    });
+}
+
+mod comparison_steps {
+    use cucumber_rust::steps;
+    use mjolnir::{
+        model::{features::step::StepType, runs::ResultStatus},
+        report::{self, compare},
+    };
+    use uuid::Uuid;
+
+    // The id of the run with the given name, created if need be.
+    async fn run_id(world: &mut crate::MyWorld, name: &str) -> Uuid {
+        if let Some((_, id)) = world.runs.named.iter().find(|(n, _)| n == name) {
+            return *id;
+        }
+        let url = format!("http://{}:4000", name);
+        let run = world
+            .context
+            .store
+            .runs
+            .create_run(Vec::new(), &url, &world.context)
+            .await
+            .unwrap();
+        world.runs.named.push((String::from(name), run.id));
+        run.id
+    }
+
+    fn named(world: &crate::MyWorld, name: &str) -> Uuid {
+        world.runs.named.iter().find(|(n, _)| n == name).unwrap().1
+    }
+
+    steps!(crate::MyWorld => {
+        // A scenario searching once, and looking for the result expected at the given rank, 0
+        // if it was not found.
+        given regex r#"^the (baseline|candidate) run where '(.*)' (passed|failed), with the expected result at (\d+) among '(.*)'$"# (String, String, String, i32, String) |world, run, scenario, status, rank, results, _step| {
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let run = run_id(world, &run).await;
+                let status = if status == "passed" { ResultStatus::Passed } else { ResultStatus::Failed };
+                let runs = &world.context.store.runs;
+                let result = runs
+                    .create_scenario_result(&run, &Uuid::new_v4(), "Paris", &scenario, Vec::new(), status, 10.0, None, None, 1, false, &world.context)
+                    .await
+                    .unwrap();
+                let results: Vec<String> = results.split(", ").map(String::from).collect();
+                runs.create_step_result(
+                    &result.id, 0, StepType::When, "I search for 'paris'", ResultStatus::Passed,
+                    None, 5.0, None, None, None, Some(results), &world.context,
+                )
+                .await
+                .unwrap();
+                let message = if status == ResultStatus::Passed { None } else { Some(String::from("Could not find 'Paris'")) };
+                runs.create_step_result(
+                    &result.id, 1, StepType::Then, "I find 'Paris' of type 'zone' within the first 1 result", status,
+                    message, 1.0, None, None, Some(rank), None, &world.context,
+                )
+                .await
+                .unwrap();
+            });
+        };
+
+        when r#"I compare the candidate run with the baseline run"# |world, _step| {
+            let (a, b) = (named(world, "baseline"), named(world, "candidate"));
+            let mut rt = crate::runtime();
+            world.runs.comparison = Some(rt.block_on(async {
+                compare::compare_runs(&a, &b, &world.context).await.unwrap()
+            }));
+        };
+
+        then regex r#"^I find that '(.*)' is newly (passing|failing)$"# (String, String) |world, scenario, change, _step| {
+            let comparison = world.runs.comparison.as_ref().unwrap();
+            let changes = if change == "passing" { &comparison.newly_passing } else { &comparison.newly_failing };
+            assert!(changes.iter().any(|c| c.scenario_name == scenario), "{:?}", comparison);
+        };
+
+        then regex r#"^I find that the expected result of '(.*)' moved from (\d+) to (\d+)$"# (String, i32, i32) |world, scenario, before, after, _step| {
+            let comparison = world.runs.comparison.as_ref().unwrap();
+            assert!(
+                comparison
+                    .rank_changes
+                    .iter()
+                    .any(|c| c.scenario_name == scenario && c.before == before && c.after == after),
+                "{:?}",
+                comparison.rank_changes
+            );
+        };
+
+        then regex r#"^I find that the results of '(.*)' (changed|did not change)$"# (String, String) |world, scenario, changed, _step| {
+            let comparison = world.runs.comparison.as_ref().unwrap();
+            let found = comparison.result_changes.iter().any(|c| c.scenario_name == scenario);
+            assert_eq!(found, changed == "changed", "{:?}", comparison.result_changes);
+        };
+
+        then regex r#"^I find that the comparison report contains '(.*)'$"# (String) |world, expected, _step| {
+            let (a, b) = (named(world, "baseline"), named(world, "candidate"));
+            let mut rt = crate::runtime();
+            let text = rt.block_on(async {
+                report::generate_report(Some(b), report::ReportFormat::Comparison, Some(a), &world.context)
+                    .await
+                    .unwrap()
+            });
+            assert!(text.contains(&expected), "'{}' does not contain '{}'", text, expected);
+        };
+    });
+}
+
+mod snapshot_steps {
+    use cucumber_rust::steps;
+    use mjolnir::runner::{bragi, matching::MatchMode, snapshots, steps::StepKind};
+    use uuid::Uuid;
+
+    fn read_places(path: &str) -> Vec<bragi::Place> {
+        let body = std::fs::read_to_string(path).unwrap();
+        bragi::parse_places(&body).unwrap()
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^the snapshot of '(.*)' / '(.*)' at (\d+) recorded from the bragi results in '(.*)'$"# (String, String, i32, String) |world, feature, scenario, position, path, _step| {
+            let results = snapshots::snapshot_places(&read_places(&path), 10);
+            let mut rt = crate::runtime();
+            world.runs.snapshot = Some(rt.block_on(async {
+                world
+                    .context
+                    .store
+                    .runs
+                    .save_snapshot(&feature, &scenario, position, results, &world.context)
+                    .await
+                    .unwrap()
+            }));
+        };
+
+        // The step is checked against the snapshot recorded by the scenario, with the results
+        // given by the last 'bragi results' step.
+        when regex r#"^I check the step "(.*)" against the snapshot$"# (String) |world, value, _step| {
+            let (limit, distance) = match mjolnir::runner::steps::parse_step(&value) {
+                Some(StepKind::Snapshot { limit, distance }) => (limit, distance),
+                other => panic!("'{}' is not a snapshot step: {:?}", value, other),
+            };
+            let snapshot = world.runs.snapshot.as_ref().unwrap();
+            let expected: Vec<_> = snapshot.results.iter().take(limit).cloned().collect();
+            let actual = snapshots::snapshot_places(world.search.places.as_ref().unwrap(), limit);
+            world.search.checked = Some(
+                snapshots::compare(&expected, &actual, MatchMode::Exact, distance).map(|()| None),
+            );
+        };
+
+        when regex r#"^I propose the bragi results in '(.*)' for the snapshot$"# (String) |world, path, _step| {
+            let results = snapshots::snapshot_places(&read_places(&path), 10);
+            let snapshot = world.runs.snapshot.take().unwrap();
+            let mut rt = crate::runtime();
+            world.runs.snapshot = Some(rt.block_on(async {
+                world
+                    .context
+                    .store
+                    .runs
+                    .propose_snapshot(
+                        &snapshot.feature_name,
+                        &snapshot.scenario_name,
+                        snapshot.position,
+                        results,
+                        &Uuid::new_v4(),
+                        &world.context,
+                    )
+                    .await
+                    .unwrap()
+            }));
+        };
+
+        when r#"I accept the proposed snapshot"# |world, _step| {
+            let step = world.runs.snapshot.as_ref().unwrap().pending_step.unwrap();
+            let mut rt = crate::runtime();
+            world.runs.snapshot = Some(rt.block_on(async {
+                world.context.store.runs.accept_snapshot(&step, &world.context).await.unwrap()
+            }));
+        };
+
+        then r#"I find that a snapshot cannot be accepted from another step result"# |world, _step| {
+            let mut rt = crate::runtime();
+            let accepted = rt.block_on(async {
+                world.context.store.runs.accept_snapshot(&Uuid::new_v4(), &world.context).await
+            });
+            assert!(accepted.is_err(), "{:?}", accepted);
+        };
+
+        then regex r#"^I find that the snapshot has '(.*)' at (\d+)$"# (String, usize) |world, label, position, _step| {
+            let snapshot = world.runs.snapshot.as_ref().unwrap();
+            assert_eq!(snapshot.results[position - 1].label, label, "{:?}", snapshot.results);
+        };
+
+        then r#"I find that nothing is pending for the snapshot"# |world, _step| {
+            let snapshot = world.runs.snapshot.as_ref().unwrap();
+            assert!(snapshot.pending.is_none(), "{:?}", snapshot.pending);
+            assert!(snapshot.pending_step.is_none(), "{:?}", snapshot.pending_step);
+        };
+    });
+}
+
+mod latency_steps {
+    use cucumber_rust::steps;
+    use mjolnir::{
+        model::runs::latency::{self, LatencyMetrics, LatencySample},
+        report::compare,
+        runner::{benchmark, steps::StepKind},
+    };
+
+    fn latencies(list: &str) -> Vec<f64> {
+        list.split(',').map(|l| l.trim().parse().unwrap()).collect()
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^the replays of a search with the latencies '(.*)' and (\d+) errors?, in (\d+) ms$"# (String, usize, f64) |world, list, errors, elapsed, _step| {
+            world.load.replays.push(LatencySample {
+                latencies: latencies(&list),
+                errors,
+                elapsed,
+            });
+        };
+
+        // The latency is the same at every percentile.
+        given regex r#"^the latency of '(.*)' in the (baseline|candidate) run is (\d+) ms$"# (String, String, f64) |world, scenario, run, value, _step| {
+            let run = world.runs.named.iter().find(|(n, _)| *n == run).unwrap().1;
+            let metrics = LatencyMetrics {
+                run,
+                feature_name: String::from("Paris"),
+                scenario_name: scenario,
+                iterations: 10,
+                concurrency: 1,
+                requests: 10,
+                errors: 0,
+                error_rate: 0.0,
+                p50: value,
+                p95: value,
+                p99: value,
+                throughput: 1000.0 / value,
+                created_at: chrono::Utc::now(),
+            };
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                world
+                    .context
+                    .store
+                    .runs
+                    .create_latency_metrics(vec![metrics], &world.context)
+                    .await
+                    .unwrap()
+            });
+        };
+
+        when r#"I compute the latency metrics of the scenario"# |world, _step| {
+            let run = uuid::Uuid::new_v4();
+            world.load.latency = latency::compute_metrics(&run, "Paris", "Search", 10, 2, &world.load.replays);
+        };
+
+        when regex r#"^I check the step "(.*)" against the latencies '(.*)'$"# (String, String) |world, value, list, _step| {
+            let (maximum, percentile) = match mjolnir::runner::steps::parse_step(&value) {
+                Some(StepKind::Latency { maximum, percentile }) => (maximum, percentile),
+                other => panic!("'{}' is not a latency step: {:?}", value, other),
+            };
+            world.search.checked = Some(
+                benchmark::check_latency(&latencies(&list), maximum, percentile).map(|()| None),
+            );
+        };
+
+        then regex r#"^I find that the latency is ([0-9.]+) ms at p50, ([0-9.]+) ms at p95 and ([0-9.]+) ms at p99$"# (String, String, String) |world, p50, p95, p99, _step| {
+            let metrics = world.load.latency.as_ref().unwrap();
+            assert_eq!(format!("{:.1}", metrics.p50), p50);
+            assert_eq!(format!("{:.1}", metrics.p95), p95);
+            assert_eq!(format!("{:.1}", metrics.p99), p99);
+        };
+
+        then regex r#"^I find that there were (\d+) requests, at ([0-9.]+) requests per second, with an error rate of ([0-9.]+)$"# (i32, String, String) |world, requests, throughput, error_rate, _step| {
+            let metrics = world.load.latency.as_ref().unwrap();
+            assert_eq!(metrics.requests, requests);
+            assert_eq!(format!("{:.1}", metrics.throughput), throughput);
+            assert_eq!(format!("{:.2}", metrics.error_rate), error_rate);
+        };
+
+        then r#"I find that there are no latency metrics"# |world, _step| {
+            assert!(world.load.latency.is_none(), "{:?}", world.load.latency);
+        };
+
+        then regex r#"^I find that the latency of '(.*)' changed by ([+-][0-9]+)% at p95$"# (String, String) |world, scenario, change, _step| {
+            let comparison = world.runs.comparison.as_ref().unwrap();
+            let found: Vec<&compare::LatencyChange> = comparison
+                .latency_changes
+                .iter()
+                .filter(|c| c.scenario_name == scenario)
+                .collect();
+            assert_eq!(found.len(), 1, "{:?}", comparison.latency_changes);
+            assert_eq!(format!("{:+.0}", found[0].p95_change * 100.0), change);
+        };
+
+        then regex r#"^I find that the latency of '(.*)' is not compared$"# (String) |world, scenario, _step| {
+            let comparison = world.runs.comparison.as_ref().unwrap();
+            assert!(
+                comparison.latency_changes.iter().all(|c| c.scenario_name != scenario),
+                "{:?}",
+                comparison.latency_changes
+            );
+        };
+    });
+}
+
+mod load_steps {
+    use cucumber_rust::steps;
+    use mjolnir::load::{self, corpus};
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use warp::Filter;
+
+    /// A bragi answering searches with no results, after the given delay, and with an error
+    /// when the query is 'error'. It stops when it is dropped.
+    pub struct Stub {
+        server: crate::StubServer,
+        requests: Arc<AtomicUsize>, // count of the searches it received
+    }
+
+    impl Stub {
+        fn start(delay: u64) -> Stub {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let counter = requests.clone();
+            let autocomplete = warp::path("autocomplete")
+                .and(warp::query::<HashMap<String, String>>())
+                .and_then(move |params: HashMap<String, String>| {
+                    let counter = counter.clone();
+                    async move {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::delay_for(Duration::from_millis(delay)).await;
+                        let status = match params.get("q").map(String::as_str) {
+                            Some("error") => warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                            _ => warp::http::StatusCode::OK,
+                        };
+                        let body =
+                            serde_json::json!({ "type": "FeatureCollection", "features": [] });
+                        Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&body),
+                            status,
+                        ))
+                    }
+                });
+            Stub {
+                server: crate::StubServer::start(autocomplete),
+                requests,
+            }
+        }
+    }
+
+    fn send(world: &mut crate::MyWorld, endpoint: &str, requests: usize, rate: f64) {
+        let queries = &world.load.corpus.as_ref().unwrap().queries;
+        let test = load::LoadTest { rate, requests };
+        let mut rt = crate::runtime();
+        world.load.report = Some(
+            rt.block_on(async { load::run(queries, endpoint, &test).await })
+                .map_err(|err| format!("{}", err)),
+        );
+    }
+
+    fn report(world: &crate::MyWorld) -> &load::LoadReport {
+        match world.load.report.as_ref().unwrap() {
+            Ok(report) => report,
+            Err(err) => panic!("The load test failed: {}", err),
+        }
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^a bragi stub answering in (\d+) ms$"# (u64) |world, delay, _step| {
+            world.load.stub = Some(Stub::start(delay));
+        };
+
+        given regex r#"^a corpus of the searches '(.*)'$"# (String) |world, queries, _step| {
+            let text: String = queries
+                .split(", ")
+                .map(|q| format!("{}\n", serde_json::json!({ "q": q })))
+                .collect();
+            world.load.corpus = Some(corpus::parse_log(&text));
+        };
+
+        when regex r#"^I import the query log '(.*)'$"# (String) |world, path, _step| {
+            let text = std::fs::read_to_string(&path).unwrap();
+            world.load.corpus = Some(corpus::parse_log(&text));
+        };
+
+        when regex r#"^I send (\d+) requests from the corpus at (\d+) requests per second$"# (usize, f64) |world, requests, rate, _step| {
+            let url = world.load.stub.as_ref().unwrap().server.url.clone();
+            send(world, &url, requests, rate);
+        };
+
+        when regex r#"^I send (\d+) requests from the corpus to '(.*)' at (\d+) requests per second$"# (usize, String, f64) |world, requests, url, rate, _step| {
+            send(world, &url, requests, rate);
+        };
+
+        then regex r#"^I find (\d+) searches in the corpus, and (\d+) lines skipped$"# (usize, usize) |world, queries, skipped, _step| {
+            let log = world.load.corpus.as_ref().unwrap();
+            assert_eq!(log.queries.len(), queries, "{:?}", log.queries);
+            assert_eq!(log.skipped.len(), skipped, "{:?}", log.skipped);
+        };
+
+        then regex r#"^I find that line (\d+) is skipped, as '(.*)'$"# (usize, String) |world, line, reason, _step| {
+            let log = world.load.corpus.as_ref().unwrap();
+            assert!(
+                log.skipped.iter().any(|s| s.line == line && s.reason.contains(&reason)),
+                "{:?}",
+                log.skipped
+            );
+        };
+
+        then regex r#"^I find that search (\d+) requests '(.*)'$"# (usize, String) |world, position, expected, _step| {
+            let query = &world.load.corpus.as_ref().unwrap().queries[position - 1];
+            assert_eq!(query.url("http://localhost:4000/").unwrap().as_str(), expected);
+        };
+
+        then r#"I find that the corpus reads back the same"# |world, _step| {
+            let queries = &world.load.corpus.as_ref().unwrap().queries;
+            let log = corpus::parse_log(&corpus::write_corpus(queries).unwrap());
+            assert!(log.skipped.is_empty(), "{:?}", log.skipped);
+            assert_eq!(&log.queries, queries);
+        };
+
+        then regex r#"^I find that (\d+) requests were sent, with (\d+) errors?$"# (usize, usize) |world, requests, errors, _step| {
+            let report = report(world);
+            assert_eq!(report.requests, requests, "{:?}", report);
+            assert_eq!(report.errors, errors, "{:?}", report);
+        };
+
+        then regex r#"^I find that the stub received (\d+) requests$"# (usize) |world, requests, _step| {
+            let stub = world.load.stub.as_ref().unwrap();
+            assert_eq!(stub.requests.load(Ordering::SeqCst), requests);
+        };
+
+        then regex r#"^I find that the latency at p50 is at least (\d+) ms$"# (f64) |world, latency, _step| {
+            let report = report(world);
+            assert!(report.p50 >= latency, "{:?}", report);
+        };
+
+        then regex r#"^I find that the histogram counts (\d+) requests$"# (usize) |world, count, _step| {
+            let report = report(world);
+            let counted: usize = report.histogram.iter().map(|bucket| bucket.count).sum();
+            assert_eq!(counted, count, "{:?}", report.histogram);
+        };
+
+        then regex r#"^I find that the load test lasted at least ([0-9.]+) s$"# (f64) |world, duration, _step| {
+            let report = report(world);
+            assert!(report.duration >= duration, "{:?}", report);
+        };
+
+        then regex r#"^I find that the load report contains '(.*)'$"# (String) |world, expected, _step| {
+            let text = load::render(report(world));
+            assert!(text.contains(&expected), "'{}' does not contain '{}'", text, expected);
+        };
+
+        then regex r#"^I find that the load test fails with '(.*)'$"# (String) |world, expected, _step| {
+            match world.load.report.as_ref().unwrap() {
+                Ok(report) => panic!("The load test passed: {:?}", report),
+                Err(err) => assert!(err.contains(&expected), "'{}' does not contain '{}'", err, expected),
+            }
+        };
+    });
+}
+
+mod flaky_steps {
+    use cucumber_rust::steps;
+    use mjolnir::{
+        model::runs::{run::ScenarioResult, ResultStatus, RunStatus},
+        report, runner,
+    };
+    use std::{
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+    use uuid::Uuid;
+    use warp::Filter;
+
+    /// How the stub answers the searches for Paris (it finds nothing else).
+    #[derive(Clone, Copy)]
+    pub enum Answers {
+        ParisEveryOtherTime,
+        Paris,
+        ServerError,
+    }
+
+    /// A bragi answering searches as told. Its status gives its version. It stops when it is
+    /// dropped.
+    pub fn stub_bragi(version: String, answers: Answers) -> crate::StubServer {
+        let searches = Arc::new(AtomicUsize::new(0));
+        let status = warp::path("status").map(move || {
+            warp::reply::json(&serde_json::json!({ "bragi": { "version": version } }))
+        });
+        let autocomplete = warp::path("autocomplete")
+            .and(warp::query::<std::collections::HashMap<String, String>>())
+            .map(move |params: std::collections::HashMap<String, String>| {
+                let paris = params.get("q").map(String::as_str) == Some("paris")
+                    && match answers {
+                        Answers::ParisEveryOtherTime => searches.fetch_add(1, Ordering::SeqCst) % 2 == 1,
+                        Answers::Paris | Answers::ServerError => true,
+                    };
+                let features = if paris {
+                    vec![serde_json::json!({
+                        "type": "Feature",
+                        "geometry": { "type": "Point", "coordinates": [2.3483915, 48.8534951] },
+                        "properties": { "geocoding": { "id": "admin:osm:relation:7444", "type": "zone", "label": "Paris" } }
+                    })]
+                } else {
+                    Vec::new()
+                };
+                let status = match answers {
+                    Answers::ServerError => warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    _ => warp::http::StatusCode::OK,
+                };
+                warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "type": "FeatureCollection", "features": features })),
+                    status,
+                )
+            });
+        crate::StubServer::start(status.or(autocomplete))
+    }
+
+    fn results(world: &crate::MyWorld, scenario: &str) -> Vec<ScenarioResult> {
+        let mut rt = crate::runtime();
+        rt.block_on(async {
+            let runs = &world.context.store.runs;
+            let mut results = Vec::new();
+            for run in runs.fetch_all_runs(&world.context).await.unwrap() {
+                results.extend(
+                    runs.fetch_scenario_results_by_run_id(&run.id, &world.context)
+                        .await
+                        .unwrap()
+                        .into_iter()
+                        .filter(|result| result.scenario_name == scenario),
+                );
+            }
+            results
+        })
+    }
+
+    fn check_result(
+        world: &crate::MyWorld,
+        scenario: &str,
+        status: &str,
+        attempts: i32,
+        quarantined: bool,
+    ) {
+        let results = results(world, scenario);
+        assert_eq!(results.len(), 1, "{:?}", results);
+        let status = if status == "passed" {
+            ResultStatus::Passed
+        } else {
+            ResultStatus::Failed
+        };
+        assert_eq!(results[0].status, status, "{:?}", results[0]);
+        assert_eq!(results[0].attempts, attempts, "{:?}", results[0]);
+        assert_eq!(results[0].quarantined, quarantined, "{:?}", results[0]);
+    }
+
+    // The report of the last run, in the format named by the step.
+    fn run_report(world: &crate::MyWorld, format: &str) -> String {
+        let id = world.runs.run.as_ref().unwrap().id;
+        let format = match format {
+            "junit" => report::ReportFormat::Junit,
+            _ => report::ReportFormat::CucumberJson,
+        };
+        let mut rt = crate::runtime();
+        rt.block_on(async {
+            report::generate_report(Some(id), format, None, &world.context)
+                .await
+                .unwrap()
+        })
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^'(.*)' (passed|failed) at attempt (\d+) against the environment '(.*)' and bragi '(.*)'$"# (String, String, i32, String, String) |world, scenario, status, attempts, environment, version, _step| {
+            let status = if status == "passed" { ResultStatus::Passed } else { ResultStatus::Failed };
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                let runs = &world.context.store.runs;
+                let run = runs.create_run(Vec::new(), "http://localhost:4000", &world.context).await.unwrap();
+                runs.create_scenario_result(
+                    &run.id, &Uuid::new_v4(), "Paris", &scenario, Vec::new(), status, 10.0,
+                    Some(&environment), Some(&version), attempts, false, &world.context,
+                )
+                .await
+                .unwrap();
+                runs.finish_run(&run.id, RunStatus::Passed, &world.context).await.unwrap();
+            });
+        };
+
+        given regex r#"^a bragi at version '(.*)' which finds Paris every other time$"# (String) |world, version, _step| {
+            world.runs.bragi = Some(stub_bragi(version, Answers::ParisEveryOtherTime));
+        };
+
+        given regex r#"^the features of '(.*)'$"# (String) |world, filename, _step| {
+            let feature = gherkin_rust::Feature::parse_path(PathBuf::from(filename).as_path()).unwrap();
+            let mut rt = crate::runtime();
+            rt.block_on(async {
+                world
+                    .context
+                    .store
+                    .features
+                    .create_or_replace_feature_from_gherkin(feature, &world.context)
+                    .await
+                    .unwrap()
+            });
+        };
+
+        given regex r#"^scenarios are attempted (\d+) times?$"# (u32) |world, attempts, _step| {
+            let mut settings = (*world.context.settings).clone();
+            settings.runner.attempts = attempts;
+            world.context.settings = Arc::new(settings);
+        };
+
+        when r#"I run the scenarios against that bragi"# |world, _step| {
+            let url = world.runs.bragi.as_ref().unwrap().url.clone();
+            let mut rt = crate::runtime();
+            world.runs.run = Some(rt.block_on(async {
+                runner::run_scenarios(Vec::new(), Some(url), None, &world.context)
+                    .await
+                    .unwrap()
+            }));
+        };
+
+        when r#"I look for flaky scenarios"# |world, _step| {
+            let mut rt = crate::runtime();
+            world.runs.flaky = rt.block_on(async {
+                world.context.store.runs.fetch_flaky_scenarios(&world.context).await.unwrap()
+            });
+        };
+
+        then regex r#"^I find that the run (passed|failed)$"# (String) |world, status, _step| {
+            let run = world.runs.run.as_ref().unwrap();
+            let expected = if status == "passed" { RunStatus::Passed } else { RunStatus::Failed };
+            assert_eq!(run.status, expected, "{:?}", run);
+        };
+
+        then regex r#"^I find that '(.*)' (passed|failed) at attempt (\d+)$"# (String, String, i32) |world, scenario, status, attempts, _step| {
+            check_result(world, &scenario, &status, attempts, false);
+        };
+
+        then regex r#"^I find that '(.*)' (passed|failed) at attempt (\d+), quarantined$"# (String, String, i32) |world, scenario, status, attempts, _step| {
+            check_result(world, &scenario, &status, attempts, true);
+        };
+
+        then regex r#"^I find that '(.*)' ran against bragi '(.*)', with no environment$"# (String, String) |world, scenario, version, _step| {
+            let results = results(world, &scenario);
+            assert_eq!(results[0].geocoder_version.as_deref(), Some(version.as_str()), "{:?}", results[0]);
+            assert_eq!(results[0].environment, None, "{:?}", results[0]);
+        };
+
+        then regex r#"^I find that '(.*)' is flaky, with (\d+) passed, (\d+) failed and (\d+) retried$"# (String, i32, i32, i32) |world, scenario, passed, failed, retried, _step| {
+            let found: Vec<_> = world.runs.flaky.iter().filter(|f| f.scenario_name == scenario).collect();
+            assert_eq!(found.len(), 1, "{:?}", world.runs.flaky);
+            assert_eq!((found[0].passed, found[0].failed, found[0].retried), (passed, failed, retried), "{:?}", found[0]);
+        };
+
+        then regex r#"^I find that '(.*)' is not flaky$"# (String) |world, scenario, _step| {
+            assert!(world.runs.flaky.iter().all(|f| f.scenario_name != scenario), "{:?}", world.runs.flaky);
+        };
+
+        then regex r#"^I find that the (junit|cucumber) report of the run contains '(.*)'$"# (String, String) |world, format, expected, _step| {
+            let text = run_report(world, &format);
+            assert!(text.contains(&expected), "'{}' does not contain '{}'", text, expected);
+        };
+
+        then regex r#"^I find that the (junit|cucumber) report of the run does not contain '(.*)'$"# (String, String) |world, format, unexpected, _step| {
+            let text = run_report(world, &format);
+            assert!(!text.contains(&unexpected), "'{}' contains '{}'", text, unexpected);
+        };
+    });
+}
+
+mod cli_steps {
+    use crate::flaky_steps::{stub_bragi, Answers};
+    use cucumber_rust::steps;
+    use std::process::Command;
+
+    // Run the command line client, which reaches the database of the tests through DATABASE_URL.
+    fn cli(world: &mut crate::MyWorld, args: &[&str]) {
+        let output = Command::new(env!("CARGO_BIN_EXE_mjolnir-cli"))
+            .args(args)
+            .env("DATABASE_URL", crate::database_url())
+            .output()
+            .expect("mjolnir-cli");
+        world.cli = Some(output);
+    }
+
+    // The id of the run printed by the last command, as 'Run <id>: <status> (...)'.
+    fn printed_run(world: &crate::MyWorld) -> String {
+        let stdout = String::from_utf8_lossy(&world.cli.as_ref().unwrap().stdout).to_string();
+        let line = stdout
+            .lines()
+            .find(|line| line.starts_with("Run "))
+            .expect("run printed");
+        line["Run ".len()..].split(':').next().unwrap().to_string()
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^a bragi at version '(.*)' which finds Paris$"# (String) |world, version, _step| {
+            world.runs.bragi = Some(stub_bragi(version, Answers::Paris));
+        };
+
+        given regex r#"^a bragi at version '(.*)' which answers every search with an error$"# (String) |world, version, _step| {
+            world.runs.bragi = Some(stub_bragi(version, Answers::ServerError));
+        };
+
+        when regex r#"^I load '(.*)' with the command line client$"# (String) |world, path, _step| {
+            cli(world, &["load", &path]);
+        };
+
+        when regex r#"^I run the scenarios tagged '(.*)' against that bragi with the command line client$"# (String) |world, tag, _step| {
+            let url = world.runs.bragi.as_ref().unwrap().url.clone();
+            cli(world, &["run", "--tags", &tag, "--bragi-url", &url]);
+            world.runs.named = vec![(String::from("cli"), printed_run(world).parse().unwrap())];
+        };
+
+        when regex r#"^I run the scenarios tagged '(.*)' with the command line client, through the server '(.*)'$"# (String, String) |world, tag, server, _step| {
+            cli(world, &["--server", &server, "run", "--tags", &tag]);
+        };
+
+        when regex r#"^I print the '(.*)' report of the run with the command line client$"# (String) |world, format, _step| {
+            let run = world.runs.named[0].1.to_string();
+            cli(world, &["report", "--format", &format, "--run", &run]);
+        };
+
+        then regex r#"^I find that the command line client exits with (\d+), printing '(.*)'$"# (i32, String) |world, code, expected, _step| {
+            let output = world.cli.as_ref().unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert_eq!(output.status.code(), Some(code), "{}{}", stdout, stderr);
+            assert!(stdout.contains(&expected) || stderr.contains(&expected), "'{}{}' does not contain '{}'", stdout, stderr, expected);
+        };
+    });
+}
+
+/// A server answering with the given filter until it is dropped. It has its own runtime, in its
+/// own thread, so that it keeps answering while the steps block.
+pub struct StubServer {
+    pub url: String,
+    _shutdown: tokio::sync::oneshot::Sender<()>,
+}
+
+impl StubServer {
+    fn start<F, R>(filter: F) -> StubServer
+    where
+        F: warp::Filter<Extract = (R,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+        R: warp::Reply,
+    {
+        let (shutdown, stopped) = tokio::sync::oneshot::channel::<()>();
+        let (bound, address) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut rt = Runtime::new().unwrap();
+            rt.block_on(async move {
+                let (addr, server) =
+                    warp::serve(filter).bind_with_graceful_shutdown(([127, 0, 0, 1], 0), async {
+                        stopped.await.ok();
+                    });
+                bound.send(addr).unwrap();
+                server.await;
+            });
+        });
+        StubServer {
+            url: format!("http://{}", address.recv().unwrap()),
+            _shutdown: shutdown,
+        }
+    }
+}
+
+// A directory of its own for the scenario, removed along with the world.
+fn scratch_dir(world: &mut MyWorld) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mjolnir-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    world.scratch.push(dir.clone());
+    dir
+}
+
+// Execute a GraphQL query with the context of the world, and return the response.
+fn graphql(world: &MyWorld, query: &str, variables: serde_json::Value) -> serde_json::Value {
+    let request: juniper::http::GraphQLRequest = serde_json::from_value(serde_json::json!({
+        "query": query,
+        "variables": variables,
+    }))
+    .unwrap();
+    let mut rt = runtime();
+    let schema = mjolnir::gql::schema();
+    let response = rt.block_on(async { request.execute(&schema, &world.context).await });
+    serde_json::to_value(&response).unwrap()
+}
+
+// The context of a scenario. It is only built when a step first uses it, so that the scenarios
+// using the in-memory store never connect to Postgres.
+pub struct ScenarioContext {
+    memory: bool,
+    context: OnceCell<mjolnir::gql::Context>,
+}
+
+impl ScenarioContext {
+    fn postgres() -> Self {
+        ScenarioContext {
+            memory: false,
+            context: OnceCell::new(),
+        }
+    }
+
+    fn memory() -> Self {
+        ScenarioContext {
+            memory: true,
+            context: OnceCell::new(),
+        }
+    }
+
+    fn is_built(&self) -> bool {
+        self.context.get().is_some()
+    }
+}
+
+impl std::ops::Deref for ScenarioContext {
+    type Target = mjolnir::gql::Context;
+
+    fn deref(&self) -> &Self::Target {
+        let memory = self.memory;
+        self.context.get_or_init(|| {
+            if memory {
+                get_memory_context()
+            } else {
+                on_own_thread(get_gql_context)
+            }
+        })
+    }
+}
+
+impl std::ops::DerefMut for ScenarioContext {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let _ = std::ops::Deref::deref(self);
+        self.context.get_mut().unwrap()
+    }
+}
+
+fn get_logger() -> slog::Logger {
+    let decorator = slog_term::TermDecorator::new().build();
+    let drain = slog_term::FullFormat::new(decorator).build().fuse();
+    let drain = slog_async::Async::new(drain).build().fuse();
+    slog::Logger::root(drain, o!())
+}
+
+fn get_gql_context() -> mjolnir::gql::Context {
+    let url = database_url();
+    let mut rt = runtime();
+    rt.block_on(async {
+        let logger = get_logger();
+        let mut settings = Settings::new(None).unwrap();
+        settings.database.url = Some(String::from(url));
+        let pool = mjolnir::connect_db(&settings.database, logger.clone())
+            .await
+            .unwrap();
+
+        mjolnir::gql::Context {
+            pool: Some(pool),
+            logger,
+            settings: Arc::new(settings),
+            principal: Principal::new("cucumber", Role::Admin),
+            shutdown: Shutdown::default(),
+            store: Store::postgres(),
+        }
+    })
+}
+
+// A context without a database: everything is kept in memory.
+fn get_memory_context() -> mjolnir::gql::Context {
+    mjolnir::gql::Context {
+        pool: None,
+        logger: get_logger(),
+        settings: Arc::new(Settings::new(None).unwrap()),
+        principal: Principal::new("cucumber", Role::Admin),
+        shutdown: Shutdown::default(),
+        store: Store::memory(),
+    }
+}
+
+mod import_steps {
+    use cucumber_rust::steps;
+    use flate2::{write::GzEncoder, Compression};
+    use mjolnir::{model::features::import, utils::archive};
+    use sqlx::postgres::PgQueryAs;
+    use std::io::{Cursor, Write};
+
+    pub struct Archive {
+        filename: String,
+        files: Vec<(String, String)>, // path and content of the features
+        symlinks: Vec<(String, String)>, // path and target of the symlinks (tar only)
+    }
+
+    impl Archive {
+        // The archive as uploaded: a .tar.gz, a .zip, or anything else, its files concatenated.
+        fn bytes(&self) -> Vec<u8> {
+            if self.filename.ends_with(".tar.gz") {
+                let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+                for (path, content) in &self.files {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_size(content.len() as u64);
+                    header.set_mode(0o644);
+                    header.set_cksum();
+                    tar.append_data(&mut header, path, content.as_bytes())
+                        .unwrap();
+                }
+                for (path, target) in &self.symlinks {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_size(0);
+                    header.set_mode(0o777);
+                    header.set_link_name(target).unwrap();
+                    header.set_cksum();
+                    tar.append_data(&mut header, path, std::io::empty())
+                        .unwrap();
+                }
+                tar.into_inner().unwrap().finish().unwrap()
+            } else if self.filename.ends_with(".zip") {
+                let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+                for (path, content) in &self.files {
+                    zip.start_file(path.as_str(), zip::write::FileOptions::default())
+                        .unwrap();
+                    zip.write_all(content.as_bytes()).unwrap();
+                }
+                zip.finish().unwrap().into_inner()
+            } else {
+                self.files
+                    .iter()
+                    .flat_map(|(_, content)| content.bytes())
+                    .collect()
+            }
+        }
+    }
+
+    fn filename(name: &str) -> String {
+        format!("{}.feature", name.to_lowercase().replace(' ', "-"))
+    }
+
+    fn feature(name: &str, scenarios: &[&str]) -> String {
+        let mut feature = format!("Feature: {}\n", name);
+        for scenario in scenarios {
+            feature.push_str(&format!(
+                "\n  Scenario: {}\n    When I search for 'paris'\n",
+                scenario
+            ));
+        }
+        feature
+    }
+
+    fn names(names: &str) -> Vec<String> {
+        names.split(", ").map(String::from).collect()
+    }
+
+    // How many of the features with the given names are in the database.
+    fn count_features(world: &crate::MyWorld, names: Vec<String>) -> i64 {
+        let mut rt = crate::runtime();
+        let (count,): (i64,) = rt.block_on(async {
+            sqlx::query_as("SELECT COUNT(*) FROM main.features WHERE name = ANY($1)")
+                .bind(names)
+                .fetch_one(world.context.pool().unwrap())
+                .await
+                .unwrap()
+        });
+        count
+    }
+
+    // Extract the features of the archive, as the server does for uploads, and import them.
+    fn import_archive(world: &mut crate::MyWorld, atomic: bool) {
+        let archive = world.import.archive.as_ref().unwrap();
+        let bytes = archive.bytes();
+        let mut rt = crate::runtime();
+        let outcome = rt.block_on(async {
+            let sources = archive::extract_features(&archive.filename, &bytes)?;
+            import::import_features(sources, atomic, &world.context).await
+        });
+        world.import.report = Some(outcome.map_err(|err| format!("{}", err)));
+    }
+
+    steps!(crate::MyWorld => {
+        given regex r#"^an archive '(.*)' with the features '(.*)'$"# (String, String) |world, filename, features, _step| {
+            let files = names(&features)
+                .iter()
+                .map(|name| (self::filename(name), feature(name, &["Searching for Paris"])))
+                .collect();
+            world.import.archive = Some(Archive { filename, files, symlinks: Vec::new() });
+        };
+
+        given regex r#"^the archive also holds a symlink '(.*)' to '(.*)'$"# (String, String) |world, path, target, _step| {
+            world.import.archive.as_mut().unwrap().symlinks.push((path, target));
+        };
+
+        given regex r#"^the archive also holds the unparsable feature '(.*)'$"# (String) |world, name, _step| {
+            let content = format!("Feature: {}\n\n  This is not a scenario\n    When\n", name);
+            world.import.archive.as_mut().unwrap().files.push((filename(&name), content));
+        };
+
+        given regex r#"^the archive also holds the feature '(.*)' with twice the same scenario$"# (String) |world, name, _step| {
+            let content = feature(&name, &["Searching for Paris", "Searching for Paris"]);
+            world.import.archive.as_mut().unwrap().files.push((filename(&name), content));
+        };
+
+        given regex r#"^a directory on the server with the features '(.*)'$"# (String) |world, features, _step| {
+            let root = crate::scratch_dir(world);
+            let dir = root.join("features");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::create_dir_all(root.join("other")).unwrap();
+            for name in names(&features) {
+                std::fs::write(dir.join(filename(&name)), feature(&name, &["Searching for Paris"])).unwrap();
+            }
+            world.import.dir = Some(dir);
+        };
+
+        given regex r#"^the directory also holds symlinks to the feature '(.*)' outside of it, and to itself$"# (String) |world, name, _step| {
+            let dir = world.import.dir.clone().unwrap();
+            let other = dir.parent().unwrap().join("other");
+            std::fs::write(other.join(filename(&name)), feature(&name, &["Searching for Paris"])).unwrap();
+            std::os::unix::fs::symlink(other.join(filename(&name)), dir.join(filename(&name))).unwrap();
+            std::os::unix::fs::symlink(&other, dir.join("other")).unwrap();
+            std::os::unix::fs::symlink(&dir, dir.join("loop")).unwrap();
+        };
+
+        given regex r#"^the import paths are (none|that directory|another directory)$"# (String) |world, paths, _step| {
+            let dir = world.import.dir.clone().unwrap();
+            let mut settings = (*world.context.settings).clone();
+            settings.import.paths = match paths.as_str() {
+                "none" => Vec::new(),
+                "that directory" => vec![dir],
+                _ => vec![dir.parent().unwrap().join("other")],
+            };
+            world.context.settings = std::sync::Arc::new(settings);
+        };
+
+        when r#"I import the directory"# |world, _step| {
+            let dir = world.import.dir.as_ref().unwrap().display().to_string();
+            let mut rt = crate::runtime();
+            let outcome = rt.block_on(async {
+                import::import_features_from_dir(&dir, false, &world.context).await
+            });
+            world.import.report = Some(outcome.map_err(|err| format!("{}", err)));
+        };
+
+        when r#"I import the archive"# |world, _step| {
+            import_archive(world, false);
+        };
+
+        when r#"I import the archive atomically"# |world, _step| {
+            import_archive(world, true);
+        };
+
+        then regex r#"^I find that the import loaded (\d+) features? and failed (\d+)$"# (i32, i32) |world, loaded, failed, _step| {
+            let report = world.import.report.as_ref().unwrap().as_ref().unwrap();
+            assert_eq!(report.loaded, loaded, "{:?}", report);
+            assert_eq!(report.failed, failed, "{:?}", report);
+        };
+
+        then regex r#"^I find that the import is rejected with '(.*)'$"# (String) |world, message, _step| {
+            let err = world.import.report.as_ref().unwrap().as_ref().unwrap_err();
+            assert!(err.contains(&message), "{}", err);
+        };
+
+        then regex r#"^I find the features '(.*)'$"# (String) |world, features, _step| {
+            let names = names(&features);
+            assert_eq!(count_features(world, names.clone()), names.len() as i64);
+        };
+
+        then regex r#"^I find none of the features '(.*)'$"# (String) |world, features, _step| {
+            assert_eq!(count_features(world, names(&features)), 0);
+        };
+    });
 }
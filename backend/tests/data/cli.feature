@@ -0,0 +1,12 @@
+@cli
+Feature: Searching from the command line
+
+  @cli-paris
+  Scenario: Searching Paris from the command line
+    When I search for 'paris'
+    Then I find at least 1 result
+
+  @cli-nowhere
+  Scenario: Searching nowhere from the command line
+    When I search for 'nowhere'
+    Then I find at least 1 result
@@ -0,0 +1,10 @@
+Feature: Flaky searches
+
+  Scenario: Searching Paris
+    When I search for 'paris'
+    Then I find at least 1 result
+
+  @quarantine
+  Scenario: Searching nowhere
+    When I search for 'nowhere'
+    Then I find at least 1 result
@@ -1,2 +1,2 @@
-CREATE USER odin WITH LOGIN PASSWORD 'secret';
+CREATE USER odin WITH LOGIN CREATEDB PASSWORD 'secret';
 CREATE DATABASE mjolnir OWNER odin;
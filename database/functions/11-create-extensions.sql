@@ -1,2 +1,2 @@
-CREATE EXTENSION pg_trgm;
-CREATE EXTENSION pgcrypto;
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
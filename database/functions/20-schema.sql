@@ -1,6 +1,5 @@
 SET CLIENT_MIN_MESSAGES TO INFO;
 SET CLIENT_ENCODING = 'UTF8';
-DROP SCHEMA IF EXISTS main CASCADE;
 CREATE SCHEMA main AUTHORIZATION odin;
 GRANT ALL ON SCHEMA main TO odin;
 SET SEARCH_PATH = main;
@@ -0,0 +1,185 @@
+SET CLIENT_MIN_MESSAGES TO INFO;
+SET CLIENT_ENCODING = 'UTF8';
+
+-- Each time a feature is loaded from the features repository, we record where it came from.
+CREATE TABLE main.feature_revisions (
+  id UUID PRIMARY KEY DEFAULT public.gen_random_uuid(),
+  feature UUID REFERENCES main.features(id) ON DELETE CASCADE,
+  path TEXT NOT NULL,       -- path of the feature file, relative to the root of the repository
+  commit_sha TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE main.feature_revisions OWNER TO odin;
+
+-- History of the synchronisations with the features repository.
+CREATE TABLE main.repository_syncs (
+  id UUID PRIMARY KEY DEFAULT public.gen_random_uuid(),
+  url TEXT NOT NULL,
+  branch TEXT NOT NULL,
+  commit_sha TEXT,
+  loaded INTEGER NOT NULL DEFAULT 0,
+  deleted INTEGER NOT NULL DEFAULT 0,
+  failures TEXT[] NOT NULL DEFAULT '{}', -- 'path: error' for each feature which could not be loaded
+  error TEXT,                            -- set if the synchronisation itself failed
+  started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  finished_at TIMESTAMPTZ
+);
+
+ALTER TABLE main.repository_syncs OWNER TO odin;
+
+CREATE TRIGGER notify_repository_syncs
+AFTER INSERT OR UPDATE
+ON main.repository_syncs
+FOR EACH ROW
+  EXECUTE PROCEDURE main.tg_notify('notifications');
+
+-- This type is used to return a feature revision to the client
+CREATE TYPE main.return_feature_revision_type AS (
+    id          UUID
+  , feature     UUID
+  , path        TEXT
+  , commit_sha  TEXT
+  , created_at  TIMESTAMPTZ
+);
+
+-- This type is used to return a repository synchronisation to the client
+CREATE TYPE main.return_repository_sync_type AS (
+    id          UUID
+  , url         TEXT
+  , branch      TEXT
+  , commit_sha  TEXT
+  , loaded      INTEGER
+  , deleted     INTEGER
+  , failures    TEXT[]
+  , error       TEXT
+  , started_at  TIMESTAMPTZ
+  , finished_at TIMESTAMPTZ
+);
+
+CREATE OR REPLACE FUNCTION main.add_feature_revision (
+    _feature    UUID  -- feature id (1)
+  , _path       TEXT  -- path       (2)
+  , _commit_sha TEXT  -- commit     (3)
+) RETURNS main.return_feature_revision_type
+AS $$
+DECLARE
+  res main.return_feature_revision_type;
+BEGIN
+  INSERT INTO main.feature_revisions (feature, path, commit_sha) VALUES (
+      $1 -- feature
+    , $2 -- path
+    , $3 -- commit
+  )
+  RETURNING id, feature, path, commit_sha, created_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- Returns the features which were loaded from the repository, along with the path of
+-- the file they were last loaded from.
+CREATE OR REPLACE FUNCTION main.repository_features ()
+RETURNS TABLE (
+    _feature UUID
+  , _path    TEXT)
+AS $$
+BEGIN
+  RETURN QUERY
+  SELECT DISTINCT ON (r.feature) r.feature, r.path
+  FROM main.feature_revisions AS r
+  ORDER BY r.feature, r.created_at DESC;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.start_repository_sync (
+    _url    TEXT  -- url    (1)
+  , _branch TEXT  -- branch (2)
+) RETURNS main.return_repository_sync_type
+AS $$
+DECLARE
+  res main.return_repository_sync_type;
+BEGIN
+  INSERT INTO main.repository_syncs (url, branch) VALUES (
+      $1 -- url
+    , $2 -- branch
+  )
+  RETURNING id, url, branch, commit_sha, loaded, deleted, failures, error, started_at, finished_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.finish_repository_sync (
+    _id         UUID    -- id         (1)
+  , _commit_sha TEXT    -- commit     (2)
+  , _loaded     INTEGER -- loaded     (3)
+  , _deleted    INTEGER -- deleted    (4)
+  , _failures   TEXT[]  -- failures   (5)
+  , _error      TEXT    -- error      (6)
+) RETURNS main.return_repository_sync_type
+AS $$
+DECLARE
+  res main.return_repository_sync_type;
+BEGIN
+  UPDATE main.repository_syncs
+  SET   commit_sha  = $2
+      , loaded      = $3
+      , deleted     = $4
+      , failures    = $5
+      , error       = $6
+      , finished_at = NOW()
+  WHERE id = $1
+  RETURNING id, url, branch, commit_sha, loaded, deleted, failures, error, started_at, finished_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- A feature which is reloaded keeps its background, which is touched rather than ignored.
+CREATE OR REPLACE FUNCTION main.create_background (
+  _feature UUID    -- feature id  (1)
+) RETURNS main.return_background_type
+AS $$
+DECLARE
+  res main.return_background_type;
+BEGIN
+  INSERT INTO main.backgrounds (feature) VALUES (
+    $1 -- feature
+  )
+  ON CONFLICT (feature) DO
+    UPDATE
+    SET updated_at = NOW()
+  RETURNING id, created_at, updated_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- When a feature is replaced, its steps are all recreated, and the scenarios it no longer
+-- contains are removed. The scenarios it still contains are kept (and later updated with
+-- create_or_replace_scenario), so that their ids remain stable across reloads.
+CREATE OR REPLACE FUNCTION main.prune_feature (
+    _id         UUID      -- feature id                          (1)
+  , _scenarios  TEXT[]    -- names of the scenarios to keep      (2)
+  , _background BOOLEAN   -- true if the background is kept      (3)
+) RETURNS VOID
+AS $$
+BEGIN
+  DELETE FROM main.steps WHERE id IN (
+    SELECT m.step FROM main.scenario_step_map AS m
+    INNER JOIN main.scenarios AS s ON s.id = m.scenario
+    WHERE s.feature = $1
+    UNION
+    SELECT m.step FROM main.background_step_map AS m
+    INNER JOIN main.backgrounds AS b ON b.id = m.background
+    WHERE b.feature = $1
+  );
+  DELETE FROM main.scenarios WHERE feature = $1 AND NOT (name = ANY($2));
+  IF NOT $3 THEN
+    DELETE FROM main.backgrounds WHERE feature = $1;
+  END IF;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,266 @@
+SET CLIENT_MIN_MESSAGES TO INFO;
+SET CLIENT_ENCODING = 'UTF8';
+
+CREATE TYPE main.run_status AS ENUM ('running', 'passed', 'failed', 'error');
+
+CREATE TYPE main.result_status AS ENUM ('passed', 'failed', 'skipped', 'undefined', 'error');
+
+-- A run is the execution of a selection of scenarios against a bragi endpoint.
+CREATE TABLE main.runs (
+  id UUID PRIMARY KEY DEFAULT public.gen_random_uuid(),
+  tags TEXT[] NOT NULL DEFAULT '{}', -- scenarios selected by tags. All scenarios if empty.
+  bragi_url TEXT NOT NULL,
+  status main.run_status NOT NULL DEFAULT 'running',
+  passed INTEGER NOT NULL DEFAULT 0,
+  failed INTEGER NOT NULL DEFAULT 0,
+  skipped INTEGER NOT NULL DEFAULT 0,
+  started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  finished_at TIMESTAMPTZ
+);
+
+ALTER TABLE main.runs OWNER TO odin;
+
+CREATE TRIGGER notify_runs
+AFTER INSERT OR UPDATE
+ON main.runs
+FOR EACH ROW
+  EXECUTE PROCEDURE main.tg_notify('notifications');
+
+-- The names of the feature and scenario are copied, so that results can still be reported
+-- after the feature has been modified or removed.
+CREATE TABLE main.scenario_results (
+  id UUID PRIMARY KEY DEFAULT public.gen_random_uuid(),
+  run UUID REFERENCES main.runs(id) ON DELETE CASCADE,
+  scenario UUID REFERENCES main.scenarios(id) ON DELETE SET NULL,
+  feature_name TEXT NOT NULL,
+  scenario_name TEXT NOT NULL,
+  tags TEXT[] NOT NULL DEFAULT '{}', -- tags of the feature and of the scenario
+  status main.result_status NOT NULL,
+  duration DOUBLE PRECISION NOT NULL DEFAULT 0.0, -- Expressed in milliseconds
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE main.scenario_results OWNER TO odin;
+
+CREATE TABLE main.step_results (
+  id UUID PRIMARY KEY DEFAULT public.gen_random_uuid(),
+  scenario_result UUID REFERENCES main.scenario_results(id) ON DELETE CASCADE,
+  position INTEGER NOT NULL, -- background steps come first
+  step_type main.step_type NOT NULL,
+  value TEXT NOT NULL,
+  status main.result_status NOT NULL,
+  message TEXT,
+  duration DOUBLE PRECISION NOT NULL DEFAULT 0.0, -- Expressed in milliseconds
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE main.step_results OWNER TO odin;
+
+-- Steps are reported in the order of the feature, so the maps record the position of each step.
+ALTER TABLE main.background_step_map ADD COLUMN position INTEGER NOT NULL DEFAULT 0; -- order of the step in the background
+ALTER TABLE main.scenario_step_map ADD COLUMN position INTEGER NOT NULL DEFAULT 0; -- order of the step in the scenario
+
+-- This type is used to return a run to the client
+CREATE TYPE main.return_run_type AS (
+    id          UUID
+  , tags        TEXT[]
+  , bragi_url   TEXT
+  , status      main.run_status
+  , passed      INTEGER
+  , failed      INTEGER
+  , skipped     INTEGER
+  , started_at  TIMESTAMPTZ
+  , finished_at TIMESTAMPTZ
+);
+
+-- This type is used to return a scenario result to the client
+CREATE TYPE main.return_scenario_result_type AS (
+    id            UUID
+  , run           UUID
+  , scenario      UUID
+  , feature_name  TEXT
+  , scenario_name TEXT
+  , tags          TEXT[]
+  , status        main.result_status
+  , duration      DOUBLE PRECISION
+  , created_at    TIMESTAMPTZ
+);
+
+-- This type is used to return a step result to the client
+CREATE TYPE main.return_step_result_type AS (
+    id              UUID
+  , scenario_result UUID
+  , position        INTEGER
+  , step_type       main.step_type
+  , value           TEXT
+  , status          main.result_status
+  , message         TEXT
+  , duration        DOUBLE PRECISION
+  , created_at      TIMESTAMPTZ
+);
+
+CREATE OR REPLACE FUNCTION main.create_run (
+    _tags      TEXT[]  -- tags      (1)
+  , _bragi_url TEXT    -- bragi url (2)
+) RETURNS main.return_run_type
+AS $$
+DECLARE
+  res main.return_run_type;
+BEGIN
+  INSERT INTO main.runs (tags, bragi_url) VALUES (
+      $1 -- tags
+    , $2 -- bragi url
+  )
+  RETURNING id, tags, bragi_url, status, passed, failed, skipped, started_at, finished_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- Counts the scenario results of the run, and sets its final status.
+CREATE OR REPLACE FUNCTION main.finish_run (
+    _id     UUID             -- id     (1)
+  , _status main.run_status  -- status (2)
+) RETURNS main.return_run_type
+AS $$
+DECLARE
+  res main.return_run_type;
+BEGIN
+  UPDATE main.runs
+  SET   status      = $2
+      , passed      = (SELECT COUNT(*) FROM main.scenario_results WHERE run = $1 AND status = 'passed')
+      , failed      = (SELECT COUNT(*) FROM main.scenario_results WHERE run = $1 AND status IN ('failed', 'error'))
+      , skipped     = (SELECT COUNT(*) FROM main.scenario_results WHERE run = $1 AND status IN ('skipped', 'undefined'))
+      , finished_at = NOW()
+  WHERE id = $1
+  RETURNING id, tags, bragi_url, status, passed, failed, skipped, started_at, finished_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.create_scenario_result (
+    _run           UUID                -- run           (1)
+  , _scenario      UUID                -- scenario      (2)
+  , _feature_name  TEXT                -- feature name  (3)
+  , _scenario_name TEXT                -- scenario name (4)
+  , _tags          TEXT[]              -- tags          (5)
+  , _status        main.result_status  -- status        (6)
+  , _duration      DOUBLE PRECISION    -- duration      (7)
+) RETURNS main.return_scenario_result_type
+AS $$
+DECLARE
+  res main.return_scenario_result_type;
+BEGIN
+  INSERT INTO main.scenario_results (run, scenario, feature_name, scenario_name, tags, status, duration)
+  VALUES ($1, $2, $3, $4, $5, $6, $7)
+  RETURNING id, run, scenario, feature_name, scenario_name, tags, status, duration, created_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.create_step_result (
+    _scenario_result UUID                -- scenario result (1)
+  , _position        INTEGER             -- position        (2)
+  , _step_type       main.step_type      -- step type       (3)
+  , _value           TEXT                -- value           (4)
+  , _status          main.result_status  -- status          (5)
+  , _message         TEXT                -- message         (6)
+  , _duration        DOUBLE PRECISION    -- duration        (7)
+) RETURNS main.return_step_result_type
+AS $$
+DECLARE
+  res main.return_step_result_type;
+BEGIN
+  INSERT INTO main.step_results (scenario_result, position, step_type, value, status, message, duration)
+  VALUES ($1, $2, $3, $4, $5, $6, $7)
+  RETURNING id, scenario_result, position, step_type, value, status, message, duration, created_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.add_step_to_scenario (
+    INOUT _scenario_id UUID   -- sceranio id (1)
+  , _step_id UUID             -- step id     (2)
+  , OUT _updated_at TIMESTAMPTZ)
+AS $$
+DECLARE
+    v_state   TEXT;
+    v_msg     TEXT;
+    v_detail  TEXT;
+    v_hint    TEXT;
+    v_context TEXT;
+BEGIN
+  -- Relying on foreign key constraints to ensure scenario id and step id exists
+  -- Steps are appended to the scenario
+  INSERT INTO main.scenario_step_map
+  VALUES (
+      $1 -- scerario id
+    , $2 -- step id
+    , (SELECT COUNT(*) FROM main.scenario_step_map WHERE scenario = $1)
+  );
+  -- Now we update the 'updated' timestamp on the scenario
+  UPDATE main.scenarios
+  SET updated_at = NOW()
+  WHERE id = $1
+  RETURNING updated_at INTO _updated_at;
+  EXCEPTION WHEN others THEN
+      GET STACKED DIAGNOSTICS
+          v_state   = RETURNED_SQLSTATE,
+          v_msg     = MESSAGE_TEXT,
+          v_detail  = PG_EXCEPTION_DETAIL,
+          v_hint    = PG_EXCEPTION_HINT,
+          v_context = PG_EXCEPTION_CONTEXT;
+      RAISE NOTICE E'Got exception:
+          state  : %
+          message: %
+          detail : %
+          hint   : %
+          context: %', v_state, v_msg, v_detail, v_hint, v_context;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.add_step_to_background (
+    INOUT _background_id UUID   -- background id (1)
+  , _step_id UUID               -- step id       (2)
+  , OUT _updated_at TIMESTAMPTZ)
+AS $$
+DECLARE
+    v_state   TEXT;
+    v_msg     TEXT;
+    v_detail  TEXT;
+    v_hint    TEXT;
+    v_context TEXT;
+BEGIN
+  -- Relying on foreign key constraints to ensure scenario id and step id exists
+  -- Steps are appended to the background
+  INSERT INTO main.background_step_map
+  VALUES (
+      $1 -- background id
+    , $2 -- step id
+    , (SELECT COUNT(*) FROM main.background_step_map WHERE background = $1)
+  );
+  -- Now we update the 'updated' timestamp on the background
+  UPDATE main.backgrounds
+  SET updated_at = NOW()
+  WHERE id = $1
+  RETURNING updated_at INTO _updated_at;
+  EXCEPTION WHEN others THEN
+      GET STACKED DIAGNOSTICS
+          v_state   = RETURNED_SQLSTATE,
+          v_msg     = MESSAGE_TEXT,
+          v_detail  = PG_EXCEPTION_DETAIL,
+          v_hint    = PG_EXCEPTION_HINT,
+          v_context = PG_EXCEPTION_CONTEXT;
+      RAISE NOTICE E'Got exception:
+          state  : %
+          message: %
+          detail : %
+          hint   : %
+          context: %', v_state, v_msg, v_detail, v_hint, v_context;
+END;
+$$
+LANGUAGE plpgsql;
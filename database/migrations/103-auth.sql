@@ -0,0 +1,182 @@
+SET CLIENT_MIN_MESSAGES TO INFO;
+SET CLIENT_ENCODING = 'UTF8';
+
+-- Roles are ordered: each role can do everything the previous ones can.
+CREATE TYPE main.role AS ENUM ('viewer', 'editor', 'operator', 'admin');
+
+-- API tokens. Only a hash of the token is stored, the token itself is given once, at creation.
+CREATE TABLE main.api_tokens (
+  id UUID PRIMARY KEY DEFAULT public.gen_random_uuid(),
+  name TEXT UNIQUE NOT NULL,       -- who (or what) uses the token, recorded as the principal
+  token_hash TEXT UNIQUE NOT NULL, -- hex encoded SHA-256 of the token
+  role main.role NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  last_used_at TIMESTAMPTZ,
+  revoked_at TIMESTAMPTZ
+);
+
+ALTER TABLE main.api_tokens OWNER TO odin;
+
+-- Features record the principals who created and last modified them.
+ALTER TABLE main.features ADD COLUMN created_by TEXT; -- principal who created the feature
+ALTER TABLE main.features ADD COLUMN updated_by TEXT; -- principal who last modified the feature
+
+-- The functions returning features are recreated along with their type.
+DROP TYPE main.return_feature_type CASCADE;
+
+-- This type is used to return a feature to the client. We skip some fields, like search
+CREATE TYPE main.return_feature_type AS (
+    id UUID
+  , name TEXT
+  , description TEXT
+  , tags TEXT[]
+  , created_by TEXT
+  , updated_by TEXT
+  , created_at TIMESTAMPTZ
+  , updated_at TIMESTAMPTZ
+);
+
+CREATE OR REPLACE FUNCTION main.create_or_replace_feature (
+    _name        TEXT      -- name        (1)
+  , _description TEXT      -- description (2)
+  , _tags        TEXT[]    -- tags        (3)
+  , _principal   TEXT      -- principal   (4)
+) RETURNS main.return_feature_type
+AS $$
+DECLARE
+  res main.return_feature_type;
+  v_state   TEXT;
+  v_msg     TEXT;
+  v_detail  TEXT;
+  v_hint    TEXT;
+  v_context TEXT;
+BEGIN
+  INSERT INTO main.features (name, description, tags, created_by, updated_by) VALUES (
+      $1  -- name
+    , $2  -- description
+    , $3  -- tags
+    , $4  -- created by
+    , $4  -- updated by
+  )
+  ON CONFLICT (name) DO
+    UPDATE
+    SET   description = EXCLUDED.description
+        , tags = EXCLUDED.tags
+        , updated_by = EXCLUDED.updated_by
+        , updated_at = NOW()
+  RETURNING id, name, description, tags, created_by, updated_by, created_at, updated_at INTO res;
+  RETURN res;
+  EXCEPTION
+  WHEN others THEN
+      GET STACKED DIAGNOSTICS
+          v_state   = RETURNED_SQLSTATE,
+          v_msg     = MESSAGE_TEXT,
+          v_detail  = PG_EXCEPTION_DETAIL,
+          v_hint    = PG_EXCEPTION_HINT,
+          v_context = PG_EXCEPTION_CONTEXT;
+      RAISE NOTICE E'Got exception:
+          state  : %
+          message: %
+          detail : %
+          hint   : %
+          context: %', v_state, v_msg, v_detail, v_hint, v_context;
+      RETURN NULL;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.delete_feature (
+    _id          UUID      -- id          (1)
+) RETURNS main.return_feature_type
+AS $$
+DECLARE
+  res main.return_feature_type;
+BEGIN
+  DELETE FROM main.features WHERE id = $1
+  RETURNING id, name, description, tags, created_by, updated_by, created_at, updated_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- This type is used to return an API token to the client. The hash is never returned.
+CREATE TYPE main.return_api_token_type AS (
+    id           UUID
+  , name         TEXT
+  , role         main.role
+  , created_at   TIMESTAMPTZ
+  , last_used_at TIMESTAMPTZ
+  , revoked_at   TIMESTAMPTZ
+);
+
+-- This type is used to return a newly created API token, along with the token itself.
+CREATE TYPE main.return_new_api_token_type AS (
+    id         UUID
+  , name       TEXT
+  , role       main.role
+  , token      TEXT
+  , created_at TIMESTAMPTZ
+);
+
+CREATE OR REPLACE FUNCTION main.hash_api_token (
+    _token TEXT -- token (1)
+) RETURNS TEXT
+AS $$
+  SELECT encode(public.digest($1, 'sha256'), 'hex');
+$$
+LANGUAGE SQL IMMUTABLE;
+
+-- Creates a token with the given name and role. The token is random, and prefixed with 'mjr_'
+-- so that it can be recognized (eg by secret scanners).
+CREATE OR REPLACE FUNCTION main.create_api_token (
+    _name TEXT      -- name (1)
+  , _role main.role -- role (2)
+) RETURNS main.return_new_api_token_type
+AS $$
+DECLARE
+  res main.return_new_api_token_type;
+  v_token TEXT;
+BEGIN
+  v_token := 'mjr_' || encode(public.gen_random_bytes(32), 'hex');
+  INSERT INTO main.api_tokens (name, token_hash, role) VALUES (
+      $1                          -- name
+    , main.hash_api_token(v_token) -- token hash
+    , $2                          -- role
+  )
+  RETURNING id, name, role, v_token, created_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- Returns the token matching the given (clear) token, if it has not been revoked, and records
+-- that it was used.
+CREATE OR REPLACE FUNCTION main.authenticate_api_token (
+    _token TEXT -- token (1)
+) RETURNS SETOF main.return_api_token_type
+AS $$
+BEGIN
+  RETURN QUERY
+  UPDATE main.api_tokens
+  SET last_used_at = NOW()
+  WHERE token_hash = main.hash_api_token($1) AND revoked_at IS NULL
+  RETURNING id, name, role, created_at, last_used_at, revoked_at;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION main.revoke_api_token (
+    _id UUID -- id (1)
+) RETURNS main.return_api_token_type
+AS $$
+DECLARE
+  res main.return_api_token_type;
+BEGIN
+  UPDATE main.api_tokens
+  SET revoked_at = COALESCE(revoked_at, NOW())
+  WHERE id = $1
+  RETURNING id, name, role, created_at, last_used_at, revoked_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
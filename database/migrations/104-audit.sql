@@ -0,0 +1,136 @@
+SET CLIENT_MIN_MESSAGES TO INFO;
+SET CLIENT_ENCODING = 'UTF8';
+
+-- Who did what to what. Entries are written by the mutations (see gql.rs), and by the
+-- triggers below when the status of an environment, an index or a run changes.
+CREATE TABLE main.audit_log (
+  id UUID PRIMARY KEY DEFAULT public.gen_random_uuid(),
+  actor TEXT NOT NULL,       -- the principal, or 'system' for background tasks
+  action TEXT NOT NULL,      -- eg 'add_feature', 'status_change'
+  target_type TEXT NOT NULL, -- eg 'feature', 'environment'
+  target_id TEXT,
+  before JSONB,              -- the target before the action, if it existed
+  after JSONB,               -- the target after the action, if it still exists
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE main.audit_log OWNER TO odin;
+
+CREATE INDEX audit_log_created_at_idx ON main.audit_log (created_at);
+CREATE INDEX audit_log_target_idx ON main.audit_log (target_type, target_id);
+
+-- Record status transitions, which are made by background tasks. The target type is the name
+-- of the table.
+CREATE OR REPLACE FUNCTION main.tg_audit_status ()
+  RETURNS TRIGGER
+  LANGUAGE plpgsql
+AS $$
+BEGIN
+  INSERT INTO main.audit_log (actor, action, target_type, target_id, before, after) VALUES (
+      'system'
+    , 'status_change'
+    , TG_TABLE_NAME
+    , NEW.id::TEXT
+    , jsonb_build_object('status', OLD.status)
+    , jsonb_build_object('status', NEW.status)
+  );
+  RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER audit_environments_status
+AFTER UPDATE OF status
+ON main.environments
+FOR EACH ROW
+  WHEN (OLD.status IS DISTINCT FROM NEW.status)
+  EXECUTE PROCEDURE main.tg_audit_status();
+
+CREATE TRIGGER audit_indexes_status
+AFTER UPDATE OF status
+ON main.indexes
+FOR EACH ROW
+  WHEN (OLD.status IS DISTINCT FROM NEW.status)
+  EXECUTE PROCEDURE main.tg_audit_status();
+
+CREATE TRIGGER audit_runs_status
+AFTER UPDATE OF status
+ON main.runs
+FOR EACH ROW
+  WHEN (OLD.status IS DISTINCT FROM NEW.status)
+  EXECUTE PROCEDURE main.tg_audit_status();
+
+-- This type is used to return an audit log entry to the client.
+-- before and after are returned as text, since there is no JSON scalar in the GraphQL schema.
+CREATE TYPE main.return_audit_entry_type AS (
+    id          UUID
+  , actor       TEXT
+  , action      TEXT
+  , target_type TEXT
+  , target_id   TEXT
+  , before      TEXT
+  , after       TEXT
+  , created_at  TIMESTAMPTZ
+);
+
+CREATE OR REPLACE FUNCTION main.add_audit_entry (
+    _actor       TEXT  -- actor       (1)
+  , _action      TEXT  -- action      (2)
+  , _target_type TEXT  -- target type (3)
+  , _target_id   TEXT  -- target id   (4)
+  , _before      JSONB -- before      (5)
+  , _after       JSONB -- after       (6)
+) RETURNS main.return_audit_entry_type
+AS $$
+DECLARE
+  res main.return_audit_entry_type;
+BEGIN
+  INSERT INTO main.audit_log (actor, action, target_type, target_id, before, after)
+  VALUES ($1, $2, $3, $4, $5, $6)
+  RETURNING id, actor, action, target_type, target_id, before::TEXT, after::TEXT, created_at
+  INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- Returns the entries matching all the given criteria (NULL matches anything), most recent first.
+CREATE OR REPLACE FUNCTION main.fetch_audit_log (
+    _actor       TEXT        -- actor       (1)
+  , _action      TEXT        -- action      (2)
+  , _target_type TEXT        -- target type (3)
+  , _target_id   TEXT        -- target id   (4)
+  , _since       TIMESTAMPTZ -- since       (5)
+  , _until       TIMESTAMPTZ -- until       (6)
+  , _limit       INTEGER     -- limit       (7)
+) RETURNS SETOF main.return_audit_entry_type
+AS $$
+BEGIN
+  RETURN QUERY
+  SELECT id, actor, action, target_type, target_id, before::TEXT, after::TEXT, created_at
+  FROM main.audit_log
+  WHERE ($1 IS NULL OR actor = $1)
+    AND ($2 IS NULL OR action = $2)
+    AND ($3 IS NULL OR target_type = $3)
+    AND ($4 IS NULL OR target_id = $4)
+    AND ($5 IS NULL OR created_at >= $5)
+    AND ($6 IS NULL OR created_at < $6)
+  ORDER BY created_at DESC
+  LIMIT $7;
+END;
+$$
+LANGUAGE plpgsql;
+
+-- Removes the entries older than the given number of days, and returns how many were removed.
+CREATE OR REPLACE FUNCTION main.prune_audit_log (
+    _retention_days INTEGER -- retention days (1)
+) RETURNS BIGINT
+AS $$
+DECLARE
+  res BIGINT;
+BEGIN
+  DELETE FROM main.audit_log WHERE created_at < NOW() - make_interval(days => $1);
+  GET DIAGNOSTICS res = ROW_COUNT;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,65 @@
+SET CLIENT_MIN_MESSAGES TO INFO;
+SET CLIENT_ENCODING = 'UTF8';
+
+-- A run is interrupted when the server shuts down (or stops) before it is finished.
+ALTER TYPE main.run_status ADD VALUE IF NOT EXISTS 'interrupted';
+
+-- This type is used to report how many rows were recovered in each table.
+CREATE TYPE main.return_recovery_type AS (
+    target TEXT
+  , count  BIGINT
+);
+
+-- Work which was in progress when the server stopped will never finish: put it back in the
+-- last state it reached, so that it can be started again.
+-- This must only be called when no server is running (ie at startup).
+CREATE OR REPLACE FUNCTION main.recover_interrupted ()
+RETURNS SETOF main.return_recovery_type
+AS $$
+DECLARE
+  res main.return_recovery_type;
+  v_count BIGINT;
+BEGIN
+  UPDATE main.indexes
+  SET status = CASE status
+      WHEN 'download_in_progress' THEN 'not_available'::main.index_status
+      WHEN 'indexing_in_progress' THEN 'downloaded'::main.index_status
+      WHEN 'validation_in_progress' THEN 'indexed'::main.index_status
+    END
+    , updated_at = NOW()
+  WHERE status IN ('download_in_progress', 'indexing_in_progress', 'validation_in_progress');
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  res := ('indexes', v_count);
+  RETURN NEXT res;
+
+  UPDATE main.environments
+  SET status = CASE status
+      WHEN 'download_in_progress' THEN 'not_available'::main.index_status
+      WHEN 'indexing_in_progress' THEN 'downloaded'::main.index_status
+      WHEN 'validation_in_progress' THEN 'indexed'::main.index_status
+    END
+    , updated_at = NOW()
+  WHERE status IN ('download_in_progress', 'indexing_in_progress', 'validation_in_progress');
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  res := ('environments', v_count);
+  RETURN NEXT res;
+
+  -- The BANO tables are not always provisioned (see 99-foo.sql).
+  IF to_regclass('main.env_bano_item') IS NOT NULL THEN
+    EXECUTE 'UPDATE main.env_bano_item
+      SET filestatus = ''not_available'', updated_at = NOW()
+      WHERE filestatus = ''download_in_progress''';
+    GET DIAGNOSTICS v_count = ROW_COUNT;
+    res := ('env_bano_item', v_count);
+    RETURN NEXT res;
+  END IF;
+
+  UPDATE main.runs
+  SET status = 'interrupted', finished_at = NOW()
+  WHERE status = 'running';
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  res := ('runs', v_count);
+  RETURN NEXT res;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,35 @@
+CREATE TYPE main.file_status AS ENUM ('not_available', 'download_in_progress', 'available', 'download_error');
+
+CREATE TABLE main.env_bano (
+  id VARCHAR(256) PRIMARY KEY,
+  description VARCHAR(256) NOT NULL
+);
+
+ALTER TABLE main.env_bano OWNER TO odin;
+
+CREATE TABLE main.env_bano_item (
+  id VARCHAR(256) PRIMARY KEY,
+  filename VARCHAR(256) DEFAULT '',
+  md5 VARCHAR(256) DEFAULT '',
+  filesize DOUBLE PRECISION NOT NULL DEFAULT 0.0, -- Expressed in kilobytes
+  filestatus main.file_status DEFAULT 'not_available',
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE main.env_bano_item OWNER TO odin;
+
+CREATE TRIGGER notify_env_bano_item
+AFTER INSERT OR UPDATE
+ON main.env_bano_item
+FOR EACH ROW
+  EXECUTE PROCEDURE main.tg_notify('notifications');
+
+-- This table maps bano to bano_items. An item may belong to several banos, and is removed
+-- once it belongs to none.
+CREATE TABLE main.env_bano_map (
+  env VARCHAR(256) REFERENCES main.env_bano(id) ON DELETE CASCADE,
+  item VARCHAR(256) REFERENCES main.env_bano_item(id) ON DELETE CASCADE,
+  PRIMARY KEY (env, item)
+);
+
+ALTER TABLE main.env_bano_map OWNER TO odin;
@@ -0,0 +1,107 @@
+-- Datasets replace the BANO tables (see 80-bano.sql). A dataset is a named collection of source
+-- files (items) of a data source, eg BANO departments or OSM regions. An item may belong to
+-- several datasets of its data source, and is removed once it belongs to none.
+CREATE TABLE main.datasets (
+  id VARCHAR(256) PRIMARY KEY,
+  data_source VARCHAR(256) NOT NULL REFERENCES main.data_sources(id),
+  description VARCHAR(256) NOT NULL,
+  UNIQUE (id, data_source)
+);
+
+ALTER TABLE main.datasets OWNER TO odin;
+
+CREATE TABLE main.dataset_items (
+  id VARCHAR(256) NOT NULL,
+  data_source VARCHAR(256) NOT NULL REFERENCES main.data_sources(id),
+  filename VARCHAR(256) NOT NULL DEFAULT '',
+  md5 VARCHAR(256) NOT NULL DEFAULT '',
+  filesize DOUBLE PRECISION NOT NULL DEFAULT 0.0, -- Expressed in kilobytes
+  filestatus main.file_status NOT NULL DEFAULT 'not_available',
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (data_source, id)
+);
+
+ALTER TABLE main.dataset_items OWNER TO odin;
+
+CREATE TRIGGER notify_dataset_items
+AFTER INSERT OR UPDATE
+ON main.dataset_items
+FOR EACH ROW
+  EXECUTE PROCEDURE main.tg_notify('notifications');
+
+-- This table maps datasets to their items, which must be of the same data source.
+CREATE TABLE main.dataset_item_map (
+  dataset VARCHAR(256) NOT NULL,
+  data_source VARCHAR(256) NOT NULL,
+  item VARCHAR(256) NOT NULL,
+  PRIMARY KEY (dataset, data_source, item),
+  FOREIGN KEY (dataset, data_source) REFERENCES main.datasets(id, data_source) ON DELETE CASCADE,
+  FOREIGN KEY (data_source, item) REFERENCES main.dataset_items(data_source, id) ON DELETE CASCADE
+);
+
+ALTER TABLE main.dataset_item_map OWNER TO odin;
+
+INSERT INTO main.datasets (id, data_source, description)
+  SELECT id, 'bano', description FROM main.env_bano;
+
+INSERT INTO main.dataset_items (id, data_source, filename, md5, filesize, filestatus, updated_at)
+  SELECT id, 'bano', COALESCE(filename, ''), COALESCE(md5, ''), filesize,
+    COALESCE(filestatus, 'not_available'), updated_at
+  FROM main.env_bano_item;
+
+INSERT INTO main.dataset_item_map (dataset, data_source, item)
+  SELECT env, 'bano', item FROM main.env_bano_map;
+
+DROP TABLE main.env_bano_map;
+DROP TABLE main.env_bano_item;
+DROP TABLE main.env_bano;
+
+-- Same as in api/recovery.sql, but recovering the downloads of dataset items instead of those
+-- of BANO items. This must only be called when no server is running (ie at startup).
+CREATE OR REPLACE FUNCTION main.recover_interrupted ()
+RETURNS SETOF main.return_recovery_type
+AS $$
+DECLARE
+  res main.return_recovery_type;
+  v_count BIGINT;
+BEGIN
+  UPDATE main.indexes
+  SET status = CASE status
+      WHEN 'download_in_progress' THEN 'not_available'::main.index_status
+      WHEN 'indexing_in_progress' THEN 'downloaded'::main.index_status
+      WHEN 'validation_in_progress' THEN 'indexed'::main.index_status
+    END
+    , updated_at = NOW()
+  WHERE status IN ('download_in_progress', 'indexing_in_progress', 'validation_in_progress');
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  res := ('indexes', v_count);
+  RETURN NEXT res;
+
+  UPDATE main.environments
+  SET status = CASE status
+      WHEN 'download_in_progress' THEN 'not_available'::main.index_status
+      WHEN 'indexing_in_progress' THEN 'downloaded'::main.index_status
+      WHEN 'validation_in_progress' THEN 'indexed'::main.index_status
+    END
+    , updated_at = NOW()
+  WHERE status IN ('download_in_progress', 'indexing_in_progress', 'validation_in_progress');
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  res := ('environments', v_count);
+  RETURN NEXT res;
+
+  UPDATE main.dataset_items
+  SET filestatus = 'not_available', updated_at = NOW()
+  WHERE filestatus = 'download_in_progress';
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  res := ('dataset_items', v_count);
+  RETURN NEXT res;
+
+  UPDATE main.runs
+  SET status = 'interrupted', finished_at = NOW()
+  WHERE status = 'running';
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  res := ('runs', v_count);
+  RETURN NEXT res;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,30 @@
+-- Assertions may compare labels loosely (see runner/matching.rs): step results record how well
+-- the label found matched the one expected, between 0 and 1, or NULL if the step looks for none.
+ALTER TABLE main.step_results ADD COLUMN score DOUBLE PRECISION;
+
+-- The function returns the type, so it goes first, and comes back with the score.
+DROP FUNCTION main.create_step_result(UUID, INTEGER, main.step_type, TEXT, main.result_status, TEXT, DOUBLE PRECISION);
+
+ALTER TYPE main.return_step_result_type ADD ATTRIBUTE score DOUBLE PRECISION;
+
+CREATE FUNCTION main.create_step_result (
+    _scenario_result UUID                -- scenario result (1)
+  , _position        INTEGER             -- position        (2)
+  , _step_type       main.step_type      -- step type       (3)
+  , _value           TEXT                -- value           (4)
+  , _status          main.result_status  -- status          (5)
+  , _message         TEXT                -- message         (6)
+  , _duration        DOUBLE PRECISION    -- duration        (7)
+  , _score           DOUBLE PRECISION    -- score           (8)
+) RETURNS main.return_step_result_type
+AS $$
+DECLARE
+  res main.return_step_result_type;
+BEGIN
+  INSERT INTO main.step_results (scenario_result, position, step_type, value, status, message, duration, score)
+  VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+  RETURNING id, scenario_result, position, step_type, value, status, message, duration, created_at, score INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,30 @@
+-- Search steps record the bragi request they made, so that it can be reproduced.
+ALTER TABLE main.step_results ADD COLUMN request_url TEXT;
+
+-- As in 90-step-scores.sql, the function goes first, and comes back with the request url.
+DROP FUNCTION main.create_step_result(UUID, INTEGER, main.step_type, TEXT, main.result_status, TEXT, DOUBLE PRECISION, DOUBLE PRECISION);
+
+ALTER TYPE main.return_step_result_type ADD ATTRIBUTE request_url TEXT;
+
+CREATE FUNCTION main.create_step_result (
+    _scenario_result UUID                -- scenario result (1)
+  , _position        INTEGER             -- position        (2)
+  , _step_type       main.step_type      -- step type       (3)
+  , _value           TEXT                -- value           (4)
+  , _status          main.result_status  -- status          (5)
+  , _message         TEXT                -- message         (6)
+  , _duration        DOUBLE PRECISION    -- duration        (7)
+  , _score           DOUBLE PRECISION    -- score           (8)
+  , _request_url     TEXT                -- request url     (9)
+) RETURNS main.return_step_result_type
+AS $$
+DECLARE
+  res main.return_step_result_type;
+BEGIN
+  INSERT INTO main.step_results (scenario_result, position, step_type, value, status, message, duration, score, request_url)
+  VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+  RETURNING id, scenario_result, position, step_type, value, status, message, duration, created_at, score, request_url INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
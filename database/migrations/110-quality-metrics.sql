@@ -0,0 +1,52 @@
+-- Steps which look for an expected result record where it was found: 1 for the first result,
+-- 0 if it was not found, and NULL for steps which expect no particular result.
+ALTER TABLE main.step_results ADD COLUMN rank INTEGER;
+
+-- As in 90-step-scores.sql, the function goes first, and comes back with the rank.
+DROP FUNCTION main.create_step_result(UUID, INTEGER, main.step_type, TEXT, main.result_status, TEXT, DOUBLE PRECISION, DOUBLE PRECISION, TEXT);
+
+ALTER TYPE main.return_step_result_type ADD ATTRIBUTE rank INTEGER;
+
+CREATE FUNCTION main.create_step_result (
+    _scenario_result UUID                -- scenario result (1)
+  , _position        INTEGER             -- position        (2)
+  , _step_type       main.step_type      -- step type       (3)
+  , _value           TEXT                -- value           (4)
+  , _status          main.result_status  -- status          (5)
+  , _message         TEXT                -- message         (6)
+  , _duration        DOUBLE PRECISION    -- duration        (7)
+  , _score           DOUBLE PRECISION    -- score           (8)
+  , _request_url     TEXT                -- request url     (9)
+  , _rank            INTEGER             -- rank            (10)
+) RETURNS main.return_step_result_type
+AS $$
+DECLARE
+  res main.return_step_result_type;
+BEGIN
+  INSERT INTO main.step_results (scenario_result, position, step_type, value, status, message, duration, score, request_url, rank)
+  VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+  RETURNING id, scenario_result, position, step_type, value, status, message, duration, created_at, score, request_url, rank INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
+
+CREATE TYPE main.quality_group AS ENUM ('run', 'feature', 'tag', 'index_type');
+
+-- Relevance of the results of a run, computed from the ranks of its steps, for the whole run
+-- (group_name is empty), and for each feature, tag and index type.
+CREATE TABLE main.quality_metrics (
+  run UUID NOT NULL REFERENCES main.runs(id) ON DELETE CASCADE,
+  group_by main.quality_group NOT NULL,
+  group_name TEXT NOT NULL,
+  queries INTEGER NOT NULL, -- count of ranked steps
+  mrr DOUBLE PRECISION NOT NULL,
+  recall_at_1 DOUBLE PRECISION NOT NULL,
+  recall_at_5 DOUBLE PRECISION NOT NULL,
+  recall_at_10 DOUBLE PRECISION NOT NULL,
+  ndcg DOUBLE PRECISION NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (run, group_by, group_name)
+);
+
+ALTER TABLE main.quality_metrics OWNER TO odin;
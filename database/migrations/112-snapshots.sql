@@ -0,0 +1,19 @@
+-- The results a snapshot step ('Then the results match the snapshot') compares the results of
+-- its search with. Steps are identified by their scenario and their position, so that snapshots
+-- are kept when features are reloaded. The results of the last run which did not match wait in
+-- pending until they are accepted as the new snapshot.
+CREATE TABLE main.snapshots (
+  feature_name TEXT NOT NULL,
+  scenario_name TEXT NOT NULL,
+  position INTEGER NOT NULL,
+  results JSONB NOT NULL,    -- label, type and coordinates of the first results
+  pending JSONB,
+  pending_step UUID REFERENCES main.step_results(id) ON DELETE SET NULL, -- step result which found them
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (feature_name, scenario_name, position)
+);
+
+ALTER TABLE main.snapshots OWNER TO odin;
+
+CREATE INDEX snapshots_pending_step_idx ON main.snapshots (pending_step);
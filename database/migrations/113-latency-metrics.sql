@@ -0,0 +1,21 @@
+-- Latency of the searches of a scenario in a benchmark run, where each search is replayed
+-- 'iterations' times, 'concurrency' at a time. Latencies are in milliseconds, and only those of
+-- the requests which succeeded count.
+CREATE TABLE main.latency_metrics (
+  run UUID NOT NULL REFERENCES main.runs(id) ON DELETE CASCADE,
+  feature_name TEXT NOT NULL,
+  scenario_name TEXT NOT NULL,
+  iterations INTEGER NOT NULL,
+  concurrency INTEGER NOT NULL,
+  requests INTEGER NOT NULL,   -- count of replayed requests
+  errors INTEGER NOT NULL,     -- count of requests which failed
+  error_rate DOUBLE PRECISION NOT NULL,
+  p50 DOUBLE PRECISION NOT NULL,
+  p95 DOUBLE PRECISION NOT NULL,
+  p99 DOUBLE PRECISION NOT NULL,
+  throughput DOUBLE PRECISION NOT NULL, -- requests per second
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (run, feature_name, scenario_name)
+);
+
+ALTER TABLE main.latency_metrics OWNER TO odin;
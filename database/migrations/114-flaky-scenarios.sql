@@ -0,0 +1,46 @@
+-- Scenario results record what they ran against, so that their outcomes can be compared from
+-- one run to the next: the signature of the environment of their feature (if it has one), and
+-- the version of bragi. They also record how many times the scenario was attempted before it
+-- passed, and whether it was quarantined, in which case it does not fail its run.
+ALTER TABLE main.scenario_results
+  ADD COLUMN environment TEXT,
+  ADD COLUMN geocoder_version TEXT,
+  ADD COLUMN attempts INTEGER NOT NULL DEFAULT 1,
+  ADD COLUMN quarantined BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Flaky scenarios are found in the history of their results.
+CREATE INDEX scenario_results_scenario_idx ON main.scenario_results (feature_name, scenario_name);
+
+-- As in 90-step-scores.sql, the function goes first, and comes back with the new columns.
+DROP FUNCTION main.create_scenario_result(UUID, UUID, TEXT, TEXT, TEXT[], main.result_status, DOUBLE PRECISION);
+
+ALTER TYPE main.return_scenario_result_type
+  ADD ATTRIBUTE environment TEXT,
+  ADD ATTRIBUTE geocoder_version TEXT,
+  ADD ATTRIBUTE attempts INTEGER,
+  ADD ATTRIBUTE quarantined BOOLEAN;
+
+CREATE FUNCTION main.create_scenario_result (
+    _run              UUID                -- run              (1)
+  , _scenario         UUID                -- scenario         (2)
+  , _feature_name     TEXT                -- feature name     (3)
+  , _scenario_name    TEXT                -- scenario name    (4)
+  , _tags             TEXT[]              -- tags             (5)
+  , _status           main.result_status  -- status           (6)
+  , _duration         DOUBLE PRECISION    -- duration         (7)
+  , _environment      TEXT                -- environment      (8)
+  , _geocoder_version TEXT                -- geocoder version (9)
+  , _attempts         INTEGER             -- attempts         (10)
+  , _quarantined      BOOLEAN             -- quarantined      (11)
+) RETURNS main.return_scenario_result_type
+AS $$
+DECLARE
+  res main.return_scenario_result_type;
+BEGIN
+  INSERT INTO main.scenario_results (run, scenario, feature_name, scenario_name, tags, status, duration, environment, geocoder_version, attempts, quarantined)
+  VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+  RETURNING id, run, scenario, feature_name, scenario_name, tags, status, duration, created_at, environment, geocoder_version, attempts, quarantined INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,17 @@
+-- Revoking a token which does not exist returned a row of NULLs. It now returns no row, which
+-- the server reports as not found. The return type changes, so the function goes first.
+DROP FUNCTION main.revoke_api_token(UUID);
+
+CREATE FUNCTION main.revoke_api_token (
+    _id UUID -- id (1)
+) RETURNS SETOF main.return_api_token_type
+AS $$
+BEGIN
+  RETURN QUERY
+  UPDATE main.api_tokens
+  SET revoked_at = COALESCE(revoked_at, NOW())
+  WHERE id = $1
+  RETURNING id, name, role, created_at, last_used_at, revoked_at;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,30 @@
+-- Errors raised while creating a feature (eg a name which is too long) were caught, and NULL
+-- was returned instead. They now reach the server, which reports them with their code.
+CREATE OR REPLACE FUNCTION main.create_or_replace_feature (
+    _name        TEXT      -- name        (1)
+  , _description TEXT      -- description (2)
+  , _tags        TEXT[]    -- tags        (3)
+  , _principal   TEXT      -- principal   (4)
+) RETURNS main.return_feature_type
+AS $$
+DECLARE
+  res main.return_feature_type;
+BEGIN
+  INSERT INTO main.features (name, description, tags, created_by, updated_by) VALUES (
+      $1  -- name
+    , $2  -- description
+    , $3  -- tags
+    , $4  -- created by
+    , $4  -- updated by
+  )
+  ON CONFLICT (name) DO
+    UPDATE
+    SET   description = EXCLUDED.description
+        , tags = EXCLUDED.tags
+        , updated_by = EXCLUDED.updated_by
+        , updated_at = NOW()
+  RETURNING id, name, description, tags, created_by, updated_by, created_at, updated_at INTO res;
+  RETURN res;
+END;
+$$
+LANGUAGE plpgsql;
@@ -9,17 +9,11 @@ for file in `find functions -name '0*.sql' | sort`; do
   cat "${file}" >> ./init.sql
 done
 
-rm -fr ./build.sql
-for file in `find functions -name '*.sql' ! -name '0*' | sort`; do
-  cat "${file}" >> ./build.sql
-done
-for file in `find api -name '*.sql' | sort`; do
-  cat "${file}" >> ./build.sql
-done
-
 echo "initializing"
 PGPASSWORD=${pgpass} psql -h postgres -U postgres < init.sql
-echo "building"
-PGPASSWORD=${pgpass} psql -h postgres -U postgres mjolnir < build.sql
-echo "provisioning"
-PGPASSWORD=${pgpass} psql -h postgres -U postgres mjolnir < provision.sql
+# Extensions may only be created by a superuser.
+echo "creating extensions"
+PGPASSWORD=${pgpass} psql -h postgres -U postgres mjolnir < functions/11-create-extensions.sql
+# The schema is built by the server, with the migrations embedded in it (see DATABASE_URL).
+echo "migrating"
+(cd ../backend && cargo run --release --bin server -- migrate)